@@ -0,0 +1,364 @@
+use rand::rngs::OsRng;
+use rand::RngCore;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The user behind requests that carry no token, when anonymous access is allowed.
+pub const ANONYMOUS: &str = "anonymous";
+pub const MAX_TOKENS_PER_USER: usize = 20;
+const TOKEN_PREFIX: &str = "calc_";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    #[default]
+    User,
+    Admin,
+}
+
+/// Who a request acts for.
+#[derive(Debug, Clone)]
+pub struct User {
+    pub name: String,
+    pub role: Role,
+}
+
+impl User {
+    pub fn anonymous() -> Self {
+        User { name: ANONYMOUS.to_string(), role: Role::User }
+    }
+
+    pub fn is_anonymous(&self) -> bool {
+        self.name == ANONYMOUS
+    }
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct UserRequest {
+    pub name: String,
+    #[serde(default)]
+    pub role: Role,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct TokenRequest {
+    pub label: Option<String>,
+    /// Issue the token for another user; admins only.
+    pub user: Option<String>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct UserView {
+    pub name: String,
+    pub role: Role,
+    pub created: u64,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct TokenView {
+    pub id: String,
+    pub user: String,
+    pub label: Option<String>,
+    pub created: u64,
+    /// The bearer token itself, shown only in the response that issues it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, JsonSchema)]
+pub struct AccountsResponse {
+    pub users: Vec<UserView>,
+    pub tokens: Vec<TokenView>,
+    pub error: Option<String>,
+}
+
+impl AccountsResponse {
+    pub fn error(message: String) -> Self {
+        Self { error: Some(message), ..Default::default() }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Account {
+    role: Role,
+    created: u64,
+}
+
+/// A token is stored only as its SHA-256 hash; `id` names it for listing and revocation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Token {
+    id: String,
+    user: String,
+    label: Option<String>,
+    created: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct AccountsFile {
+    users: BTreeMap<String, Account>,
+    /// By token hash.
+    tokens: BTreeMap<String, Token>,
+}
+
+/// User accounts and their bearer tokens, written through to a JSON file like the
+/// memory registers.
+#[derive(Debug, Default)]
+pub struct Accounts {
+    file: AccountsFile,
+    path: Option<String>,
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_secs()).unwrap_or(0)
+}
+
+fn random_hex(bytes: usize) -> String {
+    let mut buffer = vec![0u8; bytes];
+    OsRng.fill_bytes(&mut buffer);
+    buffer.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn hash(token: &str) -> String {
+    Sha256::digest(token.as_bytes()).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn check_user_name(name: &str) -> Result<(), String> {
+    let valid = !name.is_empty()
+        && name.len() <= 64
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+        && name != ANONYMOUS;
+    if !valid {
+        return Err(format!("'{}' is not a valid user name", name));
+    }
+    Ok(())
+}
+
+fn new_token(file: &mut AccountsFile, user: &str, label: Option<String>) -> Result<TokenView, String> {
+    if !file.users.contains_key(user) {
+        return Err(format!("User '{}' not found", user));
+    }
+    if file.tokens.values().filter(|token| token.user == user).count() >= MAX_TOKENS_PER_USER {
+        return Err(format!("A user holds at most {} tokens", MAX_TOKENS_PER_USER));
+    }
+    let secret = format!("{}{}", TOKEN_PREFIX, random_hex(32));
+    let token = Token { id: random_hex(8), user: user.to_string(), label, created: now() };
+    file.tokens.insert(hash(&secret), token.clone());
+    Ok(TokenView { id: token.id, user: token.user, label: token.label, created: token.created, token: Some(secret) })
+}
+
+impl Accounts {
+    /// Reads the accounts saved at `path`; a missing file has none.
+    pub fn load(path: &str) -> Result<Self, String> {
+        let file = match std::fs::read_to_string(path) {
+            Ok(text) => {
+                serde_json::from_str(&text).map_err(|error| format!("Invalid accounts file '{}': {}", path, error))?
+            }
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => AccountsFile::default(),
+            Err(error) => return Err(format!("Cannot read '{}': {}", path, error)),
+        };
+        Ok(Accounts { file, path: Some(path.to_string()) })
+    }
+
+    fn save(&self, file: &AccountsFile) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let text = serde_json::to_string_pretty(file).map_err(|error| error.to_string())?;
+        let temporary = format!("{}.tmp", path);
+        std::fs::write(&temporary, text)
+            .and_then(|_| std::fs::rename(&temporary, path))
+            .map_err(|error| format!("Cannot save accounts to '{}': {}", path, error))
+    }
+
+    /// Applies a change and persists it; nothing changes if saving fails.
+    fn update<R>(&mut self, f: impl FnOnce(&mut AccountsFile) -> Result<R, String>) -> Result<R, String> {
+        let mut file = self.file.clone();
+        let result = f(&mut file)?;
+        self.save(&file)?;
+        self.file = file;
+        Ok(result)
+    }
+
+    pub fn has_admin(&self) -> bool {
+        self.file.users.values().any(|account| account.role == Role::Admin)
+    }
+
+    /// The user a bearer token belongs to.
+    pub fn authenticate(&self, token: &str) -> Option<User> {
+        let token = self.file.tokens.get(&hash(token))?;
+        let account = self.file.users.get(&token.user)?;
+        Some(User { name: token.user.clone(), role: account.role })
+    }
+
+    pub fn users(&self) -> Vec<UserView> {
+        self.file
+            .users
+            .iter()
+            .map(|(name, account)| UserView { name: name.clone(), role: account.role, created: account.created })
+            .collect()
+    }
+
+    /// Creates an account together with its first token.
+    pub fn create_user(&mut self, name: &str, role: Role) -> Result<(UserView, TokenView), String> {
+        check_user_name(name)?;
+        self.update(|file| {
+            if file.users.contains_key(name) {
+                return Err(format!("User '{}' already exists", name));
+            }
+            let created = now();
+            file.users.insert(name.to_string(), Account { role, created });
+            let token = new_token(file, name, Some("initial".to_string()))?;
+            Ok((UserView { name: name.to_string(), role, created }, token))
+        })
+    }
+
+    /// Whether `delete_user` would delete the account, so the caller can clear its data first.
+    pub fn check_delete(&self, name: &str) -> Result<(), String> {
+        let account = self.file.users.get(name).ok_or_else(|| format!("User '{}' not found", name))?;
+        let admins = self.file.users.values().filter(|account| account.role == Role::Admin).count();
+        if account.role == Role::Admin && admins == 1 {
+            return Err("Cannot delete the last admin".to_string());
+        }
+        Ok(())
+    }
+
+    /// Deletes an account and revokes its tokens.
+    pub fn delete_user(&mut self, name: &str) -> Result<UserView, String> {
+        self.check_delete(name)?;
+        self.update(|file| {
+            let account = file.users.remove(name).unwrap();
+            file.tokens.retain(|_, token| token.user != name);
+            Ok(UserView { name: name.to_string(), role: account.role, created: account.created })
+        })
+    }
+
+    /// Tokens of one user, or of everyone.
+    pub fn tokens(&self, user: Option<&str>) -> Vec<TokenView> {
+        let mut tokens: Vec<TokenView> = self
+            .file
+            .tokens
+            .values()
+            .filter(|token| user.is_none_or(|user| token.user == user))
+            .map(|token| TokenView {
+                id: token.id.clone(),
+                user: token.user.clone(),
+                label: token.label.clone(),
+                created: token.created,
+                token: None,
+            })
+            .collect();
+        tokens.sort_by(|a, b| (a.created, &a.id).cmp(&(b.created, &b.id)));
+        tokens
+    }
+
+    /// Issues a new bearer token for `user`; the token is returned once and never again.
+    pub fn issue(&mut self, user: &str, label: Option<String>) -> Result<TokenView, String> {
+        if label.as_ref().is_some_and(|label| label.chars().count() > 100) {
+            return Err("A token label has at most 100 characters".to_string());
+        }
+        self.update(|file| new_token(file, user, label))
+    }
+
+    /// Revokes a token by id. `owner` limits it to that user's tokens.
+    pub fn revoke(&mut self, id: &str, owner: Option<&str>) -> Result<TokenView, String> {
+        self.update(|file| {
+            let key = file
+                .tokens
+                .iter()
+                .find(|(_, token)| token.id == id && owner.is_none_or(|owner| token.user == owner))
+                .map(|(key, _)| key.clone())
+                .ok_or_else(|| format!("Token '{}' not found", id))?;
+            let token = file.tokens.remove(&key).unwrap();
+            Ok(TokenView { id: token.id, user: token.user, label: token.label, created: token.created, token: None })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secret(token: &TokenView) -> &str {
+        token.token.as_deref().unwrap()
+    }
+
+    #[test]
+    fn tokens_authenticate_until_revoked() {
+        let mut accounts = Accounts::default();
+        let (user, initial) = accounts.create_user("alice", Role::User).unwrap();
+        assert_eq!((user.name.as_str(), user.role), ("alice", Role::User));
+        assert!(secret(&initial).starts_with(TOKEN_PREFIX));
+        assert_eq!(accounts.authenticate(secret(&initial)).unwrap().name, "alice");
+        assert!(accounts.authenticate("calc_0000").is_none());
+        assert!(accounts.authenticate("").is_none());
+
+        let second = accounts.issue("alice", Some("laptop".to_string())).unwrap();
+        assert_eq!(accounts.tokens(Some("alice")).len(), 2);
+        assert!(accounts.tokens(Some("alice")).iter().all(|token| token.token.is_none()));
+        accounts.revoke(&initial.id, Some("alice")).unwrap();
+        assert!(accounts.authenticate(secret(&initial)).is_none());
+        assert_eq!(accounts.authenticate(secret(&second)).unwrap().name, "alice");
+        assert!(accounts.revoke(&initial.id, None).is_err());
+    }
+
+    #[test]
+    fn users_can_revoke_only_their_own_tokens() {
+        let mut accounts = Accounts::default();
+        let (_, alice) = accounts.create_user("alice", Role::User).unwrap();
+        accounts.create_user("bob", Role::User).unwrap();
+        let error = accounts.revoke(&alice.id, Some("bob")).unwrap_err();
+        assert_eq!(error, format!("Token '{}' not found", alice.id));
+        assert!(accounts.authenticate(secret(&alice)).is_some());
+        assert_eq!(accounts.revoke(&alice.id, None).unwrap().user, "alice");
+    }
+
+    #[test]
+    fn users_are_created_once_and_deleted_with_their_tokens() {
+        let mut accounts = Accounts::default();
+        let (_, token) = accounts.create_user("alice", Role::User).unwrap();
+        assert_eq!(accounts.create_user("alice", Role::Admin).unwrap_err(), "User 'alice' already exists");
+        assert!(accounts.create_user(ANONYMOUS, Role::User).is_err());
+        assert!(accounts.create_user("a b", Role::User).is_err());
+        assert!(accounts.create_user(&"a".repeat(65), Role::User).is_err());
+
+        assert_eq!(accounts.delete_user("alice").unwrap().name, "alice");
+        assert!(accounts.authenticate(secret(&token)).is_none());
+        assert!(accounts.tokens(None).is_empty());
+        assert_eq!(accounts.delete_user("alice").unwrap_err(), "User 'alice' not found");
+        assert!(accounts.issue("alice", None).is_err());
+    }
+
+    #[test]
+    fn the_last_admin_stays() {
+        let mut accounts = Accounts::default();
+        assert!(!accounts.has_admin());
+        accounts.create_user("root", Role::Admin).unwrap();
+        accounts.create_user("alice", Role::User).unwrap();
+        assert_eq!(accounts.delete_user("root").unwrap_err(), "Cannot delete the last admin");
+        assert!(accounts.has_admin());
+
+        accounts.create_user("deputy", Role::Admin).unwrap();
+        accounts.delete_user("root").unwrap();
+        assert_eq!(accounts.check_delete("deputy").unwrap_err(), "Cannot delete the last admin");
+        assert!(accounts.check_delete("alice").is_ok());
+    }
+
+    #[test]
+    fn tokens_per_user_are_capped() {
+        let mut accounts = Accounts::default();
+        accounts.create_user("alice", Role::User).unwrap();
+        for _ in 1..MAX_TOKENS_PER_USER {
+            accounts.issue("alice", None).unwrap();
+        }
+        let error = accounts.issue("alice", None).unwrap_err();
+        assert_eq!(error, format!("A user holds at most {} tokens", MAX_TOKENS_PER_USER));
+        assert!(accounts.issue("alice", Some("x".repeat(101))).is_err());
+
+        let id = accounts.tokens(Some("alice"))[0].id.clone();
+        accounts.revoke(&id, Some("alice")).unwrap();
+        assert!(accounts.issue("alice", None).is_ok());
+    }
+}
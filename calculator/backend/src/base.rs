@@ -0,0 +1,262 @@
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::{One, Zero};
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::i18n::{
+    Error, ALPHABET_LENGTH, BASE_NON_FINITE, INVALID_BASE, INVALID_DIGIT, NOT_A_DIGIT, NO_DIGITS, REPEATED_DIGIT,
+    UNCLOSED_REPETEND,
+};
+use crate::rational::Q;
+
+/// Digits of bases up to 36; letters are read in either case.
+pub const DIGITS: &str = "0123456789abcdefghijklmnopqrstuvwxyz";
+/// Fraction digits written before giving up on finding where they repeat.
+pub const MAX_FRACTION_DIGITS: usize = 1000;
+
+/// A number written in some base. The fraction is `fraction` followed by `repeating`
+/// repeated forever, so `0.1` in base 3 is fraction `""` and repeating `"0022"`.
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct BaseDigits {
+    pub base: u32,
+    pub negative: bool,
+    pub integer: String,
+    pub fraction: String,
+    pub repeating: String,
+    /// No repeating group was found within `MAX_FRACTION_DIGITS` digits; the fraction
+    /// is cut off there.
+    pub truncated: bool,
+}
+
+/// `-12.3(45)`, with the repeating group in brackets and `…` after a cut-off fraction.
+impl fmt::Display for BaseDigits {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.negative {
+            write!(f, "-")?;
+        }
+        write!(f, "{}", self.integer)?;
+        if !self.fraction.is_empty() || !self.repeating.is_empty() {
+            write!(f, ".{}", self.fraction)?;
+        }
+        if !self.repeating.is_empty() {
+            write!(f, "({})", self.repeating)?;
+        }
+        if self.truncated {
+            write!(f, "…")?;
+        }
+        Ok(())
+    }
+}
+
+pub fn check_base(base: f64) -> Result<u32, Error> {
+    if base.fract() != 0.0 || !(2.0..=36.0).contains(&base) {
+        return Err(INVALID_BASE.with([base.into()]));
+    }
+    Ok(base as u32)
+}
+
+/// Checks a custom alphabet for `base` ahead of using it.
+pub fn check_alphabet(base: u32, alphabet: Option<&str>) -> Result<(), Error> {
+    Alphabet::new(base, alphabet).map(|_| ())
+}
+
+struct Alphabet {
+    digits: Vec<char>,
+    case_insensitive: bool,
+}
+
+impl Alphabet {
+    /// The first `base` of `DIGITS`, or exactly `base` distinct custom digits.
+    fn new(base: u32, custom: Option<&str>) -> Result<Self, Error> {
+        let Some(custom) = custom else {
+            return Ok(Alphabet { digits: DIGITS.chars().take(base as usize).collect(), case_insensitive: true });
+        };
+        let digits: Vec<char> = custom.chars().collect();
+        if digits.len() != base as usize {
+            return Err(ALPHABET_LENGTH.with([base.into(), base.into(), digits.len().into()]));
+        }
+        for (i, &digit) in digits.iter().enumerate() {
+            if matches!(digit, '.' | '-' | '+' | '(' | ')' | '…') || digit.is_whitespace() {
+                return Err(INVALID_DIGIT.with([digit.into()]));
+            }
+            if digits[..i].contains(&digit) {
+                return Err(REPEATED_DIGIT.with([digit.into()]));
+            }
+        }
+        Ok(Alphabet { digits, case_insensitive: false })
+    }
+
+    fn value(&self, digit: char) -> Option<u32> {
+        let digit = if self.case_insensitive { digit.to_ascii_lowercase() } else { digit };
+        self.digits.iter().position(|&d| d == digit).map(|position| position as u32)
+    }
+}
+
+// The simplest fraction that reads back as `value`: the first continued-fraction
+// convergent of its shortest decimal that does. So `0.1` is one tenth rather than the
+// nearest double, and `1/3` a third rather than 0.3333333333333333.
+fn simplest(value: f64) -> Option<Q> {
+    let exact = Q::parse(&format!("{:e}", value))?;
+    let (mut num, mut den) = (exact.num, exact.den);
+    let (mut h, mut h_before) = (BigInt::one(), BigInt::zero());
+    let (mut k, mut k_before) = (BigInt::zero(), BigInt::one());
+    loop {
+        let (term, rest) = num.div_rem(&den);
+        (h, h_before) = (&term * &h + &h_before, h);
+        (k, k_before) = (&term * &k + &k_before, k);
+        let convergent = Q::new(h.clone(), k.clone());
+        if rest.is_zero() || convergent.to_f64() == value {
+            return Some(convergent);
+        }
+        (num, den) = (den, rest);
+    }
+}
+
+/// Writes `value` in `base`, taking it as the simplest fraction it could stand for, so
+/// that the digits of `0.1` or `1/3` repeat as they should.
+pub fn to_base(value: f64, base: u32, alphabet: Option<&str>) -> Result<BaseDigits, Error> {
+    let alphabet = Alphabet::new(base, alphabet)?;
+    if !value.is_finite() {
+        return Err(BASE_NON_FINITE.with([base.into()]));
+    }
+    let exact = simplest(value.abs()).ok_or_else(|| Error::from(format!("Cannot convert {}", value)))?;
+    let (integer, mut remainder) = exact.num.div_rem(&exact.den);
+    let digit = |d: &BigInt| alphabet.digits[d.iter_u32_digits().next().unwrap_or(0) as usize];
+
+    let integer: String = if integer.is_zero() {
+        alphabet.digits[0].to_string()
+    } else {
+        integer.magnitude().to_radix_be(base).iter().map(|&d| alphabet.digits[d as usize]).collect()
+    };
+
+    // Long division; a remainder seen before starts the repeating group again.
+    let mut fraction = Vec::new();
+    let mut seen: HashMap<BigInt, usize> = HashMap::new();
+    let mut repeating_from = None;
+    let mut truncated = false;
+    while !remainder.is_zero() {
+        if let Some(&start) = seen.get(&remainder) {
+            repeating_from = Some(start);
+            break;
+        }
+        if fraction.len() == MAX_FRACTION_DIGITS {
+            truncated = true;
+            break;
+        }
+        seen.insert(remainder.clone(), fraction.len());
+        let (d, rest) = (remainder * base).div_rem(&exact.den);
+        fraction.push(digit(&d));
+        remainder = rest;
+    }
+    let start = repeating_from.unwrap_or(fraction.len());
+    Ok(BaseDigits {
+        base,
+        negative: value < 0.0,
+        integer,
+        fraction: fraction[..start].iter().collect(),
+        repeating: fraction[start..].iter().collect(),
+        truncated,
+    })
+}
+
+/// Reads a number written in `base`, such as `-101.011` or `0.(0022)` with its
+/// repeating group in brackets.
+pub fn from_base(text: &str, base: u32, alphabet: Option<&str>) -> Result<Q, Error> {
+    let alphabet = Alphabet::new(base, alphabet)?;
+    let trimmed = text.trim();
+    let (negative, unsigned) = match trimmed.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, trimmed.strip_prefix('+').unwrap_or(trimmed)),
+    };
+    let (integer, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+    let (fraction, repeating) = match fraction.split_once('(') {
+        Some((fraction, repeating)) => {
+            let repeating = repeating
+                .strip_suffix(')')
+                .filter(|repeating| !repeating.is_empty())
+                .ok_or_else(|| UNCLOSED_REPETEND.with([text.into()]))?;
+            (fraction, repeating)
+        }
+        None => (fraction, ""),
+    };
+    if integer.is_empty() && fraction.is_empty() && repeating.is_empty() {
+        return Err(NO_DIGITS.with([text.into()]));
+    }
+
+    let base_int = BigInt::from(base);
+    let digits = |part: &str| -> Result<BigInt, Error> {
+        part.chars().try_fold(BigInt::zero(), |value, c| {
+            let digit = alphabet.value(c).ok_or_else(|| NOT_A_DIGIT.with([c.into(), base.into()]))?;
+            Ok(value * &base_int + digit)
+        })
+    };
+    let scale = base_int.pow(fraction.chars().count() as u32);
+    let mut value = &Q::int(digits(integer)?) + &Q::new(digits(fraction)?, scale.clone());
+    if !repeating.is_empty() {
+        // 0.(r) in base b is r / (b^k - 1) for a group of k digits.
+        let period = base_int.pow(repeating.chars().count() as u32) - BigInt::one();
+        value = &value + &Q::new(digits(repeating)?, scale * period);
+    }
+    Ok(if negative { -&value } else { value })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn written(value: f64, base: u32) -> String {
+        to_base(value, base, None).unwrap().to_string()
+    }
+
+    fn read(text: &str, base: u32) -> f64 {
+        from_base(text, base, None).unwrap().to_f64()
+    }
+
+    #[test]
+    fn integers_and_terminating_fractions() {
+        assert_eq!(written(0.0, 2), "0");
+        assert_eq!(written(255.0, 16), "ff");
+        assert_eq!(written(5.375, 2), "101.011");
+        assert_eq!(written(-10.5, 2), "-1010.1");
+        assert_eq!(written(-10.5, 3), "-101.(1)");
+        assert_eq!(written(35.0, 36), "z");
+    }
+
+    #[test]
+    fn repeating_groups_are_found() {
+        assert_eq!(written(0.1, 3), "0.(0022)");
+        assert_eq!(written(0.1, 2), "0.0(0011)");
+        assert_eq!(written(1.0 / 3.0, 3), "0.1");
+        assert_eq!(written(1.0 / 3.0, 10), "0.(3)");
+        assert_eq!(written(-22.0 / 7.0, 10), "-3.(142857)");
+        assert_eq!(to_base(0.1, 3, None).unwrap().repeating, "0022");
+        let digits = to_base(0.123456789, 2, None).unwrap();
+        assert!(digits.truncated && digits.repeating.is_empty() && digits.fraction.len() == MAX_FRACTION_DIGITS);
+    }
+
+    #[test]
+    fn reading_inverts_writing() {
+        assert_eq!(read("101.011", 2), 5.375);
+        assert_eq!(read("-FF", 16), -255.0);
+        assert_eq!(read("0.(0022)", 3), 0.1);
+        assert_eq!(read("0.1", 3), 1.0 / 3.0);
+        assert_eq!(read(".(3)", 10), 1.0 / 3.0);
+        assert!(from_base("102", 2, None).unwrap_err().to_string().contains("'2' is not a digit in base 2"));
+        assert!(from_base("0.(", 2, None).is_err());
+        assert!(from_base("-", 2, None).is_err());
+    }
+
+    #[test]
+    fn custom_alphabets() {
+        assert_eq!(to_base(5.0, 2, Some("ab")).unwrap().to_string(), "bab");
+        assert_eq!(from_base("bab", 2, Some("ab")).unwrap().to_f64(), 5.0);
+        assert_eq!(to_base(0.5, 3, Some("零一二")).unwrap().to_string(), "零.(一)");
+        assert!(from_base("BAB", 2, Some("ab")).is_err());
+        assert!(to_base(1.0, 3, Some("ab")).is_err());
+        assert!(to_base(1.0, 2, Some("aa")).is_err());
+        assert!(to_base(1.0, 2, Some("a.")).is_err());
+    }
+}
@@ -0,0 +1,405 @@
+use crate::ast::Span;
+use crate::i18n::{
+    self, Error, AMBIGUOUS_DIVISION, EXPECTED_DECIMALS, EXPECTED_NUMERATOR, INVALID_NUMBER, NUMERAL_OUT_OF_PLACE,
+    NUMERAL_WITHOUT_NUMBER,
+};
+
+/// Rewrites Chinese numerals and operator words into the ASCII expression syntax, e.g.
+/// `一万五千乘以百分之八` becomes `15000 * (8/100)`. Text without Chinese comes back
+/// unchanged, so the parser stays the single source of truth for the grammar.
+pub fn normalize(input: &str) -> Result<String, Error> {
+    Ok(Normalized::new(input)?.text)
+}
+
+/// Normalized text together with where each of its characters came from in the input.
+pub struct Normalized {
+    pub text: String,
+    /// For each character of `text`, the input characters it was written for: all of
+    /// `一万五千` for each digit of `15000`.
+    sources: Vec<Span>,
+    length: usize,
+}
+
+impl Normalized {
+    pub fn new(input: &str) -> Result<Self, Error> {
+        let chars: Vec<char> = input.chars().map(full_width_to_ascii).collect();
+        let output = rewrite(&chars)?;
+
+        // Runs of whitespace become one space, and none is left at either end.
+        let mut text = String::new();
+        let mut sources = Vec::new();
+        for (i, &(c, source)) in output.iter().enumerate() {
+            if c.is_whitespace() {
+                let next = output[i + 1..].iter().find(|(c, _)| !c.is_whitespace());
+                if sources.is_empty() || next.is_none() || output[i - 1].0.is_whitespace() {
+                    continue;
+                }
+                text.push(' ');
+            } else {
+                text.push(c);
+            }
+            sources.push(source);
+        }
+        Ok(Normalized { text, sources, length: chars.len() })
+    }
+
+    /// The input characters behind `span` of the normalized text.
+    pub fn original(&self, span: Span) -> Span {
+        let start = self.sources.get(span.start).map_or(self.length, |source| source.start);
+        match span.end.checked_sub(1).and_then(|last| self.sources.get(last)) {
+            Some(source) if span.end > span.start => Span::new(start, source.end.max(start)),
+            _ => Span::new(start, start),
+        }
+    }
+}
+
+fn emit(output: &mut Vec<(char, Span)>, text: &str, start: usize, end: usize) {
+    output.extend(text.chars().map(|c| (c, Span::new(start, end))));
+}
+
+fn rewrite(chars: &[char]) -> Result<Vec<(char, Span)>, Error> {
+    let mut output = Vec::new();
+    let mut pos = 0;
+
+    while pos < chars.len() {
+        let start = pos;
+
+        // Quoted text such as a digit alphabet `"零一二"` is kept as written.
+        if chars[pos] == '"' {
+            let end = chars[pos + 1..].iter().position(|&c| c == '"').map_or(chars.len(), |offset| pos + offset + 2);
+            output.extend((pos..end).map(|i| (chars[i], Span::new(i, i + 1))));
+            pos = end;
+            continue;
+        }
+
+        if let Some((alias, name)) = function_alias(chars, pos) {
+            pos += alias.chars().count();
+            emit(&mut output, name, start, pos);
+            continue;
+        }
+
+        if is_numeral_start(chars, pos) {
+            let (number, end) = numeral(chars, pos)?;
+            pos = end;
+            if starts_with(chars, pos, "分之") {
+                let (numerator, end) = numeral(chars, pos + 2)
+                    .map_err(|_| EXPECTED_NUMERATOR.with([pos.into()]))?;
+                pos = end;
+                emit(&mut output, &format!("({}/{})", numerator, number), start, pos);
+            } else {
+                emit(&mut output, &number, start, pos);
+            }
+            continue;
+        }
+
+        if starts_with(chars, pos, "的") && is_numeral_start(chars, pos + 1) {
+            let (exponent, end) = numeral(chars, pos + 1)?;
+            if let Some(suffix) = ["次方", "次幂"].iter().find(|s| starts_with(chars, end, s)) {
+                pos = end + suffix.chars().count();
+                emit(&mut output, &format!("^{}", exponent), start, pos);
+                continue;
+            }
+        }
+
+        if starts_with(chars, pos, "根号") {
+            pos += 2;
+            if is_numeral_start(chars, pos) {
+                let (number, end) = numeral(chars, pos)?;
+                pos = end;
+                emit(&mut output, &format!("sqrt({})", number), start, pos);
+            } else {
+                emit(&mut output, "sqrt", start, pos);
+            }
+            continue;
+        }
+
+        // A closing `=` or `等于` asks for the result; inside `<=`, `>=`, `==` or `!=`
+        // the `=` belongs to the operator and is kept.
+        if let Some(phrase) = ["等于", "="].iter().find(|phrase| starts_with(chars, pos, phrase)) {
+            let end = pos + phrase.chars().count();
+            let in_operator = *phrase == "="
+                && (chars.get(end) == Some(&'=') || pos > 0 && matches!(chars[pos - 1], '<' | '>' | '=' | '!'));
+            if !in_operator && chars[end..].iter().all(|c| c.is_whitespace() || matches!(c, '?' | '？' | '。')) {
+                pos = end;
+                continue;
+            }
+        }
+
+        if let Some((phrase, replacement)) = PHRASES.iter().find(|(phrase, _)| starts_with(chars, pos, phrase)) {
+            pos += phrase.chars().count();
+            emit(&mut output, replacement, start, pos);
+            continue;
+        }
+
+        if chars[pos] == '除' {
+            return Err(AMBIGUOUS_DIVISION.with([pos.into()]));
+        }
+
+        output.push((chars[pos], Span::new(pos, pos + 1)));
+        pos += 1;
+    }
+    Ok(output)
+}
+
+// Longest phrases first, so that `乘以` wins over `乘`.
+const PHRASES: &[(&str, &str)] = &[
+    ("等于多少", ""),
+    ("等于几", ""),
+    ("是多少", ""),
+    ("的平方", "^2"),
+    ("的立方", "^3"),
+    ("的阶乘", "!"),
+    ("左括号", "("),
+    ("右括号", ")"),
+    ("加上", " + "),
+    ("减去", " - "),
+    ("乘以", " * "),
+    ("乘上", " * "),
+    ("除以", " / "),
+    ("取余", " % "),
+    ("平方", "^2"),
+    ("立方", "^3"),
+    ("加", " + "),
+    ("减", " - "),
+    ("乘", " * "),
+    ("负", "-"),
+    ("？", ""),
+    ("?", ""),
+    ("。", ""),
+];
+
+// A localized function name at `pos`, if a call follows it; `四舍五入(x)` is `round(x)`,
+// but `四舍五入` on its own is left for the numeral reader.
+fn function_alias(chars: &[char], pos: usize) -> Option<(&'static str, &'static str)> {
+    i18n::FUNCTION_ALIASES.iter().copied().find(|(alias, _)| {
+        let end = pos + alias.chars().count();
+        starts_with(chars, pos, alias) && chars[end..].iter().find(|c| !c.is_whitespace()) == Some(&'(')
+    })
+}
+
+fn full_width_to_ascii(c: char) -> char {
+    match c {
+        '０'..='９' | '＋' | '－' | '＊' | '／' | '％' | '＾' | '（' | '）' | '．' | '，' | '＝' | '！' => {
+            char::from_u32(c as u32 - 0xFEE0).unwrap()
+        }
+        '　' => ' ',
+        _ => c,
+    }
+}
+
+fn starts_with(chars: &[char], pos: usize, phrase: &str) -> bool {
+    phrase.chars().enumerate().all(|(i, c)| chars.get(pos + i) == Some(&c))
+}
+
+fn digit(c: char) -> Option<u32> {
+    Some(match c {
+        '零' | '〇' => 0,
+        '一' | '壹' => 1,
+        '二' | '两' | '贰' => 2,
+        '三' | '叁' => 3,
+        '四' | '肆' => 4,
+        '五' | '伍' => 5,
+        '六' | '陆' => 6,
+        '七' | '柒' => 7,
+        '八' | '捌' => 8,
+        '九' | '玖' => 9,
+        _ => return None,
+    })
+}
+
+fn unit(c: char) -> Option<f64> {
+    Some(match c {
+        '十' | '拾' => 10.0,
+        '百' | '佰' => 100.0,
+        '千' | '仟' => 1e3,
+        '万' => 1e4,
+        '亿' => 1e8,
+        _ => return None,
+    })
+}
+
+fn is_chinese_numeral(c: char) -> bool {
+    digit(c).is_some() || unit(c).is_some()
+}
+
+// A numeral starts at a Chinese digit or unit, or at ASCII digits that run into one
+// (`1.5万`); plain ASCII numbers are left to the parser.
+fn is_numeral_start(chars: &[char], pos: usize) -> bool {
+    let Some(&c) = chars.get(pos) else { return false };
+    if is_chinese_numeral(c) {
+        return true;
+    }
+    if !c.is_ascii_digit() {
+        return false;
+    }
+    let end = chars[pos..]
+        .iter()
+        .position(|c| !(c.is_ascii_digit() || *c == '.'))
+        .map_or(chars.len(), |offset| pos + offset);
+    chars.get(end).is_some_and(|c| unit(*c).is_some() || *c == '点')
+        || starts_with(chars, end, "分之")
+}
+
+/// Reads a numeral such as `三百二十五`, `一万五`, `两千零八`, `三点一四` or `1.5万`
+/// starting at `pos`; returns it as ASCII decimal text with the position after it.
+fn numeral(chars: &[char], pos: usize) -> Result<(String, usize), Error> {
+    let mut total = 0.0; // closed by 亿
+    let mut wan = 0.0; // closed by 万
+    let mut section = 0.0; // 十, 百, 千 within the current 万
+    let mut pending: Option<f64> = None;
+    let mut smallest = f64::INFINITY; // the last of 十, 百, 千 in the current section
+    let mut end = pos;
+
+    while end < chars.len() {
+        let c = chars[end];
+        if let Some(d) = digit(c) {
+            // Consecutive digits read positionally: 二零二六 is 2026.
+            let previous_was_digit = end > pos && digit(chars[end - 1]).is_some();
+            pending = Some(match pending {
+                Some(p) if previous_was_digit => p * 10.0 + d as f64,
+                _ => d as f64,
+            });
+            end += 1;
+        } else if c.is_ascii_digit() {
+            let start = end;
+            while end < chars.len() && (chars[end].is_ascii_digit() || chars[end] == '.') {
+                end += 1;
+            }
+            let text: String = chars[start..end].iter().collect();
+            pending = Some(text.parse().map_err(|_| INVALID_NUMBER.with([text.as_str().into(), start.into()]))?);
+        } else if let Some(u) = unit(c) {
+            if u >= 1e4 {
+                let value = section + pending.take().unwrap_or(0.0);
+                if value == 0.0 && wan == 0.0 && total == 0.0 {
+                    return Err(NUMERAL_WITHOUT_NUMBER.with([c.into(), end.into()]));
+                }
+                // 五万万 or 一万五千万: a 万 may not follow another within the same 亿.
+                if u == 1e4 && (value == 0.0 || wan != 0.0) {
+                    return Err(NUMERAL_OUT_OF_PLACE.with([c.into(), end.into()]));
+                }
+                if u == 1e4 {
+                    wan = value * u;
+                } else {
+                    total = (total + wan + value) * u;
+                    wan = 0.0;
+                }
+                section = 0.0;
+                smallest = f64::INFINITY;
+            } else {
+                // Units within a section get smaller: 三千五百, never 五百三千 or 五十十.
+                if u >= smallest {
+                    return Err(NUMERAL_OUT_OF_PLACE.with([c.into(), end.into()]));
+                }
+                smallest = u;
+                section += pending.take().unwrap_or(1.0) * u;
+            }
+            end += 1;
+        } else {
+            break;
+        }
+    }
+
+    // Colloquial trailing digit: 一万五 is 15000 and 两百五 is 250.
+    if let (Some(p), true) = (pending, end >= pos + 2) {
+        let before = chars[end - 2];
+        if digit(chars[end - 1]).is_some() && before != '零' {
+            if let Some(u) = unit(before).filter(|u| *u >= 100.0) {
+                pending = Some(p * u / 10.0);
+            }
+        }
+    }
+
+    let integer = total + wan + section + pending.unwrap_or(0.0);
+    let mut text = format_plain(integer);
+
+    if chars.get(end) == Some(&'点') {
+        let start = end + 1;
+        let mut fraction = String::new();
+        end = start;
+        while let Some(d) = chars.get(end).and_then(|c| digit(*c).or_else(|| c.to_digit(10))) {
+            fraction.push(char::from_digit(d, 10).unwrap());
+            end += 1;
+        }
+        if fraction.is_empty() {
+            return Err(EXPECTED_DECIMALS.with([(start - 1).into()]));
+        }
+        text = format!("{}.{}", text, fraction);
+    }
+    Ok((text, end))
+}
+
+fn format_plain(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < 1e21 {
+        format!("{:.0}", value)
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compile::CompiledExpression;
+    use crate::parser;
+    use crate::value::Value;
+
+    fn evaluate(input: &str) -> Result<Value, String> {
+        let ast = parser::parse(&normalize(input)?)?;
+        Ok(CompiledExpression::compile(&ast, &[])?.evaluate_checked(&[], false)?.0)
+    }
+
+    #[test]
+    fn comparison_operators_keep_their_equals_sign() {
+        for (input, expected) in [
+            ("2 <= 2", true),
+            ("3 >= 4", false),
+            ("1 == 1.0", true),
+            ("2 != 3", true),
+            ("二 <= 二", true),
+            ("三 >= 三", true),
+            ("一 == 一点零", true),
+            ("二 != 二", false),
+            ("2 <= 2 =", true),
+            ("二 <= 二等于？", true),
+        ] {
+            assert_eq!(evaluate(input), Ok(Value::Boolean(expected)), "{}", input);
+        }
+        assert_eq!(evaluate("if(3 >= 3, 1, 0)"), Ok(Value::Number(1.0)));
+        assert_eq!(evaluate("if(三 >= 三, 1, 0)"), Ok(Value::Number(1.0)));
+        assert_eq!(evaluate("绝对值(-2) <= 2"), Ok(Value::Boolean(true)));
+        assert_eq!(normalize("1 ＝＝ 1").unwrap(), "1 == 1");
+    }
+
+    #[test]
+    fn only_a_closing_equals_is_dropped() {
+        assert_eq!(normalize("1+1=").unwrap(), "1+1");
+        assert_eq!(normalize("一加一等于？").unwrap(), "1 + 1");
+        assert_eq!(normalize("一加一等于多少").unwrap(), "1 + 1");
+        assert!(evaluate("x = 3").is_err());
+    }
+
+    #[test]
+    fn numerals() {
+        for (input, expected) in [
+            ("三百二十五", "325"),
+            ("一万五", "15000"),
+            ("两百五", "250"),
+            ("两千零八", "2008"),
+            ("二零二六", "2026"),
+            ("三点一四", "3.14"),
+            ("1.5万", "15000"),
+            ("一亿二千万", "120000000"),
+            ("三亿五千零一万", "350010000"),
+            ("十二", "12"),
+        ] {
+            assert_eq!(numeral(&input.chars().collect::<Vec<_>>(), 0).unwrap().0, expected, "{}", input);
+        }
+        assert_eq!(normalize("百分之八").unwrap(), "(8/100)");
+        assert_eq!(normalize("二的三次方").unwrap(), "2^3");
+        assert!(normalize("除2").is_err());
+        for malformed in ["五万万", "一万五千万", "五十十", "五百三千", "万"] {
+            assert!(normalize(malformed).is_err(), "{}", malformed);
+        }
+        assert_eq!(normalize("一亿亿").unwrap(), "10000000000000000");
+    }
+}
@@ -0,0 +1,752 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::ast::{BinaryOp, Expr, UnaryOp};
+use crate::base;
+use crate::dates;
+use crate::format::{format_number, FormatOptions};
+use crate::functions;
+use crate::i18n::{
+    Argument, Error, BASE_NOT_CONSTANT, CONSTANT_NON_FINITE, DIVISION_BY_ZERO, FUNCTION, INFINITE, IS_INFINITE, NAN,
+    OPERANDS_MISMATCH, OPERAND_MISMATCH, OTHER_BRANCH, OVERFLOWS, RANGE, TEXT_EXPECTED, TEXT_UNSUPPORTED,
+    TO_BASE_NOT_OUTERMOST, TYPE_MISMATCH, UNCERTAIN_VALUES_UNSUPPORTED, UNDEFINED, UNKNOWN_FUNCTION,
+    VARIABLE_NON_FINITE, WRONG_ARGUMENT_COUNT, WRONG_VALUE_COUNT,
+};
+use crate::registry::{self, Function};
+use crate::value::{Type, Value};
+
+#[derive(Debug, Clone)]
+enum Instr {
+    Const(f64),
+    Load(usize),
+    Unary(UnaryOp),
+    Binary(BinaryOp),
+    Call(Arc<dyn Function>, usize),
+    Jump(usize),
+    JumpIfFalse(usize),
+}
+
+/// An expression lowered to postfix instructions, with free identifiers resolved to
+/// variable slots and constant subtrees folded, so it can be evaluated repeatedly
+/// without re-parsing. Types are checked at compile time; at run time booleans are
+/// carried as 1.0 and 0.0.
+#[derive(Debug, Clone)]
+pub struct CompiledExpression {
+    instructions: Vec<Instr>,
+    variables: Vec<String>,
+    variable_types: Vec<Option<Type>>,
+    result_type: Type,
+    stack_size: usize,
+    /// The base and digit alphabet an outer `to_base(x, b)` asks the result to be
+    /// written in.
+    base: Option<(u32, Option<String>)>,
+}
+
+// Relative tolerance for `==` and `!=`, so that `0.1 + 0.2 == 0.3` holds.
+const EQUALITY_TOLERANCE: f64 = 1e-12;
+
+fn truth(value: bool) -> f64 {
+    if value {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+fn approx_equal(a: f64, b: f64) -> bool {
+    a == b || (a - b).abs() <= EQUALITY_TOLERANCE * a.abs().max(b.abs())
+}
+
+pub fn apply_unary(op: UnaryOp, value: f64) -> Result<f64, Error> {
+    match op {
+        UnaryOp::Neg => Ok(-value),
+        UnaryOp::Plus => Ok(value),
+        UnaryOp::Factorial => functions::factorial(value),
+        UnaryOp::Not => Ok(truth(value == 0.0)),
+    }
+}
+
+pub fn apply_binary(op: BinaryOp, a: f64, b: f64) -> Result<f64, Error> {
+    match op {
+        BinaryOp::Add => Ok(a + b),
+        BinaryOp::Sub => Ok(a - b),
+        BinaryOp::Mul => Ok(a * b),
+        BinaryOp::Div | BinaryOp::Rem if b == 0.0 => Err(DIVISION_BY_ZERO.error()),
+        BinaryOp::Div => Ok(a / b),
+        BinaryOp::Rem => Ok(a % b),
+        BinaryOp::Pow => Ok(a.powf(b)),
+        BinaryOp::PlusMinus => Err(UNCERTAIN_VALUES_UNSUPPORTED.error()),
+        BinaryOp::Less => Ok(truth(a < b)),
+        BinaryOp::LessEqual => Ok(truth(a <= b)),
+        BinaryOp::Greater => Ok(truth(a > b)),
+        BinaryOp::GreaterEqual => Ok(truth(a >= b)),
+        BinaryOp::Equal => Ok(truth(approx_equal(a, b))),
+        BinaryOp::NotEqual => Ok(truth(!approx_equal(a, b))),
+        BinaryOp::And => Ok(truth(a != 0.0 && b != 0.0)),
+        BinaryOp::Or => Ok(truth(a != 0.0 || b != 0.0)),
+        BinaryOp::Xor => Ok(truth((a != 0.0) != (b != 0.0))),
+    }
+}
+
+fn number(value: f64) -> String {
+    format_number(value, &FormatOptions::default()).unwrap_or_default()
+}
+
+fn operand(value: f64) -> String {
+    if value < 0.0 {
+        format!("({})", number(value))
+    } else {
+        number(value)
+    }
+}
+
+// Why an input to the program is not finite.
+fn input_reason(value: f64, variable: Option<&str>) -> Option<Error> {
+    if value.is_finite() {
+        return None;
+    }
+    let what = if value.is_nan() { &NAN } else { &INFINITE };
+    Some(match variable {
+        Some(name) => VARIABLE_NON_FINITE.with([name.into(), what.into()]),
+        None => CONSTANT_NON_FINITE.with([what.into()]),
+    })
+}
+
+// Why an operation produced a NaN from operands that were not NaN, or an infinity from
+// finite operands. None otherwise, including when it only passes a NaN or infinity on.
+fn non_finite_reason(value: f64, operands: &[f64], overflow: bool, describe: impl FnOnce() -> String) -> Option<Error> {
+    if value.is_nan() && !operands.iter().any(|x| x.is_nan()) {
+        Some(UNDEFINED.with([describe().into()]))
+    } else if value.is_infinite() && operands.iter().all(|x| x.is_finite()) {
+        let message = if overflow { &OVERFLOWS } else { &IS_INFINITE };
+        Some(message.with([describe().into()]))
+    } else {
+        None
+    }
+}
+
+fn type_error(context: Argument, expected: Type, actual: Type) -> Error {
+    TYPE_MISMATCH.with([context, expected.phrase().into(), actual.phrase().into()])
+}
+
+fn function_context(name: &str) -> Argument {
+    FUNCTION.with([name.to_string()])
+}
+
+fn check_base_arity(name: &str, args: &[Expr]) -> Result<(), Error> {
+    if !(2..=3).contains(&args.len()) {
+        let expected = RANGE.with(["2".to_string(), "3".to_string()]);
+        return Err(WRONG_ARGUMENT_COUNT.with([name.into(), expected, args.len().into()]));
+    }
+    Ok(())
+}
+
+// The digits or alphabet of `from_base` and `to_base`, the `position`th argument.
+fn text_argument<'a>(name: &str, expr: &'a Expr, position: usize) -> Result<&'a str, Error> {
+    match expr {
+        Expr::Text { value, .. } => Ok(value),
+        _ => Err(TEXT_EXPECTED.with([name.into(), position.into()])),
+    }
+}
+
+impl CompiledExpression {
+    /// Compiles `expr`. Slots for `variables` are allocated first and in order; any other
+    /// free identifier is appended in order of first appearance. A variable takes the
+    /// type its first use calls for, defaulting to a number.
+    pub fn compile(expr: &Expr, variables: &[String]) -> Result<Self, Error> {
+        let untyped: Vec<(String, Option<Type>)> = variables.iter().map(|name| (name.clone(), None)).collect();
+        Self::compile_typed(expr, &untyped)
+    }
+
+    /// Like `compile`, with the type of some variables fixed up front.
+    pub fn compile_typed(expr: &Expr, variables: &[(String, Option<Type>)]) -> Result<Self, Error> {
+        let mut compiled = CompiledExpression {
+            instructions: Vec::new(),
+            variables: variables.iter().map(|(name, _)| name.clone()).collect(),
+            variable_types: variables.iter().map(|(_, variable_type)| *variable_type).collect(),
+            result_type: Type::Number,
+            stack_size: 0,
+            base: None,
+        };
+        // `to_base` only changes how the result is written, so it can only be outermost.
+        let expr = match expr {
+            Expr::Call { name, args, .. } if name == "to_base" => {
+                check_base_arity(name, args)?;
+                let base = compiled.constant_base(name, &args[1])?;
+                let alphabet = args.get(2).map(|arg| text_argument(name, arg, 3)).transpose()?;
+                base::check_alphabet(base, alphabet)?;
+                compiled.base = Some((base, alphabet.map(String::from)));
+                &args[0]
+            }
+            _ => expr,
+        };
+        let (result_type, _) = compiled.emit(expr, Type::Number)?;
+        if compiled.base.is_some() && result_type != Type::Number {
+            return Err(type_error(function_context("to_base"), Type::Number, result_type));
+        }
+        compiled.result_type = result_type;
+        compiled.stack_size = compiled.max_stack_depth();
+        Ok(compiled)
+    }
+
+    pub fn variables(&self) -> &[String] {
+        &self.variables
+    }
+
+    pub fn variable_type(&self, slot: usize) -> Type {
+        self.variable_types[slot].unwrap_or(Type::Number)
+    }
+
+    /// The instruction count, which bounds the steps one evaluation takes: jumps only go forward.
+    pub fn size(&self) -> usize {
+        self.instructions.len()
+    }
+
+    pub fn result_type(&self) -> Type {
+        self.result_type
+    }
+
+    /// The base and alphabet set by `to_base`, if the expression is wrapped in one.
+    pub fn base(&self) -> Option<(u32, Option<&str>)> {
+        self.base.as_ref().map(|(base, alphabet)| (*base, alphabet.as_deref()))
+    }
+
+    /// False when evaluation may call an impure function such as `rand`.
+    pub fn is_pure(&self) -> bool {
+        self.instructions.iter().all(|instr| !matches!(instr, Instr::Call(function, _) if !function.pure()))
+    }
+
+    pub fn evaluate(&self, values: &[f64]) -> Result<Value, Error> {
+        self.evaluate_checked(values, false).map(|(value, _)| value)
+    }
+
+    /// Like `evaluate`, and says why the result is NaN or infinite when it is. In strict
+    /// mode the first NaN or infinity met during evaluation is an error instead.
+    pub fn evaluate_checked(&self, values: &[f64], strict: bool) -> Result<(Value, Option<Error>), Error> {
+        let (raw, reason) = self.evaluate_raw(values, strict)?;
+        Ok((Value::from_raw(raw, self.result_type), reason))
+    }
+
+    fn evaluate_raw(&self, values: &[f64], strict: bool) -> Result<(f64, Option<Error>), Error> {
+        if values.len() != self.variables.len() {
+            return Err(WRONG_VALUE_COUNT.with([self.variables.len().into(), values.len().into()]));
+        }
+
+        let mut stack: Vec<f64> = Vec::with_capacity(self.stack_size);
+        // Parallel to `stack`: for a value that is not finite, the index in `reasons` of
+        // the operation it came from.
+        let mut origins: Vec<Option<usize>> = Vec::with_capacity(self.stack_size);
+        let mut reasons: Vec<Error> = Vec::new();
+        let mut pc = 0;
+        while pc < self.instructions.len() {
+            let (value, operands, reason) = match &self.instructions[pc] {
+                Instr::Const(value) => (*value, 0, input_reason(*value, None)),
+                Instr::Load(slot) => (values[*slot], 0, input_reason(values[*slot], Some(&self.variables[*slot]))),
+                Instr::Unary(op) => {
+                    let a = stack[stack.len() - 1];
+                    let value = apply_unary(*op, a)?;
+                    let reason = non_finite_reason(value, &[a], true, || match op {
+                        UnaryOp::Factorial => format!("{}!", operand(a)),
+                        _ => format!("{}{}", op.symbol(), operand(a)),
+                    });
+                    (value, 1, reason)
+                }
+                Instr::Binary(op) => {
+                    let (a, b) = (stack[stack.len() - 2], stack[stack.len() - 1]);
+                    let value = apply_binary(*op, a, b)?;
+                    // Only 0 to a negative power is infinite without overflowing.
+                    let overflow = !(*op == BinaryOp::Pow && a == 0.0);
+                    let reason = non_finite_reason(value, &[a, b], overflow, || {
+                        format!("{} {} {}", operand(a), op.symbol(), operand(b))
+                    });
+                    (value, 2, reason)
+                }
+                Instr::Call(function, argc) => {
+                    let args = &stack[stack.len() - argc..];
+                    let value = function.evaluate(args)?;
+                    let reason = non_finite_reason(value, args, false, || {
+                        let args: Vec<String> = args.iter().map(|&arg| number(arg)).collect();
+                        format!("{}({})", function.name(), args.join(", "))
+                    });
+                    (value, *argc, reason)
+                }
+                Instr::Jump(target) => {
+                    pc = *target;
+                    continue;
+                }
+                Instr::JumpIfFalse(target) => {
+                    origins.pop();
+                    if stack.pop().unwrap() == 0.0 {
+                        pc = *target;
+                        continue;
+                    }
+                    pc += 1;
+                    continue;
+                }
+            };
+            let base = stack.len() - operands;
+            let origin = match reason {
+                Some(reason) if strict => return Err(reason),
+                Some(reason) => {
+                    reasons.push(reason);
+                    Some(reasons.len() - 1)
+                }
+                // A NaN or infinity carried over from an operand keeps its reason.
+                None if !value.is_finite() => origins[base..].iter().flatten().next().copied(),
+                None => None,
+            };
+            stack.truncate(base);
+            origins.truncate(base);
+            stack.push(value);
+            origins.push(origin);
+            pc += 1;
+        }
+
+        let reason = origins.pop().unwrap().map(|origin| reasons.swap_remove(origin));
+        Ok((stack.pop().unwrap(), reason))
+    }
+
+    fn slot(&mut self, name: &str) -> usize {
+        match self.variables.iter().position(|v| v == name) {
+            Some(slot) => slot,
+            None => {
+                self.variables.push(name.to_string());
+                self.variable_types.push(None);
+                self.variables.len() - 1
+            }
+        }
+    }
+
+    fn expect(&mut self, expr: &Expr, expected: Type, context: impl FnOnce() -> Argument) -> Result<bool, Error> {
+        let (actual, constant) = self.emit(expr, expected)?;
+        if actual != expected {
+            return Err(type_error(context(), expected, actual));
+        }
+        Ok(constant)
+    }
+
+    // Emits `expr` and returns its type and whether it folded to a single constant.
+    // `hint` is the type the surrounding context expects, used to type fresh variables.
+    fn emit(&mut self, expr: &Expr, hint: Type) -> Result<(Type, bool), Error> {
+        match expr {
+            Expr::Number { value, .. } => {
+                self.instructions.push(Instr::Const(*value));
+                Ok((Type::Number, true))
+            }
+            Expr::Boolean { value, .. } => {
+                self.instructions.push(Instr::Const(truth(*value)));
+                Ok((Type::Boolean, true))
+            }
+            Expr::Duration { seconds, .. } => {
+                self.instructions.push(Instr::Const(*seconds));
+                Ok((Type::Duration, true))
+            }
+            Expr::Workdays { count, .. } => {
+                self.instructions.push(Instr::Const(*count));
+                Ok((Type::Workdays, true))
+            }
+            Expr::Date { days, .. } => {
+                self.instructions.push(Instr::Const(*days as f64));
+                Ok((Type::Date, true))
+            }
+            Expr::Variable { name, span } if name == "today" => {
+                self.emit(&Expr::Call { name: name.clone(), args: Vec::new(), span: *span }, hint)
+            }
+            Expr::Variable { name, .. } => {
+                if let Some(value) = functions::constant(name) {
+                    self.instructions.push(Instr::Const(value));
+                    return Ok((Type::Number, true));
+                }
+                let slot = self.slot(name);
+                let variable_type = *self.variable_types[slot].get_or_insert(hint);
+                self.instructions.push(Instr::Load(slot));
+                Ok((variable_type, false))
+            }
+            Expr::Unary { op: UnaryOp::Not, operand, .. } => {
+                let constant = self.expect(operand, Type::Boolean, || "'not'".into())?;
+                self.instructions.push(Instr::Unary(UnaryOp::Not));
+                Ok((Type::Boolean, self.fold(constant, 1)))
+            }
+            Expr::Unary { op, operand, .. } => {
+                let (operand_type, constant) = self.emit(operand, Type::Number)?;
+                let allowed = match op {
+                    UnaryOp::Factorial => operand_type == Type::Number,
+                    _ => matches!(operand_type, Type::Number | Type::Duration | Type::Workdays),
+                };
+                if !allowed {
+                    return Err(OPERAND_MISMATCH.with([op.symbol().into(), operand_type.phrase().into()]));
+                }
+                self.instructions.push(Instr::Unary(*op));
+                Ok((operand_type, self.fold(constant, 1)))
+            }
+            Expr::Binary { op: op @ (BinaryOp::And | BinaryOp::Or), left, right, .. } => {
+                // `a and b` is `if(a, b, false)`; `a or b` is `if(a, true, b)`.
+                let context = if *op == BinaryOp::And { "'and'" } else { "'or'" };
+                let short_circuit = Expr::Boolean { value: *op == BinaryOp::Or, span: expr.span() };
+                let (then_branch, else_branch) =
+                    if *op == BinaryOp::And { (&**right, &short_circuit) } else { (&short_circuit, &**right) };
+                self.conditional(left, then_branch, else_branch, Type::Boolean, context)
+            }
+            Expr::Binary { op: BinaryOp::Xor, left, right, .. } => {
+                let left_constant = self.expect(left, Type::Boolean, || "'xor'".into())?;
+                let right_constant = self.expect(right, Type::Boolean, || "'xor'".into())?;
+                self.instructions.push(Instr::Binary(BinaryOp::Xor));
+                Ok((Type::Boolean, self.fold(left_constant && right_constant, 2)))
+            }
+            Expr::Binary { op, left, right, .. } => {
+                let (left_type, left_constant) = self.emit(left, Type::Number)?;
+                let (right_type, right_constant) = self.emit(right, left_type)?;
+                let mismatch = || {
+                    OPERANDS_MISMATCH.with([op.symbol().into(), left_type.phrase().into(), right_type.phrase().into()])
+                };
+
+                let (result_type, lowering) = if op.is_comparison() {
+                    let ordered = left_type != Type::Boolean || matches!(op, BinaryOp::Equal | BinaryOp::NotEqual);
+                    if left_type != right_type || !ordered {
+                        return Err(mismatch());
+                    }
+                    (Type::Boolean, None)
+                } else {
+                    dates::arithmetic(*op, left_type, right_type).ok_or_else(mismatch)?
+                };
+                match lowering {
+                    Some(builtin) => self.instructions.push(Instr::Call(Arc::new(builtin), 2)),
+                    None => self.instructions.push(Instr::Binary(*op)),
+                }
+                Ok((result_type, self.fold(left_constant && right_constant, 2)))
+            }
+            Expr::Call { name, args, .. } if name == "if" => {
+                if args.len() != 3 {
+                    return Err(WRONG_ARGUMENT_COUNT.with(["if".into(), 3usize.into(), args.len().into()]));
+                }
+                self.conditional(&args[0], &args[1], &args[2], hint, "'if'")
+            }
+            Expr::Call { name, args, .. } if name == "from_base" => {
+                check_base_arity(name, args)?;
+                let digits = text_argument(name, &args[0], 1)?;
+                let base = self.constant_base(name, &args[1])?;
+                let alphabet = args.get(2).map(|arg| text_argument(name, arg, 3)).transpose()?;
+                let value = base::from_base(digits, base, alphabet)?;
+                self.instructions.push(Instr::Const(value.to_f64()));
+                Ok((Type::Number, true))
+            }
+            Expr::Call { name, .. } if name == "to_base" => {
+                Err(TO_BASE_NOT_OUTERMOST.error())
+            }
+            Expr::Call { name, args, .. } => {
+                let function = registry::lookup(name).ok_or_else(|| UNKNOWN_FUNCTION.with([name.into()]))?;
+                function.check_arity(args.len())?;
+                let mut constant = true;
+                for (i, arg) in args.iter().enumerate() {
+                    let expected = function.params().get(i).copied().unwrap_or(Type::Number);
+                    constant &= self.expect(arg, expected, || function_context(name))?;
+                }
+                let (result, pure) = (function.result(), function.pure());
+                self.instructions.push(Instr::Call(function, args.len()));
+                Ok((result, self.fold(constant && pure, args.len())))
+            }
+            Expr::Interval { .. } => Err(UNCERTAIN_VALUES_UNSUPPORTED.error()),
+            Expr::Text { .. } => Err(TEXT_UNSUPPORTED.error()),
+        }
+    }
+
+    // Emits `expr`, which must fold to a constant base, and takes it back off.
+    fn constant_base(&mut self, name: &str, expr: &Expr) -> Result<u32, Error> {
+        if !self.expect(expr, Type::Number, || function_context(name))? {
+            return Err(BASE_NOT_CONSTANT.with([name.into()]));
+        }
+        let Some(Instr::Const(value)) = self.instructions.pop() else { unreachable!() };
+        base::check_base(value)
+    }
+
+    // Emits a short-circuiting conditional. A constant condition keeps only the taken
+    // branch, after type-checking both.
+    fn conditional(
+        &mut self,
+        condition: &Expr,
+        then_branch: &Expr,
+        else_branch: &Expr,
+        hint: Type,
+        context: &str,
+    ) -> Result<(Type, bool), Error> {
+        let condition_constant = self.expect(condition, Type::Boolean, || context.into())?;
+        let other_branch = || OTHER_BRANCH.with([context.to_string()]);
+
+        if condition_constant {
+            let Some(Instr::Const(value)) = self.instructions.pop() else { unreachable!() };
+            let mark = self.instructions.len();
+            let (then_type, then_constant) = self.emit(then_branch, hint)?;
+            let then_end = self.instructions.len();
+            let (else_type, else_constant) = self.emit(else_branch, then_type)?;
+            if then_type != else_type {
+                return Err(type_error(other_branch(), then_type, else_type));
+            }
+            if value != 0.0 {
+                self.instructions.truncate(then_end);
+                return Ok((then_type, then_constant));
+            }
+            self.instructions.drain(mark..then_end);
+            return Ok((else_type, else_constant));
+        }
+
+        let branch = self.instructions.len();
+        self.instructions.push(Instr::JumpIfFalse(0));
+        let (then_type, _) = self.emit(then_branch, hint)?;
+        let jump = self.instructions.len();
+        self.instructions.push(Instr::Jump(0));
+        let else_start = self.instructions.len();
+        let (else_type, _) = self.emit(else_branch, then_type)?;
+        if then_type != else_type {
+            return Err(type_error(other_branch(), then_type, else_type));
+        }
+        let end = self.instructions.len();
+        self.instructions[branch] = Instr::JumpIfFalse(else_start);
+        self.instructions[jump] = Instr::Jump(end);
+        Ok((then_type, false))
+    }
+
+    // Replaces the trailing `operands` constants and their operator with the computed value.
+    fn fold(&mut self, constant: bool, operands: usize) -> bool {
+        if !constant {
+            return false;
+        }
+        let start = self.instructions.len() - operands - 1;
+        let tail = CompiledExpression {
+            instructions: self.instructions.split_off(start),
+            variables: Vec::new(),
+            variable_types: Vec::new(),
+            result_type: Type::Number,
+            stack_size: operands,
+            base: None,
+        };
+        // An error is left for run time, where a short-circuit may never reach it.
+        // So is any NaN or infinity on the way, which keeps its reason and can fail
+        // strict mode there.
+        match tail.evaluate_raw(&[], true) {
+            Ok((value, _)) => {
+                self.instructions.push(Instr::Const(value));
+                true
+            }
+            Err(_) => {
+                self.instructions.extend(tail.instructions);
+                false
+            }
+        }
+    }
+
+    // An upper bound: both branches of a conditional are counted as if they both ran.
+    fn max_stack_depth(&self) -> usize {
+        let mut depth: usize = 0;
+        let mut max = 0;
+        for instr in &self.instructions {
+            match instr {
+                Instr::Const(_) | Instr::Load(_) => depth += 1,
+                Instr::Unary(_) | Instr::Jump(_) => {}
+                Instr::Binary(_) | Instr::JumpIfFalse(_) => depth -= 1,
+                Instr::Call(_, argc) => depth = depth + 1 - argc,
+            }
+            max = max.max(depth);
+        }
+        max
+    }
+}
+
+pub const MAX_CACHED_EXPRESSIONS: usize = 1024;
+
+/// Compiled expressions kept between requests; the oldest entry is evicted once the
+/// cache is full.
+pub struct CompileCache {
+    next_id: u64,
+    entries: BTreeMap<u64, CompiledExpression>,
+}
+
+impl CompileCache {
+    pub fn new() -> Self {
+        Self { next_id: 1, entries: BTreeMap::new() }
+    }
+
+    pub fn insert(&mut self, compiled: CompiledExpression) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.entries.insert(id, compiled);
+        if self.entries.len() > MAX_CACHED_EXPRESSIONS {
+            self.entries.pop_first();
+        }
+        id
+    }
+
+    pub fn get(&self, id: u64) -> Option<&CompiledExpression> {
+        self.entries.get(&id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser;
+
+    fn evaluate(expr: &str, variables: &[(&str, f64)], strict: bool) -> Result<(Value, Option<String>), String> {
+        let names: Vec<String> = variables.iter().map(|(name, _)| name.to_string()).collect();
+        let values: Vec<f64> = variables.iter().map(|(_, value)| *value).collect();
+        let compiled = CompiledExpression::compile(&parser::parse(expr)?, &names)?;
+        let (value, reason) = compiled.evaluate_checked(&values, strict)?;
+        Ok((value, reason.map(String::from)))
+    }
+
+    fn number(expr: &str) -> (f64, Option<String>) {
+        let (value, reason) = evaluate(expr, &[], false).unwrap();
+        (value.as_f64(), reason)
+    }
+
+    fn strict_error(expr: &str) -> String {
+        evaluate(expr, &[], true).unwrap_err()
+    }
+
+    #[test]
+    fn finite_results_have_no_reason() {
+        assert_eq!(number("1 + 2"), (3.0, None));
+        assert_eq!(evaluate("1 + 2", &[], true).unwrap(), (Value::Number(3.0), None));
+    }
+
+    #[test]
+    fn overflow_is_infinite() {
+        let (value, reason) = number("1e308 * 10");
+        assert_eq!(value, f64::INFINITY);
+        assert_eq!(reason.as_deref(), Some("1e308 * 10 overflows"));
+        assert_eq!(number("-1e308 * 10").0, f64::NEG_INFINITY);
+        assert_eq!(number("171!").1.as_deref(), Some("171! overflows"));
+        assert_eq!(strict_error("1e308 * 10"), "1e308 * 10 overflows");
+    }
+
+    #[test]
+    fn poles_are_infinite() {
+        let (value, reason) = number("ln(0)");
+        assert_eq!(value, f64::NEG_INFINITY);
+        assert_eq!(reason.as_deref(), Some("ln(0) is infinite"));
+        assert_eq!(number("0 ^ -1").1.as_deref(), Some("0 ^ (-1) is infinite"));
+        assert_eq!(strict_error("ln(0)"), "ln(0) is infinite");
+    }
+
+    #[test]
+    fn domain_errors_are_nan() {
+        let (value, reason) = number("sqrt(-1)");
+        assert!(value.is_nan());
+        assert_eq!(reason.as_deref(), Some("sqrt(-1) is undefined"));
+        assert_eq!(number("asin(2)").1.as_deref(), Some("asin(2) is undefined"));
+        assert_eq!(number("(-8) ^ 0.5").1.as_deref(), Some("(-8) ^ 0.5 is undefined"));
+        assert_eq!(strict_error("sqrt(-1)"), "sqrt(-1) is undefined");
+    }
+
+    #[test]
+    fn indeterminate_forms_are_nan() {
+        assert_eq!(number("inf - inf").1.as_deref(), Some("∞ - ∞ is undefined"));
+        assert_eq!(number("0 * inf").1.as_deref(), Some("0 * ∞ is undefined"));
+        assert_eq!(number("sin(inf)").1.as_deref(), Some("sin(∞) is undefined"));
+    }
+
+    #[test]
+    fn reason_follows_the_value() {
+        // The overflow on the left does not reach the result; the NaN on the right does.
+        let (value, reason) = number("1 / (1e308 * 10) + sqrt(-4)");
+        assert!(value.is_nan());
+        assert_eq!(reason.as_deref(), Some("sqrt(-4) is undefined"));
+        // Carried through later operations unchanged.
+        assert_eq!(number("exp(1000) * 2 - 1").1.as_deref(), Some("exp(1000) is infinite"));
+    }
+
+    #[test]
+    fn infinity_can_vanish() {
+        assert_eq!(number("1 / inf"), (0.0, None));
+        assert_eq!(number("atan(inf) * 2"), (std::f64::consts::PI, None));
+        assert_eq!(strict_error("1 / inf"), "A constant in the expression is infinite");
+        assert_eq!(strict_error("1 / (1e308 * 10)"), "1e308 * 10 overflows");
+    }
+
+    #[test]
+    fn non_finite_inputs() {
+        let (value, reason) = evaluate("x + 1", &[("x", f64::INFINITY)], false).unwrap();
+        assert_eq!(value, Value::Number(f64::INFINITY));
+        assert_eq!(reason.as_deref(), Some("Variable 'x' is infinite"));
+        let (value, reason) = evaluate("x * 2", &[("x", f64::NAN)], false).unwrap();
+        assert!(value.as_f64().is_nan());
+        assert_eq!(reason.as_deref(), Some("Variable 'x' is NaN"));
+        assert_eq!(evaluate("x", &[("x", f64::NAN)], true).unwrap_err(), "Variable 'x' is NaN");
+    }
+
+    #[test]
+    fn strict_mode_skips_untaken_branches() {
+        assert_eq!(evaluate("if(x > 0, sqrt(x), 0)", &[("x", -1.0)], true).unwrap().0, Value::Number(0.0));
+        assert_eq!(
+            evaluate("if(x > 0, sqrt(x), sqrt(x))", &[("x", -1.0)], true).unwrap_err(),
+            "sqrt(-1) is undefined"
+        );
+    }
+
+    #[test]
+    fn nan_compares_unequal() {
+        assert_eq!(evaluate("sqrt(-1) == sqrt(-1)", &[], false).unwrap(), (Value::Boolean(false), None));
+        assert_eq!(evaluate("sqrt(-1) != sqrt(-1)", &[], false).unwrap(), (Value::Boolean(true), None));
+        assert_eq!(strict_error("sqrt(-1) == sqrt(-1)"), "sqrt(-1) is undefined");
+    }
+
+    #[test]
+    fn division_by_zero_is_an_error() {
+        assert_eq!(evaluate("1 / 0", &[], false).unwrap_err(), "Division by zero");
+        assert_eq!(evaluate("1 / -0", &[], false).unwrap_err(), "Division by zero");
+        assert_eq!(evaluate("5 % 0", &[], true).unwrap_err(), "Division by zero");
+    }
+
+    #[test]
+    fn negative_zero_keeps_its_sign_inside() {
+        assert!(number("-0").0.is_sign_negative());
+        assert_eq!(number("atan2(-0, -1)").0, -std::f64::consts::PI);
+        assert_eq!(number("atan2(0, -1)").0, std::f64::consts::PI);
+        assert_eq!(number("-0 == 0").0, 1.0);
+    }
+
+    #[test]
+    fn sign_of_zero_is_zero() {
+        assert_eq!(number("sign(0)").0, 0.0);
+        assert_eq!(number("sign(-0)").0, 0.0);
+        assert_eq!(number("sign(-3)").0, -1.0);
+        assert!(number("sign(sqrt(-1))").0.is_nan());
+    }
+
+    #[test]
+    fn and_or_short_circuit() {
+        let boolean = |expr: &str, x: f64| evaluate(expr, &[("x", x)], true).map(|(value, _)| value);
+        assert_eq!(boolean("false and 1/0 > 0", 0.0), Ok(Value::Boolean(false)));
+        assert_eq!(boolean("true or 1/0 > 0", 0.0), Ok(Value::Boolean(true)));
+        assert_eq!(boolean("x != 0 and 1/x > 0", 0.0), Ok(Value::Boolean(false)));
+        assert_eq!(boolean("x == 0 or 1/x > 0", 0.0), Ok(Value::Boolean(true)));
+        assert_eq!(boolean("x != 0 and 1/x > 0", 2.0), Ok(Value::Boolean(true)));
+        assert_eq!(boolean("x == 0 or sqrt(x) > 0", -1.0).unwrap_err(), "sqrt(-1) is undefined");
+        assert_eq!(boolean("true and 1/0 > 0", 0.0).unwrap_err(), "Division by zero");
+        assert_eq!(boolean("not (x > 0) xor true", 1.0), Ok(Value::Boolean(true)));
+    }
+
+    #[test]
+    fn if_takes_either_branch() {
+        let choose = |x: f64| evaluate("if(x > 0, 10, 20)", &[("x", x)], false).unwrap().0;
+        assert_eq!((choose(1.0), choose(-1.0)), (Value::Number(10.0), Value::Number(20.0)));
+        assert_eq!(number("if(true, 1, 2)").0, 1.0);
+        assert_eq!(number("if(false, 1, 2)").0, 2.0);
+        assert_eq!(number("if(false, 1/0, 2)").0, 2.0);
+        assert_eq!(number("if(1 > 2, 1, if(2 > 1, 3, 4))").0, 3.0);
+        assert_eq!(evaluate("if(2, 1, 0)", &[], false).unwrap_err(), "'if' expects a boolean, got a number");
+        assert_eq!(evaluate("if(1 > 0, 2)", &[], false).unwrap_err(), "Function 'if' expects 3 argument(s), got 2");
+    }
+
+    #[test]
+    fn operands_are_type_checked() {
+        let error = |expr: &str| evaluate(expr, &[], false).unwrap_err();
+        assert_eq!(error("1 + true"), "Cannot apply '+' to a number and a boolean");
+        assert_eq!(error("-(1 > 0)"), "Cannot apply '-' to a boolean");
+        assert_eq!(error("not 1"), "'not' expects a boolean, got a number");
+        assert_eq!(error("1 and true"), "'and' expects a boolean, got a number");
+        assert_eq!(error("true or 1"), "The other branch of 'or' expects a boolean, got a number");
+        assert_eq!(error("true xor 1"), "'xor' expects a boolean, got a number");
+        assert_eq!(error("if(1 > 0, 1, true)"), "The other branch of 'if' expects a number, got a boolean");
+        assert_eq!(error("sqrt(1 > 0)"), "Function 'sqrt' expects a number, got a boolean");
+    }
+}
@@ -0,0 +1,216 @@
+use lazy_static::lazy_static;
+use serde::Deserialize;
+use std::net::IpAddr;
+use std::sync::RwLock;
+
+use crate::table::MAX_TABLE_ROWS;
+
+pub const DEFAULT_CONFIG_PATH: &str = "calculator.json";
+
+/// Limits on what one request may ask for.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Limits {
+    /// Largest request body accepted, in bytes.
+    pub max_body_bytes: u64,
+    /// Longest expression `/calculate` evaluates, in characters.
+    pub max_expression_length: usize,
+    pub max_table_rows: usize,
+    pub max_user_functions: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_body_bytes: 1024 * 1024,
+            max_expression_length: 10_000,
+            max_table_rows: MAX_TABLE_ROWS,
+            max_user_functions: crate::MAX_USER_FUNCTIONS,
+        }
+    }
+}
+
+/// Server settings: defaults, then the config file, then `CALCULATOR_*` environment
+/// variables, each overriding the one before.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub bind: IpAddr,
+    pub port: u16,
+    /// Origins allowed to call the API from a browser; empty or `*` allows any.
+    pub cors_origins: Vec<String>,
+    /// Serves requests without a token as the anonymous user. Unset, that is only done
+    /// while listening on a loopback address; see `allows_anonymous`.
+    pub anonymous: Option<bool>,
+    pub memory_file: String,
+    pub accounts_file: String,
+    /// Expression-defined functions to load at startup.
+    pub functions_file: Option<String>,
+    pub limits: Limits,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            bind: IpAddr::from([127, 0, 0, 1]),
+            port: 3030,
+            cors_origins: Vec::new(),
+            anonymous: None,
+            memory_file: "calculator-memory.json".to_string(),
+            accounts_file: "calculator-accounts.json".to_string(),
+            functions_file: None,
+            limits: Limits::default(),
+        }
+    }
+}
+
+lazy_static! {
+    static ref LIMITS: RwLock<Limits> = RwLock::new(Limits::default());
+}
+
+/// The limits in effect.
+pub fn limits() -> Limits {
+    *LIMITS.read().unwrap()
+}
+
+pub fn set_limits(limits: Limits) {
+    *LIMITS.write().unwrap() = limits;
+}
+
+impl Config {
+    /// Reads the file named by `CALCULATOR_CONFIG`, else `calculator.json` if there is
+    /// one, and applies the environment on top.
+    pub fn load() -> Result<Self, String> {
+        let named = std::env::var("CALCULATOR_CONFIG").ok();
+        let path = named.as_deref().unwrap_or(DEFAULT_CONFIG_PATH);
+        let mut config = match std::fs::read_to_string(path) {
+            Ok(text) => Config::parse(&text).map_err(|error| format!("Invalid config file '{}': {}", path, error))?,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound && named.is_none() => Config::default(),
+            Err(error) => return Err(format!("Cannot read '{}': {}", path, error)),
+        };
+        config.apply(|name| std::env::var(name).ok())?;
+        config.check()?;
+        Ok(config)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        serde_json::from_str(text).map_err(|error| error.to_string())
+    }
+
+    /// Overrides settings from variables such as `CALCULATOR_PORT`; `var` looks one up.
+    pub fn apply(&mut self, var: impl Fn(&str) -> Option<String>) -> Result<(), String> {
+        fn parsed<T: std::str::FromStr>(name: &str, value: String) -> Result<T, String>
+        where
+            T::Err: std::fmt::Display,
+        {
+            value.trim().parse().map_err(|error| format!("Invalid {} '{}': {}", name, value, error))
+        }
+
+        if let Some(value) = var("CALCULATOR_BIND") {
+            self.bind = parsed("CALCULATOR_BIND", value)?;
+        }
+        if let Some(value) = var("CALCULATOR_PORT") {
+            self.port = parsed("CALCULATOR_PORT", value)?;
+        }
+        if let Some(value) = var("CALCULATOR_CORS_ORIGINS") {
+            self.cors_origins =
+                value.split(',').map(str::trim).filter(|origin| !origin.is_empty()).map(String::from).collect();
+        }
+        if let Some(value) = var("CALCULATOR_ANONYMOUS") {
+            self.anonymous = match value.trim().to_ascii_lowercase().as_str() {
+                "1" | "true" | "yes" => Some(true),
+                "0" | "false" | "no" => Some(false),
+                _ => return Err(format!("Invalid CALCULATOR_ANONYMOUS '{}': expected true or false", value)),
+            };
+        }
+        if let Some(value) = var("CALCULATOR_MEMORY") {
+            self.memory_file = value;
+        }
+        if let Some(value) = var("CALCULATOR_ACCOUNTS") {
+            self.accounts_file = value;
+        }
+        if let Some(value) = var("CALCULATOR_FUNCTIONS") {
+            self.functions_file = Some(value);
+        }
+        if let Some(value) = var("CALCULATOR_MAX_BODY_BYTES") {
+            self.limits.max_body_bytes = parsed("CALCULATOR_MAX_BODY_BYTES", value)?;
+        }
+        if let Some(value) = var("CALCULATOR_MAX_EXPRESSION_LENGTH") {
+            self.limits.max_expression_length = parsed("CALCULATOR_MAX_EXPRESSION_LENGTH", value)?;
+        }
+        if let Some(value) = var("CALCULATOR_MAX_TABLE_ROWS") {
+            self.limits.max_table_rows = parsed("CALCULATOR_MAX_TABLE_ROWS", value)?;
+        }
+        if let Some(value) = var("CALCULATOR_MAX_USER_FUNCTIONS") {
+            self.limits.max_user_functions = parsed("CALCULATOR_MAX_USER_FUNCTIONS", value)?;
+        }
+        Ok(())
+    }
+
+    fn check(&self) -> Result<(), String> {
+        for origin in &self.cors_origins {
+            let host = origin.strip_prefix("https://").or_else(|| origin.strip_prefix("http://"));
+            let valid = origin == "*" || host.is_some_and(|host| !host.is_empty() && !host.contains('/'));
+            if !valid {
+                return Err(format!("Invalid CORS origin '{}': expected e.g. https://example.com", origin));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether requests without a token are served. Every server has at least the admin
+    /// account, so a server reachable from other machines requires tokens unless
+    /// `anonymous` is set explicitly.
+    pub fn allows_anonymous(&self) -> bool {
+        self.anonymous.unwrap_or(self.bind.is_loopback())
+    }
+
+    pub fn any_origin(&self) -> bool {
+        self.cors_origins.is_empty() || self.cors_origins.iter().any(|origin| origin == "*")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_settings_fill_in_defaults() {
+        let config = Config::parse(r#"{ "port": 8080, "limits": { "max_table_rows": 50 } }"#).unwrap();
+        assert_eq!(config.port, 8080);
+        assert_eq!(config.bind, IpAddr::from([127, 0, 0, 1]));
+        assert_eq!(config.limits.max_table_rows, 50);
+        assert_eq!(config.limits.max_user_functions, 100);
+        assert!(Config::parse(r#"{ "prot": 8080 }"#).is_err());
+    }
+
+    #[test]
+    fn environment_overrides_the_file() {
+        let mut config = Config::parse(r#"{ "port": 8080, "anonymous": true }"#).unwrap();
+        let environment = [
+            ("CALCULATOR_PORT", "9000"),
+            ("CALCULATOR_BIND", "127.0.0.1"),
+            ("CALCULATOR_ANONYMOUS", "false"),
+            ("CALCULATOR_CORS_ORIGINS", "https://a.example, http://localhost:8000"),
+        ];
+        let var = |name: &str| environment.iter().find(|(key, _)| *key == name).map(|(_, value)| value.to_string());
+        config.apply(var).unwrap();
+        assert_eq!(config.port, 9000);
+        assert_eq!(config.bind, IpAddr::from([127, 0, 0, 1]));
+        assert_eq!(config.anonymous, Some(false));
+        assert_eq!(config.cors_origins, ["https://a.example", "http://localhost:8000"]);
+        assert!(config.check().is_ok());
+
+        let error = config.apply(|name| (name == "CALCULATOR_PORT").then(|| "http".to_string())).unwrap_err();
+        assert!(error.starts_with("Invalid CALCULATOR_PORT 'http'"), "{}", error);
+    }
+
+    #[test]
+    fn anonymous_access_defaults_to_local_servers_only() {
+        assert!(Config::default().allows_anonymous());
+        assert!(Config::parse(r#"{ "bind": "::1" }"#).unwrap().allows_anonymous());
+        assert!(!Config::parse(r#"{ "bind": "0.0.0.0" }"#).unwrap().allows_anonymous());
+        assert!(Config::parse(r#"{ "bind": "0.0.0.0", "anonymous": true }"#).unwrap().allows_anonymous());
+        assert!(!Config::parse(r#"{ "anonymous": false }"#).unwrap().allows_anonymous());
+    }
+}
@@ -0,0 +1,386 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::functions::Builtin;
+use crate::i18n::{Error, DATE_OUT_OF_RANGE, INVALID_CALENDAR_DATE, INVALID_DATE, WHOLE_DAYS, WHOLE_WORKDAYS};
+use crate::registry::Function;
+use crate::value::Type;
+
+pub const SECONDS_PER_DAY: f64 = 86_400.0;
+/// Dates are kept to four-digit years, as they are written.
+pub const MIN_YEAR: i64 = 1;
+pub const MAX_YEAR: i64 = 9999;
+const FIRST_DAY: i64 = days_from_civil(MIN_YEAR, 1, 1);
+const LAST_DAY: i64 = days_from_civil(MAX_YEAR, 12, 31);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Unit {
+    /// A fixed length, in seconds.
+    Seconds(f64),
+    Workdays,
+}
+
+/// Duration units. Short forms only count when written directly after the number
+/// (`30m`); spelled-out forms may also follow a space (`90 days`).
+pub fn unit(word: &str, spaced: bool) -> Option<Unit> {
+    let unit = match word {
+        "ms" if !spaced => Unit::Seconds(0.001),
+        "s" | "sec" if !spaced => Unit::Seconds(1.0),
+        "m" | "min" if !spaced => Unit::Seconds(60.0),
+        "h" | "hr" if !spaced => Unit::Seconds(3600.0),
+        "d" if !spaced => Unit::Seconds(SECONDS_PER_DAY),
+        "w" | "wk" if !spaced => Unit::Seconds(7.0 * SECONDS_PER_DAY),
+        "millisecond" | "milliseconds" => Unit::Seconds(0.001),
+        "second" | "seconds" => Unit::Seconds(1.0),
+        "minute" | "minutes" => Unit::Seconds(60.0),
+        "hour" | "hours" => Unit::Seconds(3600.0),
+        "day" | "days" => Unit::Seconds(SECONDS_PER_DAY),
+        "week" | "weeks" => Unit::Seconds(7.0 * SECONDS_PER_DAY),
+        "workday" | "workdays" => Unit::Workdays,
+        _ => return None,
+    };
+    Some(unit)
+}
+
+// Howard Hinnant's civil-calendar algorithms (proleptic Gregorian).
+pub const fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let m = month as i64;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if (year % 4 == 0 && year % 100 != 0) || year % 400 == 0 => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+pub fn date(year: i64, month: u32, day: u32) -> Result<i64, Error> {
+    if !(MIN_YEAR..=MAX_YEAR).contains(&year) {
+        return Err(DATE_OUT_OF_RANGE.with([MIN_YEAR.into(), MAX_YEAR.into()]));
+    }
+    if !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+        return Err(INVALID_DATE.with([format!("{:04}-{:02}-{:02}", year, month, day).into()]));
+    }
+    Ok(days_from_civil(year, month, day))
+}
+
+/// Monday is 0, Sunday is 6. 1970-01-01 was a Thursday.
+fn weekday(days: i64) -> i64 {
+    (days + 3).rem_euclid(7)
+}
+
+pub fn format_date(days: f64) -> String {
+    let (year, month, day) = civil_from_days(days as i64);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// `5400` seconds reads as `1h 30m`; fractional seconds keep up to three decimals.
+pub fn format_duration(seconds: f64) -> String {
+    if !seconds.is_finite() {
+        return format!("{}s", seconds);
+    }
+    let sign = if seconds < 0.0 { "-" } else { "" };
+    let mut rest = (seconds.abs() * 1000.0).round() / 1000.0;
+    let mut parts = Vec::new();
+    for (size, suffix) in [(SECONDS_PER_DAY, "d"), (3600.0, "h"), (60.0, "m")] {
+        let count = (rest / size).floor();
+        if count > 0.0 {
+            parts.push(format!("{}{}", count, suffix));
+            rest -= count * size;
+        }
+    }
+    if rest > 0.0 || parts.is_empty() {
+        let text = format!("{:.3}", rest);
+        parts.push(format!("{}s", text.trim_end_matches('0').trim_end_matches('.')));
+    }
+    format!("{}{}", sign, parts.join(" "))
+}
+
+// The result of date arithmetic, if it is still within the supported years.
+fn in_range(days: f64) -> Result<f64, Error> {
+    if !(FIRST_DAY as f64..=LAST_DAY as f64).contains(&days) {
+        return Err(DATE_OUT_OF_RANGE.with([MIN_YEAR.into(), MAX_YEAR.into()]));
+    }
+    Ok(days)
+}
+
+fn whole_days(seconds: f64) -> Result<f64, Error> {
+    let days = seconds / SECONDS_PER_DAY;
+    if days.fract() != 0.0 {
+        return Err(WHOLE_DAYS.error());
+    }
+    Ok(days)
+}
+
+fn whole_count(count: f64) -> Result<i64, Error> {
+    if count.fract() != 0.0 || count.abs() > 1e7 {
+        return Err(WHOLE_WORKDAYS.error());
+    }
+    Ok(count as i64)
+}
+
+/// Moves `count` working days (Monday to Friday) from `start`. A weekend start counts
+/// from the adjacent Friday (going forward) or Monday (going back).
+fn add_workdays(start: f64, count: f64) -> Result<f64, Error> {
+    let count = whole_count(count)?;
+    let mut day = start as i64;
+    if count > 0 && weekday(day) >= 5 {
+        day -= weekday(day) - 4;
+    } else if count < 0 && weekday(day) >= 5 {
+        day += 7 - weekday(day);
+    }
+    day += count / 5 * 7;
+    let step = count.signum();
+    let mut remaining = count % 5;
+    while remaining != 0 {
+        day += step;
+        if weekday(day) < 5 {
+            remaining -= step;
+        }
+    }
+    in_range(day as f64)
+}
+
+/// Working days in `[from, to)`, negative when `to` is earlier.
+fn count_workdays(from: f64, to: f64) -> f64 {
+    let (from, to) = (from as i64, to as i64);
+    if to < from {
+        return -count_workdays(to as f64, from as f64);
+    }
+    let span = to - from;
+    let mut count = span / 7 * 5;
+    for offset in 0..span % 7 {
+        if weekday(from + span / 7 * 7 + offset) < 5 {
+            count += 1;
+        }
+    }
+    count as f64
+}
+
+fn today() -> f64 {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    (seconds / 86_400) as f64
+}
+
+pub static DATE_PLUS_DURATION: Builtin =
+    Builtin { name: "+", min_args: 2, max_args: Some(2), pure: true, apply: |args| in_range(args[0] + whole_days(args[1])?) };
+pub static DURATION_PLUS_DATE: Builtin =
+    Builtin { name: "+", min_args: 2, max_args: Some(2), pure: true, apply: |args| in_range(whole_days(args[0])? + args[1]) };
+pub static DATE_MINUS_DURATION: Builtin =
+    Builtin { name: "-", min_args: 2, max_args: Some(2), pure: true, apply: |args| in_range(args[0] - whole_days(args[1])?) };
+pub static DATE_MINUS_DATE: Builtin =
+    Builtin { name: "-", min_args: 2, max_args: Some(2), pure: true, apply: |args| Ok((args[0] - args[1]) * SECONDS_PER_DAY) };
+pub static DATE_PLUS_WORKDAYS: Builtin =
+    Builtin { name: "+", min_args: 2, max_args: Some(2), pure: true, apply: |args| add_workdays(args[0], args[1]) };
+pub static WORKDAYS_PLUS_DATE: Builtin =
+    Builtin { name: "+", min_args: 2, max_args: Some(2), pure: true, apply: |args| add_workdays(args[1], args[0]) };
+pub static DATE_MINUS_WORKDAYS: Builtin =
+    Builtin { name: "-", min_args: 2, max_args: Some(2), pure: true, apply: |args| add_workdays(args[0], -args[1]) };
+
+/// A date function together with the types of its parameters and result.
+#[derive(Debug)]
+pub struct DateFunction {
+    pub builtin: Builtin,
+    pub params: &'static [Type],
+    pub result: Type,
+}
+
+pub static FUNCTIONS: &[DateFunction] = &[
+    DateFunction {
+        builtin: Builtin { name: "today", min_args: 0, max_args: Some(0), pure: false, apply: |_| Ok(today()) },
+        params: &[],
+        result: Type::Date,
+    },
+    DateFunction {
+        builtin: Builtin {
+            name: "date",
+            min_args: 3,
+            max_args: Some(3),
+            pure: true,
+            apply: |args| {
+                if args.iter().any(|a| a.fract() != 0.0) || !(1.0..=12.0).contains(&args[1]) || args[2] < 1.0 {
+                    return Err(INVALID_CALENDAR_DATE.error());
+                }
+                Ok(date(args[0] as i64, args[1] as u32, args[2] as u32)? as f64)
+            },
+        },
+        params: &[Type::Number, Type::Number, Type::Number],
+        result: Type::Date,
+    },
+    DateFunction {
+        builtin: Builtin {
+            name: "workdays",
+            min_args: 2,
+            max_args: Some(2),
+            pure: true,
+            apply: |args| Ok(count_workdays(args[0], args[1])),
+        },
+        params: &[Type::Date, Type::Date],
+        result: Type::Workdays,
+    },
+    DateFunction {
+        builtin: Builtin {
+            name: "weekday",
+            min_args: 1,
+            max_args: Some(1),
+            pure: true,
+            apply: |args| Ok((weekday(args[0] as i64) + 1) as f64),
+        },
+        params: &[Type::Date],
+        result: Type::Number,
+    },
+];
+
+impl Function for DateFunction {
+    fn name(&self) -> &str {
+        self.builtin.name
+    }
+
+    fn arity(&self) -> (usize, Option<usize>) {
+        self.builtin.arity()
+    }
+
+    fn params(&self) -> &[Type] {
+        self.params
+    }
+
+    fn result(&self) -> Type {
+        self.result
+    }
+
+    fn pure(&self) -> bool {
+        self.builtin.pure
+    }
+
+    fn signature(&self) -> String {
+        self.builtin.signature()
+    }
+
+    fn doc(&self) -> &str {
+        self.builtin.doc()
+    }
+
+    fn evaluate(&self, args: &[f64]) -> Result<f64, Error> {
+        (self.builtin.apply)(args)
+    }
+}
+
+/// The operator overloads for typed values: the result type, and the builtin that
+/// replaces the plain `f64` operator where the representations differ (dates are
+/// days, durations seconds).
+pub fn arithmetic(op: crate::ast::BinaryOp, left: Type, right: Type) -> Option<(Type, Option<&'static Builtin>)> {
+    use crate::ast::BinaryOp::*;
+    use Type::*;
+
+    Some(match (op, left, right) {
+        (Add | Sub | Mul | Div | Rem | Pow, Number, Number) => (Number, None),
+        (Add | Sub | Rem, Duration, Duration) | (Mul, Duration, Number) | (Mul, Number, Duration) | (Div, Duration, Number) => {
+            (Duration, None)
+        }
+        (Add | Sub, Workdays, Workdays) | (Mul, Workdays, Number) | (Mul, Number, Workdays) | (Div, Workdays, Number) => {
+            (Workdays, None)
+        }
+        (Div, Duration, Duration) | (Div, Workdays, Workdays) => (Number, None),
+        (Add, Date, Duration) => (Date, Some(&DATE_PLUS_DURATION)),
+        (Add, Duration, Date) => (Date, Some(&DURATION_PLUS_DATE)),
+        (Sub, Date, Duration) => (Date, Some(&DATE_MINUS_DURATION)),
+        (Sub, Date, Date) => (Duration, Some(&DATE_MINUS_DATE)),
+        (Add, Date, Workdays) => (Date, Some(&DATE_PLUS_WORKDAYS)),
+        (Add, Workdays, Date) => (Date, Some(&WORKDAYS_PLUS_DATE)),
+        (Sub, Date, Workdays) => (Date, Some(&DATE_MINUS_WORKDAYS)),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compile::CompiledExpression;
+    use crate::format::FormatOptions;
+    use crate::parser;
+
+    fn evaluate(expr: &str) -> Result<String, String> {
+        let compiled = CompiledExpression::compile(&parser::parse(expr)?, &[])?;
+        let (value, _) = compiled.evaluate_checked(&[], false)?;
+        Ok(value.format(&FormatOptions::default())?)
+    }
+
+    #[test]
+    fn dates_round_trip_through_days() {
+        for (year, month, day) in [(1, 1, 1), (1970, 1, 1), (2000, 2, 29), (2026, 3, 1), (9999, 12, 31)] {
+            let days = date(year, month, day).unwrap();
+            assert_eq!(civil_from_days(days), (year, month, day));
+        }
+        assert_eq!(date(1970, 1, 1).unwrap(), 0);
+        assert!(date(2026, 2, 29).is_err());
+        assert!(date(2026, 13, 1).is_err());
+        assert_eq!(evaluate("date(2024, 2, 29)").unwrap(), "2024-02-29");
+        assert!(evaluate("date(2024, 2, 30)").is_err());
+        assert!(evaluate("date(2024, 1.5, 1)").is_err());
+    }
+
+    #[test]
+    fn dates_move_by_durations() {
+        assert_eq!(evaluate("2026-03-01 + 30 days").unwrap(), "2026-03-31");
+        assert_eq!(evaluate("2 weeks + 2026-03-01").unwrap(), "2026-03-15");
+        assert_eq!(evaluate("2026-03-01 - 1 day").unwrap(), "2026-02-28");
+        assert_eq!(evaluate("2026-03-01 - 2025-03-01").unwrap(), "365d");
+        assert!(evaluate("2026-03-01 + 1h").is_err());
+    }
+
+    #[test]
+    fn workdays_skip_weekends() {
+        // 2026-03-06 is a Friday.
+        assert_eq!(evaluate("2026-03-06 + 1 workday").unwrap(), "2026-03-09");
+        assert_eq!(evaluate("2026-03-09 - 1 workday").unwrap(), "2026-03-06");
+        assert_eq!(evaluate("2026-03-07 + 5 workdays").unwrap(), "2026-03-13");
+        assert_eq!(evaluate("workdays(2026-03-02, 2026-03-16)").unwrap(), "10 workdays");
+        assert_eq!(evaluate("workdays(2026-03-16, 2026-03-02)").unwrap(), "-10 workdays");
+        assert_eq!(evaluate("weekday(2026-03-06)").unwrap(), "5");
+        assert!(evaluate("2026-03-06 + 1.5 workdays").is_err());
+    }
+
+    #[test]
+    fn durations_read_in_the_largest_units() {
+        assert_eq!(format_duration(5400.0), "1h 30m");
+        assert_eq!(format_duration(-90061.5), "-1d 1h 1m 1.5s");
+        assert_eq!(format_duration(0.0), "0s");
+        assert_eq!(evaluate("1h30m + 45 minutes").unwrap(), "2h 15m");
+    }
+
+    #[test]
+    fn dates_outside_four_digit_years_are_refused() {
+        let out_of_range = DATE_OUT_OF_RANGE.with([MIN_YEAR.into(), MAX_YEAR.into()]).to_string();
+        assert_eq!(date(0, 12, 31).unwrap_err().to_string(), out_of_range);
+        assert_eq!(date(10_000, 1, 1).unwrap_err().to_string(), out_of_range);
+        assert_eq!(evaluate("date(1e17, 1, 1)").unwrap_err(), out_of_range);
+        assert_eq!(evaluate("2026-03-01 + 1e20 days").unwrap_err(), out_of_range);
+        assert_eq!(evaluate("2026-03-01 + 1e15 days").unwrap_err(), out_of_range);
+        assert_eq!(evaluate("0001-01-01 - 1 day").unwrap_err(), out_of_range);
+        assert_eq!(evaluate("9999-12-31 + 1 workday").unwrap_err(), out_of_range);
+        assert_eq!(evaluate("9999-12-31 - 0001-01-01").unwrap(), "3652058d");
+    }
+}
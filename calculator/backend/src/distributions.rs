@@ -0,0 +1,340 @@
+use std::f64::consts::{PI, SQRT_2};
+
+use crate::i18n::{
+    Error, Message, Phrase, COUNT_DOMAIN, DEGREES_OF_FREEDOM_DOMAIN, NUMBER_OF_TRIALS, PROBABILITY_DOMAIN, RATE_DOMAIN,
+    STANDARD_DEVIATION_DOMAIN,
+};
+use crate::special::{beta_inc, erfc, gamma_p, gamma_q, ln_gamma};
+
+fn check(condition: bool, message: &'static Message) -> Result<(), Error> {
+    if condition {
+        Ok(())
+    } else {
+        Err(message.error())
+    }
+}
+
+fn check_probability(p: f64) -> Result<(), Error> {
+    check((0.0..=1.0).contains(&p), &PROBABILITY_DOMAIN)
+}
+
+fn check_count(n: f64, name: &'static Phrase) -> Result<(), Error> {
+    if n >= 0.0 && n.fract() == 0.0 {
+        Ok(())
+    } else {
+        Err(COUNT_DOMAIN.with([name.into()]))
+    }
+}
+
+// Safeguarded Newton iteration on a finite bracket [lo, hi] that contains the quantile.
+fn invert_continuous(p: f64, cdf: impl Fn(f64) -> f64, pdf: impl Fn(f64) -> f64, mut lo: f64, mut hi: f64) -> f64 {
+    let mut x = 0.5 * (lo + hi);
+    for _ in 0..200 {
+        let f = cdf(x) - p;
+        if f == 0.0 {
+            break;
+        }
+        if f > 0.0 {
+            hi = x;
+        } else {
+            lo = x;
+        }
+        let density = pdf(x);
+        let mut next = x - f / density;
+        if !(next > lo && next < hi) {
+            next = 0.5 * (lo + hi);
+        }
+        if (next - x).abs() <= 1e-15 * x.abs().max(1e-300) {
+            return next;
+        }
+        x = next;
+    }
+    x
+}
+
+// Smallest integer k in [lo, hi] with cdf(k) >= p.
+fn invert_discrete(p: f64, cdf: impl Fn(f64) -> f64, mut lo: f64, mut hi: f64) -> f64 {
+    while lo < hi {
+        let mid = ((lo + hi) / 2.0).floor();
+        if cdf(mid) >= p {
+            hi = mid;
+        } else {
+            lo = mid + 1.0;
+        }
+    }
+    lo
+}
+
+pub fn normal_pdf(x: f64, mu: f64, sigma: f64) -> Result<f64, Error> {
+    check(sigma > 0.0, &STANDARD_DEVIATION_DOMAIN)?;
+    let z = (x - mu) / sigma;
+    Ok((-0.5 * z * z).exp() / (sigma * (2.0 * PI).sqrt()))
+}
+
+pub fn normal_cdf(x: f64, mu: f64, sigma: f64) -> Result<f64, Error> {
+    check(sigma > 0.0, &STANDARD_DEVIATION_DOMAIN)?;
+    Ok(0.5 * erfc(-(x - mu) / (sigma * SQRT_2)))
+}
+
+/// Acklam's rational approximation refined with one Halley step.
+pub fn normal_inv(p: f64, mu: f64, sigma: f64) -> Result<f64, Error> {
+    check(sigma > 0.0, &STANDARD_DEVIATION_DOMAIN)?;
+    check_probability(p)?;
+    if p == 0.0 {
+        return Ok(f64::NEG_INFINITY);
+    }
+    if p == 1.0 {
+        return Ok(f64::INFINITY);
+    }
+
+    const A: [f64; 6] = [
+        -3.969683028665376e1, 2.209460984245205e2, -2.759285104469687e2, 1.38357751867269e2,
+        -3.066479806614716e1, 2.506628277459239,
+    ];
+    const B: [f64; 5] = [
+        -5.447609879822406e1, 1.615858368580409e2, -1.556989798598866e2, 6.680131188771972e1,
+        -1.328068155288572e1,
+    ];
+    const C: [f64; 6] = [
+        -7.784894002430293e-3, -3.223964580411365e-1, -2.400758277161838, -2.549732539343734,
+        4.374664141464968, 2.938163982698783,
+    ];
+    const D: [f64; 4] = [
+        7.784695709041462e-3, 3.224671290700398e-1, 2.445134137142996, 3.754408661907416,
+    ];
+    const LOW: f64 = 0.02425;
+
+    let z = if p < LOW {
+        let q = (-2.0 * p.ln()).sqrt();
+        (((((C[0] * q + C[1]) * q + C[2]) * q + C[3]) * q + C[4]) * q + C[5]) / ((((D[0] * q + D[1]) * q + D[2]) * q + D[3]) * q + 1.0)
+    } else if p <= 1.0 - LOW {
+        let q = p - 0.5;
+        let r = q * q;
+        (((((A[0] * r + A[1]) * r + A[2]) * r + A[3]) * r + A[4]) * r + A[5]) * q
+            / (((((B[0] * r + B[1]) * r + B[2]) * r + B[3]) * r + B[4]) * r + 1.0)
+    } else {
+        let q = (-2.0 * (1.0 - p).ln()).sqrt();
+        -(((((C[0] * q + C[1]) * q + C[2]) * q + C[3]) * q + C[4]) * q + C[5]) / ((((D[0] * q + D[1]) * q + D[2]) * q + D[3]) * q + 1.0)
+    };
+
+    let e = 0.5 * erfc(-z / SQRT_2) - p;
+    let u = e * (2.0 * PI).sqrt() * (z * z / 2.0).exp();
+    let z = z - u / (1.0 + z * u / 2.0);
+    Ok(mu + sigma * z)
+}
+
+fn ln_binomial(n: f64, k: f64) -> f64 {
+    ln_gamma(n + 1.0) - ln_gamma(k + 1.0) - ln_gamma(n - k + 1.0)
+}
+
+pub fn binomial_pdf(k: f64, n: f64, p: f64) -> Result<f64, Error> {
+    check_count(n, &NUMBER_OF_TRIALS)?;
+    check_probability(p)?;
+    if k < 0.0 || k > n || k.fract() != 0.0 {
+        return Ok(0.0);
+    }
+    if p == 0.0 || p == 1.0 {
+        let certain = if p == 0.0 { 0.0 } else { n };
+        return Ok(if k == certain { 1.0 } else { 0.0 });
+    }
+    Ok((ln_binomial(n, k) + k * p.ln() + (n - k) * (1.0 - p).ln()).exp())
+}
+
+pub fn binomial_cdf(k: f64, n: f64, p: f64) -> Result<f64, Error> {
+    check_count(n, &NUMBER_OF_TRIALS)?;
+    check_probability(p)?;
+    let k = k.floor();
+    if k < 0.0 {
+        return Ok(0.0);
+    }
+    if k >= n {
+        return Ok(1.0);
+    }
+    Ok(beta_inc(n - k, k + 1.0, 1.0 - p))
+}
+
+pub fn binomial_inv(q: f64, n: f64, p: f64) -> Result<f64, Error> {
+    check_count(n, &NUMBER_OF_TRIALS)?;
+    check_probability(p)?;
+    check_probability(q)?;
+    Ok(invert_discrete(q, |k| binomial_cdf(k, n, p).unwrap_or(1.0), 0.0, n))
+}
+
+pub fn poisson_pdf(k: f64, lambda: f64) -> Result<f64, Error> {
+    check(lambda > 0.0, &RATE_DOMAIN)?;
+    if k < 0.0 || k.fract() != 0.0 {
+        return Ok(0.0);
+    }
+    Ok((k * lambda.ln() - lambda - ln_gamma(k + 1.0)).exp())
+}
+
+pub fn poisson_cdf(k: f64, lambda: f64) -> Result<f64, Error> {
+    check(lambda > 0.0, &RATE_DOMAIN)?;
+    let k = k.floor();
+    if k < 0.0 {
+        return Ok(0.0);
+    }
+    Ok(gamma_q(k + 1.0, lambda))
+}
+
+pub fn poisson_inv(q: f64, lambda: f64) -> Result<f64, Error> {
+    check(lambda > 0.0, &RATE_DOMAIN)?;
+    check_probability(q)?;
+    if q == 1.0 {
+        return Ok(f64::INFINITY);
+    }
+    let mut hi = lambda + 20.0 * lambda.sqrt() + 20.0;
+    while gamma_q(hi + 1.0, lambda) < q {
+        hi *= 2.0;
+    }
+    Ok(invert_discrete(q, |k| gamma_q(k + 1.0, lambda), 0.0, hi.ceil()))
+}
+
+pub fn t_pdf(t: f64, df: f64) -> Result<f64, Error> {
+    check(df > 0.0, &DEGREES_OF_FREEDOM_DOMAIN)?;
+    let ln_norm = ln_gamma((df + 1.0) / 2.0) - ln_gamma(df / 2.0) - 0.5 * (df * PI).ln();
+    Ok((ln_norm - (df + 1.0) / 2.0 * (1.0 + t * t / df).ln()).exp())
+}
+
+pub fn t_cdf(t: f64, df: f64) -> Result<f64, Error> {
+    check(df > 0.0, &DEGREES_OF_FREEDOM_DOMAIN)?;
+    let tail = 0.5 * beta_inc(df / 2.0, 0.5, df / (df + t * t));
+    Ok(if t > 0.0 { 1.0 - tail } else { tail })
+}
+
+pub fn t_inv(p: f64, df: f64) -> Result<f64, Error> {
+    check(df > 0.0, &DEGREES_OF_FREEDOM_DOMAIN)?;
+    check_probability(p)?;
+    if p == 0.0 || p == 1.0 {
+        return Ok(if p == 0.0 { f64::NEG_INFINITY } else { f64::INFINITY });
+    }
+    let cdf = |t: f64| t_cdf(t, df).unwrap_or(f64::NAN);
+    let (mut lo, mut hi) = (-1.0, 1.0);
+    while cdf(lo) > p {
+        lo *= 2.0;
+    }
+    while cdf(hi) < p {
+        hi *= 2.0;
+    }
+    Ok(invert_continuous(p, cdf, |t| t_pdf(t, df).unwrap_or(f64::NAN), lo, hi))
+}
+
+pub fn chi2_pdf(x: f64, k: f64) -> Result<f64, Error> {
+    check(k > 0.0, &DEGREES_OF_FREEDOM_DOMAIN)?;
+    if x < 0.0 {
+        return Ok(0.0);
+    }
+    if x == 0.0 {
+        return Ok(match k.partial_cmp(&2.0) {
+            Some(std::cmp::Ordering::Less) => f64::INFINITY,
+            Some(std::cmp::Ordering::Equal) => 0.5,
+            _ => 0.0,
+        });
+    }
+    let half = k / 2.0;
+    Ok(((half - 1.0) * x.ln() - x / 2.0 - half * 2f64.ln() - ln_gamma(half)).exp())
+}
+
+pub fn chi2_cdf(x: f64, k: f64) -> Result<f64, Error> {
+    check(k > 0.0, &DEGREES_OF_FREEDOM_DOMAIN)?;
+    Ok(gamma_p(k / 2.0, x / 2.0))
+}
+
+pub fn chi2_inv(p: f64, k: f64) -> Result<f64, Error> {
+    check(k > 0.0, &DEGREES_OF_FREEDOM_DOMAIN)?;
+    check_probability(p)?;
+    if p == 1.0 {
+        return Ok(f64::INFINITY);
+    }
+    let cdf = |x: f64| gamma_p(k / 2.0, x / 2.0);
+    let mut hi = k.max(1.0);
+    while cdf(hi) < p {
+        hi *= 2.0;
+    }
+    Ok(invert_continuous(p, cdf, |x| chi2_pdf(x, k).unwrap_or(f64::NAN), 0.0, hi))
+}
+
+pub fn exponential_pdf(x: f64, rate: f64) -> Result<f64, Error> {
+    check(rate > 0.0, &RATE_DOMAIN)?;
+    Ok(if x < 0.0 { 0.0 } else { rate * (-rate * x).exp() })
+}
+
+pub fn exponential_cdf(x: f64, rate: f64) -> Result<f64, Error> {
+    check(rate > 0.0, &RATE_DOMAIN)?;
+    Ok(if x < 0.0 { 0.0 } else { -(-rate * x).exp_m1() })
+}
+
+pub fn exponential_inv(p: f64, rate: f64) -> Result<f64, Error> {
+    check(rate > 0.0, &RATE_DOMAIN)?;
+    check_probability(p)?;
+    Ok(-(-p).ln_1p() / rate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(actual: Result<f64, Error>, expected: f64) {
+        let actual = actual.unwrap();
+        assert!((actual - expected).abs() <= 1e-9 * expected.abs().max(1.0), "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn normal_matches_reference_values() {
+        close(normal_pdf(0.0, 0.0, 1.0), 0.3989422804014327);
+        close(normal_cdf(1.96, 0.0, 1.0), 0.9750021048517795);
+        close(normal_inv(0.975, 0.0, 1.0), 1.959963984540054);
+        close(normal_inv(0.025, 100.0, 15.0), 100.0 - 15.0 * 1.959963984540054);
+        close(normal_inv(0.5, 10.0, 2.0), 10.0);
+        assert!(normal_inv(1.5, 0.0, 1.0).is_err());
+        assert!(normal_pdf(0.0, 0.0, 0.0).is_err());
+    }
+
+    #[test]
+    fn binomial_matches_reference_values() {
+        close(binomial_pdf(3.0, 10.0, 0.5), 120.0 / 1024.0);
+        close(binomial_cdf(3.0, 10.0, 0.5), 176.0 / 1024.0);
+        close(binomial_inv(0.5, 10.0, 0.5), 5.0);
+        close(binomial_inv(0.171875, 10.0, 0.5), 3.0);
+        close(binomial_pdf(0.0, 5.0, 0.0), 1.0);
+        assert!(binomial_pdf(1.0, 2.5, 0.5).is_err());
+        assert!(binomial_cdf(1.0, 10.0, 1.5).is_err());
+    }
+
+    #[test]
+    fn poisson_matches_reference_values() {
+        let e3 = (-3.0f64).exp();
+        close(poisson_pdf(2.0, 3.0), 4.5 * e3);
+        close(poisson_cdf(2.0, 3.0), 8.5 * e3);
+        close(poisson_inv(0.5, 3.0), 3.0);
+        close(poisson_pdf(1.5, 3.0), 0.0);
+        assert!(poisson_pdf(1.0, -1.0).is_err());
+    }
+
+    #[test]
+    fn student_t_matches_reference_values() {
+        close(t_pdf(0.0, 1.0), 1.0 / std::f64::consts::PI);
+        close(t_cdf(2.0, 10.0), 0.9633059826146297);
+        close(t_cdf(0.0, 3.0), 0.5);
+        close(t_inv(0.975, 10.0), 2.228138851964938);
+        close(t_inv(0.025, 10.0), -2.228138851964938);
+        assert!(t_cdf(1.0, 0.0).is_err());
+    }
+
+    #[test]
+    fn chi_squared_matches_reference_values() {
+        close(chi2_pdf(2.0, 2.0), 0.5 * (-1.0f64).exp());
+        close(chi2_cdf(2.0, 2.0), 1.0 - (-1.0f64).exp());
+        close(chi2_inv(0.95, 1.0), 3.841458820694124);
+        close(chi2_inv(0.95, 10.0), 18.307038053275146);
+        assert!(chi2_cdf(1.0, -2.0).is_err());
+    }
+
+    #[test]
+    fn exponential_matches_reference_values() {
+        close(exponential_pdf(1.0, 2.0), 2.0 * (-2.0f64).exp());
+        close(exponential_cdf(1.0, 2.0), 1.0 - (-2.0f64).exp());
+        close(exponential_inv(1.0 - (-2.0f64).exp(), 2.0), 1.0);
+        assert!(exponential_cdf(1.0, 0.0).is_err());
+    }
+}
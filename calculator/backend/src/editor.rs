@@ -0,0 +1,469 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::accounts::User;
+use crate::ast::{Expr, Span};
+use crate::chinese::Normalized;
+use crate::compile::CompiledExpression;
+use crate::functions;
+use crate::i18n;
+use crate::parser::{self, Token, TokenKind};
+use crate::registry::{self, Function};
+use crate::value::{Type, Value};
+use crate::worksheet;
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ParseRequest {
+    pub expression: String,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct TokenView {
+    pub kind: &'static str,
+    pub text: String,
+    pub span: Span,
+}
+
+/// An AST node with the type the compiler gives it. `result_type` is null throughout
+/// when the expression does not type-check.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct Node {
+    pub kind: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub op: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<Value>,
+    pub span: Span,
+    pub result_type: Option<Type>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<Node>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct VariableType {
+    pub name: String,
+    pub result_type: Type,
+}
+
+#[derive(Debug, Default, Serialize, JsonSchema)]
+pub struct ParseResponse {
+    pub normalized: Option<String>,
+    pub tokens: Vec<TokenView>,
+    pub ast: Option<Node>,
+    pub variables: Vec<VariableType>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct CompleteRequest {
+    pub expression: String,
+    /// Character offset of the cursor; defaults to the end of the expression.
+    pub cursor: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CandidateKind {
+    Variable,
+    Function,
+    Constant,
+    Keyword,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct Candidate {
+    pub name: String,
+    pub kind: CandidateKind,
+    pub signature: String,
+    pub doc: String,
+    pub result_type: Option<Type>,
+}
+
+/// The innermost call whose argument list holds the cursor.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct CallHint {
+    pub name: String,
+    pub signature: String,
+    pub doc: String,
+    pub argument: usize,
+}
+
+#[derive(Debug, Default, Serialize, JsonSchema)]
+pub struct CompleteResponse {
+    pub prefix: String,
+    pub replace: Option<Span>,
+    pub candidates: Vec<Candidate>,
+    pub call: Option<CallHint>,
+    pub error: Option<String>,
+}
+
+static KEYWORDS: &[(&str, &str, &str)] = &[
+    ("if", "if(condition, then, else)", "then when the condition holds, else otherwise"),
+    ("from_base", "from_base(\"digits\", base, \"alphabet\")", "Reads digits such as \"101.011\" or \"0.(0022)\" in base 2 to 36"),
+    ("to_base", "to_base(x, base, \"alphabet\")", "Writes the whole result in base 2 to 36, marking repeating digits"),
+    ("and", "a and b", "Logical and"),
+    ("or", "a or b", "Logical or"),
+    ("xor", "a xor b", "Logical exclusive or"),
+    ("not", "not a", "Logical negation"),
+    ("true", "true", "Boolean true"),
+    ("false", "false", "Boolean false"),
+];
+
+fn token_kind(kind: &TokenKind) -> &'static str {
+    match kind {
+        TokenKind::Number(_) => "number",
+        TokenKind::Boolean(_) => "boolean",
+        TokenKind::Dice(..) => "dice",
+        TokenKind::Duration(_) => "duration",
+        TokenKind::Workdays(_) => "workdays",
+        TokenKind::Date(_) => "date",
+        TokenKind::Text(_) => "text",
+        TokenKind::Ident(_) => "identifier",
+        TokenKind::And | TokenKind::Or | TokenKind::Xor | TokenKind::Not => "keyword",
+        TokenKind::LParen | TokenKind::RParen | TokenKind::LBracket | TokenKind::RBracket | TokenKind::Comma => {
+            "punctuation"
+        }
+        _ => "operator",
+    }
+}
+
+fn node(expr: &Expr, typed: Option<&[(String, Option<Type>)]>) -> Node {
+    // Each subexpression is compiled on its own, with the variable types the whole
+    // expression settled on.
+    let result_type =
+        typed.and_then(|typed| CompiledExpression::compile_typed(expr, typed).ok()).map(|compiled| compiled.result_type());
+    let leaf = |kind, value| Node {
+        kind,
+        op: None,
+        name: None,
+        value: Some(value),
+        span: expr.span(),
+        result_type,
+        children: Vec::new(),
+    };
+    match expr {
+        Expr::Number { value, .. } => leaf("number", Value::Number(*value)),
+        Expr::Boolean { value, .. } => leaf("boolean", Value::Boolean(*value)),
+        Expr::Duration { seconds, .. } => leaf("duration", Value::Duration(*seconds)),
+        Expr::Workdays { count, .. } => leaf("workdays", Value::Workdays(*count)),
+        Expr::Date { days, .. } => leaf("date", Value::Date(*days as f64)),
+        Expr::Text { value, span } => Node {
+            kind: "text",
+            op: None,
+            name: Some(value.clone()),
+            value: None,
+            span: *span,
+            result_type: None,
+            children: Vec::new(),
+        },
+        Expr::Variable { name, span } => Node {
+            kind: if functions::constant(name).is_some() { "constant" } else { "variable" },
+            op: None,
+            name: Some(name.clone()),
+            value: None,
+            span: *span,
+            result_type,
+            children: Vec::new(),
+        },
+        Expr::Unary { op, operand, span } => Node {
+            kind: "unary",
+            op: Some(op.symbol()),
+            name: None,
+            value: None,
+            span: *span,
+            result_type,
+            children: vec![node(operand, typed)],
+        },
+        Expr::Binary { op, left, right, span } => Node {
+            kind: "binary",
+            op: Some(op.symbol()),
+            name: None,
+            value: None,
+            span: *span,
+            result_type,
+            children: vec![node(left, typed), node(right, typed)],
+        },
+        Expr::Call { name, args, span } => Node {
+            kind: "call",
+            op: None,
+            name: Some(name.clone()),
+            value: None,
+            span: *span,
+            result_type,
+            children: args.iter().map(|arg| node(arg, typed)).collect(),
+        },
+        Expr::Interval { low, high, span } => Node {
+            kind: "interval",
+            op: None,
+            name: None,
+            value: None,
+            span: *span,
+            result_type,
+            children: vec![node(low, typed), node(high, typed)],
+        },
+    }
+}
+
+// Moves the spans of a tree parsed from normalized text onto the original input.
+fn locate(node: &mut Node, normalized: &Normalized) {
+    node.span = normalized.original(node.span);
+    node.children.iter_mut().for_each(|child| locate(child, normalized));
+}
+
+/// Tokens and typed AST of an expression. Chinese input is normalized first, but spans
+/// still index characters of the original expression: each token written for a Chinese
+/// word covers the whole word.
+pub fn parse(user: &User, request: &ParseRequest) -> ParseResponse {
+    let normalized = match Normalized::new(&request.expression) {
+        Ok(normalized) => normalized,
+        Err(error) => return ParseResponse { error: Some(error.into()), ..Default::default() },
+    };
+    let chars: Vec<char> = request.expression.chars().collect();
+    let mut response = ParseResponse {
+        normalized: (normalized.text != request.expression).then(|| normalized.text.clone()),
+        ..Default::default()
+    };
+    let tokens = match parser::tokenize(&normalized.text) {
+        Ok(tokens) => tokens,
+        Err(error) => return ParseResponse { error: Some(error.into()), ..response },
+    };
+    response.tokens = tokens
+        .iter()
+        .map(|token| {
+            let span = normalized.original(token.span);
+            TokenView { kind: token_kind(&token.kind), text: chars[span.start..span.end].iter().collect(), span }
+        })
+        .collect();
+    let ast = match parser::parse(&normalized.text) {
+        Ok(ast) => ast,
+        Err(error) => return ParseResponse { error: Some(error.into()), ..response },
+    };
+
+    // Session variables keep their types; other free variables are inferred.
+    let mut names = Vec::new();
+    worksheet::references(&ast, &mut names);
+    let typed: Vec<(String, Option<Type>)> = {
+        let state = crate::get_user_state(user);
+        let variables = &state.lock().unwrap().variables;
+        names.into_iter().map(|name| (name.clone(), variables.get(&name).map(|value| value.value_type()))).collect()
+    };
+    match CompiledExpression::compile_typed(&ast, &typed) {
+        Ok(compiled) => {
+            let typed: Vec<(String, Option<Type>)> = (0..compiled.variables().len())
+                .map(|slot| (compiled.variables()[slot].clone(), Some(compiled.variable_type(slot))))
+                .collect();
+            response.ast = Some(node(&ast, Some(&typed)));
+            response.variables = typed
+                .into_iter()
+                .map(|(name, result_type)| VariableType { name, result_type: result_type.unwrap() })
+                .collect();
+        }
+        Err(error) => {
+            response.ast = Some(node(&ast, None));
+            response.error = Some(error.into());
+        }
+    }
+    if let Some(ast) = response.ast.as_mut() {
+        locate(ast, &normalized);
+    }
+    response
+}
+
+fn call_hint(tokens: &[Token]) -> Option<CallHint> {
+    // Open brackets before the cursor, each with the function it calls and the index
+    // of the argument being typed.
+    let mut open: Vec<(Option<&str>, usize)> = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        match &token.kind {
+            TokenKind::LParen => {
+                let name = match i.checked_sub(1).map(|previous| &tokens[previous].kind) {
+                    Some(TokenKind::Ident(name)) => Some(name.as_str()),
+                    _ => None,
+                };
+                open.push((name, 0));
+            }
+            TokenKind::LBracket => open.push((None, 0)),
+            TokenKind::RParen | TokenKind::RBracket => {
+                open.pop();
+            }
+            TokenKind::Comma => {
+                if let Some((_, argument)) = open.last_mut() {
+                    *argument += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    let (name, argument) = open.into_iter().rev().find(|(name, _)| name.is_some())?;
+    let name = name?;
+    if let Some((_, signature, doc)) = KEYWORDS.iter().find(|(keyword, _, _)| *keyword == name) {
+        return Some(CallHint { name: name.to_string(), signature: signature.to_string(), doc: doc.to_string(), argument });
+    }
+    let (function, signature) = function_named(name)?;
+    Some(CallHint { name: name.to_string(), signature, doc: function.doc().to_string(), argument })
+}
+
+// A registered function by its own name or a Chinese alias, with its signature written
+// under that name.
+fn function_named(name: &str) -> Option<(Arc<dyn Function>, String)> {
+    match i18n::FUNCTION_ALIASES.iter().find(|(alias, _)| *alias == name) {
+        Some((alias, registered)) => {
+            let function = registry::lookup(registered)?;
+            let signature = function.signature().replacen(registered, alias, 1);
+            Some((function, signature))
+        }
+        None => registry::lookup(name).map(|function| {
+            let signature = function.signature();
+            (function, signature)
+        }),
+    }
+}
+
+/// Completion candidates for the identifier ending at the cursor, and the call whose
+/// arguments the cursor is in.
+pub fn complete(user: &User, request: &CompleteRequest) -> Result<CompleteResponse, String> {
+    let chars: Vec<char> = request.expression.chars().collect();
+    let cursor = request.cursor.unwrap_or(chars.len());
+    if cursor > chars.len() {
+        return Err(format!("Cursor {} is past the end of the expression ({} characters)", cursor, chars.len()));
+    }
+
+    let mut start = cursor;
+    while start > 0 && parser::is_ident_continue(chars[start - 1]) {
+        start -= 1;
+    }
+    while start < cursor && !parser::is_ident_start(chars[start]) {
+        start += 1;
+    }
+    let mut end = cursor;
+    while end < chars.len() && parser::is_ident_continue(chars[end]) {
+        end += 1;
+    }
+    let prefix: String = chars[start..cursor].iter().collect();
+
+    let mut candidates = Vec::new();
+    for (name, value) in crate::get_user_state(user).lock().unwrap().variables.iter() {
+        candidates.push(Candidate {
+            name: name.clone(),
+            kind: CandidateKind::Variable,
+            signature: name.clone(),
+            doc: value.format(&Default::default()).unwrap_or_default(),
+            result_type: Some(value.value_type()),
+        });
+    }
+    let mut functions = registry::get_registry().read().unwrap().functions();
+    functions.extend(registry::user_functions());
+    for function in functions {
+        candidates.push(Candidate {
+            name: function.name().to_string(),
+            kind: CandidateKind::Function,
+            signature: function.signature(),
+            doc: function.doc().to_string(),
+            result_type: Some(function.result()),
+        });
+    }
+    for (alias, _) in i18n::FUNCTION_ALIASES {
+        if let Some((function, signature)) = function_named(alias) {
+            candidates.push(Candidate {
+                name: alias.to_string(),
+                kind: CandidateKind::Function,
+                signature,
+                doc: function.doc().to_string(),
+                result_type: Some(function.result()),
+            });
+        }
+    }
+    for (name, doc) in functions::CONSTANTS {
+        candidates.push(Candidate {
+            name: name.to_string(),
+            kind: CandidateKind::Constant,
+            signature: name.to_string(),
+            doc: doc.to_string(),
+            result_type: Some(Type::Number),
+        });
+    }
+    for (name, signature, doc) in KEYWORDS {
+        candidates.push(Candidate {
+            name: name.to_string(),
+            kind: CandidateKind::Keyword,
+            signature: signature.to_string(),
+            doc: doc.to_string(),
+            result_type: None,
+        });
+    }
+    candidates.retain(|candidate| candidate.name.starts_with(&prefix));
+    candidates.sort_by(|a, b| a.kind.cmp(&b.kind).then_with(|| a.name.cmp(&b.name)));
+
+    // Tokenize only up to the start of the word, so an unfinished word cannot fail it.
+    let before: String = chars[..start].iter().collect();
+    let call = parser::tokenize(&before).ok().and_then(|tokens| call_hint(&tokens));
+
+    Ok(CompleteResponse { prefix, replace: Some(Span::new(start, end)), candidates, call, error: None })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::Role;
+
+    fn user() -> User {
+        User { name: "editor-tests".to_string(), role: Role::User }
+    }
+
+    fn parsed(expression: &str) -> ParseResponse {
+        parse(&user(), &ParseRequest { expression: expression.to_string() })
+    }
+
+    fn token_spans(response: &ParseResponse) -> Vec<(String, usize, usize)> {
+        response.tokens.iter().map(|token| (token.text.clone(), token.span.start, token.span.end)).collect()
+    }
+
+    #[test]
+    fn spans_of_plain_input_are_unchanged() {
+        let response = parsed("sqrt(x) > 2");
+        assert_eq!(response.normalized, None);
+        assert_eq!(token_spans(&response)[4], (">".to_string(), 8, 9));
+        let ast = response.ast.unwrap();
+        assert_eq!((ast.span.start, ast.span.end), (0, 11));
+        assert_eq!((ast.children[1].span.start, ast.children[1].span.end), (10, 11));
+    }
+
+    #[test]
+    fn spans_of_chinese_input_point_into_the_original() {
+        let response = parsed("一万五千乘以百分之八");
+        assert_eq!(response.normalized.as_deref(), Some("15000 * (8/100)"));
+        let spans = token_spans(&response);
+        assert_eq!(spans[0], ("一万五千".to_string(), 0, 4));
+        assert_eq!(spans[1], ("乘以".to_string(), 4, 6));
+        assert!(spans[2..].iter().all(|span| *span == ("百分之八".to_string(), 6, 10)));
+        let ast = response.ast.unwrap();
+        assert_eq!((ast.span.start, ast.span.end), (0, 10));
+        assert_eq!((ast.children[0].span.start, ast.children[0].span.end), (0, 4));
+
+        let response = parsed("平方根(x)  加  二");
+        let spans = token_spans(&response);
+        assert_eq!(spans[0], ("平方根".to_string(), 0, 3));
+        assert_eq!(spans[2], ("x".to_string(), 4, 5));
+        assert_eq!(spans[4], ("加".to_string(), 8, 9));
+        assert_eq!(spans[5], ("二".to_string(), 11, 12));
+    }
+
+    #[test]
+    fn completion_offers_chinese_function_names() {
+        let complete = |expression: &str| {
+            complete(&user(), &CompleteRequest { expression: expression.to_string(), cursor: None }).unwrap()
+        };
+        let response = complete("1 + 平方");
+        assert_eq!(response.prefix, "平方");
+        let names: Vec<&str> = response.candidates.iter().map(|candidate| candidate.name.as_str()).collect();
+        assert_eq!(names, vec!["平方根"]);
+        assert_eq!(response.candidates[0].signature, "平方根(x)");
+
+        let call = complete("最大值(1, ").call.unwrap();
+        assert_eq!((call.name.as_str(), call.argument), ("最大值", 1));
+        assert!(call.signature.starts_with("最大值("));
+    }
+}
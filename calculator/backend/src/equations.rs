@@ -0,0 +1,567 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::ast::{BinaryOp, Expr, UnaryOp};
+use crate::compile::CompiledExpression;
+use crate::functions;
+use crate::parser;
+use crate::rational::Q;
+use crate::value::{ExtendedReal, Type};
+
+pub const MAX_EQUATIONS: usize = 100;
+pub const MAX_ITERATIONS: usize = 1000;
+const DEFAULT_ITERATIONS: usize = 100;
+const STEP_TOLERANCE: f64 = 1e-12;
+const RESIDUAL_TOLERANCE: f64 = 1e-8;
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct SolveRequest {
+    /// Equations separated by `;` or newlines, e.g. `"2x + y = 5; x - y = 1"`.
+    pub system: Option<String>,
+    #[serde(default)]
+    pub equations: Vec<String>,
+    /// The unknowns, in the order the solution lists them. Defaults to every free
+    /// identifier in order of first appearance.
+    pub variables: Option<Vec<String>>,
+    /// Starting point for Newton's method; unknowns not listed start at 1,
+    /// 1.1, 1.2, … in order.
+    #[serde(default)]
+    pub initial: HashMap<String, f64>,
+    pub max_iterations: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Method {
+    Gaussian,
+    Newton,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Unique,
+    NoSolution,
+    Infinite,
+    /// A root found numerically; other roots may exist.
+    Approximate,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct Assignment {
+    pub variable: String,
+    /// The numeric value, absent when it depends on free variables.
+    pub value: Option<ExtendedReal>,
+    /// The exact value (`3/2`) or, for infinitely many solutions, the value in terms of
+    /// the free variables (`5 - 2y`).
+    pub expression: String,
+    pub free: bool,
+}
+
+#[derive(Debug, Serialize, Default, JsonSchema)]
+pub struct SolveResponse {
+    pub method: Option<Method>,
+    pub status: Option<Status>,
+    pub solution: Vec<Assignment>,
+    pub iterations: Option<usize>,
+    pub residual: Option<ExtendedReal>,
+    pub error: Option<String>,
+}
+
+/// Splits `lhs = rhs` at its single `=`, leaving `==`, `<=`, `>=` and `!=` alone.
+pub fn split_equation(equation: &str) -> Result<(&str, &str), String> {
+    let chars: Vec<(usize, char)> = equation.char_indices().collect();
+    let positions: Vec<usize> = chars
+        .iter()
+        .enumerate()
+        .filter(|(i, (_, c))| {
+            *c == '='
+                && !matches!(i.checked_sub(1).map(|j| chars[j].1), Some('=' | '<' | '>' | '!'))
+                && chars.get(i + 1).map(|(_, c)| *c) != Some('=')
+        })
+        .map(|(_, (offset, _))| *offset)
+        .collect();
+    match positions.as_slice() {
+        [position] => Ok((&equation[..*position], &equation[position + 1..])),
+        [] => Err("no '='".to_string()),
+        _ => Err("more than one '='".to_string()),
+    }
+}
+
+/// One equation rewritten as `(lhs) - (rhs) = 0`, with the source text its spans refer to.
+struct Equation {
+    source: String,
+    expr: Expr,
+}
+
+fn parse_equation(equation: &str) -> Result<Equation, String> {
+    let (lhs, rhs) = split_equation(equation)?;
+    parser::parse(lhs)?;
+    parser::parse(rhs)?;
+    let source = format!("({}) - ({})", lhs.trim(), rhs.trim());
+    let expr = parser::parse(&source)?;
+    Ok(Equation { source, expr })
+}
+
+/// `Σ coefficients[i]·x_i + constant`, or `None` once the expression turns out nonlinear
+/// or inexact.
+#[derive(Debug, Clone)]
+struct Affine {
+    coefficients: Vec<Q>,
+    constant: Q,
+}
+
+impl Affine {
+    fn constant(value: Q, width: usize) -> Self {
+        Affine { coefficients: vec![Q::zero(); width], constant: value }
+    }
+
+    fn as_constant(&self) -> Option<&Q> {
+        self.coefficients.iter().all(Q::is_zero).then_some(&self.constant)
+    }
+
+    fn scale(&self, factor: &Q) -> Affine {
+        Affine {
+            coefficients: self.coefficients.iter().map(|c| c * factor).collect(),
+            constant: &self.constant * factor,
+        }
+    }
+
+    fn add(&self, other: &Affine, sign: &Q) -> Affine {
+        Affine {
+            coefficients: self.coefficients.iter().zip(&other.coefficients).map(|(a, b)| a + &(b * sign)).collect(),
+            constant: &self.constant + &(&other.constant * sign),
+        }
+    }
+}
+
+fn affine(expr: &Expr, source: &[char], variables: &[String]) -> Option<Affine> {
+    let width = variables.len();
+    match expr {
+        Expr::Number { span, .. } => {
+            let text: String = source[span.start..span.end].iter().collect();
+            Some(Affine::constant(Q::parse(&text)?, width))
+        }
+        Expr::Variable { name, .. } => {
+            let slot = variables.iter().position(|v| v == name)?;
+            let mut result = Affine::constant(Q::zero(), width);
+            result.coefficients[slot] = Q::one();
+            Some(result)
+        }
+        Expr::Unary { op: UnaryOp::Neg, operand, .. } => Some(affine(operand, source, variables)?.scale(&Q::int(-1))),
+        Expr::Unary { op: UnaryOp::Plus, operand, .. } => affine(operand, source, variables),
+        Expr::Binary { op, left, right, .. } => {
+            let a = affine(left, source, variables)?;
+            let b = affine(right, source, variables)?;
+            match op {
+                BinaryOp::Add => Some(a.add(&b, &Q::one())),
+                BinaryOp::Sub => Some(a.add(&b, &Q::int(-1))),
+                BinaryOp::Mul => match (a.as_constant(), b.as_constant()) {
+                    (Some(c), _) => Some(b.scale(c)),
+                    (_, Some(c)) => Some(a.scale(c)),
+                    _ => None,
+                },
+                BinaryOp::Div => b.as_constant().filter(|c| !c.is_zero()).map(|c| a.scale(&c.recip())),
+                BinaryOp::Pow => {
+                    let exponent = b.as_constant().filter(|c| c.is_integer())?;
+                    if let Some(base) = a.as_constant() {
+                        let power = u32::try_from(exponent.num.magnitude()).ok().filter(|p| *p <= 64)?;
+                        if base.is_zero() && exponent.is_negative() {
+                            return None;
+                        }
+                        let value = base.pow(power);
+                        Some(Affine::constant(if exponent.is_negative() { value.recip() } else { value }, width))
+                    } else if exponent == &Q::one() {
+                        Some(a)
+                    } else {
+                        None
+                    }
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn format_term(coefficient: &Q, variable: &str) -> String {
+    let magnitude = coefficient.abs();
+    if magnitude == Q::one() {
+        variable.to_string()
+    } else if magnitude.is_integer() {
+        format!("{}{}", magnitude, variable)
+    } else {
+        format!("({}){}", magnitude, variable)
+    }
+}
+
+/// `constant + Σ coefficient·variable`, e.g. `5 - 2y` or `(1/2)z`.
+fn format_affine(constant: &Q, terms: &[(Q, &str)]) -> String {
+    let mut text = String::new();
+    if !constant.is_zero() {
+        text.push_str(&constant.to_string());
+    }
+    for (coefficient, variable) in terms.iter().filter(|(c, _)| !c.is_zero()) {
+        let term = format_term(coefficient, variable);
+        match (text.is_empty(), coefficient.is_negative()) {
+            (true, false) => text.push_str(&term),
+            (true, true) => text.push_str(&format!("-{}", term)),
+            (false, false) => text.push_str(&format!(" + {}", term)),
+            (false, true) => text.push_str(&format!(" - {}", term)),
+        }
+    }
+    if text.is_empty() {
+        "0".to_string()
+    } else {
+        text
+    }
+}
+
+/// Reduces the augmented system `Σ a_ij·x_j + c_i = 0` to reduced row echelon form with
+/// exact arithmetic.
+fn solve_linear(rows: Vec<Affine>, variables: &[String]) -> SolveResponse {
+    let width = variables.len();
+    let mut rows: Vec<(Vec<Q>, Q)> = rows.into_iter().map(|row| (row.coefficients, -&row.constant)).collect();
+    let mut pivots: Vec<usize> = Vec::new();
+
+    for column in 0..width {
+        let rank = pivots.len();
+        let Some(pivot) = (rank..rows.len()).find(|&r| !rows[r].0[column].is_zero()) else { continue };
+        rows.swap(rank, pivot);
+        let inverse = rows[rank].0[column].recip();
+        rows[rank].0.iter_mut().for_each(|c| *c = &*c * &inverse);
+        rows[rank].1 = &rows[rank].1 * &inverse;
+        let (pivot_row, pivot_value) = rows[rank].clone();
+        for (r, (coefficients, value)) in rows.iter_mut().enumerate() {
+            let factor = coefficients[column].clone();
+            if r == rank || factor.is_zero() {
+                continue;
+            }
+            for (c, p) in coefficients.iter_mut().zip(&pivot_row) {
+                *c = &*c - &(&factor * p);
+            }
+            *value = &*value - &(&factor * &pivot_value);
+        }
+        pivots.push(column);
+    }
+
+    let mut response = SolveResponse { method: Some(Method::Gaussian), ..Default::default() };
+    if rows[pivots.len()..].iter().any(|(_, value)| !value.is_zero()) {
+        response.status = Some(Status::NoSolution);
+        return response;
+    }
+
+    let free: Vec<usize> = (0..width).filter(|c| !pivots.contains(c)).collect();
+    response.status = Some(if free.is_empty() { Status::Unique } else { Status::Infinite });
+    response.solution = variables
+        .iter()
+        .enumerate()
+        .map(|(column, variable)| match pivots.iter().position(|p| *p == column) {
+            Some(row) => {
+                let (coefficients, value) = &rows[row];
+                let terms: Vec<(Q, &str)> =
+                    free.iter().map(|f| (-&coefficients[*f], variables[*f].as_str())).collect();
+                let dependent = terms.iter().any(|(c, _)| !c.is_zero());
+                Assignment {
+                    variable: variable.clone(),
+                    value: (!dependent).then(|| ExtendedReal(value.to_f64())),
+                    expression: format_affine(value, &terms),
+                    free: false,
+                }
+            }
+            None => Assignment { variable: variable.clone(), value: None, expression: variable.clone(), free: true },
+        })
+        .collect();
+    response
+}
+
+/// Solves `a·x = b` by Gaussian elimination with partial pivoting.
+fn solve_dense(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Option<Vec<f64>> {
+    let n = b.len();
+    let scale = a.iter().flatten().fold(0.0f64, |m, v| m.max(v.abs()));
+    for column in 0..n {
+        let pivot = (column..n).max_by(|&i, &j| a[i][column].abs().total_cmp(&a[j][column].abs()))?;
+        if a[pivot][column].abs() <= scale * 1e-14 || a[pivot][column].is_nan() {
+            return None;
+        }
+        a.swap(column, pivot);
+        b.swap(column, pivot);
+        let pivot_row = a[column].clone();
+        for row in column + 1..n {
+            let factor = a[row][column] / pivot_row[column];
+            for (value, p) in a[row].iter_mut().zip(&pivot_row).skip(column) {
+                *value -= factor * p;
+            }
+            b[row] -= factor * b[column];
+        }
+    }
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let sum: f64 = (row + 1..n).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - sum) / a[row][row];
+    }
+    Some(x)
+}
+
+fn residuals(compiled: &[CompiledExpression], x: &[f64]) -> Result<Vec<f64>, String> {
+    compiled
+        .iter()
+        .enumerate()
+        .map(|(i, equation)| {
+            let value = equation.evaluate(x).map_err(|error| format!("Equation {}: {}", i + 1, error))?.as_f64();
+            if !value.is_finite() {
+                return Err(format!("Equation {} is not finite at the current point", i + 1));
+            }
+            Ok(value)
+        })
+        .collect()
+}
+
+fn norm(values: &[f64]) -> f64 {
+    values.iter().map(|v| v * v).sum::<f64>().sqrt()
+}
+
+/// Damped Newton iteration with a central-difference Jacobian: the step is halved until
+/// the residual shrinks.
+fn solve_newton(
+    compiled: &[CompiledExpression],
+    variables: &[String],
+    initial: &HashMap<String, f64>,
+    max_iterations: usize,
+) -> Result<SolveResponse, String> {
+    if compiled.len() != variables.len() {
+        return Err(format!(
+            "Newton's method needs as many equations as unknowns; found {} equation(s) in {} unknown(s)",
+            compiled.len(),
+            variables.len()
+        ));
+    }
+    // Staggered defaults keep symmetric systems such as `x^2 + y^2 = 4; xy = 1` away from
+    // a singular Jacobian on the diagonal.
+    let mut x: Vec<f64> = variables
+        .iter()
+        .enumerate()
+        .map(|(i, v)| initial.get(v).copied().unwrap_or(1.0 + 0.1 * i as f64))
+        .collect();
+    let mut f = residuals(compiled, &x)?;
+
+    for iteration in 1..=max_iterations {
+        if norm(&f) == 0.0 {
+            return Ok(newton_solution(variables, &x, &f, iteration - 1));
+        }
+
+        let mut jacobian = vec![vec![0.0; x.len()]; x.len()];
+        for j in 0..x.len() {
+            let h = f64::EPSILON.cbrt() * x[j].abs().max(1.0);
+            let (mut forward, mut backward) = (x.clone(), x.clone());
+            forward[j] += h;
+            backward[j] -= h;
+            let (high, low) = (residuals(compiled, &forward)?, residuals(compiled, &backward)?);
+            for i in 0..x.len() {
+                jacobian[i][j] = (high[i] - low[i]) / (2.0 * h);
+            }
+        }
+        let step = solve_dense(jacobian, f.iter().map(|v| -v).collect()).ok_or_else(|| {
+            format!("The Jacobian is singular at {}; try another initial guess", point(variables, &x))
+        })?;
+
+        let mut t = 1.0;
+        loop {
+            let candidate: Vec<f64> = x.iter().zip(&step).map(|(x, d)| x + t * d).collect();
+            match residuals(compiled, &candidate) {
+                Ok(next) if norm(&next) < norm(&f) || t < 1e-4 => {
+                    (x, f) = (candidate, next);
+                    break;
+                }
+                Err(error) if t < 1e-4 => return Err(error),
+                _ => t /= 2.0,
+            }
+        }
+
+        // Newton converges quadratically near a simple root, so a negligible step means
+        // the point is as accurate as f64 allows.
+        let scale = x.iter().fold(1.0f64, |m, v| m.max(v.abs()));
+        if t * norm(&step) <= STEP_TOLERANCE * scale && norm(&f) <= RESIDUAL_TOLERANCE * scale {
+            return Ok(newton_solution(variables, &x, &f, iteration));
+        }
+    }
+    Err(format!(
+        "Newton's method did not converge after {} iterations (residual {} at {})",
+        max_iterations,
+        norm(&f),
+        point(variables, &x)
+    ))
+}
+
+fn newton_solution(variables: &[String], x: &[f64], f: &[f64], iterations: usize) -> SolveResponse {
+    SolveResponse {
+        method: Some(Method::Newton),
+        status: Some(Status::Approximate),
+        solution: variables
+            .iter()
+            .zip(x)
+            .map(|(variable, value)| Assignment {
+                variable: variable.clone(),
+                value: Some(ExtendedReal(*value)),
+                expression: value.to_string(),
+                free: false,
+            })
+            .collect(),
+        iterations: Some(iterations),
+        residual: Some(ExtendedReal(norm(f))),
+        error: None,
+    }
+}
+
+fn point(variables: &[String], x: &[f64]) -> String {
+    let parts: Vec<String> = variables.iter().zip(x).map(|(v, x)| format!("{} = {}", v, x)).collect();
+    format!("({})", parts.join(", "))
+}
+
+pub fn solve(request: &SolveRequest) -> Result<SolveResponse, String> {
+    let texts: Vec<&str> = match &request.system {
+        Some(_) if !request.equations.is_empty() => return Err("Use either system or equations, not both".to_string()),
+        Some(system) => system.split([';', '\n']).collect(),
+        None => request.equations.iter().map(String::as_str).collect(),
+    };
+    let texts: Vec<&str> = texts.into_iter().map(str::trim).filter(|t| !t.is_empty()).collect();
+    if texts.is_empty() {
+        return Err("No equations given".to_string());
+    }
+    if texts.len() > MAX_EQUATIONS {
+        return Err(format!("A system holds at most {} equations", MAX_EQUATIONS));
+    }
+    let max_iterations = request.max_iterations.unwrap_or(DEFAULT_ITERATIONS);
+    if max_iterations > MAX_ITERATIONS {
+        return Err(format!("max_iterations is limited to {}", MAX_ITERATIONS));
+    }
+
+    let equations: Vec<Equation> = texts
+        .iter()
+        .enumerate()
+        .map(|(i, text)| parse_equation(text).map_err(|error| format!("Equation {} ('{}'): {}", i + 1, text, error)))
+        .collect::<Result<_, _>>()?;
+
+    // Compiling appends each new free identifier, so one pass collects the unknowns in
+    // order of first appearance; a second pass gives every equation the same slots.
+    let mut variables = request.variables.clone().unwrap_or_default();
+    for equation in &equations {
+        variables = CompiledExpression::compile(&equation.expr, &variables)?.variables().to_vec();
+    }
+    if let Some(requested) = &request.variables {
+        if let Some(extra) = variables.iter().find(|v| !requested.contains(v)) {
+            return Err(format!("'{}' is not one of the listed variables", extra));
+        }
+    }
+    if let Some(name) = variables.iter().find(|v| functions::constant(v).is_some()) {
+        return Err(format!("'{}' is a constant and cannot be an unknown", name));
+    }
+    if variables.is_empty() {
+        return Err("The equations have no unknowns".to_string());
+    }
+    let typed: Vec<(String, Option<Type>)> = variables.iter().map(|v| (v.clone(), Some(Type::Number))).collect();
+    let compiled: Vec<CompiledExpression> = equations
+        .iter()
+        .enumerate()
+        .map(|(i, equation)| {
+            let compiled = CompiledExpression::compile_typed(&equation.expr, &typed)?;
+            if compiled.result_type() != Type::Number {
+                return Err(format!("Equation {}: both sides must be numbers, found a {}", i + 1, compiled.result_type().name()));
+            }
+            Ok(compiled)
+        })
+        .collect::<Result<_, String>>()?;
+
+    let linear: Option<Vec<Affine>> = equations
+        .iter()
+        .map(|equation| {
+            let source: Vec<char> = equation.source.chars().collect();
+            affine(&equation.expr, &source, &variables)
+        })
+        .collect();
+    match linear {
+        Some(rows) => Ok(solve_linear(rows, &variables)),
+        None => solve_newton(&compiled, &variables, &request.initial, max_iterations),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn system(text: &str) -> SolveRequest {
+        SolveRequest {
+            system: Some(text.to_string()),
+            equations: Vec::new(),
+            variables: None,
+            initial: HashMap::new(),
+            max_iterations: None,
+        }
+    }
+
+    fn expressions(response: &SolveResponse) -> Vec<String> {
+        response.solution.iter().map(|a| format!("{} = {}", a.variable, a.expression)).collect()
+    }
+
+    #[test]
+    fn equations_split_at_their_single_equals_sign() {
+        assert_eq!(split_equation("x <= y = 1"), Ok(("x <= y ", " 1")));
+        assert_eq!(split_equation("if(x == 1, 2, 3) = y"), Ok(("if(x == 1, 2, 3) ", " y")));
+        assert_eq!(split_equation("x != 1").unwrap_err(), "no '='");
+        assert_eq!(split_equation("x = 1 = 2").unwrap_err(), "more than one '='");
+    }
+
+    #[test]
+    fn linear_systems_are_solved_exactly() {
+        let response = solve(&system("2x + y = 5; x - y = 1")).unwrap();
+        assert!(matches!(response.method, Some(Method::Gaussian)));
+        assert!(matches!(response.status, Some(Status::Unique)));
+        assert_eq!(expressions(&response), ["x = 2", "y = 1"]);
+
+        let response = solve(&system("3x = 1")).unwrap();
+        assert_eq!(expressions(&response), ["x = 1/3"]);
+        assert_eq!(response.solution[0].value, Some(ExtendedReal(1.0 / 3.0)));
+
+        let response = solve(&system("x + y = 1\nx + y = 2")).unwrap();
+        assert!(matches!(response.status, Some(Status::NoSolution)));
+        assert!(response.solution.is_empty());
+
+        let response = solve(&system("x + 2y = 5")).unwrap();
+        assert!(matches!(response.status, Some(Status::Infinite)));
+        assert_eq!(expressions(&response), ["x = 5 - 2y", "y = y"]);
+        assert!(response.solution[0].value.is_none() && response.solution[1].free);
+    }
+
+    #[test]
+    fn nonlinear_systems_use_newton() {
+        let response = solve(&system("x^2 + y^2 = 4; x = y")).unwrap();
+        assert!(matches!(response.method, Some(Method::Newton)));
+        assert!(matches!(response.status, Some(Status::Approximate)));
+        for assignment in &response.solution {
+            assert!((assignment.value.unwrap().0 - 2f64.sqrt()).abs() < 1e-9, "{}", assignment.expression);
+        }
+
+        let mut request = system("x^2 = 2");
+        request.initial.insert("x".to_string(), -1.0);
+        let x = solve(&request).unwrap().solution[0].value.unwrap().0;
+        assert!((x + 2f64.sqrt()).abs() < 1e-9, "{}", x);
+    }
+
+    #[test]
+    fn bad_requests_are_errors() {
+        let error = |request: SolveRequest| solve(&request).unwrap_err();
+        assert_eq!(error(system(" ; ")), "No equations given");
+        assert_eq!(error(system("x + 1")), "Equation 1 ('x + 1'): no '='");
+        assert_eq!(error(system("1 = 2")), "The equations have no unknowns");
+        let mut request = system("x = 1");
+        request.equations.push("x = 2".to_string());
+        assert_eq!(error(request), "Use either system or equations, not both");
+        let mut request = system("x + y = 1");
+        request.variables = Some(vec!["x".to_string()]);
+        assert_eq!(error(request), "'y' is not one of the listed variables");
+        let mut request = system("x^2 = 2");
+        request.max_iterations = Some(MAX_ITERATIONS + 1);
+        assert_eq!(error(request), format!("max_iterations is limited to {}", MAX_ITERATIONS));
+    }
+}
@@ -0,0 +1,387 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+use crate::base;
+use crate::i18n::{Error, FINANCIAL_NON_FINITE, FINANCIAL_TOO_LARGE, PRECISION_RANGE};
+
+/// The most decimals, or significant digits, a format may ask for.
+pub const MAX_PRECISION: usize = 100;
+const CHINESE_DIGITS: [&str; 10] = ["零", "壹", "贰", "叁", "肆", "伍", "陆", "柒", "捌", "玖"];
+const CHINESE_UNITS: [&str; 4] = ["", "拾", "佰", "仟"];
+const CHINESE_SECTIONS: [&str; 4] = ["", "万", "亿", "万亿"];
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Notation {
+    #[default]
+    General,
+    Fixed,
+    Significant,
+    Scientific,
+    Engineering,
+    ChineseFinancial,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct FormatOptions {
+    pub notation: Notation,
+    pub precision: Option<usize>,
+    pub thousands_separator: bool,
+    pub locale: Option<String>,
+    /// Writes numbers in this base, 2 to 36, instead of decimal; `notation` is ignored.
+    pub base: Option<u32>,
+    /// Custom digits for `base`, one per digit value.
+    pub alphabet: Option<String>,
+}
+
+struct LocaleSymbols {
+    decimal: char,
+    group: char,
+    indian_grouping: bool,
+}
+
+fn locale_symbols(locale: Option<&str>) -> LocaleSymbols {
+    let tag = locale.unwrap_or("en").to_ascii_lowercase().replace('_', "-");
+    let language = tag.split('-').next().unwrap_or("").to_string();
+
+    let (decimal, group, indian_grouping) = match tag.as_str() {
+        "de-ch" | "it-ch" | "fr-ch" => ('.', '\'', false),
+        "en-in" | "hi-in" | "hi" => ('.', ',', true),
+        _ => match language.as_str() {
+            "de" | "es" | "it" | "nl" | "pt" | "id" | "tr" | "da" | "el" => (',', '.', false),
+            "fr" | "ru" | "pl" | "cs" | "sv" | "fi" | "nb" | "uk" => (',', '\u{a0}', false),
+            _ => ('.', ',', false),
+        },
+    };
+
+    LocaleSymbols { decimal, group, indian_grouping }
+}
+
+pub fn format_number(value: f64, options: &FormatOptions) -> Result<String, Error> {
+    if let Some(precision) = options.precision.filter(|precision| *precision > MAX_PRECISION) {
+        return Err(PRECISION_RANGE.with([MAX_PRECISION.into(), precision.into()]));
+    }
+    if let Some(base) = options.base {
+        let base = base::check_base(base as f64)?;
+        return base::to_base(value, base, options.alphabet.as_deref()).map(|digits| digits.to_string());
+    }
+    if options.notation == Notation::ChineseFinancial {
+        return to_chinese_financial(value);
+    }
+
+    if value.is_nan() {
+        return Ok("NaN".to_string());
+    }
+    if value.is_infinite() {
+        return Ok(if value > 0.0 { "∞" } else { "-∞" }.to_string());
+    }
+
+    let raw = match options.notation {
+        Notation::General => {
+            let abs = value.abs();
+            if abs != 0.0 && !(1e-6..1e15).contains(&abs) {
+                format!("{:e}", value)
+            } else {
+                value.to_string()
+            }
+        }
+        Notation::Fixed => format!("{:.*}", options.precision.unwrap_or(2), value),
+        Notation::Significant => significant(value, options.precision.unwrap_or(6).max(1)),
+        Notation::Scientific => {
+            let (negative, digits, exponent) = decimal_digits(value, options.precision.unwrap_or(6).max(1));
+            let mantissa = if digits.len() > 1 {
+                format!("{}.{}", &digits[..1], &digits[1..])
+            } else {
+                digits
+            };
+            format!("{}{}e{}", if negative { "-" } else { "" }, mantissa, exponent)
+        }
+        Notation::Engineering => engineering(value, options.precision.unwrap_or(6).max(1)),
+        Notation::ChineseFinancial => unreachable!(),
+    };
+
+    Ok(localize(&drop_negative_zero(raw), options))
+}
+
+// Zero has no sign in output: -0, and negatives that round to zero such as -0.001 to two
+// decimals, print without the minus.
+fn drop_negative_zero(raw: String) -> String {
+    match raw.strip_prefix('-') {
+        Some(unsigned) if !unsigned.split('e').next().unwrap().bytes().any(|b| (b'1'..=b'9').contains(&b)) => {
+            unsigned.to_string()
+        }
+        _ => raw,
+    }
+}
+
+// Splits `value` into its sign, `precision` rounded significant digits and decimal exponent.
+fn decimal_digits(value: f64, precision: usize) -> (bool, String, i32) {
+    let formatted = format!("{:.*e}", precision - 1, value.abs());
+    let (mantissa, exponent) = formatted.split_once('e').unwrap();
+    let digits = mantissa.replace('.', "");
+    let exponent: i32 = exponent.parse().unwrap();
+    (value.is_sign_negative() && value != 0.0, digits, exponent)
+}
+
+fn significant(value: f64, precision: usize) -> String {
+    let (negative, digits, exponent) = decimal_digits(value, precision);
+    let sign = if negative { "-" } else { "" };
+
+    if exponent < 0 {
+        let zeros = "0".repeat((-exponent - 1) as usize);
+        return format!("{}0.{}{}", sign, zeros, digits);
+    }
+
+    let integer_len = exponent as usize + 1;
+    if integer_len >= digits.len() {
+        format!("{}{}{}", sign, digits, "0".repeat(integer_len - digits.len()))
+    } else {
+        format!("{}{}.{}", sign, &digits[..integer_len], &digits[integer_len..])
+    }
+}
+
+fn engineering(value: f64, precision: usize) -> String {
+    let (negative, mut digits, exponent) = decimal_digits(value, precision);
+    let sign = if negative { "-" } else { "" };
+    let eng_exponent = exponent.div_euclid(3) * 3;
+    let integer_len = (exponent - eng_exponent) as usize + 1;
+
+    if digits.len() < integer_len {
+        digits.push_str(&"0".repeat(integer_len - digits.len()));
+    }
+
+    let mantissa = if digits.len() > integer_len {
+        format!("{}.{}", &digits[..integer_len], &digits[integer_len..])
+    } else {
+        digits
+    };
+    format!("{}{}e{}", sign, mantissa, eng_exponent)
+}
+
+// Applies the locale's decimal mark and, if requested, digit grouping to a plain number string.
+fn localize(raw: &str, options: &FormatOptions) -> String {
+    let symbols = locale_symbols(options.locale.as_deref());
+
+    let (sign, unsigned) = match raw.strip_prefix('-') {
+        Some(rest) => ("-", rest),
+        None => ("", raw),
+    };
+    let (mantissa, exponent) = match unsigned.split_once('e') {
+        Some((m, e)) => (m, Some(e)),
+        None => (unsigned, None),
+    };
+    let (integer, fraction) = match mantissa.split_once('.') {
+        Some((i, f)) => (i, Some(f)),
+        None => (mantissa, None),
+    };
+
+    let integer = if options.thousands_separator && exponent.is_none() {
+        group_digits(integer, symbols.group, symbols.indian_grouping)
+    } else {
+        integer.to_string()
+    };
+
+    let mut result = format!("{}{}", sign, integer);
+    if let Some(fraction) = fraction {
+        result.push(symbols.decimal);
+        result.push_str(fraction);
+    }
+    if let Some(exponent) = exponent {
+        result.push('e');
+        result.push_str(exponent);
+    }
+    result
+}
+
+fn group_digits(integer: &str, separator: char, indian: bool) -> String {
+    let digits: Vec<char> = integer.chars().collect();
+    let mut groups: Vec<String> = Vec::new();
+    let mut end = digits.len();
+    let mut size = 3;
+
+    while end > 0 {
+        let start = end.saturating_sub(size);
+        groups.push(digits[start..end].iter().collect());
+        end = start;
+        if indian {
+            size = 2;
+        }
+    }
+
+    groups.reverse();
+    groups.join(&separator.to_string())
+}
+
+pub fn to_chinese_financial(value: f64) -> Result<String, Error> {
+    if !value.is_finite() {
+        return Err(FINANCIAL_NON_FINITE.error());
+    }
+    if value.abs() >= 1e16 {
+        return Err(FINANCIAL_TOO_LARGE.error());
+    }
+
+    let fixed = format!("{:.2}", value.abs());
+    let (integer, fraction) = fixed.split_once('.').unwrap();
+    let jiao = fraction.as_bytes()[0] - b'0';
+    let fen = fraction.as_bytes()[1] - b'0';
+    let integer_zero = integer.bytes().all(|b| b == b'0');
+
+    let mut result = String::new();
+    if value < 0.0 && !(integer_zero && jiao == 0 && fen == 0) {
+        result.push('负');
+    }
+
+    if integer_zero {
+        if jiao == 0 && fen == 0 {
+            return Ok("零元整".to_string());
+        }
+    } else {
+        result.push_str(&chinese_integer(integer));
+        result.push('元');
+    }
+
+    if jiao == 0 && fen == 0 {
+        result.push('整');
+        return Ok(result);
+    }
+
+    if jiao > 0 {
+        result.push_str(CHINESE_DIGITS[jiao as usize]);
+        result.push('角');
+    } else if !integer_zero {
+        result.push('零');
+    }
+    if fen > 0 {
+        result.push_str(CHINESE_DIGITS[fen as usize]);
+        result.push('分');
+    }
+
+    Ok(result)
+}
+
+fn chinese_integer(integer: &str) -> String {
+    let digits: Vec<u8> = integer.bytes().map(|b| b - b'0').collect();
+    let section_count = digits.len().div_ceil(4);
+    let mut result = String::new();
+    let mut pending_zero = false;
+
+    for section in (0..section_count).rev() {
+        let end = digits.len() - section * 4;
+        let start = end.saturating_sub(4);
+        let chunk = &digits[start..end];
+
+        if chunk.iter().all(|&d| d == 0) {
+            pending_zero = !result.is_empty();
+            continue;
+        }
+
+        for (i, &digit) in chunk.iter().enumerate() {
+            let position = chunk.len() - 1 - i;
+            if digit == 0 {
+                pending_zero = !result.is_empty();
+                continue;
+            }
+            if pending_zero {
+                result.push('零');
+                pending_zero = false;
+            }
+            result.push_str(CHINESE_DIGITS[digit as usize]);
+            result.push_str(CHINESE_UNITS[position]);
+        }
+        result.push_str(CHINESE_SECTIONS[section]);
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(value: f64, notation: Notation, precision: Option<usize>) -> String {
+        format_number(value, &FormatOptions { notation, precision, ..Default::default() }).unwrap()
+    }
+
+    #[test]
+    fn non_finite_numbers() {
+        assert_eq!(format(f64::NAN, Notation::General, None), "NaN");
+        assert_eq!(format(f64::INFINITY, Notation::Fixed, None), "∞");
+        assert_eq!(format(f64::NEG_INFINITY, Notation::Scientific, None), "-∞");
+        assert!(to_chinese_financial(f64::NAN).is_err());
+    }
+
+    #[test]
+    fn negative_zero_prints_as_zero() {
+        assert_eq!(format(-0.0, Notation::General, None), "0");
+        assert_eq!(format(-0.0, Notation::Fixed, None), "0.00");
+        assert_eq!(format(-0.001, Notation::Fixed, Some(2)), "0.00");
+        assert_eq!(format(-0.0, Notation::Scientific, Some(3)), "0.00e0");
+        assert_eq!(format(-0.0, Notation::Engineering, None), format(0.0, Notation::Engineering, None));
+        assert_eq!(format(-0.005, Notation::Fixed, Some(3)), "-0.005");
+        assert_eq!(format(-1e-300, Notation::General, None), "-1e-300");
+    }
+
+    #[test]
+    fn precision_is_bounded() {
+        assert_eq!(format(1.0 / 3.0, Notation::Fixed, Some(0)), "0");
+        assert_eq!(format(1.0 / 3.0, Notation::Fixed, Some(MAX_PRECISION)).len(), MAX_PRECISION + 2);
+        for notation in [Notation::Fixed, Notation::Significant, Notation::Scientific] {
+            let options = FormatOptions { notation, precision: Some(400), ..Default::default() };
+            let error = format_number(1.0, &options).unwrap_err();
+            assert_eq!(error.to_string(), "Precision must be from 0 to 100, got 400");
+        }
+    }
+
+    fn localized(value: f64, locale: &str) -> String {
+        let options = FormatOptions {
+            notation: Notation::Fixed,
+            thousands_separator: true,
+            locale: Some(locale.to_string()),
+            ..Default::default()
+        };
+        format_number(value, &options).unwrap()
+    }
+
+    #[test]
+    fn locales_choose_decimal_and_group_separators() {
+        assert_eq!(localized(-1234567.891, "en"), "-1,234,567.89");
+        assert_eq!(localized(1234567.891, "de-DE"), "1.234.567,89");
+        assert_eq!(localized(1234567.891, "fr_FR"), "1\u{a0}234\u{a0}567,89");
+        assert_eq!(localized(1234567.891, "de-CH"), "1'234'567.89");
+        assert_eq!(localized(123.0, "de"), "123,00");
+        let options = FormatOptions { locale: Some("de".to_string()), ..Default::default() };
+        assert_eq!(format_number(1234.5, &options).unwrap(), "1234,5");
+    }
+
+    #[test]
+    fn indian_grouping() {
+        assert_eq!(localized(1234567.0, "en-IN"), "12,34,567.00");
+        assert_eq!(localized(123456789.0, "hi"), "12,34,56,789.00");
+        assert_eq!(localized(999.0, "en-IN"), "999.00");
+        assert_eq!(localized(1000.0, "en-IN"), "1,000.00");
+    }
+
+    #[test]
+    fn engineering_exponents_are_multiples_of_three() {
+        assert_eq!(format(12345.0, Notation::Engineering, Some(5)), "12.345e3");
+        assert_eq!(format(0.00012, Notation::Engineering, Some(2)), "120e-6");
+        assert_eq!(format(-1.5, Notation::Engineering, Some(2)), "-1.5e0");
+        assert_eq!(format(1e6, Notation::Engineering, Some(1)), "1e6");
+    }
+
+    #[test]
+    fn chinese_financial_amounts() {
+        assert_eq!(to_chinese_financial(0.0).unwrap(), "零元整");
+        assert_eq!(to_chinese_financial(-0.001).unwrap(), "零元整");
+        assert_eq!(to_chinese_financial(0.05).unwrap(), "伍分");
+        assert_eq!(to_chinese_financial(1.5).unwrap(), "壹元伍角");
+        assert_eq!(to_chinese_financial(10.08).unwrap(), "壹拾元零捌分");
+        assert_eq!(to_chinese_financial(-1001.0).unwrap(), "负壹仟零壹元整");
+        assert_eq!(to_chinese_financial(100_000_000.0).unwrap(), "壹亿元整");
+        assert_eq!(to_chinese_financial(120_003_000.0).unwrap(), "壹亿贰仟万零叁仟元整");
+        assert_eq!(to_chinese_financial(1e16).unwrap_err().to_string(), FINANCIAL_TOO_LARGE.error().to_string());
+        let options = FormatOptions { notation: Notation::ChineseFinancial, ..Default::default() };
+        assert_eq!(format_number(3.2, &options).unwrap(), "叁元贰角");
+    }
+}
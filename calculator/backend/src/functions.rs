@@ -0,0 +1,297 @@
+use std::f64::consts;
+
+use crate::i18n::{Error, FACTORIAL_DOMAIN, RAND_ARGUMENT_COUNT};
+use crate::registry::{self, Function};
+use crate::{distributions, random, special};
+
+#[derive(Debug)]
+pub struct Builtin {
+    pub name: &'static str,
+    pub min_args: usize,
+    pub max_args: Option<usize>,
+    pub pure: bool,
+    pub apply: fn(&[f64]) -> Result<f64, Error>,
+}
+
+impl Function for Builtin {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn arity(&self) -> (usize, Option<usize>) {
+        (self.min_args, self.max_args)
+    }
+
+    fn pure(&self) -> bool {
+        self.pure
+    }
+
+    fn evaluate(&self, args: &[f64]) -> Result<f64, Error> {
+        (self.apply)(args)
+    }
+
+    fn signature(&self) -> String {
+        documentation(self.name).map_or_else(|| registry::generic_signature(self), |(signature, _)| signature.to_string())
+    }
+
+    fn doc(&self) -> &str {
+        documentation(self.name).map_or("", |(_, doc)| doc)
+    }
+}
+
+macro_rules! unary {
+    ($name:expr, $f:expr) => {
+        Builtin { name: $name, min_args: 1, max_args: Some(1), pure: true, apply: |args| Ok($f(args[0])) }
+    };
+}
+
+macro_rules! fallible {
+    ($name:expr, 2, $f:expr) => {
+        Builtin { name: $name, min_args: 2, max_args: Some(2), pure: true, apply: |args| $f(args[0], args[1]) }
+    };
+    ($name:expr, 3, $f:expr) => {
+        Builtin { name: $name, min_args: 3, max_args: Some(3), pure: true, apply: |args| $f(args[0], args[1], args[2]) }
+    };
+}
+
+macro_rules! binary {
+    ($name:expr, $f:expr) => {
+        Builtin { name: $name, min_args: 2, max_args: Some(2), pure: true, apply: |args| Ok($f(args[0], args[1])) }
+    };
+}
+
+pub static BUILTINS: &[Builtin] = &[
+    unary!("abs", f64::abs),
+    unary!("sqrt", f64::sqrt),
+    unary!("cbrt", f64::cbrt),
+    unary!("exp", f64::exp),
+    unary!("ln", f64::ln),
+    Builtin {
+        name: "log",
+        min_args: 1,
+        max_args: Some(2),
+        pure: true,
+        apply: |args| Ok(if args.len() == 2 { args[0].log(args[1]) } else { args[0].log10() }),
+    },
+    unary!("log10", f64::log10),
+    unary!("log2", f64::log2),
+    unary!("sin", f64::sin),
+    unary!("cos", f64::cos),
+    unary!("tan", f64::tan),
+    unary!("asin", f64::asin),
+    unary!("acos", f64::acos),
+    unary!("atan", f64::atan),
+    binary!("atan2", f64::atan2),
+    unary!("sinh", f64::sinh),
+    unary!("cosh", f64::cosh),
+    unary!("tanh", f64::tanh),
+    unary!("floor", f64::floor),
+    unary!("ceil", f64::ceil),
+    unary!("round", f64::round),
+    unary!("trunc", f64::trunc),
+    // f64::signum gives ±1 for ±0.
+    unary!("sign", |x: f64| if x == 0.0 { 0.0 } else { x.signum() }),
+    binary!("hypot", f64::hypot),
+    binary!("pow", f64::powf),
+    Builtin {
+        name: "min",
+        min_args: 1,
+        max_args: None,
+        pure: true,
+        apply: |args| Ok(args.iter().copied().fold(f64::INFINITY, f64::min)),
+    },
+    Builtin {
+        name: "max",
+        min_args: 1,
+        max_args: None,
+        pure: true,
+        apply: |args| Ok(args.iter().copied().fold(f64::NEG_INFINITY, f64::max)),
+    },
+    Builtin { name: "fact", min_args: 1, max_args: Some(1), pure: true, apply: |args| factorial(args[0]) },
+    Builtin { name: "gamma", min_args: 1, max_args: Some(1), pure: true, apply: |args| special::gamma(args[0]) },
+    Builtin { name: "lgamma", min_args: 1, max_args: Some(1), pure: true, apply: |args| special::lgamma(args[0]) },
+    Builtin { name: "beta", min_args: 2, max_args: Some(2), pure: true, apply: |args| special::beta(args[0], args[1]) },
+    fallible!("gammainc", 2, special::gammainc),
+    fallible!("gammaincc", 2, special::gammaincc),
+    fallible!("betainc", 3, special::betainc),
+    unary!("erf", special::erf),
+    unary!("erfc", special::erfc),
+    fallible!("besselj", 2, special::besselj),
+    fallible!("bessely", 2, special::bessely),
+    Builtin {
+        name: "lambertw",
+        min_args: 1,
+        max_args: Some(2),
+        pure: true,
+        apply: |args| special::lambertw(args[0], arg_or(args, 1, 0.0)),
+    },
+    Builtin { name: "zeta", min_args: 1, max_args: Some(1), pure: true, apply: |args| special::zeta(args[0]) },
+    Builtin {
+        name: "normal_pdf",
+        min_args: 1,
+        max_args: Some(3),
+        pure: true,
+        apply: |args| distributions::normal_pdf(args[0], arg_or(args, 1, 0.0), arg_or(args, 2, 1.0)),
+    },
+    Builtin {
+        name: "normal_cdf",
+        min_args: 1,
+        max_args: Some(3),
+        pure: true,
+        apply: |args| distributions::normal_cdf(args[0], arg_or(args, 1, 0.0), arg_or(args, 2, 1.0)),
+    },
+    Builtin {
+        name: "normal_inv",
+        min_args: 1,
+        max_args: Some(3),
+        pure: true,
+        apply: |args| distributions::normal_inv(args[0], arg_or(args, 1, 0.0), arg_or(args, 2, 1.0)),
+    },
+    fallible!("binom_pdf", 3, distributions::binomial_pdf),
+    fallible!("binom_cdf", 3, distributions::binomial_cdf),
+    fallible!("binom_inv", 3, distributions::binomial_inv),
+    fallible!("poisson_pdf", 2, distributions::poisson_pdf),
+    fallible!("poisson_cdf", 2, distributions::poisson_cdf),
+    fallible!("poisson_inv", 2, distributions::poisson_inv),
+    fallible!("t_pdf", 2, distributions::t_pdf),
+    fallible!("t_cdf", 2, distributions::t_cdf),
+    fallible!("t_inv", 2, distributions::t_inv),
+    fallible!("chi2_pdf", 2, distributions::chi2_pdf),
+    fallible!("chi2_cdf", 2, distributions::chi2_cdf),
+    fallible!("chi2_inv", 2, distributions::chi2_inv),
+    fallible!("exp_pdf", 2, distributions::exponential_pdf),
+    fallible!("exp_cdf", 2, distributions::exponential_cdf),
+    fallible!("exp_inv", 2, distributions::exponential_inv),
+    Builtin {
+        name: "rand",
+        min_args: 0,
+        max_args: Some(2),
+        pure: false,
+        apply: |args| match args {
+            [] => random::uniform(0.0, 1.0),
+            [low, high] => random::uniform(*low, *high),
+            _ => Err(RAND_ARGUMENT_COUNT.error()),
+        },
+    },
+    Builtin { name: "randint", min_args: 2, max_args: Some(2), pure: false, apply: |args| random::integer(args[0], args[1]) },
+    Builtin {
+        name: "randn",
+        min_args: 0,
+        max_args: Some(2),
+        pure: false,
+        apply: |args| random::normal(arg_or(args, 0, 0.0), arg_or(args, 1, 1.0)),
+    },
+    Builtin { name: "dice", min_args: 2, max_args: Some(2), pure: false, apply: |args| random::dice(args[0], args[1]) },
+];
+
+/// Signatures and descriptions of the builtins (including the date functions), for
+/// editor completion.
+static DOCS: &[(&str, &str, &str)] = &[
+    ("abs", "abs(x)", "Absolute value"),
+    ("sqrt", "sqrt(x)", "Square root"),
+    ("cbrt", "cbrt(x)", "Cube root"),
+    ("exp", "exp(x)", "e raised to the power x"),
+    ("ln", "ln(x)", "Natural logarithm"),
+    ("log", "log(x, base?)", "Logarithm, base 10 unless a base is given"),
+    ("log10", "log10(x)", "Base-10 logarithm"),
+    ("log2", "log2(x)", "Base-2 logarithm"),
+    ("sin", "sin(x)", "Sine of an angle in radians"),
+    ("cos", "cos(x)", "Cosine of an angle in radians"),
+    ("tan", "tan(x)", "Tangent of an angle in radians"),
+    ("asin", "asin(x)", "Inverse sine, in radians"),
+    ("acos", "acos(x)", "Inverse cosine, in radians"),
+    ("atan", "atan(x)", "Inverse tangent, in radians"),
+    ("atan2", "atan2(y, x)", "Angle of the point (x, y), in radians"),
+    ("sinh", "sinh(x)", "Hyperbolic sine"),
+    ("cosh", "cosh(x)", "Hyperbolic cosine"),
+    ("tanh", "tanh(x)", "Hyperbolic tangent"),
+    ("floor", "floor(x)", "Largest integer not above x"),
+    ("ceil", "ceil(x)", "Smallest integer not below x"),
+    ("round", "round(x)", "Nearest integer, halves away from zero"),
+    ("trunc", "trunc(x)", "Integer part of x"),
+    ("sign", "sign(x)", "1 for positive x, -1 for negative x, 0 for zero"),
+    ("hypot", "hypot(x, y)", "Length of the vector (x, y)"),
+    ("pow", "pow(x, y)", "x raised to the power y"),
+    ("min", "min(x, ...)", "Smallest argument"),
+    ("max", "max(x, ...)", "Largest argument"),
+    ("fact", "fact(n)", "Factorial of a non-negative integer"),
+    ("gamma", "gamma(x)", "Gamma function; gamma(n) = (n - 1)!"),
+    ("lgamma", "lgamma(x)", "Natural logarithm of |gamma(x)|"),
+    ("beta", "beta(a, b)", "Beta function gamma(a) gamma(b) / gamma(a + b)"),
+    ("gammainc", "gammainc(a, x)", "Regularized lower incomplete gamma function P(a, x)"),
+    ("gammaincc", "gammaincc(a, x)", "Regularized upper incomplete gamma function Q(a, x) = 1 - P(a, x)"),
+    ("betainc", "betainc(a, b, x)", "Regularized incomplete beta function I_x(a, b)"),
+    ("erf", "erf(x)", "Error function"),
+    ("erfc", "erfc(x)", "Complementary error function 1 - erf(x)"),
+    ("besselj", "besselj(n, x)", "Bessel function of the first kind, integer order n"),
+    ("bessely", "bessely(n, x)", "Bessel function of the second kind, integer order n, x > 0"),
+    ("lambertw", "lambertw(x, branch?)", "Lambert W: the w with w e^w = x; branch 0 (default) or -1"),
+    ("zeta", "zeta(s)", "Riemann zeta function"),
+    ("normal_pdf", "normal_pdf(x, mu?, sigma?)", "Normal density, standard unless mu and sigma are given"),
+    ("normal_cdf", "normal_cdf(x, mu?, sigma?)", "Normal cumulative probability P(X <= x)"),
+    ("normal_inv", "normal_inv(p, mu?, sigma?)", "Normal quantile: the x with P(X <= x) = p"),
+    ("binom_pdf", "binom_pdf(k, n, p)", "Binomial probability P(X = k)"),
+    ("binom_cdf", "binom_cdf(k, n, p)", "Binomial cumulative probability P(X <= k)"),
+    ("binom_inv", "binom_inv(q, n, p)", "Smallest k with P(X <= k) >= q"),
+    ("poisson_pdf", "poisson_pdf(k, lambda)", "Poisson probability P(X = k)"),
+    ("poisson_cdf", "poisson_cdf(k, lambda)", "Poisson cumulative probability P(X <= k)"),
+    ("poisson_inv", "poisson_inv(q, lambda)", "Smallest k with P(X <= k) >= q"),
+    ("t_pdf", "t_pdf(t, df)", "Student's t density"),
+    ("t_cdf", "t_cdf(t, df)", "Student's t cumulative probability"),
+    ("t_inv", "t_inv(p, df)", "Student's t quantile"),
+    ("chi2_pdf", "chi2_pdf(x, k)", "Chi-squared density"),
+    ("chi2_cdf", "chi2_cdf(x, k)", "Chi-squared cumulative probability"),
+    ("chi2_inv", "chi2_inv(p, k)", "Chi-squared quantile"),
+    ("exp_pdf", "exp_pdf(x, rate)", "Exponential density"),
+    ("exp_cdf", "exp_cdf(x, rate)", "Exponential cumulative probability"),
+    ("exp_inv", "exp_inv(p, rate)", "Exponential quantile"),
+    ("rand", "rand(low?, high?)", "Uniform random number in [0, 1) or [low, high)"),
+    ("randint", "randint(low, high)", "Uniform random integer in [low, high]"),
+    ("randn", "randn(mu?, sigma?)", "Normally distributed random number"),
+    ("dice", "dice(count, sides)", "Sum of count rolls of a die with the given sides; also written 3d6"),
+    ("today", "today()", "The current date (UTC)"),
+    ("date", "date(year, month, day)", "The given calendar date"),
+    ("workdays", "workdays(from, to)", "Monday-to-Friday days from one date up to another"),
+    ("weekday", "weekday(date)", "Day of the week, 1 = Monday to 7 = Sunday"),
+];
+
+fn documentation(name: &str) -> Option<(&'static str, &'static str)> {
+    DOCS.iter().find(|(entry, _, _)| *entry == name).map(|(_, signature, doc)| (*signature, *doc))
+}
+
+/// Constant names and descriptions; `constant` gives the values.
+pub static CONSTANTS: &[(&str, &str)] = &[
+    ("pi", "Ratio of a circle's circumference to its diameter"),
+    ("π", "Ratio of a circle's circumference to its diameter"),
+    ("e", "Base of the natural logarithm"),
+    ("tau", "2π"),
+    ("τ", "2π"),
+    ("phi", "The golden ratio"),
+    ("φ", "The golden ratio"),
+    ("inf", "Positive infinity"),
+];
+
+fn arg_or(args: &[f64], index: usize, default: f64) -> f64 {
+    args.get(index).copied().unwrap_or(default)
+}
+
+pub fn constant(name: &str) -> Option<f64> {
+    match name {
+        "pi" | "π" => Some(consts::PI),
+        "e" => Some(consts::E),
+        "tau" | "τ" => Some(consts::TAU),
+        "phi" | "φ" => Some(1.618_033_988_749_895),
+        "inf" => Some(f64::INFINITY),
+        _ => None,
+    }
+}
+
+pub fn factorial(n: f64) -> Result<f64, Error> {
+    if n < 0.0 || n.fract() != 0.0 {
+        return Err(FACTORIAL_DOMAIN.error());
+    }
+    if n > 170.0 {
+        return Ok(f64::INFINITY);
+    }
+    Ok((2..=n as u64).fold(1.0, |acc, k| acc * k as f64))
+}
@@ -0,0 +1,55 @@
+use serde::Serialize;
+use std::collections::VecDeque;
+
+use crate::value::Value;
+
+pub const MAX_HISTORY: usize = 100;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HistoryEntry {
+    pub id: u64,
+    pub expression: String,
+    pub result: Option<Value>,
+    pub formatted: Option<String>,
+    pub error: Option<String>,
+}
+
+/// The most recent evaluations, oldest first. Ids keep counting up after old entries
+/// are dropped or the history is cleared.
+#[derive(Debug)]
+pub struct History {
+    entries: VecDeque<HistoryEntry>,
+    next_id: u64,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self { entries: VecDeque::new(), next_id: 1 }
+    }
+
+    pub fn record(&mut self, expression: &str, outcome: Result<(Value, String), String>) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        if self.entries.len() >= MAX_HISTORY {
+            self.entries.pop_front();
+        }
+        let (result, formatted, error) = match outcome {
+            Ok((value, formatted)) => (Some(value), Some(formatted), None),
+            Err(error) => (None, None, Some(error)),
+        };
+        self.entries.push_back(HistoryEntry { id, expression: expression.to_string(), result, formatted, error });
+        id
+    }
+
+    /// Up to `limit` of the latest entries, oldest first.
+    pub fn recent(&self, limit: usize) -> Vec<HistoryEntry> {
+        self.entries.iter().skip(self.entries.len().saturating_sub(limit)).cloned().collect()
+    }
+
+    /// Empties the history and returns how many entries it held.
+    pub fn clear(&mut self) -> usize {
+        let count = self.entries.len();
+        self.entries.clear();
+        count
+    }
+}
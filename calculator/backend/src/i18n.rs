@@ -0,0 +1,435 @@
+/// Languages that error messages can be given in. Errors are built from the catalog
+/// below where they arise and written out in a language at the edge, so the catalog is
+/// the only place that knows about other languages.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Language {
+    #[default]
+    En,
+    ZhCn,
+}
+
+impl Language {
+    /// A BCP 47 tag such as `zh-CN`, `zh`, `en-US`; unsupported languages give `None`.
+    /// Every Chinese variant gets the simplified Chinese catalog.
+    pub fn parse(tag: &str) -> Option<Language> {
+        let tag = tag.trim().to_ascii_lowercase().replace('_', "-");
+        match tag.split('-').next().unwrap_or("") {
+            "en" => Some(Language::En),
+            "zh" => Some(Language::ZhCn),
+            _ => None,
+        }
+    }
+
+    /// The supported language the client prefers most, from an `Accept-Language` header
+    /// like `zh-CN,zh;q=0.9,en;q=0.8`.
+    pub fn from_accept_language(header: &str) -> Option<Language> {
+        let mut ranges: Vec<(f64, &str)> = header
+            .split(',')
+            .filter_map(|range| {
+                let mut parts = range.split(';');
+                let tag = parts.next()?.trim();
+                let quality = parts
+                    .find_map(|param| param.trim().strip_prefix("q="))
+                    .map_or(Some(1.0), |q| q.trim().parse::<f64>().ok())?;
+                Some((quality, tag))
+            })
+            .filter(|(quality, _)| *quality > 0.0)
+            .collect();
+        // Stable, so equally preferred languages keep the client's order.
+        ranges.sort_by(|a, b| b.0.total_cmp(&a.0));
+        ranges.into_iter().find_map(|(_, tag)| Language::parse(tag))
+    }
+}
+
+/// Text in every supported language: the English with `{}` for each argument, and its
+/// translation, with `{0}`, `{1}`, ... so that arguments can be reordered.
+#[derive(Debug, PartialEq)]
+pub struct Phrase {
+    english: &'static str,
+    chinese: &'static str,
+}
+
+/// An error message, with a stable code that several messages may share.
+#[derive(Debug, PartialEq)]
+pub struct Message {
+    code: &'static str,
+    phrase: Phrase,
+}
+
+/// An argument of a message.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Argument {
+    /// Shown as is in every language: names, numbers, positions, parts of the input.
+    Text(String),
+    /// A phrase of the catalog, with its own arguments shown as is.
+    Phrase(&'static Phrase, Vec<String>),
+}
+
+/// An error of the evaluator: a catalog message and its arguments, fixed where the
+/// error is produced. It displays in English; `localize` gives other languages.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Error {
+    message: &'static Message,
+    arguments: Vec<Argument>,
+}
+
+macro_rules! messages {
+    ($($name:ident = $code:literal: $english:literal => $chinese:literal;)*) => {
+        $(pub static $name: Message =
+            Message { code: $code, phrase: Phrase { english: $english, chinese: $chinese } };)*
+        #[cfg(test)]
+        static MESSAGES: &[&Message] = &[$(&$name),*];
+    };
+}
+
+macro_rules! phrases {
+    ($($name:ident: $english:literal => $chinese:literal;)*) => {
+        $(pub static $name: Phrase = Phrase { english: $english, chinese: $chinese };)*
+        #[cfg(test)]
+        static PHRASES: &[&Phrase] = &[$(&$name),*];
+    };
+}
+
+// Codes are part of the API and must not change.
+messages! {
+    // Anything the catalog does not know keeps its English text.
+    OTHER = "CALCULATOR_ERROR": "{}" => "{0}";
+    // Syntax
+    EMPTY_EXPRESSION = "EMPTY_EXPRESSION": "Empty expression" => "表达式为空";
+    EXPRESSION_TOO_LONG = "EXPRESSION_TOO_LONG":
+        "Expression exceeds the limit of {} characters" => "表达式超过了 {0} 个字符的上限";
+    EXPRESSION_TOO_DEEP = "EXPRESSION_TOO_LONG":
+        "Expression nests more than {} levels deep at position {}" => "位置 {1} 处的表达式嵌套超过了 {0} 层";
+    FUNCTION_BUDGET = "EXPRESSION_TOO_LONG":
+        "Defined functions ran more than {} steps in one request" => "自定义函数在一次请求中执行超过了 {0} 步";
+    UNEXPECTED_CHARACTER = "UNEXPECTED_CHARACTER":
+        "Unexpected character '{}' at position {}" => "位置 {1} 处有无法识别的字符 '{0}'";
+    UNEXPECTED_END = "UNEXPECTED_END": "Unexpected end of expression at position {}" => "表达式在位置 {0} 处意外结束";
+    UNTERMINATED_TEXT = "UNEXPECTED_END": "Unterminated text at position {}" => "位置 {0} 处的引号没有闭合";
+    UNEXPECTED_TOKEN = "UNEXPECTED_TOKEN": "Unexpected '{}' at position {}" => "位置 {1} 处不应出现 '{0}'";
+    EXPECTED_SEPARATOR = "EXPECTED_TOKEN":
+        "Expected ',' or ')' at position {}, found '{}'" => "位置 {0} 处应为 ',' 或 ')'，实际为 '{1}'";
+    EXPECTED_TOKEN_FOUND = "EXPECTED_TOKEN":
+        "Expected '{}' at position {}, found '{}'" => "位置 {1} 处应为 '{0}'，实际为 '{2}'";
+    EXPECTED_TOKEN = "EXPECTED_TOKEN": "Expected '{}' at position {}" => "位置 {1} 处应为 '{0}'";
+    INVALID_NUMBER = "INVALID_NUMBER": "Invalid number '{}' at position {}" => "位置 {1} 处的数字 '{0}' 无效";
+    INVALID_DICE = "INVALID_NUMBER": "Invalid dice at position {}" => "位置 {0} 处的骰子记法无效";
+    EXPECTED_NUMERATOR = "INVALID_NUMBER":
+        "Expected a number after '分之' at position {}" => "位置 {0} 处的“分之”后面应为数字";
+    EXPECTED_DECIMALS = "INVALID_NUMBER":
+        "Expected digits after '点' at position {}" => "位置 {0} 处的“点”后面应为数字";
+    NUMERAL_WITHOUT_NUMBER = "INVALID_NUMBER":
+        "'{}' needs a number before it at position {}" => "位置 {1} 处的“{0}”前面应为数字";
+    NUMERAL_OUT_OF_PLACE = "INVALID_NUMBER": "'{}' is out of place at position {}" => "位置 {1} 处的“{0}”位置不对";
+    NOT_A_DIGIT = "INVALID_NUMBER": "'{}' is not a digit in base {}" => "'{0}' 不是 {1} 进制的数字";
+    NO_DIGITS = "INVALID_NUMBER": "'{}' has no digits" => "'{0}' 中没有数字";
+    UNCLOSED_REPETEND = "INVALID_NUMBER":
+        "'{}' has an unclosed or empty repeating group" => "'{0}' 的循环节没有闭合或为空";
+    INVALID_BASE = "INVALID_BASE":
+        "Base must be an integer from 2 to 36, got {}" => "进制必须是 2 到 36 之间的整数，实际为 {0}";
+    BASE_NOT_CONSTANT = "INVALID_BASE": "The base of '{}' must be a constant" => "'{0}' 的进制必须是常量";
+    ALPHABET_LENGTH = "INVALID_BASE":
+        "An alphabet for base {} needs {} digits, got {}" => "{0} 进制的数字表需要 {1} 个数字，实际为 {2} 个";
+    INVALID_DIGIT = "INVALID_BASE": "'{}' cannot be a digit" => "'{0}' 不能用作数字";
+    REPEATED_DIGIT = "INVALID_BASE": "Digit '{}' appears twice in the alphabet" => "数字 '{0}' 在数字表中重复出现";
+    AMBIGUOUS_DIVISION = "AMBIGUOUS_DIVISION":
+        "Ambiguous '除' at position {}; write '除以' for division" => "位置 {0} 处的“除”有歧义，除法请写作“除以”";
+    INVALID_DATE_LITERAL = "INVALID_DATE": "Invalid date '{}' at position {}" => "位置 {1} 处的日期 '{0}' 无效";
+    INVALID_DATE = "INVALID_DATE": "Invalid date {}" => "日期 {0} 无效";
+    DATE_OUT_OF_RANGE = "INVALID_DATE": "Dates must fall in the years {} to {}" => "日期必须在 {0} 年到 {1} 年之间";
+    INVALID_CALENDAR_DATE = "INVALID_DATE":
+        "date(year, month, day) needs a valid calendar date" => "date(year, month, day) 需要有效的日历日期";
+    // Names and types
+    UNKNOWN_FUNCTION = "UNKNOWN_FUNCTION": "Unknown function '{}'" => "未知函数 '{0}'";
+    UNKNOWN_VARIABLE = "UNKNOWN_VARIABLE": "Unknown variable '{}'" => "未知变量 '{0}'";
+    VARIABLE_NOT_FOUND = "UNKNOWN_VARIABLE": "Variable '{}' not found" => "变量 '{0}' 不存在";
+    INVALID_NAME = "INVALID_NAME": "'{}' is not a valid variable name" => "'{0}' 不是有效的变量名";
+    WRONG_ARGUMENT_COUNT = "WRONG_ARGUMENT_COUNT":
+        "Function '{}' expects {} argument(s), got {}" => "函数 '{0}' 需要 {1} 个参数，实际为 {2} 个";
+    RAND_ARGUMENT_COUNT = "WRONG_ARGUMENT_COUNT":
+        "rand takes either no arguments or a lower and upper bound" => "rand 不带参数，或带下界与上界两个参数";
+    WRONG_VALUE_COUNT = "WRONG_ARGUMENT_COUNT":
+        "Expected {} variable value(s), got {}" => "需要 {0} 个变量值，实际为 {1} 个";
+    OPERANDS_MISMATCH = "TYPE_MISMATCH": "Cannot apply '{}' to a {} and a {}" => "不能对{1}和{2}使用 '{0}'";
+    OPERAND_MISMATCH = "TYPE_MISMATCH": "Cannot apply '{}' to a {}" => "不能对{1}使用 '{0}'";
+    MEMORY_MISMATCH = "TYPE_MISMATCH": "Memory holds numbers, got a {}" => "内存只能保存数值，实际为{0}";
+    TYPE_MISMATCH = "TYPE_MISMATCH": "{} expects a {}, got a {}" => "{0}需要{1}，实际为{2}";
+    TEXT_EXPECTED = "TYPE_MISMATCH":
+        "Function '{}' expects quoted text as argument {}" => "函数 '{0}' 的第 {1} 个参数应为带引号的文本";
+    UNCERTAIN_VALUES_UNSUPPORTED = "UNSUPPORTED_HERE":
+        "Values with uncertainty (±) and intervals ([a, b]) need the /uncertainty endpoint"
+        => "带不确定度的值（±）与区间（[a, b]）需使用 /uncertainty 接口";
+    BOOLEANS_UNSUPPORTED = "UNSUPPORTED_HERE":
+        "Comparisons and boolean logic need the /calculate endpoint" => "比较与布尔逻辑需使用 /calculate 接口";
+    DATES_UNSUPPORTED = "UNSUPPORTED_HERE":
+        "Dates and durations need the /calculate endpoint" => "日期与时长需使用 /calculate 接口";
+    TEXT_UNSUPPORTED = "UNSUPPORTED_HERE":
+        "Quoted text is only allowed as an argument of from_base and to_base"
+        => "带引号的文本只能用作 from_base 与 to_base 的参数";
+    TO_BASE_NOT_OUTERMOST = "UNSUPPORTED_HERE":
+        "to_base(x, b) must enclose the whole expression" => "to_base(x, b) 必须包住整个表达式";
+    // Arithmetic and domains
+    DIVISION_BY_ZERO = "DIVISION_BY_ZERO": "Division by zero" => "除数为零";
+    FACTORIAL_DOMAIN = "DOMAIN_ERROR":
+        "Factorial is only defined for non-negative integers" => "阶乘只对非负整数有定义";
+    POLE = "DOMAIN_ERROR": "{} has a pole at {}" => "{0} 在 {1} 处有极点";
+    BETA_DOMAIN = "DOMAIN_ERROR": "beta needs positive a and b" => "beta 需要 a 与 b 均为正数";
+    GAMMAINC_DOMAIN = "DOMAIN_ERROR": "gammainc needs a > 0 and x >= 0" => "gammainc 需要 a > 0 且 x >= 0";
+    GAMMAINCC_DOMAIN = "DOMAIN_ERROR": "gammaincc needs a > 0 and x >= 0" => "gammaincc 需要 a > 0 且 x >= 0";
+    BETAINC_DOMAIN = "DOMAIN_ERROR":
+        "betainc needs a > 0, b > 0 and 0 <= x <= 1" => "betainc 需要 a > 0、b > 0 且 0 <= x <= 1";
+    BESSEL_ORDER = "DOMAIN_ERROR":
+        "Bessel order must be an integer between -1000 and 1000" => "Bessel 函数的阶必须是 -1000 到 1000 之间的整数";
+    BESSELY_DOMAIN = "DOMAIN_ERROR": "bessely needs x > 0" => "bessely 需要 x > 0";
+    LAMBERTW_BRANCH = "DOMAIN_ERROR": "lambertw branch must be 0 or -1" => "lambertw 的分支必须是 0 或 -1";
+    LAMBERTW_DOMAIN = "DOMAIN_ERROR": "lambertw needs x >= -1/e" => "lambertw 需要 x >= -1/e";
+    LAMBERTW_BRANCH_DOMAIN = "DOMAIN_ERROR":
+        "lambertw branch -1 needs -1/e <= x < 0" => "lambertw 的 -1 分支需要 -1/e <= x < 0";
+    PROBABILITY_DOMAIN = "DOMAIN_ERROR": "Probability must be between 0 and 1" => "概率必须在 0 与 1 之间";
+    STANDARD_DEVIATION_DOMAIN = "DOMAIN_ERROR": "Standard deviation must be positive" => "标准差必须为正数";
+    RATE_DOMAIN = "DOMAIN_ERROR": "Rate must be positive" => "速率必须为正数";
+    DEGREES_OF_FREEDOM_DOMAIN = "DOMAIN_ERROR": "Degrees of freedom must be positive" => "自由度必须为正数";
+    COUNT_DOMAIN = "DOMAIN_ERROR": "{} must be a non-negative integer" => "{0}必须是非负整数";
+    RAND_BOUNDS = "DOMAIN_ERROR": "rand(a, b) needs finite bounds with a < b" => "rand(a, b) 需要满足 a < b 的有限边界";
+    RANDINT_BOUNDS = "DOMAIN_ERROR":
+        "randint(a, b) needs integer bounds with a <= b" => "randint(a, b) 需要满足 a <= b 的整数边界";
+    DICE_COUNT = "DOMAIN_ERROR":
+        "Dice count must be an integer between 1 and {}" => "骰子个数必须是 1 到 {0} 之间的整数";
+    DICE_SIDES = "DOMAIN_ERROR": "Dice must have a positive integer number of sides" => "骰子面数必须是正整数";
+    WHOLE_DAYS = "DOMAIN_ERROR": "Dates can only be shifted by whole days" => "日期只能按整天加减";
+    WHOLE_WORKDAYS = "DOMAIN_ERROR": "Workdays must be a whole number" => "工作日数必须是整数";
+    // NaN and infinity, as strict-mode errors and as reasons
+    VARIABLE_NON_FINITE = "NON_FINITE": "Variable '{}' is {}" => "变量 '{0}' 为{1}";
+    CONSTANT_NON_FINITE = "NON_FINITE": "A constant in the expression is {}" => "表达式中的常量为{0}";
+    UNDEFINED = "NON_FINITE": "{} is undefined" => "{0} 无定义";
+    OVERFLOWS = "NON_FINITE": "{} overflows" => "{0} 溢出";
+    IS_INFINITE = "NON_FINITE": "{} is infinite" => "{0} 为无穷大";
+    // Formatting
+    FINANCIAL_NON_FINITE = "FORMAT_ERROR":
+        "Cannot express a non-finite value in Chinese financial numerals" => "NaN 与无穷大无法用中文大写金额表示";
+    FINANCIAL_TOO_LARGE = "FORMAT_ERROR":
+        "Value too large for Chinese financial numerals" => "数值太大，无法用中文大写金额表示";
+    PRECISION_RANGE = "FORMAT_ERROR":
+        "Precision must be from 0 to {}, got {}" => "精度必须在 0 到 {0} 之间，实际为 {1}";
+    BASE_NON_FINITE = "FORMAT_ERROR":
+        "Only finite numbers can be written in base {}" => "只有有限数才能用 {0} 进制表示";
+}
+
+// Pieces that appear as arguments of the messages above.
+phrases! {
+    NUMBER: "number" => "数值";
+    BOOLEAN: "boolean" => "布尔值";
+    DURATION: "duration" => "时长";
+    DATE: "date" => "日期";
+    WORKDAYS: "workdays" => "工作日数";
+    NAN: "NaN" => "NaN";
+    INFINITE: "infinite" => "无穷大";
+    NUMBER_OF_TRIALS: "Number of trials" => "试验次数";
+    FUNCTION: "Function '{}'" => "函数 '{0}'";
+    OTHER_BRANCH: "The other branch of {}" => "{0}的另一个分支";
+    AT_LEAST: "at least {}" => "至少 {0}";
+    RANGE: "{} to {}" => "{0} 到 {1}";
+}
+
+impl Phrase {
+    fn text(&self, language: Language) -> &'static str {
+        match language {
+            Language::En => self.english,
+            Language::ZhCn => self.chinese,
+        }
+    }
+
+    /// This phrase as an argument, with one argument of its own for each `{}`.
+    pub fn with<const N: usize>(&'static self, arguments: [String; N]) -> Argument {
+        Argument::Phrase(self, arguments.into())
+    }
+}
+
+impl Message {
+    /// This message as an error, with one argument for each `{}`.
+    pub fn with<const N: usize>(&'static self, arguments: [Argument; N]) -> Error {
+        Error { message: self, arguments: arguments.into() }
+    }
+
+    pub fn error(&'static self) -> Error {
+        self.with([])
+    }
+}
+
+impl Argument {
+    fn text(&self, language: Language) -> String {
+        match self {
+            Argument::Text(text) => text.clone(),
+            Argument::Phrase(phrase, arguments) => fill(phrase.text(language), arguments),
+        }
+    }
+}
+
+impl From<&'static Phrase> for Argument {
+    fn from(phrase: &'static Phrase) -> Self {
+        phrase.with([])
+    }
+}
+
+macro_rules! text_arguments {
+    ($($type:ty),*) => {
+        $(impl From<$type> for Argument {
+            fn from(value: $type) -> Self {
+                Argument::Text(value.to_string())
+            }
+        })*
+    };
+}
+
+text_arguments!(String, &str, &String, char, usize, u32, i64, f64);
+
+impl Error {
+    pub fn code(&self) -> &'static str {
+        self.message.code
+    }
+
+    /// Whether this is an instance of `message`.
+    pub fn is(&self, message: &'static Message) -> bool {
+        std::ptr::eq(self.message, message)
+    }
+
+    /// The message in `language`.
+    pub fn text(&self, language: Language) -> String {
+        let arguments: Vec<String> = self.arguments.iter().map(|argument| argument.text(language)).collect();
+        fill(self.message.phrase.text(language), &arguments)
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(&self.text(Language::En))
+    }
+}
+
+/// Errors from outside the catalog keep their English text, under `OTHER`.
+impl From<String> for Error {
+    fn from(message: String) -> Self {
+        OTHER.with([message.into()])
+    }
+}
+
+impl From<Error> for String {
+    fn from(error: Error) -> Self {
+        error.to_string()
+    }
+}
+
+// Fills `template` in one pass: `{}` takes the next argument and `{0}`, `{1}`, ... the
+// numbered one. Arguments go in as they are; they are never read as templates.
+fn fill(template: &str, arguments: &[String]) -> String {
+    let mut text = String::new();
+    let mut next = 0;
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        let close = open + rest[open..].find('}').expect("placeholders are closed");
+        let index = match &rest[open + 1..close] {
+            "" => {
+                next += 1;
+                next - 1
+            }
+            index => index.parse().expect("placeholders are numbered"),
+        };
+        text.push_str(&rest[..open]);
+        text.push_str(&arguments[index]);
+        rest = &rest[close + 1..];
+    }
+    text.push_str(rest);
+    text
+}
+
+/// Localized names of functions, rewritten to the registered names before parsing, e.g.
+/// `平方根(9)` to `sqrt(9)`. Longest names first, so that `反正弦` wins over `正弦`.
+pub const FUNCTION_ALIASES: &[(&str, &str)] = &[
+    ("向下取整", "floor"),
+    ("向上取整", "ceil"),
+    ("四舍五入", "round"),
+    ("自然对数", "ln"),
+    ("反正弦", "asin"),
+    ("反余弦", "acos"),
+    ("反正切", "atan"),
+    ("平方根", "sqrt"),
+    ("立方根", "cbrt"),
+    ("绝对值", "abs"),
+    ("最大值", "max"),
+    ("最小值", "min"),
+    ("正弦", "sin"),
+    ("余弦", "cos"),
+    ("正切", "tan"),
+    ("对数", "log"),
+    ("指数", "exp"),
+    ("取整", "trunc"),
+    ("伽马", "gamma"),
+];
+
+/// An error message in the requested language, with its stable code.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Localized {
+    pub code: &'static str,
+    pub message: String,
+}
+
+pub fn localize(error: &Error, language: Language) -> Localized {
+    Localized { code: error.code(), message: error.text(language) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accept_language_prefers_the_highest_quality() {
+        assert_eq!(Language::from_accept_language("zh-CN,zh;q=0.9,en;q=0.8"), Some(Language::ZhCn));
+        assert_eq!(Language::from_accept_language("fr;q=1, en;q=0.5, zh;q=0.4"), Some(Language::En));
+        assert_eq!(Language::from_accept_language("en;q=0, zh-TW"), Some(Language::ZhCn));
+        assert_eq!(Language::from_accept_language("fr, de"), None);
+    }
+
+    #[test]
+    fn english_messages_keep_their_text_and_get_a_code() {
+        let localized = localize(&UNKNOWN_FUNCTION.with(["foo".into()]), Language::En);
+        assert_eq!(localized, Localized { code: "UNKNOWN_FUNCTION", message: "Unknown function 'foo'".to_string() });
+        let other = Error::from("Something else".to_string());
+        let localized = localize(&other, Language::ZhCn);
+        assert_eq!(localized, Localized { code: "CALCULATOR_ERROR", message: "Something else".to_string() });
+    }
+
+    #[test]
+    fn arguments_are_reordered_and_translated() {
+        let error = UNEXPECTED_CHARACTER.with(['$'.into(), 3usize.into()]);
+        assert_eq!(error.to_string(), "Unexpected character '$' at position 3");
+        assert_eq!(error.text(Language::ZhCn), "位置 3 处有无法识别的字符 '$'");
+        let error = TYPE_MISMATCH.with([FUNCTION.with(["sqrt".to_string()]), NUMBER.with([]), BOOLEAN.with([])]);
+        assert_eq!(error.code(), "TYPE_MISMATCH");
+        assert_eq!(error.to_string(), "Function 'sqrt' expects a number, got a boolean");
+        assert_eq!(error.text(Language::ZhCn), "函数 'sqrt'需要数值，实际为布尔值");
+        let error = WRONG_ARGUMENT_COUNT.with(["max".into(), AT_LEAST.with(["1".to_string()]), 0usize.into()]);
+        assert_eq!(error.to_string(), "Function 'max' expects at least 1 argument(s), got 0");
+        assert_eq!(error.text(Language::ZhCn), "函数 'max' 需要 至少 1 个参数，实际为 0 个");
+        assert_eq!(VARIABLE_NON_FINITE.with(["x".into(), INFINITE.with([])]).text(Language::ZhCn), "变量 'x' 为无穷大");
+    }
+
+    #[test]
+    fn user_text_is_never_translated() {
+        // Names that look like phrases or placeholders are shown as they are, however long.
+        let name = "x to x ".repeat(20_000) + "{0} infinite";
+        let error = INVALID_NAME.with([name.as_str().into()]);
+        assert_eq!(error.code(), "INVALID_NAME");
+        assert_eq!(error.text(Language::ZhCn), format!("'{}' 不是有效的变量名", name));
+        assert_eq!(error.to_string(), format!("'{}' is not a valid variable name", name));
+    }
+
+    #[test]
+    fn every_translation_uses_each_argument() {
+        let phrases = MESSAGES.iter().map(|message| &message.phrase).chain(PHRASES.iter().copied());
+        for phrase in phrases {
+            for i in 0..phrase.english.matches("{}").count() {
+                assert!(phrase.chinese.contains(&format!("{{{}}}", i)), "{} lacks {{{}}}", phrase.chinese, i);
+            }
+        }
+    }
+}
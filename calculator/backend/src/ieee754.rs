@@ -0,0 +1,488 @@
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::{One, Signed};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::rational::Q;
+use crate::value::{ExtendedReal, Value};
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct Ieee754Request {
+    /// A decimal literal (`0.1`, `-0`, `1e-40`, `inf`, `nan`), inspected exactly, or any
+    /// expression accepted by `/calculate`, inspected as the `f64` it evaluates to.
+    pub value: String,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct Field {
+    pub bits: String,
+    pub value: u64,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct Neighbour {
+    pub bits: String,
+    /// The shortest decimal that reads back as this value in the same format.
+    pub value: String,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct RoundingError {
+    /// `stored - input`, exactly; `relative` divides it by `|input|`.
+    pub absolute: String,
+    pub relative: Option<ExtendedReal>,
+    pub ulps: ExtendedReal,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct Layout {
+    pub format: &'static str,
+    pub bits: String,
+    /// Sign, exponent and mantissa separated by spaces.
+    pub binary: String,
+    pub class: &'static str,
+    pub sign: u8,
+    pub exponent: Field,
+    /// The unbiased exponent of a normal or subnormal value.
+    pub power: Option<i64>,
+    pub mantissa: Field,
+    pub value: String,
+    pub exact: Option<String>,
+    pub ulp: Option<ExtendedReal>,
+    pub next_up: Option<Neighbour>,
+    pub next_down: Option<Neighbour>,
+    pub rounding_error: Option<RoundingError>,
+    pub overflow: bool,
+}
+
+#[derive(Debug, Serialize, Default, JsonSchema)]
+pub struct Ieee754Response {
+    /// The exact value that was converted, when it is finite.
+    pub exact: Option<String>,
+    pub formats: Vec<Layout>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Format {
+    name: &'static str,
+    exponent_bits: u32,
+    mantissa_bits: u32,
+}
+
+const FORMATS: [Format; 3] = [
+    Format { name: "f16", exponent_bits: 5, mantissa_bits: 10 },
+    Format { name: "f32", exponent_bits: 8, mantissa_bits: 23 },
+    Format { name: "f64", exponent_bits: 11, mantissa_bits: 52 },
+];
+
+/// The value being converted: a sign, and a magnitude unless it is infinite or NaN.
+#[derive(Debug, Clone)]
+enum Input {
+    Finite { negative: bool, magnitude: Q },
+    Infinite { negative: bool },
+    NaN,
+}
+
+impl Format {
+    fn width(&self) -> u32 {
+        1 + self.exponent_bits + self.mantissa_bits
+    }
+
+    fn bias(&self) -> i64 {
+        (1 << (self.exponent_bits - 1)) - 1
+    }
+
+    fn max_exponent(&self) -> u64 {
+        (1 << self.exponent_bits) - 1
+    }
+
+    fn sign_bit(&self) -> u64 {
+        1 << (self.width() - 1)
+    }
+
+    fn infinity(&self) -> u64 {
+        self.max_exponent() << self.mantissa_bits
+    }
+
+    /// Rounds to the nearest representable value, ties to even, as IEEE 754 conversions do.
+    fn encode(&self, input: &Input) -> u64 {
+        let (negative, magnitude) = match input {
+            Input::NaN => return self.infinity() | (1 << (self.mantissa_bits - 1)),
+            Input::Infinite { negative } => return self.infinity() | if *negative { self.sign_bit() } else { 0 },
+            Input::Finite { negative, magnitude } => (*negative, magnitude),
+        };
+        let sign = if negative { self.sign_bit() } else { 0 };
+        if magnitude.is_zero() {
+            return sign;
+        }
+
+        // 2^power <= magnitude < 2^(power + 1), but never below the subnormal range.
+        let mut power = magnitude.num.bits() as i64 - magnitude.den.bits() as i64;
+        if compare_power(magnitude, power) == std::cmp::Ordering::Less {
+            power -= 1;
+        }
+        let power = power.max(1 - self.bias());
+        let quantum = power - self.mantissa_bits as i64;
+        let (num, den) = if quantum >= 0 {
+            (magnitude.num.clone(), &magnitude.den << quantum as usize)
+        } else {
+            (&magnitude.num << (-quantum) as usize, magnitude.den.clone())
+        };
+        let (mut significand, remainder) = num.div_rem(&den);
+        let twice = remainder * 2;
+        if twice > den || (twice == den && significand.is_odd()) {
+            significand += 1;
+        }
+
+        // Rounding up may carry into the next binade, or lift a subnormal into the normals.
+        let implicit = BigInt::one() << self.mantissa_bits as usize;
+        let mut power = power;
+        if significand == &implicit << 1usize {
+            significand >>= 1usize;
+            power += 1;
+        }
+        let (exponent, mantissa) = if significand >= implicit {
+            ((power + self.bias()) as u64, significand - implicit)
+        } else {
+            (0, significand)
+        };
+        if exponent >= self.max_exponent() {
+            return sign | self.infinity();
+        }
+        sign | (exponent << self.mantissa_bits) | u64::try_from(mantissa).unwrap()
+    }
+
+    fn fields(&self, bits: u64) -> (u8, u64, u64) {
+        let mantissa = bits & ((1 << self.mantissa_bits) - 1);
+        let exponent = (bits >> self.mantissa_bits) & self.max_exponent();
+        ((bits >> (self.width() - 1)) as u8, exponent, mantissa)
+    }
+
+    /// The exact value of a finite encoding as `significand · 2^quantum`.
+    fn decode(&self, bits: u64) -> Option<(bool, BigInt, i64)> {
+        let (sign, exponent, mantissa) = self.fields(bits);
+        if exponent == self.max_exponent() {
+            return None;
+        }
+        let (significand, quantum) = if exponent == 0 {
+            (mantissa, 1 - self.bias() - self.mantissa_bits as i64)
+        } else {
+            (mantissa | (1 << self.mantissa_bits), exponent as i64 - self.bias() - self.mantissa_bits as i64)
+        };
+        Some((sign == 1, BigInt::from(significand), quantum))
+    }
+
+    fn stored(&self, bits: u64) -> Option<Q> {
+        let (negative, significand, quantum) = self.decode(bits)?;
+        let significand = Q::int(if negative { -significand } else { significand });
+        Some(&significand * &exact_power_of_two(quantum))
+    }
+
+    fn float(&self, bits: u64) -> f64 {
+        match self.name {
+            "f64" => f64::from_bits(bits),
+            "f32" => f32::from_bits(bits as u32) as f64,
+            _ => match self.decode(bits) {
+                Some((negative, significand, quantum)) => {
+                    let magnitude = u64::try_from(significand).unwrap() as f64 * power_of_two(quantum);
+                    if negative {
+                        -magnitude
+                    } else {
+                        magnitude
+                    }
+                }
+                None => {
+                    let (sign, _, mantissa) = self.fields(bits);
+                    match (mantissa, sign) {
+                        (0, 0) => f64::INFINITY,
+                        (0, _) => f64::NEG_INFINITY,
+                        _ => f64::NAN,
+                    }
+                }
+            },
+        }
+    }
+
+    /// The shortest decimal that reads back as the same value: Rust's own formatting for
+    /// `f32` and `f64`, and a search over precisions for `f16`.
+    fn shortest(&self, bits: u64) -> String {
+        let value = self.float(bits);
+        match self.name {
+            _ if !value.is_finite() => value.to_string(),
+            "f64" => format!("{:?}", value),
+            "f32" => format!("{:?}", value as f32),
+            _ => (0..17)
+                .map(|precision| format!("{:.*e}", precision, value).parse::<f64>().unwrap())
+                .find(|candidate| literal(&candidate.to_string()).is_some_and(|input| self.encode(&input) == bits))
+                .unwrap_or(value)
+                .to_string(),
+        }
+    }
+
+    // Stepping the magnitude's bit pattern moves to the adjacent representable value;
+    // the zeros and the ends of the range need care.
+    fn next_up(&self, bits: u64) -> Option<u64> {
+        let (sign, exponent, mantissa) = self.fields(bits);
+        let magnitude = bits & !self.sign_bit();
+        match (sign, exponent == self.max_exponent()) {
+            (_, true) if mantissa != 0 => None,
+            (0, true) => None,
+            (1, true) => Some(self.sign_bit() | (self.infinity() - 1)),
+            (_, _) if magnitude == 0 => Some(1),
+            (0, _) => Some(bits + 1),
+            _ if magnitude == 1 => Some(self.sign_bit()),
+            _ => Some(bits - 1),
+        }
+    }
+
+    fn next_down(&self, bits: u64) -> Option<u64> {
+        self.next_up(bits ^ self.sign_bit()).map(|next| next ^ self.sign_bit())
+    }
+
+    fn class(&self, bits: u64) -> &'static str {
+        match self.fields(bits) {
+            (_, e, m) if e == self.max_exponent() && m != 0 => "nan",
+            (_, e, _) if e == self.max_exponent() => "infinite",
+            (_, 0, 0) => "zero",
+            (_, 0, _) => "subnormal",
+            _ => "normal",
+        }
+    }
+
+    fn layout(&self, input: &Input) -> Layout {
+        let bits = self.encode(input);
+        let (sign, exponent, mantissa) = self.fields(bits);
+        let class = self.class(bits);
+        let stored = self.stored(bits);
+        let binary = format!(
+            "{} {:0e$b} {:0m$b}",
+            sign,
+            exponent,
+            mantissa,
+            e = self.exponent_bits as usize,
+            m = self.mantissa_bits as usize
+        );
+
+        // The gap to the next value away from zero; at the largest finite value, the gap
+        // to where the next one would be.
+        let quantum = self.decode(bits).map(|(_, _, quantum)| quantum);
+        let ulp = quantum.map(power_of_two);
+
+        let rounding_error = match (input, &stored) {
+            (Input::Finite { negative, magnitude }, Some(stored)) => {
+                let exact = if *negative { -magnitude } else { magnitude.clone() };
+                let difference = stored - &exact;
+                Some(RoundingError {
+                    absolute: decimal(&difference),
+                    relative: (!exact.is_zero()).then(|| ExtendedReal(difference.div(&exact.abs()).to_f64())),
+                    ulps: ExtendedReal(difference.div(&exact_power_of_two(quantum.unwrap())).to_f64()),
+                })
+            }
+            _ => None,
+        };
+
+        let neighbour = |bits: u64| Neighbour { bits: self.hex(bits), value: self.shortest(bits) };
+        Layout {
+            format: self.name,
+            bits: self.hex(bits),
+            binary,
+            class,
+            sign,
+            exponent: Field { bits: format!("{:0w$b}", exponent, w = self.exponent_bits as usize), value: exponent },
+            power: match class {
+                "normal" => Some(exponent as i64 - self.bias()),
+                "subnormal" | "zero" => Some(1 - self.bias()),
+                _ => None,
+            },
+            mantissa: Field { bits: format!("{:0w$b}", mantissa, w = self.mantissa_bits as usize), value: mantissa },
+            value: self.shortest(bits),
+            exact: stored.as_ref().map(decimal),
+            ulp: ulp.map(ExtendedReal),
+            next_up: self.next_up(bits).map(neighbour),
+            next_down: self.next_down(bits).map(neighbour),
+            rounding_error,
+            overflow: matches!(input, Input::Finite { .. }) && class == "infinite",
+        }
+    }
+
+    fn hex(&self, bits: u64) -> String {
+        format!("0x{:0w$X}", bits, w = self.width() as usize / 4)
+    }
+}
+
+/// `2^power` for any power an `f64` can hold, subnormals included.
+fn power_of_two(power: i64) -> f64 {
+    if power >= -1022 {
+        f64::from_bits(((power + 1023) as u64) << 52)
+    } else {
+        f64::from_bits(1 << (power + 1074))
+    }
+}
+
+fn exact_power_of_two(power: i64) -> Q {
+    if power >= 0 {
+        Q::int(BigInt::one() << power as usize)
+    } else {
+        Q::new(BigInt::one(), BigInt::one() << (-power) as usize)
+    }
+}
+
+/// Compares `magnitude` with `2^power`.
+fn compare_power(magnitude: &Q, power: i64) -> std::cmp::Ordering {
+    if power >= 0 {
+        magnitude.num.cmp(&(&magnitude.den << power as usize))
+    } else {
+        (&magnitude.num << (-power) as usize).cmp(&magnitude.den)
+    }
+}
+
+/// The exact decimal expansion of a rational whose denominator has no prime factors
+/// other than 2 and 5, as every binary float and decimal literal does.
+fn decimal(q: &Q) -> String {
+    let mut den = q.den.clone();
+    let mut digits = 0usize;
+    let (two, five, ten) = (BigInt::from(2), BigInt::from(5), BigInt::from(10));
+    let mut num = q.num.abs();
+    while !den.is_one() {
+        if den.is_multiple_of(&ten) {
+            den /= &ten;
+        } else if den.is_multiple_of(&two) {
+            den /= &two;
+            num *= &five;
+        } else {
+            den /= &five;
+            num *= &two;
+        }
+        digits += 1;
+    }
+    let text = num.to_string();
+    let sign = if q.is_negative() { "-" } else { "" };
+    if digits == 0 {
+        return format!("{}{}", sign, text);
+    }
+    let padded = format!("{:0>w$}", text, w = digits + 1);
+    let (whole, fraction) = padded.split_at(padded.len() - digits);
+    format!("{}{}.{}", sign, whole, fraction)
+}
+
+fn literal(text: &str) -> Option<Input> {
+    let (negative, body) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+    match body.to_ascii_lowercase().as_str() {
+        "inf" | "infinity" => return Some(Input::Infinite { negative }),
+        "nan" => return Some(Input::NaN),
+        _ => {}
+    }
+    let well_formed = body.starts_with(|c: char| c.is_ascii_digit() || c == '.')
+        && body.chars().all(|c| c.is_ascii_digit() || matches!(c, '.' | 'e' | 'E' | '+' | '-'));
+    let magnitude = if well_formed { Q::parse(body)? } else { return None };
+    Some(Input::Finite { negative, magnitude })
+}
+
+fn from_f64(value: f64) -> Input {
+    if value.is_nan() {
+        return Input::NaN;
+    }
+    if value.is_infinite() {
+        return Input::Infinite { negative: value < 0.0 };
+    }
+    let format = FORMATS[2];
+    let magnitude = format.stored(value.abs().to_bits()).unwrap();
+    Input::Finite { negative: value.is_sign_negative(), magnitude }
+}
+
+/// Shows how `request.value` is stored in each binary format. Decimal literals are taken
+/// exactly; anything else goes through `evaluate`, so its `f64` result is the input.
+pub fn inspect(
+    request: &Ieee754Request,
+    evaluate: impl Fn(&str) -> Result<Value, String>,
+) -> Result<Ieee754Response, String> {
+    let text = request.value.trim();
+    let input = match literal(text) {
+        Some(input) => input,
+        None => match evaluate(text)? {
+            Value::Number(value) => from_f64(value),
+            other => return Err(format!("Expected a number, found a {}", other.value_type().name())),
+        },
+    };
+    let exact = match &input {
+        Input::Finite { negative, magnitude } => {
+            let sign = if *negative && !magnitude.is_zero() { "-" } else { "" };
+            Some(format!("{}{}", sign, decimal(magnitude)))
+        }
+        _ => None,
+    };
+    Ok(Ieee754Response {
+        exact,
+        formats: FORMATS.iter().map(|format| format.layout(&input)).collect(),
+        error: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inspected(value: &str) -> Ieee754Response {
+        let request = Ieee754Request { value: value.to_string() };
+        inspect(&request, |text| match text {
+            "1/3" => Ok(Value::Number(1.0 / 3.0)),
+            _ => Err(format!("Cannot evaluate '{}'", text)),
+        })
+        .unwrap()
+    }
+
+    fn bits(response: &Ieee754Response) -> Vec<(&str, &str)> {
+        response.formats.iter().map(|layout| (layout.bits.as_str(), layout.class)).collect()
+    }
+
+    #[test]
+    fn literals_round_to_nearest_in_each_format() {
+        let response = inspected("0.1");
+        assert_eq!(response.exact.as_deref(), Some("0.1"));
+        assert_eq!(bits(&response), [("0x2E66", "normal"), ("0x3DCCCCCD", "normal"), ("0x3FB999999999999A", "normal")]);
+        let f64 = &response.formats[2];
+        assert_eq!(f64.exact.as_deref(), Some("0.1000000000000000055511151231257827021181583404541015625"));
+        assert_eq!(f64.value, "0.1");
+        assert_eq!(f64.power, Some(-4));
+        assert_eq!(f64.rounding_error.as_ref().unwrap().absolute, "0.0000000000000000055511151231257827021181583404541015625");
+        assert_eq!(f64.next_up.as_ref().unwrap().value, "0.10000000000000002");
+
+        assert_eq!(bits(&inspected("1")), [("0x3C00", "normal"), ("0x3F800000", "normal"), ("0x3FF0000000000000", "normal")]);
+        assert_eq!(bits(&inspected("-0")), [("0x8000", "zero"), ("0x80000000", "zero"), ("0x8000000000000000", "zero")]);
+        assert_eq!(inspected("1e-40").formats[1].class, "subnormal");
+    }
+
+    #[test]
+    fn specials_and_overflow() {
+        let response = inspected("65520");
+        assert_eq!(bits(&response)[0], ("0x7C00", "infinite"));
+        assert!(response.formats[0].overflow && !response.formats[1].overflow);
+        // 65519 is below the halfway point to the next f16 step, so it rounds down to the maximum.
+        assert_eq!(bits(&inspected("65519"))[0], ("0x7BFF", "normal"));
+
+        let response = inspected("-inf");
+        assert_eq!(response.exact, None);
+        assert_eq!(bits(&response)[2], ("0xFFF0000000000000", "infinite"));
+        assert!(!response.formats[2].overflow && response.formats[2].rounding_error.is_none());
+        assert_eq!(response.formats[2].next_up.as_ref().unwrap().value, "-1.7976931348623157e308");
+        assert_eq!(bits(&inspected("nan"))[2], ("0x7FF8000000000000", "nan"));
+    }
+
+    #[test]
+    fn expressions_are_inspected_as_their_f64_result() {
+        let response = inspected("1/3");
+        assert_eq!(response.exact.as_deref(), Some("0.333333333333333314829616256247390992939472198486328125"));
+        assert_eq!(response.formats[2].rounding_error.as_ref().unwrap().ulps, ExtendedReal(0.0));
+
+        let request = Ieee754Request { value: "true".to_string() };
+        let error = inspect(&request, |_| Ok(Value::Boolean(true))).unwrap_err();
+        assert_eq!(error, "Expected a number, found a boolean");
+        let request = Ieee754Request { value: "x".to_string() };
+        assert_eq!(inspect(&request, |_| Err("Unknown variable 'x'".to_string())).unwrap_err(), "Unknown variable 'x'");
+    }
+}
@@ -0,0 +1,350 @@
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as Json};
+
+use crate::accounts::User;
+use crate::history::MAX_HISTORY;
+use crate::i18n::{self, Error, Language};
+use crate::{calculate, get_user_state, CalculationRequest};
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+/// Evaluation and variable errors; the message is the calculator's own.
+pub const CALCULATOR_ERROR: i64 = -32000;
+/// The most calls in one batch array, and the most expressions in one `batch` call.
+pub const MAX_BATCH: usize = 100;
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+    /// For calculator errors, `{ "error_code": ... }` as in `/calculate` responses.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Json>,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        RpcError { code, message: message.into(), data: None }
+    }
+
+    fn calculator(message: String, error_code: &str) -> Self {
+        RpcError { code: CALCULATOR_ERROR, message, data: Some(json!({ "error_code": error_code })) }
+    }
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct RpcResponse {
+    jsonrpc: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Json>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<RpcError>,
+    id: Json,
+}
+
+impl RpcResponse {
+    fn new(id: Json, outcome: Result<Json, RpcError>) -> Self {
+        let (result, error) = match outcome {
+            Ok(result) => (Some(result), None),
+            Err(error) => (None, Some(error)),
+        };
+        RpcResponse { jsonrpc: "2.0", result, error, id }
+    }
+}
+
+/// Handles a request body: a single call or a batch array. Returns `None` when there
+/// is nothing to send back, i.e. the body held only notifications.
+pub fn handle(user: &User, language: Language, body: &[u8]) -> Option<Json> {
+    let request: Json = match serde_json::from_slice(body) {
+        Ok(request) => request,
+        Err(error) => {
+            let response = RpcResponse::new(Json::Null, Err(RpcError::new(PARSE_ERROR, format!("Parse error: {}", error))));
+            return Some(json!(response));
+        }
+    };
+    match request {
+        Json::Array(calls) if calls.is_empty() => {
+            let response = RpcResponse::new(Json::Null, Err(RpcError::new(INVALID_REQUEST, "Invalid Request: empty batch")));
+            Some(json!(response))
+        }
+        Json::Array(calls) if calls.len() > MAX_BATCH => {
+            let message = format!("Invalid Request: a batch holds at most {} calls", MAX_BATCH);
+            Some(json!(RpcResponse::new(Json::Null, Err(RpcError::new(INVALID_REQUEST, message)))))
+        }
+        Json::Array(calls) => {
+            let responses: Vec<RpcResponse> = calls.into_iter().filter_map(|call| handle_call(user, language, call)).collect();
+            (!responses.is_empty()).then(|| json!(responses))
+        }
+        call => handle_call(user, language, call).map(|response| json!(response)),
+    }
+}
+
+fn handle_call(user: &User, language: Language, call: Json) -> Option<RpcResponse> {
+    let Json::Object(mut call) = call else {
+        return Some(RpcResponse::new(Json::Null, Err(RpcError::new(INVALID_REQUEST, "Invalid Request: expected an object"))));
+    };
+    let id = match call.remove("id") {
+        None => None,
+        Some(id @ (Json::Null | Json::Number(_) | Json::String(_))) => Some(id),
+        Some(_) => {
+            return Some(RpcResponse::new(Json::Null, Err(RpcError::new(INVALID_REQUEST, "Invalid Request: invalid id"))));
+        }
+    };
+    let (method, params) = match check_call(&call) {
+        Ok(checked) => checked,
+        // Malformed calls are answered even without an id, since they cannot be told
+        // apart from notifications.
+        Err(error) => return Some(RpcResponse::new(id.unwrap_or(Json::Null), Err(error))),
+    };
+    let outcome = dispatch(user, language, method, params);
+    // A call without an id is a notification: it runs, but gets no response.
+    id.map(|id| RpcResponse::new(id, outcome))
+}
+
+fn check_call(call: &serde_json::Map<String, Json>) -> Result<(&str, Json), RpcError> {
+    if call.get("jsonrpc").and_then(Json::as_str) != Some("2.0") {
+        return Err(RpcError::new(INVALID_REQUEST, "Invalid Request: jsonrpc must be \"2.0\""));
+    }
+    let method = call
+        .get("method")
+        .and_then(Json::as_str)
+        .ok_or_else(|| RpcError::new(INVALID_REQUEST, "Invalid Request: method must be a string"))?;
+    let params = match call.get("params") {
+        None => Json::Null,
+        Some(params @ (Json::Object(_) | Json::Array(_))) => params.clone(),
+        Some(_) => return Err(RpcError::new(INVALID_REQUEST, "Invalid Request: params must be an object or array")),
+    };
+    Ok((method, params))
+}
+
+fn params<T: DeserializeOwned>(params: Json) -> Result<T, RpcError> {
+    serde_json::from_value(params).map_err(|error| RpcError::new(INVALID_PARAMS, format!("Invalid params: {}", error)))
+}
+
+fn calculator<T: Serialize>(outcome: Result<T, Error>, language: Language) -> Result<Json, RpcError> {
+    outcome.map(|result| json!(result)).map_err(|error| {
+        let localized = i18n::localize(&error, language);
+        RpcError::calculator(localized.message, localized.code)
+    })
+}
+
+/// Positional params name the fields in order, e.g. `["1+2"]` for `evaluate`.
+fn named(params: Json, fields: &[&str]) -> Result<Json, RpcError> {
+    match params {
+        Json::Array(values) if values.len() > fields.len() => {
+            let message = format!("Invalid params: expected at most {} positional params", fields.len());
+            Err(RpcError::new(INVALID_PARAMS, message))
+        }
+        Json::Array(values) => Ok(Json::Object(fields.iter().map(|field| field.to_string()).zip(values).collect())),
+        Json::Null => Ok(json!({})),
+        params => Ok(params),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum BatchItem {
+    Expression(String),
+    Request(CalculationRequest),
+}
+
+#[derive(Debug, Deserialize)]
+struct BatchParams {
+    expressions: Vec<BatchItem>,
+}
+
+#[derive(Debug, Deserialize)]
+struct VariableParams {
+    name: String,
+    expression: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HistoryParams {
+    limit: Option<usize>,
+}
+
+fn dispatch(user: &User, language: Language, method: &str, params_json: Json) -> Result<Json, RpcError> {
+    match method {
+        "evaluate" => {
+            let request: CalculationRequest = params(named(params_json, &["expression", "seed"])?)?;
+            let response = calculate(user, &request, Some(language));
+            if let (Some(error), Some(error_code)) = (&response.error, response.error_code) {
+                return Err(RpcError::calculator(error.clone(), error_code));
+            }
+            let mut result = json!(response);
+            result.as_object_mut().unwrap().remove("error");
+            result.as_object_mut().unwrap().remove("error_code");
+            Ok(result)
+        }
+        "batch" => {
+            let items: BatchParams = match params_json {
+                Json::Array(items) => params(json!({ "expressions": items }))?,
+                params_json => params(params_json)?,
+            };
+            if items.expressions.len() > MAX_BATCH {
+                let message = format!("Invalid params: a batch holds at most {} expressions", MAX_BATCH);
+                return Err(RpcError::new(INVALID_PARAMS, message));
+            }
+            let responses: Vec<_> = items
+                .expressions
+                .into_iter()
+                .map(|item| match item {
+                    BatchItem::Expression(expression) => {
+                        let request = CalculationRequest {
+                            expression,
+                            format: Default::default(),
+                            seed: None,
+                            session: None,
+                            strict: false,
+                            language: None,
+                        };
+                        calculate(user, &request, Some(language))
+                    }
+                    BatchItem::Request(request) => calculate(user, &request, Some(language)),
+                })
+                .collect();
+            Ok(json!(responses))
+        }
+        "variables.set" => {
+            let request: VariableParams = params(named(params_json, &["name", "expression"])?)?;
+            let expression = request
+                .expression
+                .ok_or_else(|| RpcError::new(INVALID_PARAMS, "Invalid params: missing field `expression`"))?;
+            calculator(crate::set_variable(user, &request.name, &expression), language)
+        }
+        "variables.get" => {
+            let request: VariableParams = params(named(params_json, &["name"])?)?;
+            calculator(crate::get_variable(user, &request.name), language)
+        }
+        "variables.delete" => {
+            let request: VariableParams = params(named(params_json, &["name"])?)?;
+            calculator(crate::delete_variable(user, &request.name), language)
+        }
+        "variables.list" => Ok(json!(crate::list_variables(user))),
+        "history.list" => {
+            let request: HistoryParams = params(named(params_json, &["limit"])?)?;
+            let limit = request.limit.unwrap_or(MAX_HISTORY);
+            Ok(json!(get_user_state(user).lock().unwrap().history.recent(limit)))
+        }
+        "history.clear" => {
+            let cleared = get_user_state(user).lock().unwrap().history.clear();
+            Ok(json!({ "cleared": cleared }))
+        }
+        _ => Err(RpcError::new(METHOD_NOT_FOUND, format!("Method not found: {}", method))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::Role;
+
+    fn user(name: &str) -> User {
+        User { name: format!("jsonrpc-{}", name), role: Role::User }
+    }
+
+    fn call(user: &User, body: Json) -> Option<Json> {
+        handle(user, Language::En, body.to_string().as_bytes())
+    }
+
+    fn error_code(response: &Json) -> i64 {
+        response["error"]["code"].as_i64().unwrap()
+    }
+
+    #[test]
+    fn calls_are_answered_and_notifications_are_not() {
+        let user = user("notifications");
+        let evaluate = json!({ "jsonrpc": "2.0", "method": "evaluate", "params": ["1 + 2"], "id": 7 });
+        let response = call(&user, evaluate).unwrap();
+        assert_eq!(response["id"], 7);
+        assert_eq!(response["result"]["result"], 3.0);
+        assert!(response.get("error").is_none());
+
+        let notification = json!({ "jsonrpc": "2.0", "method": "variables.set", "params": ["rate", "0.5"] });
+        assert_eq!(call(&user, notification.clone()), None);
+        assert_eq!(call(&user, json!([notification.clone(), notification])), None);
+        assert_eq!(crate::get_variable(&user, "rate").unwrap().value, crate::Value::Number(0.5));
+    }
+
+    #[test]
+    fn batches_answer_each_call_with_an_id() {
+        let user = user("batch");
+        let response = call(
+            &user,
+            json!([
+                { "jsonrpc": "2.0", "method": "evaluate", "params": { "expression": "2 * 3" }, "id": "a" },
+                { "jsonrpc": "2.0", "method": "history.clear" },
+                { "jsonrpc": "2.0", "method": "nope", "id": 2 },
+                5
+            ]),
+        )
+        .unwrap();
+        let responses = response.as_array().unwrap();
+        assert_eq!(responses.len(), 3);
+        assert_eq!((&responses[0]["id"], &responses[0]["result"]["result"]), (&json!("a"), &json!(6.0)));
+        assert_eq!((&responses[1]["id"], error_code(&responses[1])), (&json!(2), METHOD_NOT_FOUND));
+        assert_eq!((&responses[2]["id"], error_code(&responses[2])), (&Json::Null, INVALID_REQUEST));
+
+        let empty = call(&user, json!([])).unwrap();
+        assert_eq!(error_code(&empty), INVALID_REQUEST);
+        let calls = vec![json!({ "jsonrpc": "2.0", "method": "variables.list", "id": 1 }); MAX_BATCH + 1];
+        assert_eq!(error_code(&call(&user, json!(calls)).unwrap()), INVALID_REQUEST);
+        let expressions = vec!["1"; MAX_BATCH + 1];
+        let batch = json!({ "jsonrpc": "2.0", "method": "batch", "params": expressions, "id": 1 });
+        assert_eq!(error_code(&call(&user, batch).unwrap()), INVALID_PARAMS);
+    }
+
+    #[test]
+    fn malformed_requests_are_reported() {
+        let user = user("malformed");
+        let response = handle(&user, Language::En, b"{ not json").unwrap();
+        assert_eq!((&response["id"], error_code(&response)), (&Json::Null, PARSE_ERROR));
+
+        let invalid_id = call(&user, json!({ "jsonrpc": "2.0", "method": "variables.list", "id": [1] })).unwrap();
+        assert_eq!((&invalid_id["id"], error_code(&invalid_id)), (&Json::Null, INVALID_REQUEST));
+        for body in [
+            json!({ "method": "variables.list", "id": 1 }),
+            json!({ "jsonrpc": "2.0", "method": 3, "id": 1 }),
+            json!({ "jsonrpc": "2.0", "method": "evaluate", "params": "1 + 2", "id": 1 }),
+            json!({ "jsonrpc": "2.0", "method": "evaluate", "params": "1 + 2" }),
+        ] {
+            let response = call(&user, body).unwrap();
+            assert_eq!(error_code(&response), INVALID_REQUEST, "{}", response);
+        }
+    }
+
+    #[test]
+    fn params_may_be_positional_or_named() {
+        let user = user("params");
+        let positional = json!({ "jsonrpc": "2.0", "method": "variables.set", "params": ["x", "4"], "id": 1 });
+        assert_eq!(call(&user, positional).unwrap()["result"]["value"], 4.0);
+        let named = json!({ "jsonrpc": "2.0", "method": "variables.get", "params": { "name": "x" }, "id": 2 });
+        assert_eq!(call(&user, named).unwrap()["result"]["value"], 4.0);
+
+        let extra = json!({ "jsonrpc": "2.0", "method": "variables.get", "params": ["x", "y"], "id": 3 });
+        assert_eq!(error_code(&call(&user, extra).unwrap()), INVALID_PARAMS);
+        let missing = json!({ "jsonrpc": "2.0", "method": "variables.set", "params": ["y"], "id": 4 });
+        assert_eq!(error_code(&call(&user, missing).unwrap()), INVALID_PARAMS);
+        let unknown = call(&user, json!({ "jsonrpc": "2.0", "method": "evaluate", "params": ["y"], "id": 5 })).unwrap();
+        assert_eq!(error_code(&unknown), CALCULATOR_ERROR);
+        assert_eq!(unknown["error"]["data"]["error_code"], "UNKNOWN_VARIABLE");
+    }
+
+    #[test]
+    fn over_long_expressions_stay_out_of_the_history() {
+        let user = user("history");
+        let long = "1+".repeat(crate::config::limits().max_expression_length) + "1";
+        let batch = json!({ "jsonrpc": "2.0", "method": "batch", "params": [long, "1 + 1"], "id": 1 });
+        let responses = call(&user, batch).unwrap();
+        assert_eq!(responses["result"][0]["error_code"], "EXPRESSION_TOO_LONG");
+        let history = call(&user, json!({ "jsonrpc": "2.0", "method": "history.list", "id": 2 })).unwrap();
+        let entries = history["result"].as_array().unwrap();
+        let expressions: Vec<&Json> = entries.iter().map(|entry| &entry["expression"]).collect();
+        assert_eq!(expressions, [&json!("1 + 1")]);
+    }
+}
@@ -1,71 +1,1080 @@
+use lazy_static::lazy_static;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use warp::Filter;
 
-#[derive(Debug, Deserialize)]
+mod accounts;
+mod ast;
+mod base;
+mod chinese;
+mod compile;
+mod config;
+mod dates;
+mod editor;
+mod distributions;
+mod equations;
+mod format;
+mod functions;
+mod history;
+mod i18n;
+mod ieee754;
+mod jsonrpc;
+mod memory;
+mod metrics;
+mod number_theory;
+mod openapi;
+mod parser;
+mod polynomial;
+mod random;
+mod rational;
+mod registry;
+mod rpn;
+mod sequence;
+mod special;
+mod table;
+mod uncertainty;
+mod value;
+mod worksheet;
+use accounts::{Accounts, AccountsResponse, Role, TokenRequest, User, UserRequest};
+use ast::Expr;
+use compile::{CompileCache, CompiledExpression};
+use config::Config;
+use editor::{CompleteRequest, CompleteResponse, ParseRequest};
+use equations::{SolveRequest, SolveResponse};
+use base::BaseDigits;
+use format::FormatOptions;
+use history::History;
+use i18n::{Error, Language, EXPRESSION_TOO_LONG, INVALID_NAME, MEMORY_MISMATCH, UNKNOWN_VARIABLE, VARIABLE_NOT_FOUND};
+use registry::{ExpressionFunction, FunctionDefinition, UserFunctions};
+use memory::{Memory, MemoryRequest, MemoryResponse, MemorySlot};
+use metrics::get_metrics;
+use ieee754::{Ieee754Request, Ieee754Response};
+use number_theory::{NumberTheoryRequest, NumberTheoryResponse};
+use polynomial::{PolynomialRequest, PolynomialResponse};
+use rpn::RpnRequest;
+use sequence::{SequenceRequest, SequenceResponse};
+use table::{evaluate_table, evaluate_truth_table, TableRequest, TableResponse, TruthTableRequest, TruthTableResponse};
+use uncertainty::{UncertaintyRequest, UncertaintyResponse};
+use value::{ExtendedReal, Type, Value};
+use worksheet::{CellRequest, CellView, Worksheet, WorksheetResponse};
+
+pub const MAX_USER_FUNCTIONS: usize = 100;
+
+/// What a user keeps between requests. Each account has its own; requests without a
+/// token share the anonymous user's.
+struct UserState {
+    variables: BTreeMap<String, Value>,
+    history: History,
+    worksheets: HashMap<String, Worksheet>,
+    compile_cache: CompileCache,
+    functions: Arc<UserFunctions>,
+}
+
+impl UserState {
+    fn new() -> Self {
+        UserState {
+            variables: BTreeMap::new(),
+            history: History::new(),
+            worksheets: HashMap::new(),
+            compile_cache: CompileCache::new(),
+            functions: Arc::default(),
+        }
+    }
+}
+
+lazy_static! {
+    static ref USERS: Mutex<HashMap<String, Arc<Mutex<UserState>>>> = Mutex::new(HashMap::new());
+    static ref MEMORY: Mutex<Memory> = Mutex::new(Memory::default());
+    static ref ACCOUNTS: Mutex<Accounts> = Mutex::new(Accounts::default());
+}
+
+// Whether requests without a token are served, as the anonymous user.
+static ALLOW_ANONYMOUS: AtomicBool = AtomicBool::new(true);
+// Set once the server listens, cleared when it starts shutting down.
+static READY: AtomicBool = AtomicBool::new(false);
+
+fn get_user_state(user: &User) -> Arc<Mutex<UserState>> {
+    let mut users = USERS.lock().unwrap();
+    users.entry(user.name.clone()).or_insert_with(|| Arc::new(Mutex::new(UserState::new()))).clone()
+}
+
+fn get_memory() -> &'static Mutex<Memory> {
+    &MEMORY
+}
+
+fn get_accounts() -> &'static Mutex<Accounts> {
+    &ACCOUNTS
+}
+
+/// Runs `f` with the user's own functions callable.
+fn as_user<R>(user: &User, f: impl FnOnce() -> R) -> R {
+    let functions = get_user_state(user).lock().unwrap().functions.clone();
+    registry::with_user_functions(functions, f)
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
 struct CalculationRequest {
     expression: String,
+    #[serde(default)]
+    format: FormatOptions,
+    seed: Option<u64>,
+    /// Makes the session's memory slots available as identifiers.
+    session: Option<String>,
+    /// Makes any NaN or infinity met during evaluation an error.
+    #[serde(default)]
+    strict: bool,
+    /// Language of `error` and `reason`, e.g. `zh-CN`; overrides `Accept-Language`.
+    language: Option<String>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema)]
 struct CalculationResponse {
-    result: f64,
+    result: ExtendedReal,
+    boolean: Option<bool>,
+    result_type: Option<Type>,
+    formatted: Option<String>,
+    normalized: Option<String>,
+    seed: Option<u64>,
+    /// Why `result` is NaN or infinite, when it is.
+    reason: Option<String>,
+    /// The digits of `formatted` when it is written in another base, with any
+    /// repeating group apart.
+    base: Option<BaseDigits>,
+    error: Option<String>,
+    /// Identifies `error` whatever its language.
+    error_code: Option<&'static str>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct CompileRequest {
+    expression: String,
+    #[serde(default)]
+    variables: Vec<String>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+struct CompileResponse {
+    id: Option<u64>,
+    variables: Vec<String>,
+    result_type: Option<Type>,
     error: Option<String>,
 }
 
-fn evaluate_expression(expr: &str) -> Result<f64, String> {
-    let tokens: Vec<&str> = expr.split_whitespace().collect();
-    if tokens.len() != 3 {
-        return Err("Invalid expression format. Expected: 'number operator number'".to_string());
+fn evaluate_expression(user: &User, expr: &str) -> Result<Value, Error> {
+    evaluate_in_session(user, expr, None, false).map(|evaluation| evaluation.value)
+}
+
+/// What `evaluate_in_session` computed.
+struct Evaluation {
+    value: Value,
+    /// Why `value` is NaN or infinite, when it is.
+    reason: Option<Error>,
+    /// The base and alphabet an outer `to_base` asks for.
+    base: Option<(u32, Option<String>)>,
+}
+
+/// Parses an expression as the endpoints accept it: within the length limit, and with
+/// Chinese numerals, operators and function names rewritten.
+fn parse_expression(expr: &str) -> Result<Expr, Error> {
+    let limit = config::limits().max_expression_length;
+    if expr.chars().count() > limit {
+        return Err(EXPRESSION_TOO_LONG.with([limit.into()]));
     }
+    parser::parse(&chinese::normalize(expr)?)
+}
 
-    let a: f64 = tokens[0].parse()
-        .map_err(|_| "Invalid first number".to_string())?;
-    let b: f64 = tokens[2].parse()
-        .map_err(|_| "Invalid second number".to_string())?;
+// Free identifiers resolve to the session's memory slots, then to stored variables, and
+// are compiled with the types of their values. The default memory slot reads as 0 until
+// something is stored in it.
+fn evaluate_in_session(user: &User, expr: &str, session: Option<&str>, strict: bool) -> Result<Evaluation, Error> {
+    let ast = parse_expression(expr)?;
+    let mut names = Vec::new();
+    worksheet::references(&ast, &mut names);
+    let values: Vec<Value> = {
+        let state = get_user_state(user);
+        let memory = get_memory().lock().unwrap();
+        let state = state.lock().unwrap();
+        names
+            .iter()
+            .map(|name| {
+                let slot = session.map(|session| memory.get(&user.name, session, name));
+                match (slot, state.variables.get(name)) {
+                    (Some(Some(value)), _) => Ok(Value::Number(value)),
+                    (_, Some(value)) => Ok(*value),
+                    (Some(None), None) if name == memory::DEFAULT_SLOT => Ok(Value::Number(0.0)),
+                    _ => Err(UNKNOWN_VARIABLE.with([name.into()])),
+                }
+            })
+            .collect::<Result<_, _>>()?
+    };
+    let typed: Vec<(String, Option<Type>)> =
+        names.into_iter().zip(&values).map(|(name, value)| (name, Some(value.value_type()))).collect();
+    let raw: Vec<f64> = values.iter().map(|value| value.as_f64()).collect();
+    let compiled = CompiledExpression::compile_typed(&ast, &typed)?;
+    let (value, reason) = compiled.evaluate_checked(&raw, strict)?;
+    let base = compiled.base().map(|(base, alphabet)| (base, alphabet.map(String::from)));
+    Ok(Evaluation { value, reason, base })
+}
 
-    let result = match tokens[1] {
-        "+" => a + b,
-        "-" => a - b,
-        "*" => a * b,
-        "/" => {
-            if b == 0.0 {
-                return Err("Division by zero".to_string());
+/// Formats `value`, and for a number written in another base also gives its digits.
+fn written(value: Value, format: &FormatOptions) -> Result<(Value, String, Option<BaseDigits>), Error> {
+    let formatted = value.format(format)?;
+    let digits = match (value, format.base) {
+        (Value::Number(x), Some(base)) => Some(base::to_base(x, base, format.alphabet.as_deref())?),
+        _ => None,
+    };
+    Ok((value, formatted, digits))
+}
+
+/// Evaluates one `/calculate` request and records it in the user's history. Messages
+/// are given in the request's language, else in `accepted`, else in English.
+fn calculate(user: &User, request: &CalculationRequest, accepted: Option<Language>) -> CalculationResponse {
+    let language = request.language.as_deref().and_then(Language::parse).or(accepted).unwrap_or_default();
+    let session = request.session.as_deref();
+    let started = Instant::now();
+    let (outcome, seed) =
+        random::with_seed(request.seed, || evaluate_in_session(user, &request.expression, session, request.strict));
+    let elapsed = started.elapsed();
+    let normalized = chinese::normalize(&request.expression)
+        .ok()
+        .filter(|normalized| !normalized.split_whitespace().eq(request.expression.split_whitespace()));
+    let (outcome, reason) = match outcome {
+        Ok(Evaluation { value, reason, base }) => {
+            let mut format = request.format.clone();
+            if let Some((base, alphabet)) = base {
+                format.base = Some(base);
+                format.alphabet = alphabet;
             }
-            a / b
+            (written(value, &format), reason)
+        }
+        Err(error) => (Err(error), None),
+    };
+    // An expression over the length limit would take that much room in the history too.
+    if !matches!(&outcome, Err(error) if error.is(&EXPRESSION_TOO_LONG)) {
+        get_user_state(user).lock().unwrap().history.record(
+            &request.expression,
+            outcome.clone().map(|(value, formatted, _)| (value, formatted)).map_err(String::from),
+        );
+    }
+    let outcome = outcome.map_err(|error| i18n::localize(&error, language));
+    get_metrics().lock().unwrap().record_evaluation(outcome.as_ref().err().map(|localized| localized.code), elapsed);
+    match outcome {
+        Ok((value, formatted, digits)) => CalculationResponse {
+            result: ExtendedReal(value.as_f64()),
+            boolean: match value {
+                Value::Boolean(value) => Some(value),
+                _ => None,
+            },
+            result_type: Some(value.value_type()),
+            formatted: Some(formatted),
+            normalized,
+            seed,
+            reason: reason.map(|reason| i18n::localize(&reason, language).message),
+            base: digits,
+            error: None,
+            error_code: None,
         },
-        _ => return Err("Invalid operator. Use +, -, *, /".to_string()),
+        Err(localized) => CalculationResponse {
+            result: ExtendedReal(0.0),
+            boolean: None,
+            result_type: None,
+            formatted: None,
+            normalized,
+            seed,
+            reason: None,
+            base: None,
+            error: Some(localized.message),
+            error_code: Some(localized.code),
+        },
+    }
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+struct VariableView {
+    name: String,
+    value: Value,
+    result_type: Type,
+}
+
+impl VariableView {
+    fn new(name: &str, value: Value) -> Self {
+        Self { name: name.to_string(), value, result_type: value.value_type() }
+    }
+}
+
+fn check_variable_name(name: &str) -> Result<(), Error> {
+    if !parser::is_identifier(name) || functions::constant(name).is_some() || name == "today" {
+        return Err(INVALID_NAME.with([name.into()]));
+    }
+    Ok(())
+}
+
+/// Evaluates `expression` and stores its value under `name`; later expressions see
+/// the value, not the expression.
+fn set_variable(user: &User, name: &str, expression: &str) -> Result<VariableView, Error> {
+    check_variable_name(name)?;
+    let value = evaluate_expression(user, expression)?;
+    get_user_state(user).lock().unwrap().variables.insert(name.to_string(), value);
+    Ok(VariableView::new(name, value))
+}
+
+fn get_variable(user: &User, name: &str) -> Result<VariableView, Error> {
+    let value = get_user_state(user).lock().unwrap().variables.get(name).copied();
+    value.map(|value| VariableView::new(name, value)).ok_or_else(|| VARIABLE_NOT_FOUND.with([name.into()]))
+}
+
+fn delete_variable(user: &User, name: &str) -> Result<VariableView, Error> {
+    let value = get_user_state(user)
+        .lock()
+        .unwrap()
+        .variables
+        .remove(name)
+        .ok_or_else(|| VARIABLE_NOT_FOUND.with([name.into()]))?;
+    Ok(VariableView::new(name, value))
+}
+
+fn list_variables(user: &User) -> Vec<VariableView> {
+    let state = get_user_state(user);
+    let state = state.lock().unwrap();
+    state.variables.iter().map(|(name, value)| VariableView::new(name, *value)).collect()
+}
+
+fn compile_expression(expression: &str, variables: &[String]) -> Result<CompiledExpression, String> {
+    let ast = parse_expression(expression)?;
+    Ok(CompiledExpression::compile(&ast, variables)?)
+}
+
+fn run_truth_table(request: &TruthTableRequest) -> Result<TruthTableResponse, String> {
+    let compiled = compile_expression(&request.expression, &[])?;
+    evaluate_truth_table(&compiled, &request.bindings)
+}
+
+fn with_worksheet(
+    user: &User,
+    sheet: &str,
+    f: impl FnOnce(&mut Worksheet) -> Result<Vec<CellView>, String>,
+) -> WorksheetResponse {
+    let state = get_user_state(user);
+    let worksheets = &mut state.lock().unwrap().worksheets;
+    let Some(worksheet) = worksheets.get_mut(sheet) else {
+        return WorksheetResponse::error(format!("Worksheet '{}' not found", sheet));
     };
+    let result = f(worksheet);
+    if worksheet.is_empty() {
+        worksheets.remove(sheet);
+    }
+    match result {
+        Ok(cells) => WorksheetResponse { cells, error: None },
+        Err(error) => WorksheetResponse::error(error),
+    }
+}
 
-    Ok(result)
+fn set_cell(user: &User, sheet: &str, name: &str, expression: &str) -> WorksheetResponse {
+    get_user_state(user).lock().unwrap().worksheets.entry(sheet.to_string()).or_default();
+    with_worksheet(user, sheet, |worksheet| worksheet.set(name, expression))
+}
+
+/// Evaluates a memory operation's expression in the session, so `M * 2` can refer to
+/// the memory itself; memory only holds numbers.
+fn memory_operand(user: &User, session: &str, expression: &str) -> Result<f64, String> {
+    match evaluate_in_session(user, expression, Some(session), false)?.value {
+        Value::Number(value) => Ok(value),
+        value => Err(MEMORY_MISMATCH.with([value.value_type().phrase().into()]).into()),
+    }
+}
+
+fn memory_response(outcome: Result<Vec<MemorySlot>, String>) -> MemoryResponse {
+    outcome.map_or_else(MemoryResponse::error, |slots| MemoryResponse { slots, error: None })
+}
+
+fn run_table(user: &User, request: &TableRequest) -> Result<TableResponse, String> {
+    let compiled = match (&request.expression, request.id) {
+        (Some(expression), None) => compile_expression(expression, &request.variables)?,
+        (None, Some(id)) => get_user_state(user)
+            .lock()
+            .unwrap()
+            .compile_cache
+            .get(id)
+            .cloned()
+            .ok_or_else(|| format!("Compiled expression {} not found", id))?,
+        _ => return Err("Provide either an expression or a compiled expression id".to_string()),
+    };
+
+    let (rows, seed) = random::with_seed(request.seed, || evaluate_table(&compiled, request));
+    Ok(TableResponse {
+        variables: compiled.variables().to_vec(),
+        rows: rows?,
+        seed,
+        error: None,
+    })
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+struct FunctionView {
+    name: String,
+    signature: String,
+    doc: String,
+}
+
+#[derive(Debug, Default, Serialize, JsonSchema)]
+struct FunctionsResponse {
+    functions: Vec<FunctionView>,
+    error: Option<String>,
+}
+
+fn functions_response(outcome: Result<Vec<Arc<dyn registry::Function>>, String>) -> FunctionsResponse {
+    match outcome {
+        Ok(functions) => FunctionsResponse {
+            functions: functions
+                .iter()
+                .map(|function| FunctionView {
+                    name: function.name().to_string(),
+                    signature: function.signature(),
+                    doc: function.doc().to_string(),
+                })
+                .collect(),
+            error: None,
+        },
+        Err(error) => FunctionsResponse { error: Some(error), ..Default::default() },
+    }
+}
+
+/// Defines or replaces one of the user's functions. It may call the user's other
+/// functions, but not shadow a registered one.
+fn define_function(user: &User, definition: &FunctionDefinition) -> Result<Arc<dyn registry::Function>, String> {
+    registry::check_function_name(&definition.name)?;
+    if registry::get_registry().read().unwrap().lookup(&definition.name).is_some() {
+        return Err(format!("Function '{}' is already defined", definition.name));
+    }
+    let function: Arc<dyn registry::Function> = Arc::new(as_user(user, || ExpressionFunction::compile(definition))?);
+    let state = get_user_state(user);
+    let mut state = state.lock().unwrap();
+    let limit = config::limits().max_user_functions;
+    if !state.functions.contains_key(&definition.name) && state.functions.len() >= limit {
+        return Err(format!("A user defines at most {} functions", limit));
+    }
+    Arc::make_mut(&mut state.functions).insert(definition.name.clone(), function.clone());
+    Ok(function)
+}
+
+fn delete_function(user: &User, name: &str) -> Result<Arc<dyn registry::Function>, String> {
+    let state = get_user_state(user);
+    let mut state = state.lock().unwrap();
+    Arc::make_mut(&mut state.functions).remove(name).ok_or_else(|| format!("Function '{}' not found", name))
+}
+
+#[derive(Debug)]
+enum AuthError {
+    Unauthorized(&'static str),
+    Forbidden,
+}
+
+impl warp::reject::Reject for AuthError {}
+
+fn authenticate(header: Option<&str>) -> Result<User, AuthError> {
+    let Some(header) = header else {
+        if ALLOW_ANONYMOUS.load(Ordering::Relaxed) {
+            return Ok(User::anonymous());
+        }
+        return Err(AuthError::Unauthorized("A bearer token is required"));
+    };
+    let token = header
+        .strip_prefix("Bearer ")
+        .ok_or(AuthError::Unauthorized("The Authorization header must hold a bearer token"))?;
+    get_accounts().lock().unwrap().authenticate(token.trim()).ok_or(AuthError::Unauthorized("Invalid or revoked token"))
+}
+
+/// The user a request acts for: the owner of its bearer token, or the anonymous user
+/// when it has none and anonymous access is allowed.
+fn authenticated() -> impl Filter<Extract = (User,), Error = warp::Rejection> + Clone {
+    warp::header::optional::<String>("authorization").and_then(|header: Option<String>| async move {
+        authenticate(header.as_deref()).map_err(warp::reject::custom)
+    })
+}
+
+/// The language the client prefers for messages, from `Accept-Language`.
+fn accepted_language() -> impl Filter<Extract = (Option<Language>,), Error = warp::Rejection> + Clone {
+    warp::header::optional::<String>("accept-language")
+        .map(|header: Option<String>| header.as_deref().and_then(Language::from_accept_language))
+}
+
+/// Like `authenticated`, but a token is required.
+fn signed_in() -> impl Filter<Extract = (User,), Error = warp::Rejection> + Clone {
+    authenticated().and_then(|user: User| async move {
+        if user.is_anonymous() {
+            return Err(warp::reject::custom(AuthError::Unauthorized("A bearer token is required")));
+        }
+        Ok(user)
+    })
+}
+
+fn admin() -> impl Filter<Extract = (User,), Error = warp::Rejection> + Clone {
+    signed_in().and_then(|user: User| async move {
+        if user.role != Role::Admin {
+            return Err(warp::reject::custom(AuthError::Forbidden));
+        }
+        Ok(user)
+    })
+}
+
+async fn auth_rejection(rejection: warp::Rejection) -> Result<Box<dyn warp::Reply>, warp::Rejection> {
+    let (status, message) = match rejection.find::<AuthError>() {
+        Some(AuthError::Unauthorized(message)) => (warp::http::StatusCode::UNAUTHORIZED, *message),
+        Some(AuthError::Forbidden) => (warp::http::StatusCode::FORBIDDEN, "This needs the admin role"),
+        None => return Err(rejection),
+    };
+    let reply = warp::reply::with_status(warp::reply::json(&serde_json::json!({ "error": message })), status);
+    if status == warp::http::StatusCode::UNAUTHORIZED {
+        return Ok(Box::new(warp::reply::with_header(reply, "www-authenticate", "Bearer")));
+    }
+    Ok(Box::new(reply))
+}
+
+fn accounts_response(outcome: Result<AccountsResponse, String>) -> AccountsResponse {
+    outcome.unwrap_or_else(AccountsResponse::error)
+}
+
+// Clears the user's data before the account goes, holding the accounts lock throughout:
+// if clearing fails the account stays, and no new account of the same name can appear
+// meanwhile and inherit the data.
+fn delete_user(name: &str) -> Result<AccountsResponse, String> {
+    let mut accounts = get_accounts().lock().unwrap();
+    accounts.check_delete(name)?;
+    get_memory().lock().unwrap().clear_owner(name)?;
+    USERS.lock().unwrap().remove(name);
+    let user = accounts.delete_user(name)?;
+    Ok(AccountsResponse { users: vec![user], ..Default::default() })
+}
+
+fn issue_token(user: &User, request: &TokenRequest) -> Result<AccountsResponse, String> {
+    let owner = match &request.user {
+        Some(owner) if *owner != user.name && user.role != Role::Admin => {
+            return Err("Only admins can issue tokens for other users".to_string());
+        }
+        Some(owner) => owner.as_str(),
+        None => user.name.as_str(),
+    };
+    let token = get_accounts().lock().unwrap().issue(owner, request.label.clone())?;
+    Ok(AccountsResponse { tokens: vec![token], ..Default::default() })
+}
+
+/// A JSON request body, refused with 413 above the configured size.
+fn json_body<T: serde::de::DeserializeOwned + Send>() -> impl Filter<Extract = (T,), Error = warp::Rejection> + Clone {
+    warp::body::content_length_limit(config::limits().max_body_bytes).and(warp::body::json())
+}
+
+/// Logs each request as one JSON line and counts it in the metrics.
+fn log_request(info: warp::log::Info) {
+    let elapsed = info.elapsed();
+    get_metrics().lock().unwrap().record_request(info.method().as_str(), info.path(), info.status().as_u16(), elapsed);
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|now| now.as_millis()).unwrap_or(0);
+    let line = serde_json::json!({
+        "timestamp_ms": timestamp,
+        "method": info.method().as_str(),
+        "path": info.path(),
+        "status": info.status().as_u16(),
+        "duration_ms": elapsed.as_secs_f64() * 1000.0,
+        "remote": info.remote_addr().map(|address| address.ip().to_string()),
+        "user_agent": info.user_agent(),
+    });
+    println!("{}", line);
+}
+
+/// Resolves on SIGTERM or Ctrl-C. The server then stops accepting connections and
+/// finishes the requests in flight; memory and accounts are saved on every change, so
+/// there is nothing else to flush.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(_) => std::future::pending().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = terminate => {}
+    }
+    READY.store(false, Ordering::Relaxed);
+    println!("Shutting down after the requests in flight");
 }
 
 #[tokio::main]
 async fn main() {
+    let config = Config::load().unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    });
+    config::set_limits(config.limits);
+    ALLOW_ANONYMOUS.store(config.allows_anonymous(), Ordering::Relaxed);
+
+    match Memory::load(&config.memory_file) {
+        Ok(memory) => *get_memory().lock().unwrap() = memory,
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    }
+
+    match Accounts::load(&config.accounts_file) {
+        Ok(accounts) => *get_accounts().lock().unwrap() = accounts,
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    }
+    if !get_accounts().lock().unwrap().has_admin() {
+        match get_accounts().lock().unwrap().create_user("admin", Role::Admin) {
+            Ok((_, token)) => {
+                println!("Created the admin account 'admin'. Its token, shown only this once:");
+                println!("{}", token.token.unwrap_or_default());
+            }
+            Err(error) => {
+                eprintln!("{}", error);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(path) = &config.functions_file {
+        match registry::load_functions_file(path) {
+            Ok(names) => println!("Loaded {} function(s) from {}: {}", names.len(), path, names.join(", ")),
+            Err(error) => {
+                eprintln!("{}", error);
+                std::process::exit(1);
+            }
+        }
+    }
+
     let calculate = warp::path("calculate")
         .and(warp::post())
-        .and(warp::body::json())
-        .map(|request: CalculationRequest| {
-            let response = match evaluate_expression(&request.expression) {
-                Ok(result) => CalculationResponse {
-                    result,
-                    error: None,
-                },
-                Err(error) => CalculationResponse {
-                    result: 0.0,
-                    error: Some(error),
-                },
+        .and(authenticated())
+        .and(accepted_language())
+        .and(json_body())
+        .map(|user: User, language: Option<Language>, request: CalculationRequest| {
+            warp::reply::json(&as_user(&user, || calculate(&user, &request, language)))
+        });
+
+    let rpn = warp::path("rpn")
+        .and(warp::post())
+        .and(authenticated())
+        .and(json_body())
+        .map(|user: User, request: RpnRequest| warp::reply::json(&as_user(&user, || rpn::run(&request))));
+
+    let compile = warp::path("compile")
+        .and(warp::post())
+        .and(authenticated())
+        .and(json_body())
+        .map(|user: User, request: CompileRequest| {
+            let response = match as_user(&user, || compile_expression(&request.expression, &request.variables)) {
+                Ok(compiled) => {
+                    let variables = compiled.variables().to_vec();
+                    let result_type = Some(compiled.result_type());
+                    let id = get_user_state(&user).lock().unwrap().compile_cache.insert(compiled);
+                    CompileResponse { id: Some(id), variables, result_type, error: None }
+                }
+                Err(error) => {
+                    CompileResponse { id: None, variables: Vec::new(), result_type: None, error: Some(error) }
+                }
             };
             warp::reply::json(&response)
         });
 
+    let table = warp::path("table")
+        .and(warp::post())
+        .and(authenticated())
+        .and(json_body())
+        .map(|user: User, request: TableRequest| {
+            let response = as_user(&user, || run_table(&user, &request)).unwrap_or_else(TableResponse::error);
+            warp::reply::json(&response)
+        });
+
+    let truth_table = warp::path("truth-table")
+        .and(warp::post())
+        .and(authenticated())
+        .and(json_body())
+        .map(|user: User, request: TruthTableRequest| {
+            let response = as_user(&user, || run_truth_table(&request)).unwrap_or_else(TruthTableResponse::error);
+            warp::reply::json(&response)
+        });
+
+    let sheet_get = warp::path!("worksheets" / String)
+        .and(warp::get())
+        .and(authenticated())
+        .map(|sheet: String, user: User| {
+            warp::reply::json(&with_worksheet(&user, &sheet, |worksheet| Ok(worksheet.cells())))
+        });
+
+    let sheet_evaluate = warp::path!("worksheets" / String / "evaluate")
+        .and(warp::post())
+        .and(authenticated())
+        .map(|sheet: String, user: User| {
+            warp::reply::json(&as_user(&user, || with_worksheet(&user, &sheet, |worksheet| Ok(worksheet.evaluate_all()))))
+        });
+
+    let sheet_delete = warp::path!("worksheets" / String)
+        .and(warp::delete())
+        .and(authenticated())
+        .map(|sheet: String, user: User| {
+            let response = match get_user_state(&user).lock().unwrap().worksheets.remove(&sheet) {
+                Some(worksheet) => WorksheetResponse { cells: worksheet.cells(), error: None },
+                None => WorksheetResponse::error(format!("Worksheet '{}' not found", sheet)),
+            };
+            warp::reply::json(&response)
+        });
+
+    let cell_get = warp::path!("worksheets" / String / "cells" / String)
+        .and(warp::get())
+        .and(authenticated())
+        .map(|sheet: String, name: String, user: User| {
+            warp::reply::json(&with_worksheet(&user, &sheet, |worksheet| Ok(vec![worksheet.get(&name)?])))
+        });
+
+    let cell_put = warp::path!("worksheets" / String / "cells" / String)
+        .and(warp::put())
+        .and(authenticated())
+        .and(json_body())
+        .map(|sheet: String, name: String, user: User, request: CellRequest| {
+            warp::reply::json(&as_user(&user, || set_cell(&user, &sheet, &name, &request.expression)))
+        });
+
+    let cell_delete = warp::path!("worksheets" / String / "cells" / String)
+        .and(warp::delete())
+        .and(authenticated())
+        .map(|sheet: String, name: String, user: User| {
+            warp::reply::json(&as_user(&user, || with_worksheet(&user, &sheet, |worksheet| worksheet.remove(&name))))
+        });
+
+    let worksheets = sheet_get.or(sheet_evaluate).or(sheet_delete).or(cell_get).or(cell_put).or(cell_delete);
+
+    let number_theory = warp::path("number-theory")
+        .and(warp::post())
+        .and(authenticated())
+        .and(json_body())
+        .map(|_: User, request: NumberTheoryRequest| {
+            let response = number_theory::run(&request).unwrap_or_else(|error| NumberTheoryResponse {
+                error: Some(error),
+                ..Default::default()
+            });
+            warp::reply::json(&response)
+        });
+
+    let uncertainty = warp::path("uncertainty")
+        .and(warp::post())
+        .and(authenticated())
+        .and(json_body())
+        .map(|user: User, request: UncertaintyRequest| {
+            let response = as_user(&user, || uncertainty::run(&request))
+                .unwrap_or_else(|error| UncertaintyResponse::error(request.mode, error));
+            warp::reply::json(&response)
+        });
+
+    let polynomial = warp::path("polynomial")
+        .and(warp::post())
+        .and(authenticated())
+        .and(json_body())
+        .map(|_: User, request: PolynomialRequest| {
+            let response = polynomial::run(&request).unwrap_or_else(|error| PolynomialResponse {
+                error: Some(error),
+                ..Default::default()
+            });
+            warp::reply::json(&response)
+        });
+
+    let sequence = warp::path("sequence")
+        .and(warp::post())
+        .and(authenticated())
+        .and(json_body())
+        .map(|user: User, request: SequenceRequest| {
+            let response = as_user(&user, || sequence::run(&request)).unwrap_or_else(|error| SequenceResponse {
+                error: Some(error),
+                ..Default::default()
+            });
+            warp::reply::json(&response)
+        });
+
+    let solve = warp::path("solve")
+        .and(warp::post())
+        .and(authenticated())
+        .and(json_body())
+        .map(|user: User, request: SolveRequest| {
+            let response = as_user(&user, || equations::solve(&request)).unwrap_or_else(|error| SolveResponse {
+                error: Some(error),
+                ..Default::default()
+            });
+            warp::reply::json(&response)
+        });
+
+    let ieee754 = warp::path("ieee754")
+        .and(warp::post())
+        .and(authenticated())
+        .and(json_body())
+        .map(|user: User, request: Ieee754Request| {
+            let evaluate = |expression: &str| evaluate_expression(&user, expression).map_err(String::from);
+            let response = as_user(&user, || ieee754::inspect(&request, evaluate)).unwrap_or_else(|error| {
+                Ieee754Response { error: Some(error), ..Default::default() }
+            });
+            warp::reply::json(&response)
+        });
+
+    let memory_list = warp::path!("memory" / String)
+        .and(warp::get())
+        .and(authenticated())
+        .map(|session: String, user: User| {
+            warp::reply::json(&memory_response(get_memory().lock().unwrap().slots(&user.name, &session)))
+        });
+
+    let memory_clear_all = warp::path!("memory" / String)
+        .and(warp::delete())
+        .and(authenticated())
+        .map(|session: String, user: User| {
+            warp::reply::json(&memory_response(get_memory().lock().unwrap().clear_session(&user.name, &session)))
+        });
+
+    let memory_recall = warp::path!("memory" / String / String)
+        .and(warp::get())
+        .and(authenticated())
+        .map(|session: String, slot: String, user: User| {
+            let outcome = get_memory().lock().unwrap().recall(&user.name, &session, &slot);
+            warp::reply::json(&memory_response(outcome.map(|slot| vec![slot])))
+        });
+
+    let memory_store = warp::path!("memory" / String / String)
+        .and(warp::put())
+        .and(authenticated())
+        .and(json_body())
+        .map(|session: String, slot: String, user: User, request: MemoryRequest| {
+            let outcome = as_user(&user, || memory_operand(&user, &session, &request.expression))
+                .and_then(|value| get_memory().lock().unwrap().store(&user.name, &session, &slot, value));
+            warp::reply::json(&memory_response(outcome.map(|slot| vec![slot])))
+        });
+
+    let memory_add = warp::path!("memory" / String / String / "add")
+        .and(warp::post())
+        .and(authenticated())
+        .and(json_body())
+        .map(|session: String, slot: String, user: User, request: MemoryRequest| {
+            let outcome = as_user(&user, || memory_operand(&user, &session, &request.expression))
+                .and_then(|amount| get_memory().lock().unwrap().add(&user.name, &session, &slot, amount));
+            warp::reply::json(&memory_response(outcome.map(|slot| vec![slot])))
+        });
+
+    let memory_subtract = warp::path!("memory" / String / String / "subtract")
+        .and(warp::post())
+        .and(authenticated())
+        .and(json_body())
+        .map(|session: String, slot: String, user: User, request: MemoryRequest| {
+            let outcome = as_user(&user, || memory_operand(&user, &session, &request.expression))
+                .and_then(|amount| get_memory().lock().unwrap().add(&user.name, &session, &slot, -amount));
+            warp::reply::json(&memory_response(outcome.map(|slot| vec![slot])))
+        });
+
+    let memory_clear = warp::path!("memory" / String / String)
+        .and(warp::delete())
+        .and(authenticated())
+        .map(|session: String, slot: String, user: User| {
+            let outcome = get_memory().lock().unwrap().clear(&user.name, &session, &slot);
+            warp::reply::json(&memory_response(outcome.map(|slot| vec![slot])))
+        });
+
+    let memory = memory_list
+        .or(memory_clear_all)
+        .or(memory_recall)
+        .or(memory_store)
+        .or(memory_add)
+        .or(memory_subtract)
+        .or(memory_clear);
+
+    let parse = warp::path("parse")
+        .and(warp::post())
+        .and(authenticated())
+        .and(json_body())
+        .map(|user: User, request: ParseRequest| warp::reply::json(&as_user(&user, || editor::parse(&user, &request))));
+
+    let complete = warp::path("complete")
+        .and(warp::post())
+        .and(authenticated())
+        .and(json_body())
+        .map(|user: User, request: CompleteRequest| {
+            let response = as_user(&user, || editor::complete(&user, &request)).unwrap_or_else(|error| {
+                CompleteResponse { error: Some(error), ..Default::default() }
+            });
+            warp::reply::json(&response)
+        });
+
+    let jsonrpc = warp::path("jsonrpc")
+        .and(warp::post())
+        .and(authenticated())
+        .and(accepted_language())
+        .and(warp::body::content_length_limit(config::limits().max_body_bytes))
+        .and(warp::body::bytes())
+        .map(|user: User, language: Option<Language>, body: warp::hyper::body::Bytes| -> Box<dyn warp::Reply> {
+            match as_user(&user, || jsonrpc::handle(&user, language.unwrap_or_default(), &body)) {
+                Some(response) => Box::new(warp::reply::json(&response)),
+                None => Box::new(warp::http::StatusCode::NO_CONTENT),
+            }
+        });
+
+    let functions_list = warp::path!("functions")
+        .and(warp::get())
+        .and(authenticated())
+        .map(|user: User| warp::reply::json(&functions_response(Ok(as_user(&user, registry::user_functions)))));
+
+    let functions_define = warp::path!("functions")
+        .and(warp::post())
+        .and(authenticated())
+        .and(json_body())
+        .map(|user: User, definition: FunctionDefinition| {
+            warp::reply::json(&functions_response(define_function(&user, &definition).map(|function| vec![function])))
+        });
+
+    let functions_delete = warp::path!("functions" / String)
+        .and(warp::delete())
+        .and(authenticated())
+        .map(|name: String, user: User| {
+            warp::reply::json(&functions_response(delete_function(&user, &name).map(|function| vec![function])))
+        });
+
+    let functions = functions_list.or(functions_define).or(functions_delete);
+
+    let users_list = warp::path!("users").and(warp::get()).and(admin()).map(|_: User| {
+        let users = get_accounts().lock().unwrap().users();
+        warp::reply::json(&AccountsResponse { users, ..Default::default() })
+    });
+
+    let users_create = warp::path!("users")
+        .and(warp::post())
+        .and(admin())
+        .and(json_body())
+        .map(|_: User, request: UserRequest| {
+            let outcome = get_accounts().lock().unwrap().create_user(&request.name, request.role);
+            let outcome = outcome.map(|(user, token)| AccountsResponse { users: vec![user], tokens: vec![token], error: None });
+            warp::reply::json(&accounts_response(outcome))
+        });
+
+    let users_delete = warp::path!("users" / String)
+        .and(warp::delete())
+        .and(admin())
+        .map(|name: String, _: User| warp::reply::json(&accounts_response(delete_user(&name))));
+
+    let tokens_list = warp::path!("tokens").and(warp::get()).and(signed_in()).map(|user: User| {
+        // Admins see every token, others their own.
+        let owner = (user.role != Role::Admin).then_some(user.name.as_str());
+        let tokens = get_accounts().lock().unwrap().tokens(owner);
+        warp::reply::json(&AccountsResponse { tokens, ..Default::default() })
+    });
+
+    let tokens_issue = warp::path!("tokens")
+        .and(warp::post())
+        .and(signed_in())
+        .and(json_body())
+        .map(|user: User, request: TokenRequest| warp::reply::json(&accounts_response(issue_token(&user, &request))));
+
+    let tokens_revoke = warp::path!("tokens" / String)
+        .and(warp::delete())
+        .and(signed_in())
+        .map(|id: String, user: User| {
+            let owner = (user.role != Role::Admin).then_some(user.name.as_str());
+            let outcome = get_accounts().lock().unwrap().revoke(&id, owner);
+            let outcome = outcome.map(|token| AccountsResponse { tokens: vec![token], ..Default::default() });
+            warp::reply::json(&accounts_response(outcome))
+        });
+
+    let accounts = users_list.or(users_create).or(users_delete).or(tokens_list).or(tokens_issue).or(tokens_revoke);
+
+    let healthz = warp::path!("healthz").and(warp::get()).map(|| warp::reply::json(&serde_json::json!({ "status": "ok" })));
+
+    let readyz = warp::path!("readyz").and(warp::get()).map(|| {
+        let (status, code) = match READY.load(Ordering::Relaxed) {
+            true => ("ready", warp::http::StatusCode::OK),
+            false => ("not ready", warp::http::StatusCode::SERVICE_UNAVAILABLE),
+        };
+        warp::reply::with_status(warp::reply::json(&serde_json::json!({ "status": status })), code)
+    });
+
+    let metrics = warp::path!("metrics").and(warp::get()).map(|| {
+        let text = get_metrics().lock().unwrap().render(READY.load(Ordering::Relaxed));
+        warp::reply::with_header(text, "content-type", "text/plain; version=0.0.4")
+    });
+
     let cors = warp::cors()
-        .allow_any_origin()
-        .allow_headers(vec!["content-type"])
-        .allow_methods(vec!["POST"]);
+        .allow_headers(vec!["content-type", "authorization"])
+        .allow_methods(vec!["GET", "POST", "PUT", "DELETE"]);
+    let cors = match config.any_origin() {
+        true => cors.allow_any_origin(),
+        false => cors.allow_origins(config.cors_origins.iter().map(String::as_str)),
+    };
 
-    let routes = calculate.with(cors);
+    let openapi = warp::path!("openapi.json").and(warp::get()).map(|| warp::reply::json(openapi::get_document()));
+    let docs = warp::path!("docs").and(warp::get()).map(|| warp::reply::html(openapi::DOCS_PAGE));
 
-    println!("Calculator server running on http://0.0.0.0:3030");
-    warp::serve(routes).run(([0, 0, 0, 0], 3030)).await;
-}
\ No newline at end of file
+    let api = calculate.or(rpn).or(compile).or(table).or(truth_table).or(number_theory).or(uncertainty).or(polynomial).or(sequence).or(solve).or(ieee754).or(parse).or(complete).or(jsonrpc).or(worksheets).or(memory).or(functions).or(accounts);
+    // The unprefixed paths are the same routes, kept for clients written before `/v1`.
+    let versioned = warp::path(openapi::PREFIX).and(api.clone()).or(api);
+    let routes = versioned.or(openapi).or(docs).or(healthz).or(readyz).or(metrics).recover(auth_rejection).with(cors).with(warp::log::custom(log_request));
+
+    let (address, server) =
+        match warp::serve(routes).try_bind_with_graceful_shutdown((config.bind, config.port), shutdown_signal()) {
+            Ok(bound) => bound,
+            Err(error) => {
+                eprintln!("Cannot listen on {}:{}: {}", config.bind, config.port, error);
+                std::process::exit(1);
+            }
+        };
+    READY.store(true, Ordering::Relaxed);
+    println!("Calculator server running on http://{}", address);
+    server.await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(name: &str) -> User {
+        User { name: name.to_string(), role: Role::User }
+    }
+
+    fn define(user: &User, name: &str, body: &str) {
+        let params = vec!["x".to_string()];
+        let definition = FunctionDefinition { name: name.to_string(), params, body: body.to_string(), doc: None };
+        define_function(user, &definition).unwrap();
+    }
+
+    #[test]
+    fn users_see_only_their_own_state() {
+        let (alice, bob) = (user("isolation-alice"), user("isolation-bob"));
+        set_variable(&alice, "rate", "0.5").unwrap();
+        define(&alice, "twice", "2 * x");
+        get_memory().lock().unwrap().store(&alice.name, "s", "M", 7.0).unwrap();
+
+        assert_eq!(as_user(&alice, || evaluate_expression(&alice, "twice(rate)")).unwrap(), Value::Number(1.0));
+        assert!(get_variable(&bob, "rate").is_err());
+        assert!(as_user(&bob, || evaluate_expression(&bob, "rate")).is_err());
+        assert!(as_user(&bob, || evaluate_expression(&bob, "twice(1)")).is_err());
+        assert!(as_user(&bob, registry::user_functions).is_empty());
+        assert_eq!(as_user(&bob, || memory_operand(&bob, "s", "M")).unwrap(), 0.0);
+        assert_eq!(as_user(&alice, || memory_operand(&alice, "s", "M")).unwrap(), 7.0);
+    }
+
+    #[test]
+    fn deleting_a_user_clears_their_state() {
+        let name = "isolation-carol";
+        get_accounts().lock().unwrap().create_user(name, Role::User).unwrap();
+        let carol = user(name);
+        set_variable(&carol, "rate", "0.5").unwrap();
+        get_memory().lock().unwrap().store(name, "s", "M", 7.0).unwrap();
+
+        delete_user(name).unwrap();
+        assert!(get_variable(&carol, "rate").is_err());
+        assert_eq!(get_memory().lock().unwrap().get(name, "s", "M"), None);
+        assert!(delete_user(name).is_err());
+    }
+}
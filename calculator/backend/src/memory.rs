@@ -0,0 +1,298 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::accounts::ANONYMOUS;
+use crate::functions;
+use crate::parser;
+use crate::value::ExtendedReal;
+
+/// The slot a desk calculator's single memory maps to.
+pub const DEFAULT_SLOT: &str = "M";
+pub const MAX_SLOTS: usize = 100;
+pub const MAX_SESSIONS: usize = 1000;
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct MemoryRequest {
+    pub expression: String,
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct MemorySlot {
+    pub name: String,
+    pub value: ExtendedReal,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct MemoryResponse {
+    pub slots: Vec<MemorySlot>,
+    pub error: Option<String>,
+}
+
+impl MemoryResponse {
+    pub fn error(message: String) -> Self {
+        Self { slots: Vec::new(), error: Some(message) }
+    }
+}
+
+/// Memory registers per user and session, written through to a JSON file so they survive
+/// a restart. A slot that was never stored reads as 0, like an empty desk calculator memory.
+#[derive(Debug, Default)]
+pub struct Memory {
+    sessions: BTreeMap<String, BTreeMap<String, f64>>,
+    path: Option<String>,
+}
+
+fn check_session(session: &str) -> Result<(), String> {
+    let valid = !session.is_empty()
+        && session.len() <= 64
+        && session.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(format!("'{}' is not a valid session id", session));
+    }
+    Ok(())
+}
+
+// The key a user's session is stored under. Anonymous sessions keep their plain id, as
+// in files written before there were accounts; '/' cannot occur in either part.
+fn key(owner: &str, session: &str) -> Result<String, String> {
+    check_session(session)?;
+    Ok(if owner == ANONYMOUS { session.to_string() } else { format!("{}/{}", owner, session) })
+}
+
+fn check_slot(slot: &str) -> Result<(), String> {
+    if !parser::is_identifier(slot) || functions::constant(slot).is_some() || slot == "today" {
+        return Err(format!("'{}' is not a valid memory slot name", slot));
+    }
+    Ok(())
+}
+
+impl Memory {
+    /// Reads the registers saved at `path`; a missing file is an empty memory.
+    pub fn load(path: &str) -> Result<Self, String> {
+        let sessions = match std::fs::read_to_string(path) {
+            Ok(text) => {
+                serde_json::from_str(&text).map_err(|error| format!("Invalid memory file '{}': {}", path, error))?
+            }
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(error) => return Err(format!("Cannot read '{}': {}", path, error)),
+        };
+        Ok(Memory { sessions, path: Some(path.to_string()) })
+    }
+
+    /// Writes to a temporary file first so a crash never leaves a half-written one.
+    fn save(&self, sessions: &BTreeMap<String, BTreeMap<String, f64>>) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let text = serde_json::to_string_pretty(sessions).map_err(|error| error.to_string())?;
+        let temporary = format!("{}.tmp", path);
+        std::fs::write(&temporary, text)
+            .and_then(|_| std::fs::rename(&temporary, path))
+            .map_err(|error| format!("Cannot save memory to '{}': {}", path, error))
+    }
+
+    // The keys of the owner's sessions.
+    fn keys<'a>(&'a self, owner: &'a str) -> impl Iterator<Item = &'a String> + 'a {
+        self.sessions.keys().filter(move |key| match key.split_once('/') {
+            Some((key_owner, _)) => key_owner == owner,
+            None => owner == ANONYMOUS,
+        })
+    }
+
+    /// Applies a change to one session and persists it; nothing changes if `f` or saving
+    /// fails. Only that session is copied to roll back.
+    fn update<R>(
+        &mut self,
+        owner: &str,
+        session: &str,
+        f: impl FnOnce(&mut BTreeMap<String, f64>) -> Result<R, String>,
+    ) -> Result<R, String> {
+        let key = key(owner, session)?;
+        let previous = self.sessions.get(&key).cloned();
+        let slots = self.sessions.entry(key.clone()).or_default();
+        let mut result = f(slots);
+        if slots.is_empty() {
+            self.sessions.remove(&key);
+        } else if previous.is_none() && result.is_ok() && self.keys(owner).count() > MAX_SESSIONS {
+            result = Err(format!("A user keeps at most {} memory sessions", MAX_SESSIONS));
+        }
+        match result.and_then(|result| self.save(&self.sessions).map(|_| result)) {
+            Ok(result) => Ok(result),
+            Err(error) => {
+                match previous {
+                    Some(slots) => self.sessions.insert(key, slots),
+                    None => self.sessions.remove(&key),
+                };
+                Err(error)
+            }
+        }
+    }
+
+    pub fn slots(&self, owner: &str, session: &str) -> Result<Vec<MemorySlot>, String> {
+        let slots = self.sessions.get(&key(owner, session)?);
+        Ok(slots
+            .into_iter()
+            .flatten()
+            .map(|(name, value)| MemorySlot { name: name.clone(), value: ExtendedReal(*value) })
+            .collect())
+    }
+
+    pub fn get(&self, owner: &str, session: &str, slot: &str) -> Option<f64> {
+        self.sessions.get(&key(owner, session).ok()?)?.get(slot).copied()
+    }
+
+    /// MR: the slot's value, 0 if it was never stored.
+    pub fn recall(&self, owner: &str, session: &str, slot: &str) -> Result<MemorySlot, String> {
+        check_session(session)?;
+        check_slot(slot)?;
+        Ok(MemorySlot { name: slot.to_string(), value: ExtendedReal(self.get(owner, session, slot).unwrap_or(0.0)) })
+    }
+
+    /// MS: replaces the slot's value.
+    pub fn store(&mut self, owner: &str, session: &str, slot: &str, value: f64) -> Result<MemorySlot, String> {
+        self.add_with(owner, session, slot, |_| value)
+    }
+
+    /// M+ and M− (with a negated amount).
+    pub fn add(&mut self, owner: &str, session: &str, slot: &str, amount: f64) -> Result<MemorySlot, String> {
+        self.add_with(owner, session, slot, |value| value + amount)
+    }
+
+    fn add_with(
+        &mut self,
+        owner: &str,
+        session: &str,
+        slot: &str,
+        f: impl FnOnce(f64) -> f64,
+    ) -> Result<MemorySlot, String> {
+        check_slot(slot)?;
+        self.update(owner, session, |slots| {
+            if !slots.contains_key(slot) && slots.len() >= MAX_SLOTS {
+                return Err(format!("A session holds at most {} memory slots", MAX_SLOTS));
+            }
+            let value = slots.entry(slot.to_string()).or_insert(0.0);
+            *value = f(*value);
+            if !value.is_finite() {
+                return Err(format!("Memory slot '{}' cannot hold {}", slot, value));
+            }
+            Ok(MemorySlot { name: slot.to_string(), value: ExtendedReal(*value) })
+        })
+    }
+
+    /// MC for one slot; returns the value it held.
+    pub fn clear(&mut self, owner: &str, session: &str, slot: &str) -> Result<MemorySlot, String> {
+        check_slot(slot)?;
+        self.update(owner, session, |slots| {
+            let value = slots.remove(slot).unwrap_or(0.0);
+            Ok(MemorySlot { name: slot.to_string(), value: ExtendedReal(value) })
+        })
+    }
+
+    /// MC for every slot of a session; returns what they held.
+    pub fn clear_session(&mut self, owner: &str, session: &str) -> Result<Vec<MemorySlot>, String> {
+        let cleared = self.slots(owner, session)?;
+        self.update(owner, session, |slots| {
+            slots.clear();
+            Ok(())
+        })?;
+        Ok(cleared)
+    }
+
+    /// Drops every session of a deleted user.
+    pub fn clear_owner(&mut self, owner: &str) -> Result<(), String> {
+        let keys: Vec<String> = self.keys(owner).cloned().collect();
+        let removed: Vec<_> = keys.into_iter().filter_map(|key| self.sessions.remove_entry(&key)).collect();
+        if let Err(error) = self.save(&self.sessions) {
+            self.sessions.extend(removed);
+            return Err(error);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(slot: Result<MemorySlot, String>) -> f64 {
+        slot.unwrap().value.0
+    }
+
+    #[test]
+    fn slots_store_add_and_clear() {
+        let mut memory = Memory::default();
+        assert_eq!(value(memory.recall("alice", "s", "M")), 0.0);
+        assert_eq!(value(memory.store("alice", "s", "M", 10.0)), 10.0);
+        assert_eq!(value(memory.add("alice", "s", "M", 2.5)), 12.5);
+        assert_eq!(value(memory.add("alice", "s", "M", -5.0)), 7.5);
+        assert_eq!(value(memory.add("alice", "s", "tax", 0.08)), 0.08);
+        let names: Vec<String> = memory.slots("alice", "s").unwrap().into_iter().map(|slot| slot.name).collect();
+        assert_eq!(names, ["M", "tax"]);
+
+        assert_eq!(value(memory.clear("alice", "s", "M")), 7.5);
+        assert_eq!(memory.get("alice", "s", "M"), None);
+        assert_eq!(memory.clear_session("alice", "s").unwrap().len(), 1);
+        assert!(memory.slots("alice", "s").unwrap().is_empty());
+    }
+
+    #[test]
+    fn bad_names_and_values_change_nothing() {
+        let mut memory = Memory::default();
+        memory.store("alice", "s", "M", 1.0).unwrap();
+        assert!(memory.store("alice", "s", "pi", 1.0).is_err());
+        assert!(memory.store("alice", "a/b", "M", 1.0).is_err());
+        assert!(memory.add("alice", "s", "M", f64::INFINITY).is_err());
+        assert!(memory.store("alice", "t", "M", f64::NAN).is_err());
+        assert_eq!(memory.get("alice", "s", "M"), Some(1.0));
+        assert!(memory.slots("alice", "t").unwrap().is_empty());
+        for i in 1..MAX_SLOTS {
+            memory.store("alice", "s", &format!("m{}", i), 1.0).unwrap();
+        }
+        assert!(memory.store("alice", "s", "one_more", 1.0).is_err());
+    }
+
+    #[test]
+    fn sessions_per_owner_are_capped() {
+        let mut memory = Memory::default();
+        for i in 0..MAX_SESSIONS {
+            memory.store("alice", &format!("s{}", i), "M", 1.0).unwrap();
+        }
+        let error = memory.store("alice", "one-more", "M", 1.0).unwrap_err();
+        assert_eq!(error, format!("A user keeps at most {} memory sessions", MAX_SESSIONS));
+        assert!(memory.slots("alice", "one-more").unwrap().is_empty());
+        assert!(memory.store("alice", "s0", "N", 1.0).is_ok());
+        assert!(memory.clear("alice", "one-more", "M").is_ok());
+        assert!(memory.store("bob", "one-more", "M", 1.0).is_ok());
+        assert!(memory.store(ANONYMOUS, "one-more", "M", 1.0).is_ok());
+    }
+
+    #[test]
+    fn registers_survive_a_reload() {
+        let path = std::env::temp_dir().join(format!("calculator-memory-{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut memory = Memory::load(path).unwrap();
+        memory.store("alice", "s", "M", 42.0).unwrap();
+        memory.store(ANONYMOUS, "s", "M", 1.5).unwrap();
+        memory.store("bob", "s", "M", 7.0).unwrap();
+        memory.clear_owner("bob").unwrap();
+
+        let reloaded = Memory::load(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(reloaded.get("alice", "s", "M"), Some(42.0));
+        assert_eq!(reloaded.get(ANONYMOUS, "s", "M"), Some(1.5));
+        assert_eq!(reloaded.get("bob", "s", "M"), None);
+    }
+
+    #[test]
+    fn failed_saves_roll_back() {
+        let mut memory = Memory { path: Some("/nonexistent/memory.json".to_string()), ..Memory::default() };
+        assert!(memory.store("alice", "s", "M", 1.0).unwrap_err().starts_with("Cannot save memory"));
+        assert_eq!(memory.get("alice", "s", "M"), None);
+
+        memory.sessions.insert("alice/s".to_string(), BTreeMap::from([("M".to_string(), 1.0)]));
+        assert!(memory.add("alice", "s", "M", 1.0).is_err());
+        assert!(memory.clear_owner("alice").is_err());
+        assert_eq!(memory.get("alice", "s", "M"), Some(1.0));
+    }
+}
@@ -0,0 +1,174 @@
+use lazy_static::lazy_static;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Upper bounds of the latency histogram buckets, in seconds.
+const BUCKETS: &[f64] = &[0.0001, 0.0005, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0];
+
+/// The routes requests are counted under, with or without the version prefix; anything
+/// else is `other`, so that paths such as `/memory/{session}` do not each get their own
+/// series.
+const ROUTES: &[&str] = &[
+    "calculate",
+    "rpn",
+    "compile",
+    "table",
+    "truth-table",
+    "number-theory",
+    "uncertainty",
+    "polynomial",
+    "sequence",
+    "solve",
+    "ieee754",
+    "parse",
+    "complete",
+    "jsonrpc",
+    "worksheets",
+    "memory",
+    "functions",
+    "users",
+    "tokens",
+    "healthz",
+    "readyz",
+    "metrics",
+    "openapi.json",
+    "docs",
+];
+
+#[derive(Debug, Clone, Default)]
+struct Histogram {
+    /// Observations per bucket, not cumulative; the last is above every bound.
+    buckets: [u64; BUCKETS.len() + 1],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        let bucket = BUCKETS.iter().position(|bound| seconds <= *bound).unwrap_or(BUCKETS.len());
+        self.buckets[bucket] += 1;
+        self.sum += seconds;
+        self.count += 1;
+    }
+
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let mut cumulative = 0;
+        for (bound, count) in BUCKETS.iter().zip(&self.buckets) {
+            cumulative += count;
+            let _ = writeln!(out, "{}_bucket{{{}le=\"{}\"}} {}", name, labels, bound, cumulative);
+        }
+        let _ = writeln!(out, "{}_bucket{{{}le=\"+Inf\"}} {}", name, labels, self.count);
+        let labels = labels.trim_end_matches(',');
+        let _ = writeln!(out, "{}_sum{{{}}} {}", name, labels, self.sum);
+        let _ = writeln!(out, "{}_count{{{}}} {}", name, labels, self.count);
+    }
+}
+
+/// Counters and histograms exposed at `/metrics` in the Prometheus text format.
+#[derive(Debug)]
+pub struct Metrics {
+    started: Instant,
+    /// By method, route and status.
+    requests: BTreeMap<(String, &'static str, u16), u64>,
+    /// By route.
+    request_durations: BTreeMap<&'static str, Histogram>,
+    /// By error code, `none` for evaluations that succeeded.
+    evaluations: BTreeMap<&'static str, Histogram>,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        Metrics {
+            started: Instant::now(),
+            requests: BTreeMap::new(),
+            request_durations: BTreeMap::new(),
+            evaluations: BTreeMap::new(),
+        }
+    }
+
+    pub fn record_request(&mut self, method: &str, path: &str, status: u16, elapsed: Duration) {
+        let path = path.trim_start_matches('/');
+        let path = path.strip_prefix(crate::openapi::PREFIX).and_then(|rest| rest.strip_prefix('/')).unwrap_or(path);
+        let first = path.split('/').next().unwrap_or("");
+        let route = ROUTES.iter().copied().find(|route| *route == first).unwrap_or("other");
+        *self.requests.entry((method.to_string(), route, status)).or_default() += 1;
+        self.request_durations.entry(route).or_default().observe(elapsed.as_secs_f64());
+    }
+
+    pub fn record_evaluation(&mut self, error_code: Option<&'static str>, elapsed: Duration) {
+        self.evaluations.entry(error_code.unwrap_or("none")).or_default().observe(elapsed.as_secs_f64());
+    }
+
+    pub fn render(&self, ready: bool) -> String {
+        let mut out = String::new();
+        out.push_str("# HELP calculator_http_requests_total HTTP requests by method, route and status.\n");
+        out.push_str("# TYPE calculator_http_requests_total counter\n");
+        for ((method, route, status), count) in &self.requests {
+            let _ = writeln!(
+                out,
+                "calculator_http_requests_total{{method=\"{}\",route=\"{}\",status=\"{}\"}} {}",
+                method, route, status, count
+            );
+        }
+        out.push_str("# HELP calculator_http_request_duration_seconds Time to answer an HTTP request.\n");
+        out.push_str("# TYPE calculator_http_request_duration_seconds histogram\n");
+        for (route, histogram) in &self.request_durations {
+            histogram.render(&mut out, "calculator_http_request_duration_seconds", &format!("route=\"{}\",", route));
+        }
+        out.push_str(
+            "# HELP calculator_evaluation_duration_seconds Time to evaluate a /calculate expression, by error code.\n",
+        );
+        out.push_str("# TYPE calculator_evaluation_duration_seconds histogram\n");
+        for (code, histogram) in &self.evaluations {
+            histogram.render(&mut out, "calculator_evaluation_duration_seconds", &format!("error_code=\"{}\",", code));
+        }
+        out.push_str("# HELP calculator_uptime_seconds Seconds since the server started.\n");
+        out.push_str("# TYPE calculator_uptime_seconds gauge\n");
+        let _ = writeln!(out, "calculator_uptime_seconds {}", self.started.elapsed().as_secs_f64());
+        out.push_str("# HELP calculator_ready Whether the server accepts traffic.\n");
+        out.push_str("# TYPE calculator_ready gauge\n");
+        let _ = writeln!(out, "calculator_ready {}", u8::from(ready));
+        out
+    }
+}
+
+lazy_static! {
+    static ref METRICS: Mutex<Metrics> = Mutex::new(Metrics::new());
+}
+
+pub fn get_metrics() -> &'static Mutex<Metrics> {
+    &METRICS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histograms_are_cumulative() {
+        let mut metrics = Metrics::new();
+        metrics.record_evaluation(None, Duration::from_micros(50));
+        metrics.record_evaluation(None, Duration::from_millis(3));
+        metrics.record_evaluation(Some("DIVISION_BY_ZERO"), Duration::from_secs(10));
+        let text = metrics.render(true);
+        assert!(text.contains("calculator_evaluation_duration_seconds_bucket{error_code=\"none\",le=\"0.0001\"} 1\n"));
+        assert!(text.contains("calculator_evaluation_duration_seconds_bucket{error_code=\"none\",le=\"0.005\"} 2\n"));
+        assert!(text.contains("calculator_evaluation_duration_seconds_count{error_code=\"none\"} 2\n"));
+        assert!(text.contains("calculator_evaluation_duration_seconds_bucket{error_code=\"DIVISION_BY_ZERO\",le=\"5\"} 0\n"));
+        assert!(text.contains("calculator_evaluation_duration_seconds_bucket{error_code=\"DIVISION_BY_ZERO\",le=\"+Inf\"} 1\n"));
+        assert!(text.contains("calculator_ready 1\n"));
+    }
+
+    #[test]
+    fn requests_are_counted_by_route() {
+        let mut metrics = Metrics::new();
+        metrics.record_request("GET", "/memory/s1/M", 200, Duration::from_millis(1));
+        metrics.record_request("GET", "/v1/memory/s2", 200, Duration::from_millis(1));
+        metrics.record_request("GET", "/secret-scan", 404, Duration::from_millis(1));
+        let text = metrics.render(true);
+        assert!(text.contains("calculator_http_requests_total{method=\"GET\",route=\"memory\",status=\"200\"} 2\n"));
+        assert!(text.contains("calculator_http_requests_total{method=\"GET\",route=\"other\",status=\"404\"} 1\n"));
+    }
+}
@@ -0,0 +1,456 @@
+use num_bigint::{BigInt, BigUint, Sign};
+use num_integer::Integer;
+use num_traits::{One, Signed, ToPrimitive, Zero};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::ast::{BinaryOp, Expr, UnaryOp};
+use crate::i18n::{BOOLEANS_UNSUPPORTED, DATES_UNSUPPORTED, TEXT_UNSUPPORTED, UNCERTAIN_VALUES_UNSUPPORTED};
+use crate::parser;
+
+pub const MAX_FACTORIAL: u64 = 20_000;
+pub const MAX_RESULT_BITS: u64 = 1_000_000;
+const RHO_ITERATION_LIMIT: u64 = 2_000_000;
+const SMALL_PRIMES: [u32; 13] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41];
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    #[default]
+    Evaluate,
+    IsPrime,
+    Factor,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct NumberTheoryRequest {
+    pub expression: String,
+    #[serde(default)]
+    pub operation: Operation,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct PrimeFactor {
+    pub prime: String,
+    pub exponent: u32,
+}
+
+#[derive(Debug, Serialize, Default, JsonSchema)]
+pub struct NumberTheoryResponse {
+    pub result: Option<String>,
+    pub digits: Option<usize>,
+    pub is_prime: Option<bool>,
+    pub factors: Option<Vec<PrimeFactor>>,
+    pub error: Option<String>,
+}
+
+pub fn run(request: &NumberTheoryRequest) -> Result<NumberTheoryResponse, String> {
+    let value = evaluate_exact(&request.expression)?;
+    let digits = value.magnitude().to_string().len();
+    let mut response = NumberTheoryResponse {
+        result: Some(value.to_string()),
+        digits: Some(digits),
+        ..Default::default()
+    };
+
+    match request.operation {
+        Operation::Evaluate => {}
+        Operation::IsPrime => {
+            response.is_prime = Some(value.sign() == Sign::Plus && is_prime(value.magnitude()));
+        }
+        Operation::Factor => {
+            if value.is_zero() {
+                return Err("Cannot factor zero".to_string());
+            }
+            let factors = factorize(value.magnitude())?
+                .into_iter()
+                .map(|(prime, exponent)| PrimeFactor { prime: prime.to_string(), exponent })
+                .collect();
+            response.factors = Some(factors);
+        }
+    }
+
+    Ok(response)
+}
+
+/// Evaluates an integer-only expression exactly. Literals are read from the source text,
+/// so values beyond 2^53 keep every digit.
+pub fn evaluate_exact(source: &str) -> Result<BigInt, String> {
+    let expr = parser::parse(source)?;
+    let chars: Vec<char> = source.chars().collect();
+    eval(&expr, &chars)
+}
+
+fn eval(expr: &Expr, source: &[char]) -> Result<BigInt, String> {
+    match expr {
+        Expr::Number { span, .. } => {
+            let text: String = source[span.start..span.end].iter().collect();
+            text.parse::<BigInt>()
+                .map_err(|_| format!("'{}' is not an integer; exact arithmetic only accepts integers", text))
+        }
+        Expr::Variable { name, .. } => Err(format!("Unknown variable '{}'", name)),
+        Expr::Boolean { .. } | Expr::Unary { op: UnaryOp::Not, .. } => Err(BOOLEANS_UNSUPPORTED.error().to_string()),
+        Expr::Duration { .. } | Expr::Workdays { .. } | Expr::Date { .. } => Err(DATES_UNSUPPORTED.error().to_string()),
+        Expr::Text { .. } => Err(TEXT_UNSUPPORTED.error().to_string()),
+        Expr::Binary { op, .. } if op.is_comparison() || op.is_logical() => {
+            Err(BOOLEANS_UNSUPPORTED.error().to_string())
+        }
+        Expr::Unary { op, operand, .. } => {
+            let value = eval(operand, source)?;
+            match op {
+                UnaryOp::Neg => Ok(-value),
+                UnaryOp::Plus => Ok(value),
+                UnaryOp::Factorial => factorial(&value),
+                UnaryOp::Not => unreachable!(),
+            }
+        }
+        Expr::Binary { op, left, right, .. } => {
+            let a = eval(left, source)?;
+            let b = eval(right, source)?;
+            match op {
+                BinaryOp::Add => Ok(a + b),
+                BinaryOp::Sub => Ok(a - b),
+                BinaryOp::Mul => Ok(a * b),
+                BinaryOp::Div => {
+                    if b.is_zero() {
+                        return Err("Division by zero".to_string());
+                    }
+                    let (quotient, remainder) = a.div_rem(&b);
+                    if !remainder.is_zero() {
+                        return Err(format!("{} is not divisible by {}; use div(a, b) for floor division", a, b));
+                    }
+                    Ok(quotient)
+                }
+                BinaryOp::Rem => {
+                    if b.is_zero() {
+                        return Err("Division by zero".to_string());
+                    }
+                    Ok(a.mod_floor(&b))
+                }
+                BinaryOp::Pow => pow(&a, &b),
+                BinaryOp::PlusMinus => Err(UNCERTAIN_VALUES_UNSUPPORTED.error().to_string()),
+                _ => unreachable!(),
+            }
+        }
+        Expr::Call { name, args, .. } => {
+            let values = args.iter().map(|arg| eval(arg, source)).collect::<Result<Vec<_>, _>>()?;
+            call(name, &values)
+        }
+        Expr::Interval { .. } => Err(UNCERTAIN_VALUES_UNSUPPORTED.error().to_string()),
+    }
+}
+
+fn expect_args(name: &str, values: &[BigInt], count: usize) -> Result<(), String> {
+    if values.len() != count {
+        return Err(format!("Function '{}' expects {} argument(s), got {}", name, count, values.len()));
+    }
+    Ok(())
+}
+
+fn call(name: &str, values: &[BigInt]) -> Result<BigInt, String> {
+    match name {
+        "gcd" | "lcm" => {
+            if values.len() < 2 {
+                return Err(format!("Function '{}' expects at least 2 argument(s), got {}", name, values.len()));
+            }
+            let fold = if name == "gcd" { BigInt::gcd } else { BigInt::lcm };
+            Ok(values[1..].iter().fold(values[0].clone(), |acc, v| fold(&acc, v)))
+        }
+        "abs" => {
+            expect_args(name, values, 1)?;
+            Ok(values[0].abs())
+        }
+        "div" | "mod" => {
+            expect_args(name, values, 2)?;
+            if values[1].is_zero() {
+                return Err("Division by zero".to_string());
+            }
+            Ok(if name == "div" { values[0].div_floor(&values[1]) } else { values[0].mod_floor(&values[1]) })
+        }
+        "fact" => {
+            expect_args(name, values, 1)?;
+            factorial(&values[0])
+        }
+        "binomial" => {
+            expect_args(name, values, 2)?;
+            binomial(&values[0], &values[1])
+        }
+        "modinv" => {
+            expect_args(name, values, 2)?;
+            mod_inverse(&values[0], &values[1])
+        }
+        "modpow" => {
+            expect_args(name, values, 3)?;
+            mod_pow(&values[0], &values[1], &values[2])
+        }
+        _ => Err(format!("Unknown function '{}' in exact arithmetic", name)),
+    }
+}
+
+fn pow(base: &BigInt, exponent: &BigInt) -> Result<BigInt, String> {
+    if exponent.is_negative() {
+        return Err("Negative exponents are not allowed in exact integer arithmetic".to_string());
+    }
+    if base.magnitude() <= &BigUint::one() {
+        if exponent.is_zero() || (base.is_negative() && exponent.is_even()) {
+            return Ok(BigInt::one());
+        }
+        return Ok(base.clone());
+    }
+    let exponent = exponent
+        .to_u32()
+        .filter(|&e| base.bits().saturating_mul(e as u64) <= MAX_RESULT_BITS)
+        .ok_or_else(|| format!("Result would exceed {} bits", MAX_RESULT_BITS))?;
+    Ok(base.pow(exponent))
+}
+
+pub fn factorial(n: &BigInt) -> Result<BigInt, String> {
+    if n.is_negative() {
+        return Err("Factorial is only defined for non-negative integers".to_string());
+    }
+    let n = n
+        .to_u64()
+        .filter(|&n| n <= MAX_FACTORIAL)
+        .ok_or_else(|| format!("Factorial is limited to n <= {}", MAX_FACTORIAL))?;
+    Ok((2..=n).fold(BigInt::one(), |acc, k| acc * k))
+}
+
+pub fn binomial(n: &BigInt, k: &BigInt) -> Result<BigInt, String> {
+    if n.is_negative() {
+        return Err("Binomial coefficients need a non-negative n".to_string());
+    }
+    if k.is_negative() || k > n {
+        return Ok(BigInt::zero());
+    }
+    let k = std::cmp::min(k.clone(), n - k)
+        .to_u64()
+        .filter(|&k| k <= MAX_FACTORIAL)
+        .ok_or_else(|| format!("Binomial coefficients are limited to min(k, n - k) <= {}", MAX_FACTORIAL))?;
+
+    let mut result = BigInt::one();
+    for i in 0..k {
+        result = result * (n - i) / (i + 1);
+    }
+    Ok(result)
+}
+
+pub fn mod_inverse(a: &BigInt, m: &BigInt) -> Result<BigInt, String> {
+    if m <= &BigInt::one() {
+        return Err("Modulus must be greater than 1".to_string());
+    }
+    let extended = a.mod_floor(m).extended_gcd(m);
+    if !extended.gcd.is_one() {
+        return Err(format!("{} has no inverse modulo {}", a, m));
+    }
+    Ok(extended.x.mod_floor(m))
+}
+
+pub fn mod_pow(base: &BigInt, exponent: &BigInt, m: &BigInt) -> Result<BigInt, String> {
+    if !m.is_positive() {
+        return Err("Modulus must be positive".to_string());
+    }
+    if m.is_one() {
+        return Ok(BigInt::zero());
+    }
+    let base = if exponent.is_negative() { mod_inverse(base, m)? } else { base.mod_floor(m) };
+    Ok(base.modpow(&exponent.abs(), m))
+}
+
+/// Miller–Rabin with the first 13 primes as witnesses, which is deterministic for
+/// n < 3.3 * 10^24 and a strong probable-prime test beyond that.
+pub fn is_prime(n: &BigUint) -> bool {
+    let two = BigUint::from(2u32);
+    if n < &two {
+        return false;
+    }
+    for &p in SMALL_PRIMES.iter() {
+        if n == &BigUint::from(p) {
+            return true;
+        }
+        if (n % p).is_zero() {
+            return false;
+        }
+    }
+
+    let n_minus_one = n - 1u32;
+    let shift = n_minus_one.trailing_zeros().unwrap_or(0);
+    let d = &n_minus_one >> shift;
+
+    'witness: for &a in SMALL_PRIMES.iter() {
+        let mut x = BigUint::from(a).modpow(&d, n);
+        if x.is_one() || x == n_minus_one {
+            continue;
+        }
+        for _ in 1..shift {
+            x = x.modpow(&two, n);
+            if x == n_minus_one {
+                continue 'witness;
+            }
+        }
+        return false;
+    }
+    true
+}
+
+/// Brent's variant of Pollard's rho; returns a non-trivial factor of the composite `n`.
+fn pollard_rho(n: &BigUint, budget: &mut u64) -> Option<BigUint> {
+    if n.is_even() {
+        return Some(BigUint::from(2u32));
+    }
+
+    for c in 1u32..64 {
+        let c = BigUint::from(c);
+        let step = |x: &BigUint| (x * x + &c) % n;
+        let (mut y, mut r, mut q) = (BigUint::from(2u32), 1u64, BigUint::one());
+        let (mut x, mut ys) = (y.clone(), y.clone());
+        let mut g = BigUint::one();
+
+        while g.is_one() {
+            x = y.clone();
+            for _ in 0..r {
+                y = step(&y);
+            }
+            let mut k = 0;
+            while k < r && g.is_one() {
+                ys = y.clone();
+                let batch = std::cmp::min(128, r - k);
+                for _ in 0..batch {
+                    y = step(&y);
+                    let diff = if x > y { &x - &y } else { &y - &x };
+                    q = q * diff % n;
+                }
+                g = q.gcd(n);
+                k += batch;
+                *budget = budget.checked_sub(batch)?;
+            }
+            r *= 2;
+        }
+
+        if &g == n {
+            // The batch overshot; retrace one step at a time.
+            loop {
+                ys = step(&ys);
+                let diff = if x > ys { &x - &ys } else { &ys - &x };
+                g = diff.gcd(n);
+                if !g.is_one() {
+                    break;
+                }
+            }
+        }
+        if &g != n {
+            return Some(g);
+        }
+    }
+    None
+}
+
+pub fn factorize(n: &BigUint) -> Result<Vec<(BigUint, u32)>, String> {
+    let mut primes = Vec::new();
+    let mut remaining = n.clone();
+
+    for p in 2u32..1000 {
+        let divisor = BigUint::from(p);
+        while (&remaining % p).is_zero() {
+            remaining /= &divisor;
+            primes.push(divisor.clone());
+        }
+    }
+
+    let mut budget = RHO_ITERATION_LIMIT;
+    let mut pending = vec![remaining];
+    while let Some(m) = pending.pop() {
+        if m.is_one() {
+            continue;
+        }
+        if is_prime(&m) {
+            primes.push(m);
+            continue;
+        }
+        let factor = pollard_rho(&m, &mut budget)
+            .ok_or_else(|| "Factorization gave up; the number has very large prime factors".to_string())?;
+        pending.push(&m / &factor);
+        pending.push(factor);
+    }
+
+    primes.sort();
+    let mut factors: Vec<(BigUint, u32)> = Vec::new();
+    for prime in primes {
+        match factors.last_mut() {
+            Some((last, exponent)) if *last == prime => *exponent += 1,
+            _ => factors.push((prime, 1)),
+        }
+    }
+    Ok(factors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exact(source: &str) -> String {
+        evaluate_exact(source).unwrap().to_string()
+    }
+
+    fn factors(source: &str) -> Vec<(String, u32)> {
+        let request = NumberTheoryRequest { expression: source.to_string(), operation: Operation::Factor };
+        run(&request).unwrap().factors.unwrap().into_iter().map(|f| (f.prime, f.exponent)).collect()
+    }
+
+    #[test]
+    fn arithmetic_keeps_every_digit() {
+        assert_eq!(exact("12345678901234567890 * 98765432109876543210"), "1219326311370217952237463801111263526900");
+        assert_eq!(exact("2^64 + 1"), "18446744073709551617");
+        assert_eq!(exact("fact(10)"), "3628800");
+        assert_eq!(exact("binomial(50, 25)"), "126410606437752");
+        assert_eq!(exact("gcd(84, 36, 120)"), "12");
+        assert_eq!(exact("lcm(4, 6, 10)"), "60");
+        assert_eq!(exact("div(-7, 2)"), "-4");
+        assert_eq!(exact("mod(-7, 2)"), "1");
+        assert_eq!(exact("modinv(3, 11)"), "4");
+        assert_eq!(exact("modpow(2, 100, 1000000007)"), "976371285");
+        assert_eq!(exact("12 / 4"), "3");
+    }
+
+    #[test]
+    fn inexact_inputs_are_errors() {
+        let error = |source: &str| evaluate_exact(source).unwrap_err();
+        assert_eq!(error("7 / 2"), "7 is not divisible by 2; use div(a, b) for floor division");
+        assert_eq!(error("1 / 0"), "Division by zero");
+        assert_eq!(error("1.5 + 1"), "'1.5' is not an integer; exact arithmetic only accepts integers");
+        assert_eq!(error("2^-1"), "Negative exponents are not allowed in exact integer arithmetic");
+        assert_eq!(error("modinv(2, 4)"), "2 has no inverse modulo 4");
+        assert_eq!(error("fact(-1)"), "Factorial is only defined for non-negative integers");
+        assert_eq!(error("gcd(4)"), "Function 'gcd' expects at least 2 argument(s), got 1");
+        assert_eq!(error("sin(1)"), "Unknown function 'sin' in exact arithmetic");
+        assert!(error("2^2000000").starts_with("Result would exceed"));
+    }
+
+    #[test]
+    fn primality() {
+        let prime = |source: &str| {
+            let request = NumberTheoryRequest { expression: source.to_string(), operation: Operation::IsPrime };
+            run(&request).unwrap().is_prime.unwrap()
+        };
+        assert!(prime("2"));
+        assert!(prime("2^127 - 1"));
+        assert!(prime("1000000007"));
+        assert!(!prime("1"));
+        assert!(!prime("-7"));
+        // A Carmichael number, which fools the Fermat test.
+        assert!(!prime("561"));
+        assert!(!prime("2^67 - 1"));
+    }
+
+    #[test]
+    fn factorization() {
+        let owned = |pairs: &[(&str, u32)]| pairs.iter().map(|(p, e)| (p.to_string(), *e)).collect::<Vec<_>>();
+        assert_eq!(factors("360"), owned(&[("2", 3), ("3", 2), ("5", 1)]));
+        assert_eq!(factors("-12"), owned(&[("2", 2), ("3", 1)]));
+        assert_eq!(factors("1"), owned(&[]));
+        assert_eq!(factors("600851475143"), owned(&[("71", 1), ("839", 1), ("1471", 1), ("6857", 1)]));
+        assert_eq!(factors("2^64 + 1"), owned(&[("274177", 1), ("67280421310721", 1)]));
+        assert_eq!(factors("2^67 - 1"), owned(&[("193707721", 1), ("761838257287", 1)]));
+        let request = NumberTheoryRequest { expression: "0".to_string(), operation: Operation::Factor };
+        assert_eq!(run(&request).unwrap_err(), "Cannot factor zero");
+    }
+}
@@ -0,0 +1,322 @@
+use lazy_static::lazy_static;
+use schemars::gen::{SchemaGenerator, SchemaSettings};
+use schemars::JsonSchema;
+use serde_json::{json, Map, Value as Json};
+
+use crate::accounts::{AccountsResponse, TokenRequest, UserRequest};
+use crate::editor::{CompleteRequest, CompleteResponse, ParseRequest, ParseResponse};
+use crate::equations::{SolveRequest, SolveResponse};
+use crate::ieee754::{Ieee754Request, Ieee754Response};
+use crate::jsonrpc::RpcResponse;
+use crate::memory::{MemoryRequest, MemoryResponse};
+use crate::number_theory::{NumberTheoryRequest, NumberTheoryResponse};
+use crate::polynomial::{PolynomialRequest, PolynomialResponse};
+use crate::registry::FunctionDefinition;
+use crate::rpn::{RpnRequest, RpnResponse};
+use crate::sequence::{SequenceRequest, SequenceResponse};
+use crate::table::{TableRequest, TableResponse, TruthTableRequest, TruthTableResponse};
+use crate::uncertainty::{UncertaintyRequest, UncertaintyResponse};
+use crate::worksheet::{CellRequest, WorksheetResponse};
+use crate::{CalculationRequest, CalculationResponse, CompileRequest, CompileResponse, FunctionsResponse};
+
+/// The first path segment of the current API version. The same routes without it are
+/// kept for clients written before the API was versioned.
+pub const PREFIX: &str = "v1";
+
+/// A page that renders the document below, served at `/docs`.
+pub const DOCS_PAGE: &str = include_str!("docs.html");
+
+/// Who may call an operation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Access {
+    /// No token needed; for probes and the document itself.
+    Public,
+    /// Anyone with a token, and without one while anonymous access is allowed.
+    Anyone,
+    /// Signed-in users, never the anonymous user.
+    SignedIn,
+    Admin,
+}
+
+/// What an operation is called, takes and returns.
+struct Operation<'a> {
+    id: &'a str,
+    summary: &'a str,
+    request: Option<Json>,
+    response: Json,
+}
+
+struct Document {
+    generator: SchemaGenerator,
+    paths: Map<String, Json>,
+}
+
+impl Document {
+    fn schema<T: JsonSchema>(&mut self) -> Json {
+        serde_json::to_value(self.generator.subschema_for::<T>()).unwrap()
+    }
+
+    fn get<Response: JsonSchema>(&mut self, path: &str, id: &str, summary: &str, access: Access) {
+        let response = self.schema::<Response>();
+        self.add("get", path, access, Operation { id, summary, request: None, response });
+    }
+
+    fn delete<Response: JsonSchema>(&mut self, path: &str, id: &str, summary: &str, access: Access) {
+        let response = self.schema::<Response>();
+        self.add("delete", path, access, Operation { id, summary, request: None, response });
+    }
+
+    fn post<Request: JsonSchema, Response: JsonSchema>(&mut self, path: &str, id: &str, summary: &str, access: Access) {
+        let (request, response) = (Some(self.schema::<Request>()), self.schema::<Response>());
+        self.add("post", path, access, Operation { id, summary, request, response });
+    }
+
+    fn put<Request: JsonSchema, Response: JsonSchema>(&mut self, path: &str, id: &str, summary: &str, access: Access) {
+        let (request, response) = (Some(self.schema::<Request>()), self.schema::<Response>());
+        self.add("put", path, access, Operation { id, summary, request, response });
+    }
+
+    // Adds one operation. `path` is under the current version unless the operation is
+    // public; its `{name}` segments become string path parameters.
+    fn add(&mut self, method: &str, path: &str, access: Access, operation: Operation) {
+        let Operation { id, summary, request, response } = operation;
+        let path = match access {
+            Access::Public => path.to_string(),
+            _ => format!("/{}{}", PREFIX, path),
+        };
+        let tag = path.trim_start_matches(&format!("/{}", PREFIX)).split('/').nth(1).unwrap_or("");
+        let mut operation = json!({
+            "operationId": id,
+            "summary": summary,
+            "tags": [tag],
+            "responses": {
+                "200": {
+                    "description": "The result. Errors in the input are reported in its `error` field.",
+                    "content": { "application/json": { "schema": response } }
+                }
+            }
+        });
+        let parameters: Vec<Json> = path
+            .split('/')
+            .filter_map(|segment| segment.strip_prefix('{')?.strip_suffix('}'))
+            .map(|name| json!({ "name": name, "in": "path", "required": true, "schema": { "type": "string" } }))
+            .collect();
+        if !parameters.is_empty() {
+            operation["parameters"] = json!(parameters);
+        }
+        if let Some(request) = request {
+            operation["requestBody"] = json!({ "required": true, "content": { "application/json": { "schema": request } } });
+            operation["responses"]["413"] = json!({ "description": "The body is over the configured size limit." });
+        }
+        let error = json!({ "content": { "application/json": { "schema": { "$ref": "#/components/schemas/AuthError" } } } });
+        let (security, forbidden) = match access {
+            Access::Public => (json!([]), None),
+            Access::Anyone => (json!([{}, { "bearer": [] }]), None),
+            Access::SignedIn => (json!([{ "bearer": [] }]), Some("The anonymous user cannot do this.")),
+            Access::Admin => (json!([{ "bearer": [] }]), Some("Only administrators can do this.")),
+        };
+        operation["security"] = security;
+        if access != Access::Public {
+            let mut unauthorized = error.clone();
+            unauthorized["description"] = json!("The token is missing, unknown or revoked.");
+            operation["responses"]["401"] = unauthorized;
+        }
+        if let Some(description) = forbidden {
+            let mut forbidden = error;
+            forbidden["description"] = json!(description);
+            operation["responses"]["403"] = forbidden;
+        }
+        self.paths.entry(path).or_insert_with(|| json!({}))[method] = operation;
+    }
+}
+
+fn build() -> Json {
+    let mut document = Document { generator: SchemaSettings::openapi3().into_generator(), paths: Map::new() };
+    let doc = &mut document;
+    use Access::*;
+
+    doc.post::<CalculationRequest, CalculationResponse>("/calculate", "calculate", "Evaluate an expression", Anyone);
+    doc.post::<RpnRequest, RpnResponse>("/rpn", "rpn", "Run RPN keystrokes on a stack", Anyone);
+    doc.post::<CompileRequest, CompileResponse>("/compile", "compile", "Compile an expression for reuse", Anyone);
+    doc.post::<TableRequest, TableResponse>("/table", "table", "Tabulate compiled expressions", Anyone);
+    doc.post::<TruthTableRequest, TruthTableResponse>("/truth-table", "truthTable", "Build a truth table", Anyone);
+    doc.post::<NumberTheoryRequest, NumberTheoryResponse>(
+        "/number-theory",
+        "numberTheory",
+        "Exact big-integer arithmetic and number theory",
+        Anyone,
+    );
+    doc.post::<UncertaintyRequest, UncertaintyResponse>(
+        "/uncertainty",
+        "uncertainty",
+        "Evaluate with uncertainties or intervals",
+        Anyone,
+    );
+    doc.post::<PolynomialRequest, PolynomialResponse>("/polynomial", "polynomial", "Polynomial algebra", Anyone);
+    doc.post::<SequenceRequest, SequenceResponse>("/sequence", "sequence", "Sequences and series", Anyone);
+    doc.post::<SolveRequest, SolveResponse>("/solve", "solve", "Solve a system of equations", Anyone);
+    doc.post::<Ieee754Request, Ieee754Response>("/ieee754", "ieee754", "Inspect floating-point encodings", Anyone);
+    doc.post::<ParseRequest, ParseResponse>("/parse", "parse", "Tokens and typed syntax tree", Anyone);
+    doc.post::<CompleteRequest, CompleteResponse>("/complete", "complete", "Completions at the cursor", Anyone);
+
+    let rpc_request = json!({
+        "description": "A JSON-RPC 2.0 request object, or an array of them for a batch.",
+        "oneOf": [{ "type": "object" }, { "type": "array", "items": { "type": "object" } }]
+    });
+    let rpc_response = doc.schema::<RpcResponse>();
+    let rpc_response = json!({ "oneOf": [rpc_response, { "type": "array", "items": rpc_response }] });
+    let jsonrpc =
+        Operation { id: "jsonrpc", summary: "JSON-RPC 2.0 endpoint", request: Some(rpc_request), response: rpc_response };
+    doc.add("post", "/jsonrpc", Anyone, jsonrpc);
+    doc.paths[&format!("/{}/jsonrpc", PREFIX)]["post"]["responses"]["204"] =
+        json!({ "description": "The request held only notifications." });
+
+    doc.get::<WorksheetResponse>("/worksheets/{sheet}", "getWorksheet", "List a worksheet's cells", Anyone);
+    doc.delete::<WorksheetResponse>("/worksheets/{sheet}", "deleteWorksheet", "Delete a worksheet", Anyone);
+    let response = doc.schema::<WorksheetResponse>();
+    let evaluate = Operation { id: "evaluateWorksheet", summary: "Re-evaluate every cell", request: None, response };
+    doc.add("post", "/worksheets/{sheet}/evaluate", Anyone, evaluate);
+    doc.get::<WorksheetResponse>("/worksheets/{sheet}/cells/{cell}", "getCell", "Read a cell", Anyone);
+    doc.put::<CellRequest, WorksheetResponse>(
+        "/worksheets/{sheet}/cells/{cell}",
+        "setCell",
+        "Set a cell and recompute its dependents",
+        Anyone,
+    );
+    doc.delete::<WorksheetResponse>("/worksheets/{sheet}/cells/{cell}", "deleteCell", "Delete a cell", Anyone);
+
+    doc.get::<MemoryResponse>("/memory/{session}", "listMemory", "List a session's memory slots", Anyone);
+    doc.delete::<MemoryResponse>("/memory/{session}", "clearAllMemory", "Clear every slot (MC)", Anyone);
+    doc.get::<MemoryResponse>("/memory/{session}/{slot}", "recallMemory", "Recall a slot (MR)", Anyone);
+    doc.put::<MemoryRequest, MemoryResponse>("/memory/{session}/{slot}", "storeMemory", "Store into a slot", Anyone);
+    doc.post::<MemoryRequest, MemoryResponse>("/memory/{session}/{slot}/add", "addMemory", "Add to a slot (M+)", Anyone);
+    doc.post::<MemoryRequest, MemoryResponse>(
+        "/memory/{session}/{slot}/subtract",
+        "subtractMemory",
+        "Subtract from a slot (M−)",
+        Anyone,
+    );
+    doc.delete::<MemoryResponse>("/memory/{session}/{slot}", "clearMemory", "Clear a slot", Anyone);
+
+    doc.get::<FunctionsResponse>("/functions", "listFunctions", "List your functions", Anyone);
+    doc.post::<FunctionDefinition, FunctionsResponse>("/functions", "defineFunction", "Define a function", Anyone);
+    doc.delete::<FunctionsResponse>("/functions/{name}", "deleteFunction", "Delete a function", Anyone);
+
+    doc.get::<AccountsResponse>("/users", "listUsers", "List users", Admin);
+    doc.post::<UserRequest, AccountsResponse>("/users", "createUser", "Create a user and their first token", Admin);
+    doc.delete::<AccountsResponse>("/users/{name}", "deleteUser", "Delete a user and revoke their tokens", Admin);
+    doc.get::<AccountsResponse>("/tokens", "listTokens", "List tokens; admins see everyone's", SignedIn);
+    doc.post::<TokenRequest, AccountsResponse>("/tokens", "issueToken", "Issue a token", SignedIn);
+    doc.delete::<AccountsResponse>("/tokens/{id}", "revokeToken", "Revoke a token", SignedIn);
+
+    let status = json!({ "type": "object", "required": ["status"], "properties": { "status": { "type": "string" } } });
+    let health = Operation { id: "health", summary: "Liveness probe", request: None, response: status.clone() };
+    doc.add("get", "/healthz", Public, health);
+    let readiness = Operation { id: "readiness", summary: "Readiness probe", request: None, response: status };
+    doc.add("get", "/readyz", Public, readiness);
+    doc.paths["/readyz"]["get"]["responses"]["503"] = json!({ "description": "The server is shutting down." });
+    let metrics = Operation { id: "metrics", summary: "Prometheus metrics", request: None, response: json!({}) };
+    doc.add("get", "/metrics", Public, metrics);
+    doc.paths["/metrics"]["get"]["responses"]["200"]["content"] =
+        json!({ "text/plain; version=0.0.4": { "schema": { "type": "string" } } });
+
+    let mut schemas: Map<String, Json> =
+        document.generator.definitions().iter().map(|(name, schema)| (name.clone(), json!(schema))).collect();
+    schemas.insert(
+        "AuthError".to_string(),
+        json!({ "type": "object", "required": ["error"], "properties": { "error": { "type": "string" } } }),
+    );
+    let mut document = json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Calculator API",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": format!(
+                "Every operation is under `/{0}/`. The same paths without `/{0}` are kept for older clients \
+                 and will not follow later versions.",
+                PREFIX
+            )
+        },
+        "paths": document.paths,
+        "components": {
+            "schemas": schemas,
+            "securitySchemes": { "bearer": { "type": "http", "scheme": "bearer" } }
+        }
+    });
+    wrap_references(&mut document);
+    document
+}
+
+// OpenAPI 3.0 ignores anything beside a `$ref`, such as the `nullable` of an optional
+// field, so such a reference is moved into an `allOf` that the other keys can sit beside.
+fn wrap_references(value: &mut Json) {
+    match value {
+        Json::Object(map) => {
+            if map.len() > 1 {
+                if let Some(reference) = map.remove("$ref") {
+                    map.insert("allOf".to_string(), json!([{ "$ref": reference }]));
+                }
+            }
+            map.values_mut().for_each(wrap_references);
+        }
+        Json::Array(items) => items.iter_mut().for_each(wrap_references),
+        _ => {}
+    }
+}
+
+lazy_static! {
+    static ref DOCUMENT: Json = build();
+}
+
+/// The OpenAPI 3 description of the API, served at `/openapi.json`.
+pub fn get_document() -> &'static Json {
+    &DOCUMENT
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn references<'a>(value: &'a Json, found: &mut Vec<&'a str>) {
+        match value {
+            Json::Object(map) => {
+                if let Some(Json::String(target)) = map.get("$ref") {
+                    found.push(target);
+                }
+                map.values().for_each(|value| references(value, found));
+            }
+            Json::Array(items) => items.iter().for_each(|value| references(value, found)),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn every_reference_resolves() {
+        let document = get_document();
+        let mut found = Vec::new();
+        references(document, &mut found);
+        assert!(found.len() > 50);
+        for target in found {
+            let name = target.strip_prefix("#/components/schemas/").unwrap_or_else(|| panic!("{}", target));
+            assert!(document["components"]["schemas"].get(name).is_some(), "{} is not defined", target);
+        }
+    }
+
+    #[test]
+    fn operations_are_versioned_and_typed() {
+        let document = get_document();
+        let calculate = &document["paths"]["/v1/calculate"]["post"];
+        assert_eq!(calculate["operationId"], "calculate");
+        assert_eq!(
+            calculate["requestBody"]["content"]["application/json"]["schema"]["$ref"],
+            "#/components/schemas/CalculationRequest"
+        );
+        assert_eq!(document["paths"]["/v1/memory/{session}/{slot}"]["put"]["parameters"][1]["name"], "slot");
+        assert!(document["paths"]["/v1/users"]["get"]["responses"].get("403").is_some());
+        assert!(document["paths"].get("/v1/healthz").is_none() && document["paths"].get("/healthz").is_some());
+        let request = &document["components"]["schemas"]["CalculationRequest"];
+        assert_eq!(request["required"], json!(["expression"]));
+        let result_type = &document["components"]["schemas"]["CalculationResponse"]["properties"]["result_type"];
+        assert_eq!(result_type, &json!({ "nullable": true, "allOf": [{ "$ref": "#/components/schemas/Type" }] }));
+    }
+}
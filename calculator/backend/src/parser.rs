@@ -0,0 +1,589 @@
+use crate::ast::{BinaryOp, Expr, Span, UnaryOp};
+use crate::dates::{self, Unit};
+use crate::i18n::{
+    Error, EMPTY_EXPRESSION, EXPECTED_SEPARATOR, EXPECTED_TOKEN, EXPECTED_TOKEN_FOUND, EXPRESSION_TOO_DEEP,
+    INVALID_DATE_LITERAL, INVALID_DICE, INVALID_NUMBER, UNEXPECTED_CHARACTER, UNEXPECTED_END, UNEXPECTED_TOKEN,
+    UNTERMINATED_TEXT,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
+    Number(f64),
+    Boolean(bool),
+    Dice(f64, f64),
+    Duration(f64),
+    Workdays(f64),
+    Date(i64),
+    Text(String),
+    Ident(String),
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    Caret,
+    PlusMinus,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    EqualEqual,
+    NotEqual,
+    And,
+    Or,
+    Xor,
+    Not,
+    Bang,
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    Comma,
+}
+
+impl TokenKind {
+    fn describe(&self) -> String {
+        match self {
+            TokenKind::Number(value) => value.to_string(),
+            TokenKind::Boolean(value) => value.to_string(),
+            TokenKind::Dice(count, sides) => format!("{}d{}", count, sides),
+            TokenKind::Duration(seconds) => dates::format_duration(*seconds),
+            TokenKind::Workdays(count) => format!("{} workdays", count),
+            TokenKind::Date(days) => dates::format_date(*days as f64),
+            TokenKind::Text(text) => format!("\"{}\"", text),
+            TokenKind::Ident(name) => name.clone(),
+            TokenKind::Plus => "+".to_string(),
+            TokenKind::Minus => "-".to_string(),
+            TokenKind::Star => "*".to_string(),
+            TokenKind::Slash => "/".to_string(),
+            TokenKind::Percent => "%".to_string(),
+            TokenKind::Caret => "^".to_string(),
+            TokenKind::PlusMinus => "±".to_string(),
+            TokenKind::Less => "<".to_string(),
+            TokenKind::LessEqual => "<=".to_string(),
+            TokenKind::Greater => ">".to_string(),
+            TokenKind::GreaterEqual => ">=".to_string(),
+            TokenKind::EqualEqual => "==".to_string(),
+            TokenKind::NotEqual => "!=".to_string(),
+            TokenKind::And => "and".to_string(),
+            TokenKind::Or => "or".to_string(),
+            TokenKind::Xor => "xor".to_string(),
+            TokenKind::Not => "not".to_string(),
+            TokenKind::Bang => "!".to_string(),
+            TokenKind::LParen => "(".to_string(),
+            TokenKind::RParen => ")".to_string(),
+            TokenKind::LBracket => "[".to_string(),
+            TokenKind::RBracket => "]".to_string(),
+            TokenKind::Comma => ",".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+pub fn is_ident_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+pub fn is_ident_continue(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn number(chars: &[char], start: usize) -> Result<(f64, usize), Error> {
+    let mut pos = start;
+    while pos < chars.len() && (chars[pos].is_ascii_digit() || chars[pos] == '.') {
+        pos += 1;
+    }
+    if pos < chars.len() && (chars[pos] == 'e' || chars[pos] == 'E') {
+        let mut lookahead = pos + 1;
+        if lookahead < chars.len() && (chars[lookahead] == '+' || chars[lookahead] == '-') {
+            lookahead += 1;
+        }
+        if lookahead < chars.len() && chars[lookahead].is_ascii_digit() {
+            pos = lookahead;
+            while pos < chars.len() && chars[pos].is_ascii_digit() {
+                pos += 1;
+            }
+        }
+    }
+    let text: String = chars[start..pos].iter().collect();
+    let value = text
+        .parse::<f64>()
+        .map_err(|_| INVALID_NUMBER.with([text.as_str().into(), start.into()]))?;
+    Ok((value, pos))
+}
+
+// `2026-03-01`: four-digit year, two-digit month and day.
+fn date_literal(chars: &[char], start: usize) -> Result<Option<(i64, usize)>, Error> {
+    let shape = "dddd-dd-dd";
+    let matches = shape.chars().enumerate().all(|(i, expected)| match (expected, chars.get(start + i)) {
+        ('d', Some(c)) => c.is_ascii_digit(),
+        ('-', Some(c)) => *c == '-',
+        _ => false,
+    });
+    let end = start + shape.len();
+    if !matches || chars.get(end).is_some_and(|c| c.is_ascii_digit()) {
+        return Ok(None);
+    }
+    let text: String = chars[start..end].iter().collect();
+    let field = |range: std::ops::Range<usize>| text[range].parse::<u32>().unwrap();
+    dates::date(field(0..4) as i64, field(5..7), field(8..10))
+        .map(|days| Some((days, end)))
+        .map_err(|_| INVALID_DATE_LITERAL.with([text.as_str().into(), start.into()]))
+}
+
+// A unit word after a number, either attached (`30m`) or after spaces (`90 days`).
+fn unit_after(chars: &[char], pos: usize) -> Option<(Unit, usize)> {
+    let mut start = pos;
+    while chars.get(start).is_some_and(|c| c.is_whitespace()) {
+        start += 1;
+    }
+    let mut end = start;
+    while chars.get(end).is_some_and(|c| c.is_alphabetic()) {
+        end += 1;
+    }
+    if chars.get(end) == Some(&'_') {
+        return None;
+    }
+    let word: String = chars[start..end].iter().collect();
+    dates::unit(&word, start > pos).map(|unit| (unit, end))
+}
+
+// Reads the unit, if any, following the number that ends at `pos`. Fixed-length parts
+// chain into one duration: `1h30m`, `1d 12h`, `2 hours 15 minutes`.
+fn duration(chars: &[char], value: f64, pos: usize) -> Result<Option<(TokenKind, usize)>, Error> {
+    let Some((unit, mut end)) = unit_after(chars, pos) else { return Ok(None) };
+    let Unit::Seconds(size) = unit else { return Ok(Some((TokenKind::Workdays(value), end))) };
+
+    let mut seconds = value * size;
+    loop {
+        let mut next = end;
+        while chars.get(next).is_some_and(|c| c.is_whitespace()) {
+            next += 1;
+        }
+        if !chars.get(next).is_some_and(|c| c.is_ascii_digit()) {
+            break;
+        }
+        let (part, after) = number(chars, next)?;
+        match unit_after(chars, after) {
+            Some((Unit::Seconds(size), unit_end)) => {
+                seconds += part * size;
+                end = unit_end;
+            }
+            _ => break,
+        }
+    }
+    Ok(Some((TokenKind::Duration(seconds), end)))
+}
+
+pub fn tokenize(input: &str) -> Result<Vec<Token>, Error> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+
+    while pos < chars.len() {
+        let c = chars[pos];
+        if c.is_whitespace() {
+            pos += 1;
+            continue;
+        }
+
+        let start = pos;
+        if c.is_ascii_digit() || (c == '.' && chars.get(pos + 1).is_some_and(|n| n.is_ascii_digit())) {
+            if let Some((days, end)) = date_literal(&chars, start)? {
+                tokens.push(Token { kind: TokenKind::Date(days), span: Span::new(start, end) });
+                pos = end;
+                continue;
+            }
+
+            let (value, end) = number(&chars, start)?;
+            pos = end;
+            let text: String = chars[start..pos].iter().collect();
+
+            // Dice notation: `3d6` rolls three six-sided dice.
+            let is_integer = text.chars().all(|c| c.is_ascii_digit());
+            if is_integer && chars.get(pos) == Some(&'d') && chars.get(pos + 1).is_some_and(|c| c.is_ascii_digit()) {
+                let sides_start = pos + 1;
+                pos = sides_start;
+                while pos < chars.len() && chars[pos].is_ascii_digit() {
+                    pos += 1;
+                }
+                let sides: String = chars[sides_start..pos].iter().collect();
+                let sides = sides.parse::<f64>().map_err(|_| INVALID_DICE.with([start.into()]))?;
+                tokens.push(Token { kind: TokenKind::Dice(value, sides), span: Span::new(start, pos) });
+                continue;
+            }
+
+            let kind = match duration(&chars, value, pos)? {
+                Some((kind, end)) => {
+                    pos = end;
+                    kind
+                }
+                None => TokenKind::Number(value),
+            };
+            tokens.push(Token { kind, span: Span::new(start, pos) });
+            continue;
+        }
+
+        if c == '"' {
+            let end = chars[start + 1..]
+                .iter()
+                .position(|&c| c == '"')
+                .map(|offset| start + 1 + offset)
+                .ok_or_else(|| UNTERMINATED_TEXT.with([start.into()]))?;
+            let text: String = chars[start + 1..end].iter().collect();
+            pos = end + 1;
+            tokens.push(Token { kind: TokenKind::Text(text), span: Span::new(start, pos) });
+            continue;
+        }
+
+        if is_ident_start(c) {
+            while pos < chars.len() && is_ident_continue(chars[pos]) {
+                pos += 1;
+            }
+            let name: String = chars[start..pos].iter().collect();
+            let kind = match name.as_str() {
+                "and" => TokenKind::And,
+                "or" => TokenKind::Or,
+                "xor" => TokenKind::Xor,
+                "not" => TokenKind::Not,
+                "true" => TokenKind::Boolean(true),
+                "false" => TokenKind::Boolean(false),
+                _ => TokenKind::Ident(name),
+            };
+            tokens.push(Token { kind, span: Span::new(start, pos) });
+            continue;
+        }
+
+        let next = chars.get(pos + 1).copied();
+        let kind = match c {
+            '<' if next == Some('=') => {
+                pos += 1;
+                TokenKind::LessEqual
+            }
+            '>' if next == Some('=') => {
+                pos += 1;
+                TokenKind::GreaterEqual
+            }
+            '=' if next == Some('=') => {
+                pos += 1;
+                TokenKind::EqualEqual
+            }
+            '!' if next == Some('=') => {
+                pos += 1;
+                TokenKind::NotEqual
+            }
+            '&' if next == Some('&') => {
+                pos += 1;
+                TokenKind::And
+            }
+            '|' if next == Some('|') => {
+                pos += 1;
+                TokenKind::Or
+            }
+            '<' => TokenKind::Less,
+            '>' => TokenKind::Greater,
+            '≤' => TokenKind::LessEqual,
+            '≥' => TokenKind::GreaterEqual,
+            '≠' => TokenKind::NotEqual,
+            '+' if chars.get(pos + 1) == Some(&'/') && chars.get(pos + 2) == Some(&'-') => {
+                pos += 2;
+                TokenKind::PlusMinus
+            }
+            '±' => TokenKind::PlusMinus,
+            '+' => TokenKind::Plus,
+            '-' | '−' => TokenKind::Minus,
+            '*' if chars.get(pos + 1) == Some(&'*') => {
+                pos += 1;
+                TokenKind::Caret
+            }
+            '*' | '×' => TokenKind::Star,
+            '/' | '÷' => TokenKind::Slash,
+            '%' => TokenKind::Percent,
+            '^' => TokenKind::Caret,
+            '!' => TokenKind::Bang,
+            '(' => TokenKind::LParen,
+            ')' => TokenKind::RParen,
+            '[' => TokenKind::LBracket,
+            ']' => TokenKind::RBracket,
+            ',' => TokenKind::Comma,
+            _ => return Err(UNEXPECTED_CHARACTER.with([c.into(), start.into()])),
+        };
+        pos += 1;
+        tokens.push(Token { kind, span: Span::new(start, pos) });
+    }
+
+    Ok(tokens)
+}
+
+pub fn parse(input: &str) -> Result<Expr, Error> {
+    let tokens = tokenize(input)?;
+    if tokens.is_empty() {
+        return Err(EMPTY_EXPRESSION.error());
+    }
+
+    let mut parser = Parser { tokens, pos: 0, depth: 0 };
+    let (expr, _) = parser.nested(0)?;
+    if let Some(token) = parser.peek() {
+        return Err(UNEXPECTED_TOKEN.with([token.kind.describe().into(), token.span.start.into()]));
+    }
+    Ok(expr)
+}
+
+/// Whether `name` reads back as a single identifier, so it can name a variable or function.
+pub fn is_identifier(name: &str) -> bool {
+    matches!(parse(name), Ok(Expr::Variable { name: parsed, .. }) if parsed == name)
+}
+
+/// How deeply operators, calls and brackets may nest. Evaluation walks the tree
+/// recursively, so this bounds the stack an expression can use.
+pub const MAX_DEPTH: usize = 256;
+
+const NOT_BP: u8 = 7;
+const PREFIX_BP: u8 = 17;
+const POSTFIX_BP: u8 = 21;
+
+fn infix_binding_power(kind: &TokenKind) -> Option<(BinaryOp, u8, u8)> {
+    match kind {
+        TokenKind::Or => Some((BinaryOp::Or, 1, 2)),
+        TokenKind::Xor => Some((BinaryOp::Xor, 3, 4)),
+        TokenKind::And => Some((BinaryOp::And, 5, 6)),
+        TokenKind::Less => Some((BinaryOp::Less, 9, 10)),
+        TokenKind::LessEqual => Some((BinaryOp::LessEqual, 9, 10)),
+        TokenKind::Greater => Some((BinaryOp::Greater, 9, 10)),
+        TokenKind::GreaterEqual => Some((BinaryOp::GreaterEqual, 9, 10)),
+        TokenKind::EqualEqual => Some((BinaryOp::Equal, 9, 10)),
+        TokenKind::NotEqual => Some((BinaryOp::NotEqual, 9, 10)),
+        TokenKind::Plus => Some((BinaryOp::Add, 11, 12)),
+        TokenKind::Minus => Some((BinaryOp::Sub, 11, 12)),
+        TokenKind::PlusMinus => Some((BinaryOp::PlusMinus, 13, 14)),
+        TokenKind::Star => Some((BinaryOp::Mul, 15, 16)),
+        TokenKind::Slash => Some((BinaryOp::Div, 15, 16)),
+        TokenKind::Percent => Some((BinaryOp::Rem, 15, 16)),
+        TokenKind::Caret => Some((BinaryOp::Pow, 19, 18)),
+        // `2x` and `3(x + 1)` are read as implicit multiplication.
+        TokenKind::Ident(_) | TokenKind::LParen => Some((BinaryOp::Mul, 15, 16)),
+        _ => None,
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn end_position(&self) -> usize {
+        self.tokens.last().map(|t| t.span.end).unwrap_or(0)
+    }
+
+    fn expect(&mut self, kind: TokenKind) -> Result<Token, Error> {
+        match self.next() {
+            Some(token) if token.kind == kind => Ok(token),
+            Some(token) => Err(EXPECTED_TOKEN_FOUND.with([
+                kind.describe().into(),
+                token.span.start.into(),
+                token.kind.describe().into(),
+            ])),
+            None => Err(EXPECTED_TOKEN.with([kind.describe().into(), self.end_position().into()])),
+        }
+    }
+
+    // Nesting counts twice: brackets make the parser recurse without adding to the tree,
+    // and chains such as `1+1+1` deepen the tree without making the parser recurse.
+    fn too_deep(&self, position: usize) -> Error {
+        EXPRESSION_TOO_DEEP.with([MAX_DEPTH.into(), position.into()])
+    }
+
+    fn grown(&self, height: usize, position: usize) -> Result<usize, Error> {
+        match height + 1 {
+            height if height > MAX_DEPTH => Err(self.too_deep(position)),
+            height => Ok(height),
+        }
+    }
+
+    /// Parses an expression and gives its height as a tree.
+    fn nested(&mut self, min_bp: u8) -> Result<(Expr, usize), Error> {
+        if self.depth == MAX_DEPTH {
+            return Err(self.too_deep(self.peek().map_or(self.end_position(), |token| token.span.start)));
+        }
+        self.depth += 1;
+        let parsed = self.operators(min_bp);
+        self.depth -= 1;
+        parsed
+    }
+
+    fn operators(&mut self, min_bp: u8) -> Result<(Expr, usize), Error> {
+        let (mut lhs, mut height) = self.prefix()?;
+
+        while let Some(token) = self.peek() {
+            if token.kind == TokenKind::Bang {
+                if POSTFIX_BP < min_bp {
+                    break;
+                }
+                let span = lhs.span().to(token.span);
+                height = self.grown(height, token.span.start)?;
+                self.pos += 1;
+                lhs = Expr::Unary { op: UnaryOp::Factorial, operand: Box::new(lhs), span };
+                continue;
+            }
+
+            let Some((op, left_bp, right_bp)) = infix_binding_power(&token.kind) else { break };
+            if left_bp < min_bp {
+                break;
+            }
+            let position = token.span.start;
+            let implicit = matches!(token.kind, TokenKind::Ident(_) | TokenKind::LParen);
+            if !implicit {
+                self.pos += 1;
+            }
+
+            let (rhs, rhs_height) = self.nested(right_bp)?;
+            height = self.grown(height.max(rhs_height), position)?;
+            let span = lhs.span().to(rhs.span());
+            lhs = Expr::Binary { op, left: Box::new(lhs), right: Box::new(rhs), span };
+        }
+
+        Ok((lhs, height))
+    }
+
+    // Only the arms that recurse live here; the rest are in `leaf`, which keeps this
+    // frame, repeated once per nesting level, small.
+    fn prefix(&mut self) -> Result<(Expr, usize), Error> {
+        let end = self.end_position();
+        let token = self.next().ok_or_else(|| UNEXPECTED_END.with([end.into()]))?;
+        let position = token.span.start;
+
+        match token.kind {
+            TokenKind::Ident(_) if self.peek().is_some_and(|t| t.kind == TokenKind::LParen) => {
+                let TokenKind::Ident(name) = token.kind else { unreachable!() };
+                self.pos += 1;
+                let (args, height) = self.arguments()?;
+                let span = token.span.to(self.tokens[self.pos - 1].span);
+                Ok((Expr::Call { name, args, span }, self.grown(height, position)?))
+            }
+            TokenKind::Not | TokenKind::Bang | TokenKind::Minus | TokenKind::Plus => {
+                let (op, min_bp) = match token.kind {
+                    TokenKind::Minus => (UnaryOp::Neg, PREFIX_BP),
+                    TokenKind::Plus => (UnaryOp::Plus, PREFIX_BP),
+                    _ => (UnaryOp::Not, NOT_BP),
+                };
+                let (operand, height) = self.nested(min_bp)?;
+                let span = token.span.to(operand.span());
+                Ok((Expr::Unary { op, operand: Box::new(operand), span }, self.grown(height, position)?))
+            }
+            TokenKind::LParen => {
+                let inner = self.nested(0)?;
+                self.expect(TokenKind::RParen)?;
+                Ok(inner)
+            }
+            TokenKind::LBracket => {
+                let (low, low_height) = self.nested(0)?;
+                self.expect(TokenKind::Comma)?;
+                let (high, high_height) = self.nested(0)?;
+                let span = token.span.to(self.expect(TokenKind::RBracket)?.span);
+                let expr = Expr::Interval { low: Box::new(low), high: Box::new(high), span };
+                Ok((expr, self.grown(low_height.max(high_height), position)?))
+            }
+            _ => leaf(token),
+        }
+    }
+
+    /// The arguments of a call after its `(`, and the height of the tallest.
+    fn arguments(&mut self) -> Result<(Vec<Expr>, usize), Error> {
+        let mut args = Vec::new();
+        let mut height = 0;
+        if self.peek().is_some_and(|t| t.kind == TokenKind::RParen) {
+            self.pos += 1;
+            return Ok((args, height));
+        }
+
+        loop {
+            let (arg, arg_height) = self.nested(0)?;
+            args.push(arg);
+            height = height.max(arg_height);
+            match self.next() {
+                Some(Token { kind: TokenKind::Comma, .. }) => continue,
+                Some(Token { kind: TokenKind::RParen, .. }) => break,
+                Some(token) => {
+                    return Err(EXPECTED_SEPARATOR.with([token.span.start.into(), token.kind.describe().into()]))
+                }
+                None => return Err(EXPECTED_TOKEN.with([")".into(), self.end_position().into()])),
+            }
+        }
+
+        Ok((args, height))
+    }
+}
+
+/// A token that is an expression by itself, and its height.
+fn leaf(token: Token) -> Result<(Expr, usize), Error> {
+    let span = token.span;
+    let expr = match token.kind {
+        TokenKind::Number(value) => Expr::Number { value, span },
+        TokenKind::Boolean(value) => Expr::Boolean { value, span },
+        TokenKind::Duration(seconds) => Expr::Duration { seconds, span },
+        TokenKind::Workdays(count) => Expr::Workdays { count, span },
+        TokenKind::Date(days) => Expr::Date { days, span },
+        TokenKind::Text(value) => Expr::Text { value, span },
+        TokenKind::Ident(name) => Expr::Variable { name, span },
+        TokenKind::Dice(count, sides) => {
+            let args = vec![Expr::Number { value: count, span }, Expr::Number { value: sides, span }];
+            return Ok((Expr::Call { name: "dice".to_string(), args, span }, 2));
+        }
+        kind => return Err(UNEXPECTED_TOKEN.with([kind.describe().into(), span.start.into()])),
+    };
+    Ok((expr, 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nesting_is_limited() {
+        let nested = |depth: usize| format!("{}1{}", "(".repeat(depth), ")".repeat(depth));
+        // The deepest expressions allowed still compile and evaluate.
+        let deepest = format!("{}1{}", "abs(-".repeat(MAX_DEPTH / 2 - 1), ")".repeat(MAX_DEPTH / 2 - 1));
+        let compiled = crate::compile::CompiledExpression::compile(&parse(&deepest).unwrap(), &[]).unwrap();
+        assert_eq!(compiled.evaluate(&[]).unwrap().as_f64(), 1.0);
+        assert!(parse(&nested(MAX_DEPTH - 1)).is_ok());
+        assert_eq!(
+            parse(&nested(MAX_DEPTH)).unwrap_err().to_string(),
+            format!("Expression nests more than {} levels deep at position {}", MAX_DEPTH, MAX_DEPTH)
+        );
+        for prefix in ["-", "2^", "f("] {
+            let error = parse(&format!("{}1", prefix.repeat(5000))).unwrap_err();
+            assert!(error.to_string().starts_with("Expression nests more than"));
+        }
+    }
+
+    #[test]
+    fn long_chains_count_as_nesting() {
+        let sum = |terms: usize| format!("{}1", "1+".repeat(terms - 1));
+        let ast = parse(&sum(MAX_DEPTH)).unwrap();
+        let compiled = crate::compile::CompiledExpression::compile(&ast, &[]).unwrap();
+        assert_eq!(compiled.evaluate(&[]).unwrap().as_f64(), MAX_DEPTH as f64);
+        assert_eq!(
+            parse(&sum(MAX_DEPTH + 1)).unwrap_err().to_string(),
+            format!("Expression nests more than {} levels deep at position {}", MAX_DEPTH, 2 * MAX_DEPTH - 1)
+        );
+        assert!(parse(&format!("{}1", "1+".repeat(4999))).is_err());
+        assert!(parse(&format!("max({})", vec!["1"; 1000].join(", "))).is_ok());
+    }
+}
@@ -0,0 +1,934 @@
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::{One, Signed, ToPrimitive, Zero};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::ops::{Add, Mul, Sub};
+
+use crate::ast::{BinaryOp, Expr, UnaryOp};
+use crate::functions;
+use crate::i18n::{BOOLEANS_UNSUPPORTED, DATES_UNSUPPORTED, TEXT_UNSUPPORTED, UNCERTAIN_VALUES_UNSUPPORTED};
+use crate::number_theory::factorize;
+use crate::parser;
+use crate::rational::Q;
+
+pub const MAX_DEGREE: usize = 500;
+const MAX_DIVISORS: usize = 10_000;
+const MAX_KRONECKER_CANDIDATES: usize = 20_000;
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    #[default]
+    Expand,
+    Factor,
+    Divide,
+    Gcd,
+    PartialFractions,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Domain {
+    #[default]
+    Integers,
+    Rationals,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct PolynomialRequest {
+    pub expression: String,
+    /// The divisor for `divide`, the second polynomial for `gcd`.
+    pub other: Option<String>,
+    #[serde(default)]
+    pub operation: Operation,
+    #[serde(default)]
+    pub domain: Domain,
+    pub variable: Option<String>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct PolynomialFactor {
+    pub factor: String,
+    pub multiplicity: u32,
+}
+
+#[derive(Debug, Serialize, Default, JsonSchema)]
+pub struct PolynomialResponse {
+    pub result: Option<String>,
+    pub variable: Option<String>,
+    pub quotient: Option<String>,
+    pub remainder: Option<String>,
+    pub constant: Option<String>,
+    pub factors: Option<Vec<PolynomialFactor>>,
+    pub terms: Option<Vec<String>>,
+    /// False when a factor was too large to split within the search limits.
+    pub complete: Option<bool>,
+    pub error: Option<String>,
+}
+
+/// A univariate polynomial with rational coefficients, lowest degree first and no
+/// trailing zeros (the zero polynomial has no coefficients).
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Poly(Vec<Q>);
+
+impl Poly {
+    fn new(mut coefficients: Vec<Q>) -> Self {
+        while coefficients.last().is_some_and(Q::is_zero) {
+            coefficients.pop();
+        }
+        Poly(coefficients)
+    }
+
+    fn constant(value: Q) -> Self {
+        Poly::new(vec![value])
+    }
+
+    fn x() -> Self {
+        Poly(vec![Q::zero(), Q::one()])
+    }
+
+    fn is_zero(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn degree(&self) -> usize {
+        self.0.len().saturating_sub(1)
+    }
+
+    fn leading(&self) -> Q {
+        self.0.last().cloned().unwrap_or_else(Q::zero)
+    }
+
+    fn scale(&self, factor: &Q) -> Poly {
+        Poly::new(self.0.iter().map(|c| c * factor).collect())
+    }
+
+    fn monic(&self) -> Poly {
+        if self.is_zero() {
+            return self.clone();
+        }
+        self.scale(&self.leading().recip())
+    }
+
+    fn derivative(&self) -> Poly {
+        Poly::new(self.0.iter().enumerate().skip(1).map(|(i, c)| c * &Q::int(i)).collect())
+    }
+
+    fn div_rem(&self, divisor: &Poly) -> (Poly, Poly) {
+        let mut remainder = self.0.clone();
+        if self.degree() < divisor.degree() || self.is_zero() {
+            return (Poly(Vec::new()), self.clone());
+        }
+        let lead = divisor.leading().recip();
+        let mut quotient = vec![Q::zero(); self.degree() - divisor.degree() + 1];
+        for i in (0..quotient.len()).rev() {
+            let coefficient = &remainder[i + divisor.degree()] * &lead;
+            if coefficient.is_zero() {
+                continue;
+            }
+            for (j, d) in divisor.0.iter().enumerate() {
+                remainder[i + j] = &remainder[i + j] - &(&coefficient * d);
+            }
+            quotient[i] = coefficient;
+        }
+        (Poly::new(quotient), Poly::new(remainder))
+    }
+
+    fn gcd(&self, other: &Poly) -> Poly {
+        let (mut a, mut b) = (self.clone(), other.clone());
+        while !b.is_zero() {
+            let (_, r) = a.div_rem(&b);
+            a = b;
+            b = r;
+        }
+        a.monic()
+    }
+
+    /// Returns `(g, s, t)` with `s·a + t·b = g`, `g` monic.
+    fn extended_gcd(a: &Poly, b: &Poly) -> (Poly, Poly, Poly) {
+        let (mut r0, mut r1) = (a.clone(), b.clone());
+        let (mut s0, mut s1) = (Poly::constant(Q::one()), Poly(Vec::new()));
+        let (mut t0, mut t1) = (Poly(Vec::new()), Poly::constant(Q::one()));
+        while !r1.is_zero() {
+            let (q, r) = r0.div_rem(&r1);
+            let s = &s0 - &(&q * &s1);
+            let t = &t0 - &(&q * &t1);
+            (r0, r1) = (r1, r);
+            (s0, s1) = (s1, s);
+            (t0, t1) = (t1, t);
+        }
+        let lead = r0.leading().recip();
+        (r0.scale(&lead), s0.scale(&lead), t0.scale(&lead))
+    }
+
+    fn pow(&self, exponent: usize) -> Poly {
+        (0..exponent).fold(Poly::constant(Q::one()), |acc, _| &acc * self)
+    }
+
+    /// Splits into a rational content and a primitive integer polynomial with a positive
+    /// leading coefficient.
+    fn content_and_primitive(&self) -> (Q, Poly) {
+        if self.is_zero() {
+            return (Q::zero(), self.clone());
+        }
+        let denominator = self.0.iter().fold(BigInt::one(), |acc, c| acc.lcm(&c.den));
+        let numerator = self
+            .0
+            .iter()
+            .fold(BigInt::zero(), |acc, c| acc.gcd(&(&c.num * (&denominator / &c.den))));
+        let mut content = Q::new(numerator, denominator);
+        if self.leading().is_negative() {
+            content = -&content;
+        }
+        (content.clone(), self.scale(&content.recip()))
+    }
+
+    fn integer_coefficients(&self) -> Vec<BigInt> {
+        self.0.iter().map(|c| c.num.clone()).collect()
+    }
+
+    fn evaluate_integer(&self, x: &BigInt) -> BigInt {
+        self.integer_coefficients().iter().rev().fold(BigInt::zero(), |acc, c| acc * x + c)
+    }
+
+    fn is_monomial_x(&self) -> bool {
+        self.0.len() == 2 && self.0[0].is_zero() && self.0[1] == Q::one()
+    }
+
+    fn format(&self, variable: &str) -> String {
+        if self.is_zero() {
+            return "0".to_string();
+        }
+        let mut text = String::new();
+        for (power, coefficient) in self.0.iter().enumerate().rev() {
+            if coefficient.is_zero() {
+                continue;
+            }
+            let magnitude = coefficient.abs();
+            let body = match power {
+                0 => magnitude.to_string(),
+                _ => {
+                    let monomial = if power == 1 { variable.to_string() } else { format!("{}^{}", variable, power) };
+                    if magnitude == Q::one() {
+                        monomial
+                    } else if magnitude.is_integer() {
+                        format!("{}{}", magnitude, monomial)
+                    } else {
+                        format!("({}){}", magnitude, monomial)
+                    }
+                }
+            };
+            match (text.is_empty(), coefficient.is_negative()) {
+                (true, false) => text.push_str(&body),
+                (true, true) => text.push_str(&format!("-{}", body)),
+                (false, false) => text.push_str(&format!(" + {}", body)),
+                (false, true) => text.push_str(&format!(" - {}", body)),
+            }
+        }
+        text
+    }
+
+    /// `x`, `x^2`, `(x - 1)` or `(x - 1)^2`, ready to be juxtaposed with other factors.
+    fn format_factor(&self, variable: &str, multiplicity: u32) -> String {
+        let base = if self.is_monomial_x() { variable.to_string() } else { format!("({})", self.format(variable)) };
+        if multiplicity == 1 {
+            base
+        } else {
+            format!("{}^{}", base, multiplicity)
+        }
+    }
+}
+
+impl Add for &Poly {
+    type Output = Poly;
+    fn add(self, other: &Poly) -> Poly {
+        let length = self.0.len().max(other.0.len());
+        let zero = Q::zero();
+        Poly::new(
+            (0..length)
+                .map(|i| self.0.get(i).unwrap_or(&zero) + other.0.get(i).unwrap_or(&zero))
+                .collect(),
+        )
+    }
+}
+
+impl Sub for &Poly {
+    type Output = Poly;
+    fn sub(self, other: &Poly) -> Poly {
+        self + &other.scale(&Q::int(-1))
+    }
+}
+
+impl Mul for &Poly {
+    type Output = Poly;
+    fn mul(self, other: &Poly) -> Poly {
+        if self.is_zero() || other.is_zero() {
+            return Poly(Vec::new());
+        }
+        let mut product = vec![Q::zero(); self.0.len() + other.0.len() - 1];
+        for (i, a) in self.0.iter().enumerate() {
+            for (j, b) in other.0.iter().enumerate() {
+                product[i + j] = &product[i + j] + &(a * b);
+            }
+        }
+        Poly::new(product)
+    }
+}
+
+/// A quotient of polynomials kept in lowest terms with a monic denominator.
+#[derive(Debug, Clone)]
+struct Fraction {
+    numerator: Poly,
+    denominator: Poly,
+}
+
+impl Fraction {
+    fn polynomial(numerator: Poly) -> Self {
+        Fraction { numerator, denominator: Poly::constant(Q::one()) }
+    }
+
+    fn new(numerator: Poly, denominator: Poly) -> Result<Self, String> {
+        if denominator.is_zero() {
+            return Err("Division by zero".to_string());
+        }
+        let g = numerator.gcd(&denominator);
+        let g = if g.is_zero() { Poly::constant(Q::one()) } else { g };
+        let (numerator, _) = numerator.div_rem(&g);
+        let (denominator, _) = denominator.div_rem(&g);
+        let lead = denominator.leading().recip();
+        let fraction = Fraction { numerator: numerator.scale(&lead), denominator: denominator.scale(&lead) };
+        if fraction.numerator.degree().max(fraction.denominator.degree()) > MAX_DEGREE {
+            return Err(format!("Polynomials are limited to degree {}", MAX_DEGREE));
+        }
+        Ok(fraction)
+    }
+
+    fn is_polynomial(&self) -> bool {
+        self.denominator.degree() == 0
+    }
+
+    fn into_polynomial(self) -> Result<Poly, String> {
+        if !self.is_polynomial() {
+            return Err("Expected a polynomial, found a rational function".to_string());
+        }
+        Ok(self.numerator.scale(&self.denominator.leading().recip()))
+    }
+}
+
+struct Converter<'a> {
+    source: &'a [char],
+    variable: Option<String>,
+}
+
+impl Converter<'_> {
+    fn convert(&mut self, expr: &Expr) -> Result<Fraction, String> {
+        match expr {
+            Expr::Number { span, .. } => {
+                let text: String = self.source[span.start..span.end].iter().collect();
+                let value = Q::parse(&text).ok_or_else(|| format!("'{}' is not an exact number", text))?;
+                Ok(Fraction::polynomial(Poly::constant(value)))
+            }
+            Expr::Variable { name, .. } => {
+                if functions::constant(name).is_some() {
+                    return Err(format!("Coefficients must be rational; '{}' is irrational", name));
+                }
+                match &self.variable {
+                    Some(variable) if variable != name => Err(format!(
+                        "Polynomials in more than one variable are not supported ('{}' and '{}')",
+                        variable, name
+                    )),
+                    _ => {
+                        self.variable = Some(name.clone());
+                        Ok(Fraction::polynomial(Poly::x()))
+                    }
+                }
+            }
+            Expr::Unary { op: UnaryOp::Neg, operand, .. } => {
+                let value = self.convert(operand)?;
+                Ok(Fraction { numerator: value.numerator.scale(&Q::int(-1)), ..value })
+            }
+            Expr::Unary { op: UnaryOp::Plus, operand, .. } => self.convert(operand),
+            Expr::Binary { op, left, right, .. } => {
+                let a = self.convert(left)?;
+                match op {
+                    BinaryOp::Pow => {
+                        let exponent = self.exponent(right)?;
+                        let power = exponent.unsigned_abs() as usize;
+                        // Checked before raising, which would do all the work of a
+                        // result too large to keep.
+                        if a.numerator.degree().max(a.denominator.degree()) * power > MAX_DEGREE {
+                            return Err(format!("Polynomials are limited to degree {}", MAX_DEGREE));
+                        }
+                        let numerator = a.numerator.pow(power);
+                        let denominator = a.denominator.pow(power);
+                        if exponent >= 0 {
+                            Fraction::new(numerator, denominator)
+                        } else {
+                            Fraction::new(denominator, numerator)
+                        }
+                    }
+                    BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div => {
+                        let b = self.convert(right)?;
+                        let (an, ad, bn, bd) = (&a.numerator, &a.denominator, &b.numerator, &b.denominator);
+                        match op {
+                            BinaryOp::Add => Fraction::new(&(an * bd) + &(bn * ad), ad * bd),
+                            BinaryOp::Sub => Fraction::new(&(an * bd) - &(bn * ad), ad * bd),
+                            BinaryOp::Mul => Fraction::new(an * bn, ad * bd),
+                            _ => Fraction::new(an * bd, ad * bn),
+                        }
+                    }
+                    BinaryOp::PlusMinus => Err(UNCERTAIN_VALUES_UNSUPPORTED.error().to_string()),
+                    BinaryOp::Rem => Err("'%' is not defined for polynomials".to_string()),
+                    _ => Err(BOOLEANS_UNSUPPORTED.error().to_string()),
+                }
+            }
+            Expr::Unary { op: UnaryOp::Factorial, .. } => Err("'!' is not defined for polynomials".to_string()),
+            Expr::Boolean { .. } | Expr::Unary { op: UnaryOp::Not, .. } => {
+                Err(BOOLEANS_UNSUPPORTED.error().to_string())
+            }
+            Expr::Duration { .. } | Expr::Workdays { .. } | Expr::Date { .. } => {
+                Err(DATES_UNSUPPORTED.error().to_string())
+            }
+            Expr::Text { .. } => Err(TEXT_UNSUPPORTED.error().to_string()),
+            Expr::Interval { .. } => Err(UNCERTAIN_VALUES_UNSUPPORTED.error().to_string()),
+            Expr::Call { name, .. } => Err(format!("Function '{}' is not allowed in a polynomial", name)),
+        }
+    }
+
+    fn exponent(&mut self, expr: &Expr) -> Result<i64, String> {
+        let value = self.convert(expr)?.into_polynomial()?;
+        let error = || "Exponents must be integer constants".to_string();
+        if value.degree() > 0 {
+            return Err(error());
+        }
+        let exponent = value.0.first().cloned().unwrap_or_else(Q::zero);
+        if !exponent.is_integer() {
+            return Err(error());
+        }
+        match exponent.num.to_i64() {
+            Some(e) if e.unsigned_abs() as usize <= MAX_DEGREE => Ok(e),
+            _ => Err(format!("Polynomials are limited to degree {}", MAX_DEGREE)),
+        }
+    }
+}
+
+fn convert(source: &str, variable: &mut Option<String>) -> Result<Fraction, String> {
+    let expr = parser::parse(source)?;
+    let chars: Vec<char> = source.chars().collect();
+    let mut converter = Converter { source: &chars, variable: variable.take() };
+    let result = converter.convert(&expr);
+    *variable = converter.variable;
+    result
+}
+
+fn divisors(n: &BigInt) -> Option<Vec<BigInt>> {
+    let n = n.abs();
+    if n.is_zero() {
+        return None;
+    }
+    let factors = factorize(&n.to_biguint()?).ok()?;
+    let count: usize = factors.iter().map(|(_, e)| *e as usize + 1).product();
+    if count > MAX_DIVISORS {
+        return None;
+    }
+    let mut divisors = vec![BigInt::one()];
+    for (prime, exponent) in factors {
+        let prime = BigInt::from(prime);
+        let mut next = Vec::with_capacity(divisors.len() * (exponent as usize + 1));
+        for d in &divisors {
+            let mut power = d.clone();
+            for _ in 0..=exponent {
+                next.push(power.clone());
+                power *= &prime;
+            }
+        }
+        divisors = next;
+    }
+    divisors.sort();
+    Some(divisors)
+}
+
+/// Square-free decomposition (Yun): monic `s_i` with `f = lc · Π s_i^i`.
+fn square_free(f: &Poly) -> Vec<(Poly, u32)> {
+    let mut parts = Vec::new();
+    let derivative = f.derivative();
+    let c = f.gcd(&derivative);
+    let (mut w, _) = f.div_rem(&c);
+    let (mut y, _) = derivative.div_rem(&c);
+    let mut z = &y - &w.derivative();
+    let mut i = 1;
+    while w.degree() > 0 {
+        let g = w.gcd(&z);
+        (w, _) = w.div_rem(&g);
+        (y, _) = z.div_rem(&g);
+        z = &y - &w.derivative();
+        if g.degree() > 0 {
+            parts.push((g, i));
+        }
+        i += 1;
+    }
+    parts
+}
+
+struct Factorizer {
+    complete: bool,
+}
+
+impl Factorizer {
+    /// Splits a primitive, square-free integer polynomial with a nonzero constant term
+    /// into irreducible factors: rational roots first, then Kronecker's method for
+    /// higher-degree factors.
+    fn irreducible_factors(&mut self, f: Poly) -> Vec<Poly> {
+        let mut factors = Vec::new();
+        let mut rest = f;
+
+        if rest.degree() >= 1 {
+            let coefficients = rest.integer_coefficients();
+            match (divisors(&coefficients[0]), divisors(coefficients.last().unwrap())) {
+                (Some(ps), Some(qs)) => {
+                    for p in &ps {
+                        for q in &qs {
+                            if !p.gcd(q).is_one() {
+                                continue;
+                            }
+                            for p in [p.clone(), -p] {
+                                let linear = Poly(vec![Q::int(-&p), Q::int(q.clone())]);
+                                let (quotient, remainder) = rest.div_rem(&linear);
+                                if remainder.is_zero() && rest.degree() >= 1 {
+                                    factors.push(linear);
+                                    rest = quotient.content_and_primitive().1;
+                                }
+                            }
+                        }
+                    }
+                }
+                _ => self.complete = false,
+            }
+        }
+
+        if rest.degree() >= 4 {
+            factors.extend(self.kronecker(rest));
+        } else if rest.degree() >= 1 {
+            factors.push(rest);
+        }
+        factors
+    }
+
+    fn kronecker(&mut self, f: Poly) -> Vec<Poly> {
+        let mut factors = Vec::new();
+        let mut rest = f;
+        let mut d = 2;
+        while 2 * d <= rest.degree() {
+            match self.kronecker_factor(&rest, d) {
+                Some(factor) => {
+                    let (quotient, _) = rest.div_rem(&factor);
+                    rest = quotient.content_and_primitive().1;
+                    factors.push(factor);
+                }
+                None => d += 1,
+            }
+        }
+        factors.push(rest);
+        factors
+    }
+
+    // Finds a primitive integer factor of degree `d` of `f`, if one exists, by
+    // interpolating through divisors of `f` at `d + 1` integer points.
+    fn kronecker_factor(&mut self, f: &Poly, d: usize) -> Option<Poly> {
+        let mut points: Vec<(BigInt, Vec<BigInt>)> = Vec::new();
+        for k in 0..60i64 {
+            let x = BigInt::from(if k % 2 == 0 { k / 2 } else { -(k / 2) - 1 });
+            if let Some(ds) = divisors(&f.evaluate_integer(&x)) {
+                points.push((x, ds));
+            }
+        }
+        if points.len() < d + 1 {
+            self.complete = false;
+            return None;
+        }
+        points.sort_by_key(|(_, ds)| ds.len());
+        points.truncate(d + 1);
+
+        // The first value stays positive: a factor and its negation are the same factor.
+        let choices: Vec<Vec<BigInt>> = points
+            .iter()
+            .enumerate()
+            .map(|(i, (_, ds))| if i == 0 { ds.clone() } else { ds.iter().flat_map(|v| [v.clone(), -v]).collect() })
+            .collect();
+        let total = choices.iter().try_fold(1usize, |acc, c| acc.checked_mul(c.len()));
+        if total.is_none_or(|total| total > MAX_KRONECKER_CANDIDATES) {
+            self.complete = false;
+            return None;
+        }
+
+        let xs: Vec<Q> = points.iter().map(|(x, _)| Q::int(x.clone())).collect();
+        let mut index = vec![0usize; choices.len()];
+        loop {
+            let ys: Vec<Q> = index.iter().zip(&choices).map(|(i, c)| Q::int(c[*i].clone())).collect();
+            let candidate = interpolate(&xs, &ys);
+            if candidate.degree() == d && candidate.0.iter().all(Q::is_integer) {
+                let (quotient, remainder) = f.div_rem(&candidate);
+                if remainder.is_zero() && quotient.0.iter().all(Q::is_integer) {
+                    return Some(candidate.content_and_primitive().1);
+                }
+            }
+
+            let mut position = 0;
+            loop {
+                if position == index.len() {
+                    return None;
+                }
+                index[position] += 1;
+                if index[position] < choices[position].len() {
+                    break;
+                }
+                index[position] = 0;
+                position += 1;
+            }
+        }
+    }
+}
+
+fn interpolate(xs: &[Q], ys: &[Q]) -> Poly {
+    let mut result = Poly(Vec::new());
+    for (i, (xi, yi)) in xs.iter().zip(ys).enumerate() {
+        let mut term = Poly::constant(yi.clone());
+        for (j, xj) in xs.iter().enumerate() {
+            if i != j {
+                let factor = Poly(vec![-xj, Q::one()]).scale(&(xi - xj).recip());
+                term = &term * &factor;
+            }
+        }
+        result = &result + &term;
+    }
+    result
+}
+
+/// Factors `f` as `constant · Π factor^multiplicity` with primitive integer factors
+/// (positive leading coefficient), sorted by degree.
+fn factor(f: &Poly) -> (Q, Vec<(Poly, u32)>, bool) {
+    let (content, primitive) = f.content_and_primitive();
+    let mut factorizer = Factorizer { complete: true };
+    let mut factors: Vec<(Poly, u32)> = Vec::new();
+
+    // The root 0 has no divisors to try, so `x^k` comes off before the search.
+    let zeros = primitive.0.iter().position(|c| !c.is_zero()).unwrap_or(0);
+    if zeros > 0 {
+        factors.push((Poly::x(), zeros as u32));
+    }
+    for (part, multiplicity) in square_free(&Poly::new(primitive.0[zeros..].to_vec())) {
+        let part = part.content_and_primitive().1;
+        for irreducible in factorizer.irreducible_factors(part) {
+            factors.push((irreducible, multiplicity));
+        }
+    }
+
+    let product = factors.iter().fold(Poly::constant(Q::one()), |acc, (p, m)| &acc * &p.pow(*m as usize));
+    let unit = primitive.leading().div(&product.leading());
+    factors.sort_by(|(a, _), (b, _)| a.degree().cmp(&b.degree()).then_with(|| compare(a, b)));
+    (&content * &unit, factors, factorizer.complete)
+}
+
+fn compare(a: &Poly, b: &Poly) -> Ordering {
+    for (x, y) in a.0.iter().zip(&b.0).rev() {
+        let ordering = (&x.num * &y.den).cmp(&(&y.num * &x.den));
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
+
+fn format_factored(constant: &Q, factors: &[(Poly, u32)], variable: &str) -> String {
+    let body: String = factors.iter().map(|(p, m)| p.format_factor(variable, *m)).collect();
+    if body.is_empty() {
+        return constant.to_string();
+    }
+    match constant {
+        c if *c == Q::one() => body,
+        c if *c == Q::int(-1) => format!("-{}", body),
+        c if c.is_integer() => format!("{}{}", c, body),
+        c => format!("({}){}", c, body),
+    }
+}
+
+// One partial-fraction term `numerator / (base)^power`, rendered with integer
+// coefficients where possible and its sign pulled out front.
+fn format_term(numerator: &Poly, base: &Poly, power: u32, variable: &str) -> (bool, String) {
+    let (content, primitive) = numerator.content_and_primitive();
+    let negative = content.is_negative();
+    let magnitude = content.abs();
+    let numerator_text = match (magnitude.num.is_one(), primitive.degree() == 0) {
+        (_, true) => magnitude.num.to_string(),
+        (true, false) if primitive.0.iter().filter(|c| !c.is_zero()).count() == 1 => primitive.format(variable),
+        (true, false) => format!("({})", primitive.format(variable)),
+        (false, false) => format!("{}({})", magnitude.num, primitive.format(variable)),
+    };
+    let factor = base.format_factor(variable, power);
+    let denominator = if magnitude.den.is_one() {
+        factor
+    } else {
+        format!("({}{})", magnitude.den, factor)
+    };
+    (negative, format!("{}/{}", numerator_text, denominator))
+}
+
+// A denominator as written after `/`: bare when it is a lone power of the variable, in
+// brackets otherwise.
+fn grouped(polynomial: &Poly, variable: &str) -> String {
+    let terms: Vec<&Q> = polynomial.0.iter().filter(|c| !c.is_zero()).collect();
+    match terms[..] {
+        [c] if *c == Q::one() => polynomial.format(variable),
+        _ => format!("({})", polynomial.format(variable)),
+    }
+}
+
+fn join_terms(terms: &[(bool, String)]) -> String {
+    let mut text = String::new();
+    for (negative, body) in terms {
+        match (text.is_empty(), negative) {
+            (true, false) => text.push_str(body),
+            (true, true) => text.push_str(&format!("-{}", body)),
+            (false, false) => text.push_str(&format!(" + {}", body)),
+            (false, true) => text.push_str(&format!(" - {}", body)),
+        }
+    }
+    if text.is_empty() {
+        "0".to_string()
+    } else {
+        text
+    }
+}
+
+fn partial_fractions(fraction: &Fraction, variable: &str) -> (Vec<String>, String, bool) {
+    let (polynomial_part, mut remainder) = fraction.numerator.div_rem(&fraction.denominator);
+    let (constant, factors, complete) = factor(&fraction.denominator);
+    remainder = remainder.scale(&constant.recip());
+
+    let mut terms: Vec<(bool, String)> = Vec::new();
+    if !polynomial_part.is_zero() {
+        let text = polynomial_part.format(variable);
+        match text.strip_prefix('-') {
+            Some(rest) if polynomial_part.0.iter().filter(|c| !c.is_zero()).count() == 1 => {
+                terms.push((true, rest.to_string()))
+            }
+            _ => terms.push((false, text)),
+        }
+    }
+
+    // Peel off one prime power at a time: with s·P + t·Q = 1, R/(P·Q) = (R·t mod P)/P + rest/Q.
+    let mut rest_denominator =
+        factors.iter().fold(Poly::constant(Q::one()), |acc, (p, m)| &acc * &p.pow(*m as usize));
+    for (base, multiplicity) in &factors {
+        let power = base.pow(*multiplicity as usize);
+        let (other, _) = rest_denominator.div_rem(&power);
+        let (_, _, t) = Poly::extended_gcd(&power, &other);
+        let (_, mut numerator) = (&remainder * &t).div_rem(&power);
+        let (next, _) = (&remainder - &(&numerator * &other)).div_rem(&power);
+        remainder = next;
+        rest_denominator = other;
+
+        // numerator / base^m = Σ c_j / base^j, the c_j being the digits of numerator in base `base`.
+        let mut digits = Vec::new();
+        for _ in 0..*multiplicity {
+            let (quotient, digit) = numerator.div_rem(base);
+            digits.push(digit);
+            numerator = quotient;
+        }
+        for (j, digit) in digits.iter().enumerate().rev() {
+            if !digit.is_zero() {
+                terms.push(format_term(digit, base, multiplicity - j as u32, variable));
+            }
+        }
+    }
+
+    let result = join_terms(&terms);
+    let texts = terms
+        .into_iter()
+        .map(|(negative, body)| if negative { format!("-{}", body) } else { body })
+        .collect();
+    (texts, result, complete)
+}
+
+pub fn run(request: &PolynomialRequest) -> Result<PolynomialResponse, String> {
+    let mut variable = request.variable.clone();
+    let fraction = convert(&request.expression, &mut variable)?;
+    let other = match &request.other {
+        Some(other) => Some(convert(other, &mut variable)?),
+        None => None,
+    };
+    let name = variable.clone().unwrap_or_else(|| "x".to_string());
+    let mut response = PolynomialResponse { variable: Some(name.clone()), ..Default::default() };
+
+    let needs_other = || "This operation needs a second polynomial in 'other'".to_string();
+    match request.operation {
+        Operation::Expand => {
+            response.result = Some(if fraction.is_polynomial() {
+                fraction.into_polynomial()?.format(&name)
+            } else {
+                let (content, numerator) = fraction.numerator.content_and_primitive();
+                let denominator = fraction.denominator.scale(&content.recip());
+                let (scale, denominator) = denominator.content_and_primitive();
+                let numerator = numerator.scale(&scale.recip());
+                let terms = numerator.0.iter().filter(|c| !c.is_zero()).count();
+                let numerator = if terms == 1 && (numerator.degree() > 0 || numerator.0[0].is_integer()) {
+                    numerator.format(&name)
+                } else {
+                    format!("({})", numerator.format(&name))
+                };
+                format!("{}/{}", numerator, grouped(&denominator, &name))
+            });
+        }
+        Operation::Factor => {
+            let polynomial = fraction.into_polynomial()?;
+            if polynomial.is_zero() {
+                return Err("Cannot factor zero".to_string());
+            }
+            let (mut constant, mut factors, complete) = factor(&polynomial);
+            if request.domain == Domain::Rationals {
+                for (p, m) in factors.iter_mut() {
+                    constant = &constant * &p.leading().pow(*m);
+                    *p = p.monic();
+                }
+            }
+            response.result = Some(format_factored(&constant, &factors, &name));
+            response.constant = Some(constant.to_string());
+            response.factors = Some(
+                factors
+                    .iter()
+                    .map(|(p, m)| PolynomialFactor { factor: p.format(&name), multiplicity: *m })
+                    .collect(),
+            );
+            response.complete = Some(complete);
+        }
+        Operation::Divide => {
+            let dividend = fraction.into_polynomial()?;
+            let divisor = other.ok_or_else(needs_other)?.into_polynomial()?;
+            if divisor.is_zero() {
+                return Err("Division by zero".to_string());
+            }
+            let (quotient, remainder) = dividend.div_rem(&divisor);
+            response.result = Some(quotient.format(&name));
+            response.quotient = Some(quotient.format(&name));
+            response.remainder = Some(remainder.format(&name));
+        }
+        Operation::Gcd => {
+            let a = fraction.into_polynomial()?;
+            let b = other.ok_or_else(needs_other)?.into_polynomial()?;
+            let g = a.gcd(&b);
+            let g = match request.domain {
+                Domain::Rationals => g,
+                Domain::Integers => {
+                    let (ca, _) = a.content_and_primitive();
+                    let (cb, _) = b.content_and_primitive();
+                    let content = Q::new(ca.num.gcd(&cb.num), ca.den.lcm(&cb.den));
+                    let content = if content.is_zero() { Q::one() } else { content };
+                    g.content_and_primitive().1.scale(&content)
+                }
+            };
+            response.result = Some(g.format(&name));
+        }
+        Operation::PartialFractions => {
+            let (terms, result, complete) = partial_fractions(&fraction, &name);
+            response.result = Some(result);
+            response.terms = Some(terms);
+            response.complete = Some(complete);
+        }
+    }
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_operation(expression: &str, operation: Operation) -> PolynomialResponse {
+        let request =
+            PolynomialRequest { expression: expression.to_string(), other: None, operation, domain: Domain::Integers, variable: None };
+        run(&request).unwrap()
+    }
+
+    fn factored(expression: &str) -> (String, bool) {
+        let response = run_operation(expression, Operation::Factor);
+        (response.result.unwrap(), response.complete.unwrap())
+    }
+
+    #[test]
+    fn expand_brackets_only_where_needed() {
+        assert_eq!(run_operation("(x + 1)^2", Operation::Expand).result.unwrap(), "x^2 + 2x + 1");
+        assert_eq!(run_operation("1/(x + 1)", Operation::Expand).result.unwrap(), "1/(x + 1)");
+        assert_eq!(run_operation("(x + 2)/x^2", Operation::Expand).result.unwrap(), "(x + 2)/x^2");
+        assert_eq!(run_operation("x/(2x + 2)", Operation::Expand).result.unwrap(), "(1/2)x/(x + 1)");
+        assert_eq!(run_operation("1/(3x)", Operation::Expand).result.unwrap(), "(1/3)/x");
+        assert_eq!(run_operation("(x^2 - 1)/(x - 1)", Operation::Expand).result.unwrap(), "x + 1");
+    }
+
+    #[test]
+    fn factors_include_powers_of_x() {
+        assert_eq!(factored("x^2 + x"), ("x(x + 1)".to_string(), true));
+        assert_eq!(factored("x^3 - x"), ("(x - 1)x(x + 1)".to_string(), true));
+        assert_eq!(factored("x^3 + x"), ("x(x^2 + 1)".to_string(), true));
+        assert_eq!(factored("2x^5 - 2x^3"), ("2(x - 1)x^3(x + 1)".to_string(), true));
+        assert_eq!(factored("x^2"), ("x^2".to_string(), true));
+    }
+
+    #[test]
+    fn factors_over_the_integers_and_rationals() {
+        assert_eq!(factored("6x^2 + 5x + 1"), ("(2x + 1)(3x + 1)".to_string(), true));
+        assert_eq!(factored("(x - 1)^2 (x + 2)"), ("(x - 1)^2(x + 2)".to_string(), true));
+        assert_eq!(factored("x^4 + 4"), ("(x^2 - 2x + 2)(x^2 + 2x + 2)".to_string(), true));
+        let request = PolynomialRequest {
+            expression: "2x^2 - 1/2".to_string(),
+            other: None,
+            operation: Operation::Factor,
+            domain: Domain::Rationals,
+            variable: None,
+        };
+        assert_eq!(run(&request).unwrap().result.unwrap(), "2(x - 1/2)(x + 1/2)");
+        let zero = PolynomialRequest { expression: "0".to_string(), ..request };
+        assert_eq!(run(&zero).unwrap_err(), "Cannot factor zero");
+    }
+
+    #[test]
+    fn powers_past_the_degree_limit_are_refused_up_front() {
+        let request = |expression: &str| PolynomialRequest {
+            expression: expression.to_string(),
+            other: None,
+            operation: Operation::Expand,
+            domain: Domain::Integers,
+            variable: None,
+        };
+        let limit = format!("Polynomials are limited to degree {}", MAX_DEGREE);
+        assert_eq!(run(&request("(x^500 + 1)^500")).unwrap_err(), limit);
+        assert_eq!(run(&request("(x^2 + 1)^251")).unwrap_err(), limit);
+        assert_eq!(run(&request("1/(x^2 + 1)^-251")).unwrap_err(), limit);
+        assert!(run(&request("(x^250 + 1)^2")).is_ok());
+    }
+
+    #[test]
+    fn partial_fractions_split_over_factors() {
+        let response = run_operation("1/(x^3 + x)", Operation::PartialFractions);
+        assert_eq!(response.result.unwrap(), "1/x - x/(x^2 + 1)");
+        assert_eq!(response.complete, Some(true));
+        let response = run_operation("(x^3 + 1)/((x - 1)^2 (x^2 + 1))", Operation::PartialFractions);
+        assert_eq!(response.result.unwrap(), "1/(2(x - 1)) + 1/(x - 1)^2 + (x + 1)/(2(x^2 + 1))");
+    }
+
+    #[test]
+    fn division_and_gcd() {
+        let request = |expression: &str, other: &str, operation| PolynomialRequest {
+            expression: expression.to_string(),
+            other: Some(other.to_string()),
+            operation,
+            domain: Domain::Integers,
+            variable: None,
+        };
+        let response = run(&request("x^3 + 2x + 1", "x - 1", Operation::Divide)).unwrap();
+        assert_eq!((response.quotient.unwrap(), response.remainder.unwrap()), ("x^2 + x + 3".to_string(), "4".to_string()));
+        assert_eq!(run(&request("2x^2 - 2", "4x + 4", Operation::Gcd)).unwrap().result.unwrap(), "2x + 2");
+        assert!(run(&request("x", "0", Operation::Divide)).is_err());
+        assert!(run(&request("x y", "x", Operation::Expand)).is_err());
+    }
+}
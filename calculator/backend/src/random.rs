@@ -0,0 +1,184 @@
+use std::cell::{Cell, RefCell};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::i18n::{Error, DICE_COUNT, DICE_SIDES, RANDINT_BOUNDS, RAND_BOUNDS, STANDARD_DEVIATION_DOMAIN};
+
+pub const MAX_DICE: f64 = 1000.0;
+
+/// xoshiro256** seeded through SplitMix64, so a seed replays the same sequence on
+/// every platform and release.
+pub struct Rng {
+    state: [u64; 4],
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        let mut x = seed;
+        let mut splitmix = || {
+            x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = x;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            z ^ (z >> 31)
+        };
+        Self { state: [splitmix(), splitmix(), splitmix(), splitmix()] }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let s = &mut self.state;
+        let result = s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = s[1] << 17;
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+        result
+    }
+
+    /// Uniform in [0, 1).
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+    }
+
+    /// Uniform in [0, bound) without modulo bias.
+    pub fn below(&mut self, bound: u64) -> u64 {
+        let zone = u64::MAX - u64::MAX % bound;
+        loop {
+            let value = self.next_u64();
+            if value < zone {
+                return value % bound;
+            }
+        }
+    }
+}
+
+thread_local! {
+    static RNG: RefCell<Rng> = RefCell::new(Rng::new(entropy_seed()));
+    static USED: Cell<bool> = const { Cell::new(false) };
+}
+
+fn entropy_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
+/// Runs `f` with the random stream seeded from `seed` (or a fresh seed). Returns the seed
+/// when it was given explicitly or when `f` drew random numbers, so results can be replayed.
+pub fn with_seed<R>(seed: Option<u64>, f: impl FnOnce() -> R) -> (R, Option<u64>) {
+    let effective = seed.unwrap_or_else(entropy_seed);
+    RNG.with(|rng| *rng.borrow_mut() = Rng::new(effective));
+    USED.with(|used| used.set(false));
+
+    let result = f();
+    let used = USED.with(|used| used.get());
+    (result, (seed.is_some() || used).then_some(effective))
+}
+
+fn draw<T>(f: impl FnOnce(&mut Rng) -> T) -> T {
+    USED.with(|used| used.set(true));
+    RNG.with(|rng| f(&mut rng.borrow_mut()))
+}
+
+pub fn uniform(low: f64, high: f64) -> Result<f64, Error> {
+    if !(high - low).is_finite() || high <= low {
+        return Err(RAND_BOUNDS.error());
+    }
+    Ok(low + (high - low) * draw(Rng::next_f64))
+}
+
+pub fn integer(low: f64, high: f64) -> Result<f64, Error> {
+    if low.fract() != 0.0 || high.fract() != 0.0 || low > high || high - low >= 2f64.powi(53) {
+        return Err(RANDINT_BOUNDS.error());
+    }
+    Ok(low + draw(|rng| rng.below((high - low) as u64 + 1)) as f64)
+}
+
+/// Normal deviate via the Box–Muller transform.
+pub fn normal(mu: f64, sigma: f64) -> Result<f64, Error> {
+    if sigma <= 0.0 {
+        return Err(STANDARD_DEVIATION_DOMAIN.error());
+    }
+    let (u1, u2) = draw(|rng| (1.0 - rng.next_f64(), rng.next_f64()));
+    Ok(mu + sigma * (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos())
+}
+
+/// Sum of `count` rolls of a `sides`-sided die, as in `3d6`.
+pub fn dice(count: f64, sides: f64) -> Result<f64, Error> {
+    if count.fract() != 0.0 || !(1.0..=MAX_DICE).contains(&count) {
+        return Err(DICE_COUNT.with([MAX_DICE.into()]));
+    }
+    if sides.fract() != 0.0 || sides < 1.0 || sides > u32::MAX as f64 {
+        return Err(DICE_SIDES.error());
+    }
+    Ok(draw(|rng| (0..count as u64).map(|_| rng.below(sides as u64) + 1).sum::<u64>()) as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compile::CompiledExpression;
+    use crate::parser;
+
+    fn draws() -> Vec<f64> {
+        vec![
+            uniform(0.0, 1.0).unwrap(),
+            integer(1.0, 100.0).unwrap(),
+            normal(0.0, 1.0).unwrap(),
+            dice(3.0, 6.0).unwrap(),
+        ]
+    }
+
+    fn roll(expr: &str) -> Result<f64, Error> {
+        let compiled = CompiledExpression::compile(&parser::parse(expr)?, &[])?;
+        Ok(compiled.evaluate(&[])?.as_f64())
+    }
+
+    #[test]
+    fn the_generator_is_xoshiro256_starstar() {
+        let mut rng = Rng::new(42);
+        let expected = [0x1578_0b2e_0c2e_c716, 0x6104_d986_6d11_3a7e, 0xae17_5332_39e4_99a1];
+        assert_eq!([rng.next_u64(), rng.next_u64(), rng.next_u64()], expected);
+    }
+
+    #[test]
+    fn a_seed_replays_the_same_draws() {
+        let (first, seed) = with_seed(Some(42), draws);
+        assert_eq!(seed, Some(42));
+        assert_eq!(with_seed(Some(42), draws).0, first);
+        assert_ne!(with_seed(Some(43), draws).0, first);
+
+        let (drawn, seed) = with_seed(None, draws);
+        assert_eq!(with_seed(seed, draws).0, drawn);
+        assert_eq!(with_seed(None, || 1.0).1, None);
+    }
+
+    #[test]
+    fn dice_notation_rolls_within_range() {
+        with_seed(Some(7), || {
+            for _ in 0..200 {
+                let total = roll("3d6+2").unwrap();
+                assert!((5.0..=20.0).contains(&total) && total.fract() == 0.0, "{}", total);
+            }
+            assert_eq!(roll("1d1").unwrap(), 1.0);
+        });
+        let notation = with_seed(Some(7), || roll("10d20")).0.unwrap();
+        assert_eq!(with_seed(Some(7), || roll("dice(10, 20)")).0.unwrap(), notation);
+        assert!(roll("0d6").is_err());
+        assert!(roll("1001d6").is_err());
+        assert!(roll("3d0").is_err());
+        assert!(dice(2.5, 6.0).is_err());
+    }
+
+    #[test]
+    fn bounds_are_checked() {
+        assert!(uniform(1.0, 1.0).is_err());
+        assert!(integer(1.5, 3.0).is_err());
+        assert!(integer(3.0, 1.0).is_err());
+        assert!(normal(0.0, 0.0).is_err());
+        with_seed(Some(1), || assert_eq!(integer(4.0, 4.0).unwrap(), 4.0));
+    }
+}
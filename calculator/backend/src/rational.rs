@@ -0,0 +1,139 @@
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::{One, Signed, ToPrimitive, Zero};
+use std::fmt;
+use std::ops::{Add, Mul, Neg, Sub};
+
+/// Decimal exponents beyond this are refused rather than expanded into huge integers.
+const MAX_EXPONENT: i32 = 10_000;
+
+/// An exact rational number in lowest terms with a positive denominator.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Q {
+    pub num: BigInt,
+    pub den: BigInt,
+}
+
+impl Q {
+    pub fn new(num: BigInt, den: BigInt) -> Self {
+        let g = num.gcd(&den);
+        let (mut num, mut den) = (num / &g, den / &g);
+        if den.is_negative() {
+            num = -num;
+            den = -den;
+        }
+        Q { num, den }
+    }
+
+    pub fn int(value: impl Into<BigInt>) -> Self {
+        Q { num: value.into(), den: BigInt::one() }
+    }
+
+    pub fn zero() -> Self {
+        Q::int(0)
+    }
+
+    pub fn one() -> Self {
+        Q::int(1)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.num.is_zero()
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.den.is_one()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.num.is_negative()
+    }
+
+    pub fn abs(&self) -> Q {
+        Q { num: self.num.abs(), den: self.den.clone() }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        // Keep the top 64 bits of each side and apply the dropped powers of two at the
+        // end, so huge and tiny ratios both come out right.
+        let magnitude = self.num.magnitude();
+        let (num_shift, den_shift) = (magnitude.bits().saturating_sub(64), self.den.bits().saturating_sub(64));
+        let num = (magnitude >> num_shift).to_f64().unwrap_or(0.0);
+        let den = (self.den.magnitude() >> den_shift).to_f64().unwrap_or(1.0);
+        let mut value = num / den;
+        let mut shift = num_shift as i64 - den_shift as i64;
+        while shift != 0 {
+            let step = shift.clamp(-512, 512);
+            value *= 2f64.powi(step as i32);
+            shift -= step;
+        }
+        if self.num.is_negative() {
+            -value
+        } else {
+            value
+        }
+    }
+
+    pub fn recip(&self) -> Q {
+        Q::new(self.den.clone(), self.num.clone())
+    }
+
+    pub fn pow(&self, exponent: u32) -> Q {
+        Q { num: self.num.pow(exponent), den: self.den.pow(exponent) }
+    }
+
+    pub fn div(&self, other: &Q) -> Q {
+        Q::new(&self.num * &other.den, &self.den * &other.num)
+    }
+
+    /// Reads a decimal literal such as `12`, `0.25` or `1.5e-3` exactly.
+    pub fn parse(text: &str) -> Option<Q> {
+        let (mantissa, exponent) = match text.find(['e', 'E']) {
+            Some(i) => (&text[..i], text[i + 1..].parse::<i32>().ok().filter(|e| e.abs() <= MAX_EXPONENT)?),
+            None => (text, 0),
+        };
+        let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+        let digits: BigInt = format!("{}{}", whole, fraction).parse().ok()?;
+        let scale = exponent - fraction.len() as i32;
+        let power = BigInt::from(10).pow(scale.unsigned_abs());
+        Some(if scale >= 0 { Q::int(digits * power) } else { Q::new(digits, power) })
+    }
+}
+
+impl Add for &Q {
+    type Output = Q;
+    fn add(self, other: &Q) -> Q {
+        Q::new(&self.num * &other.den + &other.num * &self.den, &self.den * &other.den)
+    }
+}
+
+impl Sub for &Q {
+    type Output = Q;
+    fn sub(self, other: &Q) -> Q {
+        Q::new(&self.num * &other.den - &other.num * &self.den, &self.den * &other.den)
+    }
+}
+
+impl Mul for &Q {
+    type Output = Q;
+    fn mul(self, other: &Q) -> Q {
+        Q::new(&self.num * &other.num, &self.den * &other.den)
+    }
+}
+
+impl Neg for &Q {
+    type Output = Q;
+    fn neg(self) -> Q {
+        Q { num: -&self.num, den: self.den.clone() }
+    }
+}
+
+impl fmt::Display for Q {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_integer() {
+            write!(f, "{}", self.num)
+        } else {
+            write!(f, "{}/{}", self.num, self.den)
+        }
+    }
+}
@@ -0,0 +1,437 @@
+use lazy_static::lazy_static;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::sync::{Arc, RwLock};
+
+use crate::compile::CompiledExpression;
+use crate::dates;
+use crate::functions;
+use crate::i18n::{Argument, Error, AT_LEAST, FUNCTION_BUDGET, RANGE, WRONG_ARGUMENT_COUNT};
+use crate::parser;
+use crate::value::Type;
+
+/// Steps that expression-defined functions may take per request, counted in compiled
+/// instructions. Calls nest, so a chain of functions that each call the previous one twice
+/// would otherwise double its cost with every link.
+pub const MAX_FUNCTION_STEPS: usize = 10_000_000;
+
+/// A function callable from expressions. Arguments arrive in their raw `f64` form
+/// (booleans as 1/0, dates as days, durations as seconds), checked against `params`
+/// at compile time.
+pub trait Function: Send + Sync + fmt::Debug {
+    fn name(&self) -> &str;
+
+    /// The minimum and maximum argument count; `None` means no upper limit.
+    fn arity(&self) -> (usize, Option<usize>);
+
+    /// Parameter types; arguments past the end of the list are numbers.
+    fn params(&self) -> &[Type] {
+        &[]
+    }
+
+    fn result(&self) -> Type {
+        Type::Number
+    }
+
+    /// Pure functions may be constant-folded and propagate uncertainty.
+    fn pure(&self) -> bool {
+        true
+    }
+
+    fn evaluate(&self, args: &[f64]) -> Result<f64, Error>;
+
+    /// A call template shown by editor completion, e.g. `log(x, base?)`.
+    fn signature(&self) -> String {
+        generic_signature(self)
+    }
+
+    /// A one-line description shown by editor completion.
+    fn doc(&self) -> &str {
+        ""
+    }
+
+    fn check_arity(&self, count: usize) -> Result<(), Error> {
+        let (min, max) = self.arity();
+        if count >= min && max.is_none_or(|max| count <= max) {
+            return Ok(());
+        }
+        let expected: Argument = match max {
+            Some(max) if max == min => max.into(),
+            Some(max) => RANGE.with([min.to_string(), max.to_string()]),
+            None => AT_LEAST.with([min.to_string()]),
+        };
+        Err(WRONG_ARGUMENT_COUNT.with([self.name().into(), expected, count.into()]))
+    }
+
+    /// Whether every parameter and the result are plain numbers, as the RPN stack and
+    /// uncertainty propagation need.
+    fn is_numeric(&self) -> bool {
+        self.result() == Type::Number && self.params().iter().all(|t| *t == Type::Number)
+    }
+}
+
+/// Lets the builtins, which are statics, sit in the registry next to functions it owns.
+impl<T: Function + ?Sized> Function for &'static T {
+    fn name(&self) -> &str {
+        (**self).name()
+    }
+
+    fn arity(&self) -> (usize, Option<usize>) {
+        (**self).arity()
+    }
+
+    fn params(&self) -> &[Type] {
+        (**self).params()
+    }
+
+    fn result(&self) -> Type {
+        (**self).result()
+    }
+
+    fn pure(&self) -> bool {
+        (**self).pure()
+    }
+
+    fn evaluate(&self, args: &[f64]) -> Result<f64, Error> {
+        (**self).evaluate(args)
+    }
+
+    fn signature(&self) -> String {
+        (**self).signature()
+    }
+
+    fn doc(&self) -> &str {
+        (**self).doc()
+    }
+}
+
+/// Names each parameter by its type: `?` marks optional ones, `...` a variable tail.
+pub fn generic_signature<F: Function + ?Sized>(function: &F) -> String {
+    let (min, max) = function.arity();
+    let param = |i: usize| function.params().get(i).copied().unwrap_or(Type::Number).name();
+    let mut params: Vec<String> = (0..min).map(|i| param(i).to_string()).collect();
+    match max {
+        Some(max) => params.extend((min..max).map(|i| format!("{}?", param(i)))),
+        None => params.push("...".to_string()),
+    }
+    format!("{}({})", function.name(), params.join(", "))
+}
+
+/// A function defined in the functions file or by a user, e.g. `bmi(weight, height) = weight / height^2`.
+#[derive(Debug)]
+pub struct ExpressionFunction {
+    name: String,
+    params: Vec<Type>,
+    signature: String,
+    doc: String,
+    // Worked out once: asking the body each time would walk every nested function again.
+    pure: bool,
+    compiled: CompiledExpression,
+}
+
+impl Function for ExpressionFunction {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn arity(&self) -> (usize, Option<usize>) {
+        (self.params.len(), Some(self.params.len()))
+    }
+
+    fn params(&self) -> &[Type] {
+        &self.params
+    }
+
+    fn result(&self) -> Type {
+        self.compiled.result_type()
+    }
+
+    fn pure(&self) -> bool {
+        self.pure
+    }
+
+    fn signature(&self) -> String {
+        self.signature.clone()
+    }
+
+    fn doc(&self) -> &str {
+        &self.doc
+    }
+
+    fn evaluate(&self, args: &[f64]) -> Result<f64, Error> {
+        spend_steps(self.compiled.size())?;
+        self.compiled.evaluate(args).map(|value| value.as_f64()).map_err(|error| {
+            // Running out of steps is about the whole request, not this function.
+            if error.is(&FUNCTION_BUDGET) {
+                error
+            } else {
+                format!("In '{}': {}", self.name, error).into()
+            }
+        })
+    }
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct FunctionDefinition {
+    pub name: String,
+    #[serde(default)]
+    pub params: Vec<String>,
+    pub body: String,
+    /// Shown by editor completion; defaults to the body.
+    pub doc: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FunctionsFile {
+    pub functions: Vec<FunctionDefinition>,
+}
+
+impl ExpressionFunction {
+    /// Compiles a definition against the functions visible so far, so a definition may
+    /// call the ones before it but never itself.
+    pub fn compile(definition: &FunctionDefinition) -> Result<Self, String> {
+        let context = |error: String| format!("Function '{}': {}", definition.name, error);
+        for (i, param) in definition.params.iter().enumerate() {
+            if functions::constant(param).is_some() || definition.params[..i].contains(param) {
+                return Err(context(format!("'{}' cannot be a parameter name", param)));
+            }
+        }
+        let ast = parser::parse(&definition.body).map_err(|error| context(error.to_string()))?;
+        let typed: Vec<(String, Option<Type>)> =
+            definition.params.iter().map(|param| (param.clone(), Some(Type::Number))).collect();
+        let compiled = CompiledExpression::compile_typed(&ast, &typed).map_err(|error| context(error.to_string()))?;
+        if let Some(unknown) = compiled.variables().get(definition.params.len()) {
+            return Err(context(format!("unknown variable '{}'", unknown)));
+        }
+        Ok(ExpressionFunction {
+            name: definition.name.clone(),
+            params: vec![Type::Number; definition.params.len()],
+            signature: format!("{}({})", definition.name, definition.params.join(", ")),
+            doc: definition.doc.clone().unwrap_or_else(|| format!("= {}", definition.body)),
+            pure: compiled.is_pure(),
+            compiled,
+        })
+    }
+}
+
+/// Every function the evaluator can call, by name. Registration happens at startup;
+/// functions live for the rest of the process.
+#[derive(Debug, Default)]
+pub struct Registry {
+    functions: BTreeMap<String, Arc<dyn Function>>,
+}
+
+impl Registry {
+    pub fn with_builtins() -> Self {
+        let mut registry = Registry::default();
+        for builtin in functions::BUILTINS {
+            registry.register(Arc::new(builtin)).unwrap();
+        }
+        for function in dates::FUNCTIONS {
+            registry.register(Arc::new(function)).unwrap();
+        }
+        registry
+    }
+
+    pub fn register(&mut self, function: Arc<dyn Function>) -> Result<(), String> {
+        let name = function.name();
+        check_function_name(name)?;
+        if self.functions.contains_key(name) {
+            return Err(format!("Function '{}' is already defined", name));
+        }
+        self.functions.insert(name.to_string(), function);
+        Ok(())
+    }
+
+    pub fn lookup(&self, name: &str) -> Option<Arc<dyn Function>> {
+        self.functions.get(name).cloned()
+    }
+
+    /// Every registered function, by name.
+    pub fn functions(&self) -> Vec<Arc<dyn Function>> {
+        self.functions.values().cloned().collect()
+    }
+}
+
+pub fn check_function_name(name: &str) -> Result<(), String> {
+    if !parser::is_identifier(name) || name == "if" || functions::constant(name).is_some() {
+        return Err(format!("'{}' is not a valid function name", name));
+    }
+    Ok(())
+}
+
+/// Functions a user defined, visible only to that user's requests.
+pub type UserFunctions = BTreeMap<String, Arc<dyn Function>>;
+
+thread_local! {
+    static USER_FUNCTIONS: RefCell<Arc<UserFunctions>> = RefCell::new(Arc::default());
+    static STEPS_LEFT: Cell<usize> = const { Cell::new(MAX_FUNCTION_STEPS) };
+}
+
+lazy_static! {
+    static ref REGISTRY: RwLock<Registry> = RwLock::new(Registry::with_builtins());
+}
+
+pub fn get_registry() -> &'static RwLock<Registry> {
+    &REGISTRY
+}
+
+/// Runs `f` with `functions` visible next to the registered ones, the way
+/// `random::with_seed` scopes a generator. `f` gets a fresh budget of
+/// `MAX_FUNCTION_STEPS`.
+pub fn with_user_functions<R>(functions: Arc<UserFunctions>, f: impl FnOnce() -> R) -> R {
+    let previous = USER_FUNCTIONS.with(|current| current.replace(functions));
+    let steps = STEPS_LEFT.with(|left| left.replace(MAX_FUNCTION_STEPS));
+    let result = f();
+    USER_FUNCTIONS.with(|current| current.replace(previous));
+    STEPS_LEFT.with(|left| left.set(steps));
+    result
+}
+
+// Takes `steps` from the budget of the current `with_user_functions` scope.
+fn spend_steps(steps: usize) -> Result<(), Error> {
+    STEPS_LEFT.with(|left| match left.get().checked_sub(steps) {
+        Some(rest) => {
+            left.set(rest);
+            Ok(())
+        }
+        None => Err(FUNCTION_BUDGET.with([MAX_FUNCTION_STEPS.into()])),
+    })
+}
+
+/// The user's functions in scope, by name.
+pub fn user_functions() -> Vec<Arc<dyn Function>> {
+    USER_FUNCTIONS.with(|current| current.borrow().values().cloned().collect())
+}
+
+/// A registered function, or else one of the user's in scope.
+pub fn lookup(name: &str) -> Option<Arc<dyn Function>> {
+    get_registry()
+        .read()
+        .unwrap()
+        .lookup(name)
+        .or_else(|| USER_FUNCTIONS.with(|current| current.borrow().get(name).cloned()))
+}
+
+/// Adds a function implemented in Rust, e.g. from a domain module's `register` hook.
+pub fn register(function: Arc<dyn Function>) -> Result<(), String> {
+    get_registry().write().unwrap().register(function)
+}
+
+/// Loads expression-defined functions from a JSON file and registers them in order.
+/// Returns the names registered.
+pub fn load_functions_file(path: &str) -> Result<Vec<String>, String> {
+    let text = std::fs::read_to_string(path).map_err(|error| format!("Cannot read '{}': {}", path, error))?;
+    let file: FunctionsFile =
+        serde_json::from_str(&text).map_err(|error| format!("Invalid functions file '{}': {}", path, error))?;
+    let mut names = Vec::with_capacity(file.functions.len());
+    for definition in &file.functions {
+        // Compile before taking the write lock: compiling looks functions up.
+        let function = ExpressionFunction::compile(definition)?;
+        register(Arc::new(function))?;
+        names.push(definition.name.clone());
+    }
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn definition(name: &str, params: &[&str], body: &str) -> FunctionDefinition {
+        FunctionDefinition {
+            name: name.to_string(),
+            params: params.iter().map(|param| param.to_string()).collect(),
+            body: body.to_string(),
+            doc: None,
+        }
+    }
+
+    fn evaluate(expr: &str) -> Result<f64, Error> {
+        let compiled = CompiledExpression::compile(&parser::parse(expr)?, &[])?;
+        Ok(compiled.evaluate(&[])?.as_f64())
+    }
+
+    fn scope(definitions: &[FunctionDefinition]) -> Arc<UserFunctions> {
+        let mut functions = UserFunctions::new();
+        for definition in definitions {
+            let visible = Arc::new(functions.clone());
+            let function = with_user_functions(visible, || ExpressionFunction::compile(definition)).unwrap();
+            functions.insert(definition.name.clone(), Arc::new(function));
+        }
+        Arc::new(functions)
+    }
+
+    #[test]
+    fn registered_functions_are_looked_up_by_name() {
+        let mut registry = Registry::with_builtins();
+        assert!(registry.lookup("sqrt").is_some());
+        let bmi = definition("bmi", &["weight", "height"], "weight / height^2");
+        let function = ExpressionFunction::compile(&bmi).unwrap();
+        assert_eq!(function.signature(), "bmi(weight, height)");
+        assert_eq!(function.doc(), "= weight / height^2");
+        registry.register(Arc::new(function)).unwrap();
+        assert_eq!(registry.lookup("bmi").unwrap().evaluate(&[80.0, 2.0]).unwrap(), 20.0);
+    }
+
+    #[test]
+    fn names_must_be_new_and_valid() {
+        let mut registry = Registry::with_builtins();
+        let square = || Arc::new(ExpressionFunction::compile(&definition("square", &["x"], "x^2")).unwrap());
+        registry.register(square()).unwrap();
+        assert_eq!(registry.register(square()).unwrap_err(), "Function 'square' is already defined");
+        let sqrt = ExpressionFunction::compile(&definition("sqrt", &["x"], "x^0.5")).unwrap();
+        assert_eq!(registry.register(Arc::new(sqrt)).unwrap_err(), "Function 'sqrt' is already defined");
+        assert!(check_function_name("pi").is_err());
+        assert!(check_function_name("if").is_err());
+        assert!(check_function_name("2x").is_err());
+        assert!(ExpressionFunction::compile(&definition("f", &["x", "x"], "x")).is_err());
+        assert!(ExpressionFunction::compile(&definition("f", &["x"], "x + y")).is_err());
+        assert!(ExpressionFunction::compile(&definition("f", &["x"], "f(x)")).is_err());
+    }
+
+    #[test]
+    fn functions_files_register_in_order() {
+        let path = std::env::temp_dir().join(format!("calculator-functions-{}.json", std::process::id()));
+        let text = r#"{ "functions": [
+            { "name": "test_double", "params": ["x"], "body": "2 * x" },
+            { "name": "test_quadruple", "params": ["x"], "body": "test_double(test_double(x))" }
+        ] }"#;
+        std::fs::write(&path, text).unwrap();
+        let names = load_functions_file(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(names.unwrap(), ["test_double", "test_quadruple"]);
+        assert_eq!(evaluate("test_quadruple(3)").unwrap(), 12.0);
+        assert!(load_functions_file("/nonexistent/functions.json").unwrap_err().starts_with("Cannot read"));
+    }
+
+    #[test]
+    fn user_functions_are_visible_only_in_their_scope() {
+        let functions = scope(&[definition("registry_test_mine", &["x"], "x + 1")]);
+        assert_eq!(with_user_functions(functions.clone(), || evaluate("registry_test_mine(1)")).unwrap(), 2.0);
+        assert!(evaluate("registry_test_mine(1)").is_err());
+        assert_eq!(with_user_functions(functions, names), ["registry_test_mine"]);
+        assert!(names().is_empty());
+    }
+
+    fn names() -> Vec<String> {
+        user_functions().iter().map(|function| function.name().to_string()).collect()
+    }
+
+    #[test]
+    fn nested_calls_share_one_budget_per_request() {
+        let mut definitions = vec![definition("f0", &["x"], "x + 1")];
+        for i in 1..=40 {
+            definitions.push(definition(&format!("f{}", i), &["x"], &format!("f{0}(x) + f{0}(x)", i - 1)));
+        }
+        let functions = scope(&definitions);
+        assert_eq!(with_user_functions(functions.clone(), || evaluate("f10(1)")).unwrap(), 2048.0);
+        let error = with_user_functions(functions.clone(), || evaluate("f40(1)")).unwrap_err();
+        assert!(error.is(&FUNCTION_BUDGET), "{}", error);
+        // The next request starts with a fresh budget.
+        assert_eq!(with_user_functions(functions, || evaluate("f10(1)")).unwrap(), 2048.0);
+    }
+}
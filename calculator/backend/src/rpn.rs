@@ -0,0 +1,313 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::ast::{BinaryOp, UnaryOp};
+use crate::compile::{apply_binary, apply_unary};
+use crate::format::{format_number, FormatOptions};
+use crate::functions;
+use crate::random;
+use crate::registry;
+use crate::value::ExtendedReal;
+
+pub const MAX_STACK: usize = 10_000;
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct RpnRequest {
+    /// Whitespace-separated tokens, e.g. `"3 enter 4 + 2 *"`.
+    pub input: Option<String>,
+    #[serde(default)]
+    pub tokens: Vec<String>,
+    /// The starting stack, bottom first.
+    #[serde(default)]
+    pub stack: Vec<f64>,
+    #[serde(default)]
+    pub format: FormatOptions,
+    pub seed: Option<u64>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct RpnResponse {
+    /// The stack after the last successful token, bottom first; the top is the last entry.
+    pub stack: Vec<ExtendedReal>,
+    pub formatted: Vec<String>,
+    pub seed: Option<u64>,
+    pub error: Option<String>,
+}
+
+/// An HP-style stack. `enter` copies X and disables stack lift, so the next number
+/// overwrites the copy: `3 enter 4 +` gives 7, as on the calculator.
+struct Machine {
+    stack: Vec<f64>,
+    lift: bool,
+}
+
+impl Machine {
+    fn push(&mut self, value: f64) -> Result<(), String> {
+        if !self.lift {
+            self.lift = true;
+            if let Some(top) = self.stack.last_mut() {
+                *top = value;
+                return Ok(());
+            }
+        }
+        self.grow(value)
+    }
+
+    // Adds a value to the stack, within its limit.
+    fn grow(&mut self, value: f64) -> Result<(), String> {
+        if self.stack.len() >= MAX_STACK {
+            return Err(format!("Stack is limited to {} values", MAX_STACK));
+        }
+        self.stack.push(value);
+        Ok(())
+    }
+
+    // The top `count` values, bottom first, without removing them.
+    fn peek(&self, count: usize, token: &str) -> Result<&[f64], String> {
+        if self.stack.len() < count {
+            return Err(format!(
+                "'{}' needs {} value(s) on the stack, found {}",
+                token,
+                count,
+                self.stack.len()
+            ));
+        }
+        Ok(&self.stack[self.stack.len() - count..])
+    }
+
+    /// Runs one token. Every check comes before the stack changes, so a failing token
+    /// leaves the machine as it was.
+    fn execute(&mut self, token: &str) -> Result<(), String> {
+        if let Ok(value) = token.parse::<f64>() {
+            return self.push(value);
+        }
+        if let Some(value) = functions::constant(token) {
+            return self.push(value);
+        }
+
+        match token {
+            "enter" => {
+                let top = self.peek(1, token)?[0];
+                self.grow(top)?;
+                self.lift = false;
+                return Ok(());
+            }
+            "dup" => {
+                let top = self.peek(1, token)?[0];
+                self.grow(top)?;
+            }
+            "drop" => {
+                self.peek(1, token)?;
+                self.stack.pop();
+            }
+            "swap" => {
+                self.peek(2, token)?;
+                let len = self.stack.len();
+                self.stack.swap(len - 1, len - 2);
+            }
+            // R↓ moves X to the bottom of the stack; R↑ brings the bottom value up to X.
+            "roll" if !self.stack.is_empty() => self.stack.rotate_right(1),
+            "rollup" if !self.stack.is_empty() => self.stack.rotate_left(1),
+            "roll" | "rollup" => {}
+            "clear" => self.stack.clear(),
+            _ => {
+                // A function may be given the number of arguments to take, as in `max:3`;
+                // otherwise it takes its required ones.
+                let (name, count) = token.split_once(':').map_or((token, None), |(name, count)| (name, Some(count)));
+                let (count, result) = if let Some(op) = binary_operator(token) {
+                    let args = self.peek(2, token)?;
+                    (2, apply_binary(op, args[0], args[1]))
+                } else if let Some(op) = unary_operator(token) {
+                    let args = self.peek(1, token)?;
+                    (1, apply_unary(op, args[0]))
+                } else if let Some(function) = registry::lookup(name) {
+                    if !function.is_numeric() {
+                        return Err(format!("Function '{}' is not available in RPN mode", name));
+                    }
+                    let count = match count {
+                        Some(count) => count.parse().map_err(|_| format!("Invalid argument count in '{}'", token))?,
+                        None => function.arity().0,
+                    };
+                    function.check_arity(count)?;
+                    (count, function.evaluate(self.peek(count, token)?))
+                } else {
+                    return Err(format!("Unknown token '{}'", token));
+                };
+                let result = result?;
+                // Taking at least one argument leaves room for the result; otherwise `grow`
+                // refuses before anything changed.
+                self.stack.truncate(self.stack.len() - count);
+                self.grow(result)?;
+            }
+        }
+        self.lift = true;
+        Ok(())
+    }
+}
+
+fn binary_operator(token: &str) -> Option<BinaryOp> {
+    Some(match token {
+        "+" => BinaryOp::Add,
+        "-" | "−" => BinaryOp::Sub,
+        "*" | "×" => BinaryOp::Mul,
+        "/" | "÷" => BinaryOp::Div,
+        "%" => BinaryOp::Rem,
+        "^" | "**" => BinaryOp::Pow,
+        _ => return None,
+    })
+}
+
+fn unary_operator(token: &str) -> Option<UnaryOp> {
+    Some(match token {
+        "neg" | "chs" => UnaryOp::Neg,
+        "!" => UnaryOp::Factorial,
+        _ => return None,
+    })
+}
+
+/// Runs the tokens against the starting stack. On an error the stack is left as it was
+/// before the failing token, and the error names the token's position.
+pub fn run(request: &RpnRequest) -> RpnResponse {
+    // Refused before copying: the starting stack is not echoed back either.
+    if request.stack.len() > MAX_STACK {
+        return RpnResponse {
+            stack: Vec::new(),
+            formatted: Vec::new(),
+            seed: None,
+            error: Some(format!("Stack is limited to {} values", MAX_STACK)),
+        };
+    }
+    let tokens: Vec<&str> = match &request.input {
+        Some(_) if !request.tokens.is_empty() => {
+            return RpnResponse {
+                stack: request.stack.iter().copied().map(ExtendedReal).collect(),
+                formatted: Vec::new(),
+                seed: None,
+                error: Some("Use either input or tokens, not both".to_string()),
+            };
+        }
+        Some(input) => input.split_whitespace().collect(),
+        None => request.tokens.iter().map(|token| token.trim()).collect(),
+    };
+
+    let mut machine = Machine { stack: request.stack.clone(), lift: true };
+    let (error, seed) = random::with_seed(request.seed, || {
+        for (index, token) in tokens.iter().enumerate() {
+            if let Err(error) = machine.execute(token) {
+                return Some(format!("Token {} ('{}'): {}", index + 1, token, error));
+            }
+        }
+        None
+    });
+
+    let formatted = machine
+        .stack
+        .iter()
+        .map(|value| format_number(*value, &request.format))
+        .collect::<Result<Vec<_>, _>>();
+    let stack = machine.stack.into_iter().map(ExtendedReal).collect();
+    match formatted {
+        Ok(formatted) => RpnResponse { stack, formatted, seed, error },
+        Err(format_error) => {
+            RpnResponse { stack, formatted: Vec::new(), seed, error: error.or(Some(format_error.into())) }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::distributions;
+
+    fn run_input(input: &str, stack: Vec<f64>) -> RpnResponse {
+        run(&RpnRequest {
+            input: Some(input.to_string()),
+            tokens: Vec::new(),
+            stack,
+            format: Default::default(),
+            seed: None,
+        })
+    }
+
+    fn stack(response: &RpnResponse) -> Vec<f64> {
+        response.stack.iter().map(|value| value.0).collect()
+    }
+
+    #[test]
+    fn enter_disables_stack_lift() {
+        assert_eq!(stack(&run_input("3 enter 4 +", Vec::new())), vec![7.0]);
+        assert_eq!(stack(&run_input("3 enter enter *", Vec::new())), vec![3.0, 9.0]);
+        assert_eq!(stack(&run_input("2 dup 5", Vec::new())), vec![2.0, 2.0, 5.0]);
+        assert_eq!(stack(&run_input("1 2 3 roll", Vec::new())), vec![3.0, 1.0, 2.0]);
+        assert_eq!(stack(&run_input("1 2 swap - 2 3 ^", Vec::new())), vec![1.0, 8.0]);
+    }
+
+    #[test]
+    fn errors_leave_the_stack_as_before_the_token() {
+        let response = run_input("1 2 + *", Vec::new());
+        assert_eq!(stack(&response), vec![3.0]);
+        assert_eq!(response.error.as_deref(), Some("Token 4 ('*'): '*' needs 2 value(s) on the stack, found 1"));
+        let response = run_input("1 frobnicate", vec![5.0]);
+        assert_eq!(stack(&response), vec![5.0, 1.0]);
+        assert!(response.error.unwrap().contains("Unknown token 'frobnicate'"));
+    }
+
+    #[test]
+    fn functions_take_their_required_arguments_or_a_given_count() {
+        assert_eq!(stack(&run_input("1 4 3 max", Vec::new())), vec![1.0, 4.0, 3.0]);
+        assert_eq!(stack(&run_input("1 4 3 max:3", Vec::new())), vec![4.0]);
+        let density = |x, mu| distributions::normal_pdf(x, mu, 1.0).unwrap();
+        assert_eq!(stack(&run_input("0 2 normal_pdf", Vec::new())), vec![0.0, density(2.0, 0.0)]);
+        assert_eq!(stack(&run_input("0 2 normal_pdf:2", Vec::new())), vec![density(0.0, 2.0)]);
+
+        let response = run_input("1 2 3 atan2:3", Vec::new());
+        assert_eq!(stack(&response), vec![1.0, 2.0, 3.0]);
+        assert!(response.error.unwrap().contains("expects 2 argument(s), got 3"));
+        let response = run_input("1 2 max:two", Vec::new());
+        assert_eq!(response.error.as_deref(), Some("Token 3 ('max:two'): Invalid argument count in 'max:two'"));
+        assert!(run_input("1 2 max:3", Vec::new()).error.unwrap().contains("needs 3 value(s)"));
+    }
+
+    #[test]
+    fn dup_and_enter_respect_the_stack_limit() {
+        let full = vec![1.0; MAX_STACK];
+        for input in ["dup", "enter", "1"] {
+            let response = run_input(input, full.clone());
+            assert_eq!(response.stack.len(), MAX_STACK);
+            assert!(response.error.unwrap().contains("Stack is limited"), "{}", input);
+        }
+        let response = run_input(&"dup ".repeat(MAX_STACK), vec![1.0]);
+        assert_eq!(response.stack.len(), MAX_STACK);
+        assert!(response.error.is_some());
+        let response = run_input("rand", full.clone());
+        assert_eq!((response.stack.len(), response.stack[0].0), (MAX_STACK, 1.0));
+        assert!(response.error.unwrap().contains("Stack is limited"));
+        let response = run_input("+", full);
+        assert_eq!((response.stack.len(), response.stack[MAX_STACK - 2].0), (MAX_STACK - 1, 2.0));
+    }
+
+    #[test]
+    fn starting_stacks_over_the_limit_are_refused() {
+        let response = run_input("1", vec![0.0; MAX_STACK + 1]);
+        assert!(response.stack.is_empty());
+        assert_eq!(response.error, Some(format!("Stack is limited to {} values", MAX_STACK)));
+    }
+
+    #[test]
+    fn failing_tokens_change_nothing() {
+        let response = run_input("1 0 / 5", vec![7.0]);
+        assert_eq!(stack(&response), vec![7.0, 1.0, 0.0]);
+        assert!(response.error.unwrap().starts_with("Token 3 ('/')"));
+        let response = run_input("2 3 swap 1 2 3 atan2:3", Vec::new());
+        assert_eq!(stack(&response), vec![3.0, 2.0, 1.0, 2.0, 3.0]);
+        assert!(response.error.is_some());
+        let response = run_input("swap", vec![4.0]);
+        assert_eq!((stack(&response), response.error.is_some()), (vec![4.0], true));
+    }
+
+    #[test]
+    fn non_finite_stack_values_serialize_as_strings() {
+        let response = serde_json::to_value(run_input("-1 sqrt 1e308 10 * 0 1e308 10 * - 0.5", Vec::new())).unwrap();
+        assert_eq!(response["stack"], serde_json::json!(["nan", "inf", "-inf", 0.5]));
+    }
+}
@@ -0,0 +1,674 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::ast::Expr;
+use crate::compile::CompiledExpression;
+use crate::equations::split_equation;
+use crate::format::{format_number, FormatOptions};
+use crate::functions;
+use crate::parser;
+use crate::registry;
+use crate::value::{ExtendedReal, Type};
+
+/// Terms a recurrence may compute, and memoize, to reach one index.
+pub const MAX_TERMS: usize = 1_000_000;
+/// Terms one `terms` or `partial_sums` request lists.
+pub const MAX_COUNT: usize = 10_000;
+const DEFAULT_COUNT: usize = 10;
+// Limits are extrapolated from the values at from + 2^k for k in this range.
+const SAMPLE_EXPONENTS: std::ops::RangeInclusive<u32> = 3..=17;
+// Differences between samples must shrink at least this fast to count as converging.
+const SHRINK_FACTOR: f64 = 0.9;
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    #[default]
+    Terms,
+    Nth,
+    PartialSums,
+    Sum,
+    Limit,
+    Series,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ArithmeticSpec {
+    pub first: f64,
+    pub difference: f64,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct GeometricSpec {
+    pub first: f64,
+    pub ratio: f64,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct SequenceRequest {
+    /// A recurrence such as `"a(n) = a(n-1) + a(n-2), a(0) = 0, a(1) = 1"`, or an
+    /// explicit formula such as `"a(n) = 1/n^2"`.
+    pub definition: Option<String>,
+    pub arithmetic: Option<ArithmeticSpec>,
+    pub geometric: Option<GeometricSpec>,
+    #[serde(default)]
+    pub operation: Operation,
+    /// The first index listed, summed or sampled; defaults to the sequence's first index.
+    pub from: Option<i64>,
+    /// How many terms `terms` and `partial_sums` list.
+    pub count: Option<usize>,
+    /// The index for `nth`.
+    pub n: Option<i64>,
+    /// The last index for `sum`.
+    pub to: Option<i64>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct Term {
+    pub n: i64,
+    pub value: ExtendedReal,
+    /// The partial sum from `from` up to this term, for `partial_sums`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sum: Option<ExtendedReal>,
+}
+
+#[derive(Debug, Serialize, Default, JsonSchema)]
+pub struct SequenceResponse {
+    pub name: Option<String>,
+    pub first_index: Option<i64>,
+    pub terms: Option<Vec<Term>>,
+    pub result: Option<ExtendedReal>,
+    /// For an extrapolated `limit` or `series`, how far the last two estimates differ.
+    /// Absent when the result is exact or infinite.
+    pub estimated_error: Option<ExtendedReal>,
+    pub error: Option<String>,
+}
+
+/// A sequence defined by a rule for `a(n)`, which may refer to earlier terms, and initial
+/// values that override it. Terms of a recurrence are computed in order from the first
+/// index and memoized, so each is computed once.
+struct Recurrence {
+    name: String,
+    first: i64,
+    initial: BTreeMap<i64, f64>,
+    /// Compiled over the rule's variable followed by one slot per self-reference.
+    rule: CompiledExpression,
+    /// The index of each self-reference in the rule, compiled over the rule's variable.
+    references: Vec<CompiledExpression>,
+    /// a(first), a(first + 1), …
+    memo: Vec<f64>,
+}
+
+enum Sequence {
+    Recurrence(Box<Recurrence>),
+    /// a(n) = first + (n - 1)·difference, from n = 1.
+    Arithmetic { first: f64, difference: f64 },
+    /// a(n) = first·ratio^(n - 1), from n = 1.
+    Geometric { first: f64, ratio: f64 },
+}
+
+fn number(value: f64) -> String {
+    format_number(value, &FormatOptions::default()).unwrap_or_default()
+}
+
+/// A running sum with Neumaier's compensation, so long sums of small terms keep their
+/// precision.
+#[derive(Default)]
+struct CompensatedSum {
+    sum: f64,
+    compensation: f64,
+}
+
+impl CompensatedSum {
+    fn add(&mut self, value: f64) {
+        let total = self.sum + value;
+        if self.sum.abs() >= value.abs() {
+            self.compensation += (self.sum - total) + value;
+        } else {
+            self.compensation += (value - total) + self.sum;
+        }
+        self.sum = total;
+    }
+
+    fn value(&self) -> f64 {
+        if self.sum.is_finite() {
+            self.sum + self.compensation
+        } else {
+            self.sum
+        }
+    }
+}
+
+/// Splits at commas, semicolons and newlines outside brackets.
+fn split_parts(definition: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0i32;
+    let mut start = 0;
+    for (offset, c) in definition.char_indices() {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth -= 1,
+            ',' | ';' | '\n' if depth == 0 => {
+                parts.push(&definition[start..offset]);
+                start = offset + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&definition[start..]);
+    parts.into_iter().map(str::trim).filter(|part| !part.is_empty()).collect()
+}
+
+// Replaces each call of the sequence in `expr` by a variable `a#i`, where `i` indexes
+// the call's argument in `references`.
+fn substitute(expr: &Expr, name: &str, references: &mut Vec<Expr>) -> Result<Expr, String> {
+    Ok(match expr {
+        Expr::Call { name: callee, args, span } if callee == name => {
+            let [index] = args.as_slice() else {
+                return Err(format!("{}(…) takes a single index", name));
+            };
+            if uses(index, name) {
+                return Err(format!("The index in {}(…) cannot itself use {}", name, name));
+            }
+            references.push(index.clone());
+            Expr::Variable { name: format!("{}#{}", name, references.len() - 1), span: *span }
+        }
+        Expr::Call { name: callee, args, span } => Expr::Call {
+            name: callee.clone(),
+            args: args.iter().map(|arg| substitute(arg, name, references)).collect::<Result<_, _>>()?,
+            span: *span,
+        },
+        Expr::Unary { op, operand, span } => {
+            Expr::Unary { op: *op, operand: Box::new(substitute(operand, name, references)?), span: *span }
+        }
+        Expr::Binary { op, left, right, span } => Expr::Binary {
+            op: *op,
+            left: Box::new(substitute(left, name, references)?),
+            right: Box::new(substitute(right, name, references)?),
+            span: *span,
+        },
+        Expr::Interval { low, high, span } => Expr::Interval {
+            low: Box::new(substitute(low, name, references)?),
+            high: Box::new(substitute(high, name, references)?),
+            span: *span,
+        },
+        leaf => leaf.clone(),
+    })
+}
+
+fn uses(expr: &Expr, name: &str) -> bool {
+    match expr {
+        Expr::Call { name: callee, args, .. } => callee == name || args.iter().any(|arg| uses(arg, name)),
+        Expr::Unary { operand, .. } => uses(operand, name),
+        Expr::Binary { left, right, .. } | Expr::Interval { low: left, high: right, .. } => {
+            uses(left, name) || uses(right, name)
+        }
+        _ => false,
+    }
+}
+
+// Compiles `expr` over exactly `variables`, all numbers.
+fn compile_over(expr: &Expr, variables: &[String]) -> Result<CompiledExpression, String> {
+    let typed: Vec<(String, Option<Type>)> = variables.iter().map(|name| (name.clone(), Some(Type::Number))).collect();
+    let compiled = CompiledExpression::compile_typed(expr, &typed)?;
+    if let Some(unknown) = compiled.variables().get(variables.len()) {
+        return Err(format!("Unknown variable '{}'", unknown));
+    }
+    if compiled.result_type() != Type::Number {
+        return Err(format!("Expected a number, got a {}", compiled.result_type().name()));
+    }
+    Ok(compiled)
+}
+
+fn constant_index(expr: &Expr) -> Result<i64, String> {
+    let index = compile_over(expr, &[])?.evaluate(&[])?.as_f64();
+    if index.fract() != 0.0 || index.abs() > 1e15 {
+        return Err(format!("{} is not an integer index", number(index)));
+    }
+    Ok(index as i64)
+}
+
+impl Recurrence {
+    fn parse(definition: &str) -> Result<Self, String> {
+        let mut name: Option<String> = None;
+        let mut rule: Option<(String, Expr)> = None;
+        let mut initial = BTreeMap::new();
+        for part in split_parts(definition) {
+            let (lhs, rhs) = split_equation(part).map_err(|error| format!("'{}': {}", part, error))?;
+            let (term, argument) = match parser::parse(lhs)? {
+                Expr::Call { name, mut args, .. } if args.len() == 1 => (name, args.remove(0)),
+                _ => return Err(format!("'{}' should be a term such as a(n) or a(0)", lhs.trim())),
+            };
+            match &name {
+                None => {
+                    if functions::constant(&term).is_some() || registry::lookup(&term).is_some() {
+                        return Err(format!("'{}' is already a function or constant", term));
+                    }
+                    name = Some(term.clone());
+                }
+                Some(name) if *name != term => {
+                    return Err(format!("Only one sequence can be defined, found '{}' and '{}'", name, term));
+                }
+                Some(_) => {}
+            }
+            let rhs = parser::parse(rhs)?;
+            match argument {
+                Expr::Variable { name: variable, .. } if functions::constant(&variable).is_none() => {
+                    if rule.is_some() {
+                        return Err(format!("{} has more than one rule", term));
+                    }
+                    rule = Some((variable, rhs));
+                }
+                argument => {
+                    let index = constant_index(&argument).map_err(|error| format!("In '{}': {}", part, error))?;
+                    let value = compile_over(&rhs, &[])
+                        .and_then(|compiled| compiled.evaluate(&[]).map_err(String::from))
+                        .map_err(|error| format!("{}({}): {}", term, index, error))?;
+                    if initial.insert(index, value.as_f64()).is_some() {
+                        return Err(format!("{}({}) is given more than once", term, index));
+                    }
+                }
+            }
+        }
+
+        let name = name.ok_or("No sequence given")?;
+        let (variable, rhs) = rule.ok_or_else(|| format!("No rule such as {}(n) = … given", name))?;
+        let mut references = Vec::new();
+        let rhs = substitute(&rhs, &name, &mut references)?;
+        let mut slots = vec![variable.clone()];
+        slots.extend((0..references.len()).map(|i| format!("{}#{}", name, i)));
+        let rule = compile_over(&rhs, &slots).map_err(|error| format!("{}({}): {}", name, variable, error))?;
+        let references = references
+            .iter()
+            .map(|index| compile_over(index, std::slice::from_ref(&variable)))
+            .collect::<Result<_, _>>()
+            .map_err(|error| format!("Index in {}({}): {}", name, variable, error))?;
+        let first = initial.keys().next().copied().unwrap_or(0);
+        Ok(Recurrence { name, first, initial, rule, references, memo: Vec::new() })
+    }
+
+    fn term(&mut self, n: i64) -> Result<f64, String> {
+        if n < self.first {
+            return Err(format!("{} starts at {}({})", self.name, self.name, self.first));
+        }
+        if let Some(value) = self.initial.get(&n) {
+            return Ok(*value);
+        }
+        // An explicit formula needs no earlier terms.
+        if self.references.is_empty() {
+            return self.apply(n);
+        }
+        let offset = n as i128 - self.first as i128;
+        if offset >= MAX_TERMS as i128 {
+            return Err(format!(
+                "{}({}) lies more than {} terms past the first, {}({})",
+                self.name, n, MAX_TERMS, self.name, self.first
+            ));
+        }
+        while self.memo.len() as i128 <= offset {
+            let k = self.first + self.memo.len() as i64;
+            let value = match self.initial.get(&k) {
+                Some(value) => *value,
+                None => self.apply(k)?,
+            };
+            self.memo.push(value);
+        }
+        Ok(self.memo[offset as usize])
+    }
+
+    // Evaluates the rule at `n`; every earlier term is memoized by now. A reference that
+    // cannot be resolved reads as NaN, so it only fails the term if the rule uses it
+    // (e.g. in the branch of an `if` that is taken).
+    fn apply(&self, n: i64) -> Result<f64, String> {
+        let term = |index: &str| format!("{}({})", self.name, index);
+        let mut values = vec![n as f64];
+        let mut unresolved = None;
+        for reference in &self.references {
+            let index = reference.evaluate(&[n as f64]).map_err(|error| format!("{}: {}", term(&n.to_string()), error))?;
+            let index = index.as_f64();
+            let problem = if index.fract() != 0.0 || !index.is_finite() {
+                Some("which is not an integer index".to_string())
+            } else if index >= n as f64 {
+                Some("which does not come before it".to_string())
+            } else if index < self.first as f64 {
+                Some(format!("but the sequence starts at {}", term(&self.first.to_string())))
+            } else {
+                None
+            };
+            match problem {
+                Some(problem) => {
+                    values.push(f64::NAN);
+                    unresolved.get_or_insert_with(|| {
+                        format!("{} refers to {}, {}", term(&n.to_string()), term(&number(index)), problem)
+                    });
+                }
+                None => values.push(self.memo[(index as i64 - self.first) as usize]),
+            }
+        }
+        let value = self
+            .rule
+            .evaluate(&values)
+            .map_err(|error| format!("{}: {}", term(&n.to_string()), error))?
+            .as_f64();
+        match unresolved {
+            Some(message) if value.is_nan() => Err(message),
+            _ => Ok(value),
+        }
+    }
+}
+
+// Extrapolates samples taken at doubling indices. Differences that keep shrinking are
+// accelerated with Aitken's Δ² process, and the last two estimates give the error;
+// differences that do not shrink mean divergence, to ±∞ if the samples are monotonic.
+fn extrapolate(samples: &[f64], what: &str) -> Result<(f64, Option<f64>), String> {
+    let last = *samples.last().unwrap();
+    if last.is_nan() {
+        return Err(format!("{} is NaN", what));
+    }
+    if last.is_infinite() {
+        return Ok((last, None));
+    }
+    let differences: Vec<f64> = samples.windows(2).map(|pair| pair[1] - pair[0]).collect();
+    let tail = &differences[differences.len() - 3..];
+    if tail.iter().all(|difference| difference.abs() <= 1e-14 * last.abs()) {
+        return Ok((last, Some(tail.iter().fold(0.0, |max, difference| difference.abs().max(max)))));
+    }
+    let shrinking = tail.windows(2).all(|pair| pair[1].abs() <= SHRINK_FACTOR * pair[0].abs());
+    if shrinking {
+        let aitken = |k: usize| {
+            let (d0, d1) = (differences[k], differences[k + 1]);
+            if d1 == d0 {
+                samples[k + 2]
+            } else {
+                samples[k + 2] - d1 * d1 / (d1 - d0)
+            }
+        };
+        let estimate = aitken(differences.len() - 2);
+        let previous = aitken(differences.len() - 3);
+        return Ok((estimate, Some((estimate - previous).abs())));
+    }
+    if differences.iter().all(|difference| *difference > 0.0) {
+        return Ok((f64::INFINITY, None));
+    }
+    if differences.iter().all(|difference| *difference < 0.0) {
+        return Ok((f64::NEG_INFINITY, None));
+    }
+    Err(format!("{} does not appear to converge", what))
+}
+
+fn diverges(what: &str) -> String {
+    format!("{} does not converge", what)
+}
+
+impl Sequence {
+    fn name(&self) -> &str {
+        match self {
+            Sequence::Recurrence(recurrence) => &recurrence.name,
+            _ => "a",
+        }
+    }
+
+    fn first_index(&self) -> i64 {
+        match self {
+            Sequence::Recurrence(recurrence) => recurrence.first,
+            _ => 1,
+        }
+    }
+
+    fn term(&mut self, n: i64) -> Result<f64, String> {
+        if let Sequence::Recurrence(recurrence) = self {
+            return recurrence.term(n);
+        }
+        if n < 1 {
+            return Err("The sequence starts at a(1)".to_string());
+        }
+        Ok(match *self {
+            Sequence::Arithmetic { first, difference } => first + (n - 1) as f64 * difference,
+            Sequence::Geometric { first, ratio } => first * ratio.powf((n - 1) as f64),
+            Sequence::Recurrence(_) => unreachable!(),
+        })
+    }
+
+    fn sum(&mut self, from: i64, to: i64) -> Result<f64, String> {
+        if to < from {
+            return Err(format!("to ({}) comes before from ({})", to, from));
+        }
+        let count = (to as i128 - from as i128 + 1) as f64;
+        match *self {
+            Sequence::Arithmetic { .. } => Ok(count * (self.term(from)? + self.term(to)?) / 2.0),
+            Sequence::Geometric { ratio: 1.0, .. } => Ok(count * self.term(from)?),
+            Sequence::Geometric { ratio, .. } => Ok(self.term(from)? * (1.0 - ratio.powf(count)) / (1.0 - ratio)),
+            Sequence::Recurrence(_) => {
+                if count > MAX_TERMS as f64 {
+                    return Err(format!("A sum covers at most {} terms", MAX_TERMS));
+                }
+                let mut sum = CompensatedSum::default();
+                for n in from..=to {
+                    sum.add(self.term(n)?);
+                }
+                Ok(sum.value())
+            }
+        }
+    }
+
+    fn limit(&mut self, from: i64) -> Result<(f64, Option<f64>), String> {
+        let what = format!("{}(n)", self.name());
+        match *self {
+            Sequence::Arithmetic { first, difference } => {
+                Ok((if difference == 0.0 { first } else { difference.signum() * f64::INFINITY }, None))
+            }
+            Sequence::Geometric { first, ratio } => match ratio {
+                _ if first == 0.0 || ratio.abs() < 1.0 => Ok((0.0, None)),
+                _ if ratio == 1.0 => Ok((first, None)),
+                _ if ratio > 1.0 => Ok((first.signum() * f64::INFINITY, None)),
+                _ => Err(diverges(&what)),
+            },
+            Sequence::Recurrence(_) => {
+                let samples = SAMPLE_EXPONENTS
+                    .map(|k| self.term(from + (1 << k)))
+                    .collect::<Result<Vec<f64>, String>>()?;
+                extrapolate(&samples, &what)
+            }
+        }
+    }
+
+    fn series(&mut self, from: i64) -> Result<(f64, Option<f64>), String> {
+        let what = format!("The series of {}(n)", self.name());
+        match *self {
+            Sequence::Arithmetic { first, difference } => {
+                // The terms eventually take the sign of the difference, or of the first term.
+                let direction = if difference != 0.0 { difference } else { first };
+                Ok((if direction == 0.0 { 0.0 } else { direction.signum() * f64::INFINITY }, None))
+            }
+            Sequence::Geometric { first, ratio } => match ratio {
+                _ if first == 0.0 => Ok((0.0, None)),
+                _ if ratio.abs() < 1.0 => Ok((self.term(from)? / (1.0 - ratio), None)),
+                _ if ratio >= 1.0 => Ok((first.signum() * f64::INFINITY, None)),
+                _ => Err(diverges(&what)),
+            },
+            Sequence::Recurrence(_) => {
+                let mut sum = CompensatedSum::default();
+                let mut samples = Vec::new();
+                let mut n = from;
+                for k in SAMPLE_EXPONENTS {
+                    while n < from + (1 << k) {
+                        sum.add(self.term(n)?);
+                        n += 1;
+                    }
+                    samples.push(sum.value());
+                }
+                extrapolate(&samples, &what)
+            }
+        }
+    }
+}
+
+fn index_after(from: i64, offset: usize) -> Result<i64, String> {
+    from.checked_add(offset as i64).ok_or_else(|| "Index out of range".to_string())
+}
+
+pub fn run(request: &SequenceRequest) -> Result<SequenceResponse, String> {
+    let mut sequence = match (&request.definition, &request.arithmetic, &request.geometric) {
+        (Some(definition), None, None) => Sequence::Recurrence(Box::new(Recurrence::parse(definition)?)),
+        (None, Some(spec), None) => Sequence::Arithmetic { first: spec.first, difference: spec.difference },
+        (None, None, Some(spec)) => Sequence::Geometric { first: spec.first, ratio: spec.ratio },
+        (None, None, None) => return Err("Give a definition, an arithmetic or a geometric sequence".to_string()),
+        _ => return Err("Give only one of definition, arithmetic and geometric".to_string()),
+    };
+    let first = sequence.first_index();
+    let from = request.from.unwrap_or(first);
+    let count = request.count.unwrap_or(DEFAULT_COUNT);
+    if count > MAX_COUNT {
+        return Err(format!("count is limited to {}", MAX_COUNT));
+    }
+    if from > i64::MAX - (1 << SAMPLE_EXPONENTS.end()) {
+        return Err("Index out of range".to_string());
+    }
+
+    let mut response = SequenceResponse {
+        name: Some(sequence.name().to_string()),
+        first_index: Some(first),
+        ..Default::default()
+    };
+    match request.operation {
+        Operation::Terms => {
+            let terms = (0..count)
+                .map(|i| {
+                    let n = index_after(from, i)?;
+                    Ok(Term { n, value: ExtendedReal(sequence.term(n)?), sum: None })
+                })
+                .collect::<Result<_, String>>()?;
+            response.terms = Some(terms);
+        }
+        Operation::PartialSums => {
+            let mut sum = CompensatedSum::default();
+            let terms = (0..count)
+                .map(|i| {
+                    let n = index_after(from, i)?;
+                    let value = sequence.term(n)?;
+                    sum.add(value);
+                    Ok(Term { n, value: ExtendedReal(value), sum: Some(ExtendedReal(sum.value())) })
+                })
+                .collect::<Result<_, String>>()?;
+            response.terms = Some(terms);
+        }
+        Operation::Nth => {
+            let n = request.n.ok_or("nth needs n")?;
+            response.result = Some(ExtendedReal(sequence.term(n)?));
+        }
+        Operation::Sum => {
+            let to = request.to.ok_or("sum needs to")?;
+            response.result = Some(ExtendedReal(sequence.sum(from, to)?));
+        }
+        Operation::Limit | Operation::Series => {
+            let (value, estimated_error) = match request.operation {
+                Operation::Limit => sequence.limit(from)?,
+                _ => sequence.series(from)?,
+            };
+            response.result = Some(ExtendedReal(value));
+            response.estimated_error = estimated_error.map(ExtendedReal);
+        }
+    }
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(definition: &str, operation: Operation) -> SequenceRequest {
+        SequenceRequest {
+            definition: Some(definition.to_string()),
+            arithmetic: None,
+            geometric: None,
+            operation,
+            from: None,
+            count: None,
+            n: None,
+            to: None,
+        }
+    }
+
+    fn values(response: &SequenceResponse) -> Vec<f64> {
+        response.terms.as_ref().unwrap().iter().map(|term| term.value.0).collect()
+    }
+
+    const FIBONACCI: &str = "a(n) = a(n-1) + a(n-2), a(0) = 0, a(1) = 1";
+
+    #[test]
+    fn recurrences_list_terms_and_sums() {
+        let response = run(&request(FIBONACCI, Operation::Terms)).unwrap();
+        assert_eq!(response.first_index, Some(0));
+        assert_eq!(values(&response), [0.0, 1.0, 1.0, 2.0, 3.0, 5.0, 8.0, 13.0, 21.0, 34.0]);
+
+        let mut nth = request(FIBONACCI, Operation::Nth);
+        nth.n = Some(50);
+        assert_eq!(run(&nth).unwrap().result, Some(ExtendedReal(12586269025.0)));
+
+        let mut sums = request("a(n) = n", Operation::PartialSums);
+        sums.count = Some(4);
+        let sums: Vec<f64> = run(&sums).unwrap().terms.unwrap().iter().map(|t| t.sum.unwrap().0).collect();
+        assert_eq!(sums, [0.0, 1.0, 3.0, 6.0]);
+    }
+
+    #[test]
+    fn closed_forms_for_arithmetic_and_geometric_sequences() {
+        let mut sum = request("", Operation::Sum);
+        sum.definition = None;
+        sum.arithmetic = Some(ArithmeticSpec { first: 1.0, difference: 1.0 });
+        sum.to = Some(100);
+        assert_eq!(run(&sum).unwrap().result, Some(ExtendedReal(5050.0)));
+
+        let mut series = request("", Operation::Series);
+        series.definition = None;
+        series.geometric = Some(GeometricSpec { first: 1.0, ratio: 0.5 });
+        let response = run(&series).unwrap();
+        assert_eq!((response.result, response.estimated_error), (Some(ExtendedReal(2.0)), None));
+
+        series.geometric = Some(GeometricSpec { first: 1.0, ratio: -1.0 });
+        assert_eq!(run(&series).unwrap_err(), "The series of a(n) does not converge");
+        series.operation = Operation::Limit;
+        series.geometric = Some(GeometricSpec { first: -1.0, ratio: 2.0 });
+        assert_eq!(run(&series).unwrap().result, Some(ExtendedReal(f64::NEG_INFINITY)));
+    }
+
+    #[test]
+    fn limits_and_series_are_extrapolated() {
+        let mut series = request("a(n) = 1/n^2", Operation::Series);
+        series.from = Some(1);
+        let response = run(&series).unwrap();
+        let expected = std::f64::consts::PI.powi(2) / 6.0;
+        assert!((response.result.unwrap().0 - expected).abs() < 1e-6, "{:?}", response.result);
+        assert!(response.estimated_error.is_some());
+
+        let mut limit = request("a(n) = (1 + 1/n)^n", Operation::Limit);
+        limit.from = Some(1);
+        let response = run(&limit).unwrap();
+        assert!((response.result.unwrap().0 - std::f64::consts::E).abs() < 1e-6, "{:?}", response.result);
+        // An explicit formula starts at a(0), where this one divides by zero.
+        assert_eq!(run(&request("a(n) = 1/n", Operation::Terms)).unwrap_err(), "a(0): Division by zero");
+
+        let response = run(&request("a(n) = n", Operation::Series)).unwrap();
+        assert_eq!((response.result, response.estimated_error), (Some(ExtendedReal(f64::INFINITY)), None));
+    }
+
+    #[test]
+    fn bad_requests_are_errors() {
+        let error = |request: SequenceRequest| run(&request).unwrap_err();
+        assert_eq!(error(request("a(0) = 1", Operation::Terms)), "No rule such as a(n) = … given");
+        let mut nth = request(FIBONACCI, Operation::Nth);
+        nth.n = Some(-1);
+        assert_eq!(error(nth), "a starts at a(0)");
+        let mut sum = request(FIBONACCI, Operation::Sum);
+        sum.to = Some(-5);
+        assert_eq!(error(sum), "to (-5) comes before from (0)");
+        let mut count = request(FIBONACCI, Operation::Terms);
+        count.count = Some(MAX_COUNT + 1);
+        assert_eq!(error(count), format!("count is limited to {}", MAX_COUNT));
+        let mut both = request(FIBONACCI, Operation::Terms);
+        both.arithmetic = Some(ArithmeticSpec { first: 1.0, difference: 1.0 });
+        assert_eq!(error(both), "Give only one of definition, arithmetic and geometric");
+        assert_eq!(error(request("sin(n) = n", Operation::Terms)), "'sin' is already a function or constant");
+    }
+}
@@ -0,0 +1,287 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::compile::CompiledExpression;
+use crate::config;
+use crate::value::{ExtendedReal, Type, Value};
+
+pub const MAX_TABLE_ROWS: usize = 100_000;
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct RangeSpec {
+    pub variable: String,
+    pub from: f64,
+    pub to: f64,
+    pub step: f64,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct TableRequest {
+    pub expression: Option<String>,
+    pub id: Option<u64>,
+    #[serde(default)]
+    pub variables: Vec<String>,
+    #[serde(default)]
+    pub ranges: Vec<RangeSpec>,
+    #[serde(default)]
+    pub rows: Vec<HashMap<String, f64>>,
+    #[serde(default)]
+    pub bindings: HashMap<String, f64>,
+    pub seed: Option<u64>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct TableRow {
+    pub inputs: Vec<ExtendedReal>,
+    pub result: Option<Value>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct TableResponse {
+    pub variables: Vec<String>,
+    pub rows: Vec<TableRow>,
+    pub seed: Option<u64>,
+    pub error: Option<String>,
+}
+
+impl TableResponse {
+    pub fn error(message: String) -> Self {
+        Self { variables: Vec::new(), rows: Vec::new(), seed: None, error: Some(message) }
+    }
+}
+
+fn range_values(range: &RangeSpec) -> Result<Vec<f64>, String> {
+    if range.step == 0.0 || !range.step.is_finite() {
+        return Err(format!("Range for '{}' needs a finite, non-zero step", range.variable));
+    }
+    let span = range.to - range.from;
+    if !span.is_finite() {
+        return Err(format!("Range for '{}' needs finite bounds", range.variable));
+    }
+    if span != 0.0 && span.signum() != range.step.signum() {
+        return Err(format!("Range for '{}' never reaches its end with this step", range.variable));
+    }
+
+    // A small tolerance keeps `0 to 1 step 0.1` from losing its last point to rounding.
+    // The count stays a float until it is known to fit, as `0 to 1e300` would not.
+    let count = (span / range.step + 1e-9).floor() + 1.0;
+    if !count.is_finite() || count > config::limits().max_table_rows as f64 {
+        return Err(format!("Table exceeds the limit of {} rows", config::limits().max_table_rows));
+    }
+    Ok((0..count as usize).map(|i| range.from + i as f64 * range.step).collect())
+}
+
+fn evaluate_row(compiled: &CompiledExpression, values: Vec<Option<f64>>) -> TableRow {
+    let missing = compiled
+        .variables()
+        .iter()
+        .zip(&values)
+        .find(|(_, value)| value.is_none())
+        .map(|(name, _)| name.clone());
+
+    let inputs: Vec<f64> = values.iter().map(|v| v.unwrap_or(f64::NAN)).collect();
+    let outcome = match missing {
+        Some(name) => Err(format!("Unbound variable '{}'", name)),
+        None => compiled.evaluate(&inputs).map_err(String::from),
+    };
+    let inputs = inputs.into_iter().map(ExtendedReal).collect();
+    match outcome {
+        Ok(result) => TableRow { inputs, result: Some(result), error: None },
+        Err(error) => TableRow { inputs, result: None, error: Some(error) },
+    }
+}
+
+pub fn evaluate_table(compiled: &CompiledExpression, request: &TableRequest) -> Result<Vec<TableRow>, String> {
+    let variables = compiled.variables();
+    let base: Vec<Option<f64>> = variables.iter().map(|v| request.bindings.get(v).copied()).collect();
+
+    if !request.ranges.is_empty() && !request.rows.is_empty() {
+        return Err("Use either ranges or rows, not both".to_string());
+    }
+
+    if !request.rows.is_empty() {
+        if request.rows.len() > config::limits().max_table_rows {
+            return Err(format!("Table exceeds the limit of {} rows", config::limits().max_table_rows));
+        }
+        return Ok(request
+            .rows
+            .iter()
+            .map(|row| {
+                let values = variables
+                    .iter()
+                    .zip(&base)
+                    .map(|(name, fallback)| row.get(name).copied().or(*fallback))
+                    .collect();
+                evaluate_row(compiled, values)
+            })
+            .collect());
+    }
+
+    let mut axes = Vec::new();
+    let mut total: usize = 1;
+    for range in &request.ranges {
+        let slot = variables
+            .iter()
+            .position(|v| *v == range.variable)
+            .ok_or_else(|| format!("Variable '{}' does not appear in the expression", range.variable))?;
+        let values = range_values(range)?;
+        total = total.saturating_mul(values.len());
+        if total > config::limits().max_table_rows {
+            return Err(format!("Table exceeds the limit of {} rows", config::limits().max_table_rows));
+        }
+        axes.push((slot, values));
+    }
+
+    let mut rows = Vec::with_capacity(total);
+    for index in 0..total {
+        let mut values = base.clone();
+        let mut remainder = index;
+        for (slot, axis) in axes.iter().rev() {
+            values[*slot] = Some(axis[remainder % axis.len()]);
+            remainder /= axis.len();
+        }
+        rows.push(evaluate_row(compiled, values));
+    }
+    Ok(rows)
+}
+
+pub const MAX_TRUTH_TABLE_VARIABLES: usize = 16;
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct TruthTableRequest {
+    pub expression: String,
+    #[serde(default)]
+    pub bindings: HashMap<String, f64>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct TruthTableRow {
+    pub inputs: Vec<bool>,
+    pub result: Option<Value>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct TruthTableResponse {
+    pub variables: Vec<String>,
+    pub rows: Vec<TruthTableRow>,
+    pub error: Option<String>,
+}
+
+impl TruthTableResponse {
+    pub fn error(message: String) -> Self {
+        Self { variables: Vec::new(), rows: Vec::new(), error: Some(message) }
+    }
+}
+
+/// Evaluates `compiled` for every assignment of its boolean variables, counting from all
+/// false to all true with the first variable as the most significant bit. Numeric
+/// variables are fixed by `bindings`.
+pub fn evaluate_truth_table(compiled: &CompiledExpression, bindings: &HashMap<String, f64>) -> Result<TruthTableResponse, String> {
+    let mut values = vec![0.0; compiled.variables().len()];
+    let mut boolean_slots = Vec::new();
+    for (slot, name) in compiled.variables().iter().enumerate() {
+        match compiled.variable_type(slot) {
+            Type::Boolean => boolean_slots.push(slot),
+            _ => {
+                values[slot] = *bindings
+                    .get(name)
+                    .ok_or_else(|| format!("Numeric variable '{}' needs a value in bindings", name))?;
+            }
+        }
+    }
+    if boolean_slots.len() > MAX_TRUTH_TABLE_VARIABLES {
+        return Err(format!("Truth tables are limited to {} boolean variables", MAX_TRUTH_TABLE_VARIABLES));
+    }
+
+    let count = boolean_slots.len();
+    let rows = (0..1usize << count)
+        .map(|index| {
+            let inputs: Vec<bool> = (0..count).map(|i| index >> (count - 1 - i) & 1 == 1).collect();
+            for (slot, input) in boolean_slots.iter().zip(&inputs) {
+                values[*slot] = if *input { 1.0 } else { 0.0 };
+            }
+            match compiled.evaluate(&values) {
+                Ok(result) => TruthTableRow { inputs, result: Some(result), error: None },
+                Err(error) => TruthTableRow { inputs, result: None, error: Some(error.into()) },
+            }
+        })
+        .collect();
+
+    Ok(TruthTableResponse {
+        variables: boolean_slots.iter().map(|slot| compiled.variables()[*slot].clone()).collect(),
+        rows,
+        error: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser;
+
+    fn compiled(expression: &str) -> CompiledExpression {
+        CompiledExpression::compile(&parser::parse(expression).unwrap(), &[]).unwrap()
+    }
+
+    fn range(from: f64, to: f64, step: f64) -> RangeSpec {
+        RangeSpec { variable: "x".to_string(), from, to, step }
+    }
+
+    fn request(ranges: Vec<RangeSpec>) -> TableRequest {
+        TableRequest {
+            expression: None,
+            id: None,
+            variables: Vec::new(),
+            ranges,
+            rows: Vec::new(),
+            bindings: HashMap::new(),
+            seed: None,
+        }
+    }
+
+    #[test]
+    fn ranges_include_their_end() {
+        assert_eq!(range_values(&range(0.0, 1.0, 0.25)).unwrap(), vec![0.0, 0.25, 0.5, 0.75, 1.0]);
+        assert_eq!(range_values(&range(0.0, 1.0, 0.1)).unwrap().len(), 11);
+        assert_eq!(range_values(&range(3.0, 1.0, -1.0)).unwrap(), vec![3.0, 2.0, 1.0]);
+        assert_eq!(range_values(&range(2.0, 2.0, 1.0)).unwrap(), vec![2.0]);
+    }
+
+    #[test]
+    fn bad_and_oversized_ranges_are_errors() {
+        assert!(range_values(&range(0.0, 1.0, 0.0)).is_err());
+        assert!(range_values(&range(0.0, 1.0, -1.0)).is_err());
+        assert!(range_values(&range(0.0, f64::INFINITY, 1.0)).is_err());
+        assert!(range_values(&range(f64::NAN, 1.0, 1.0)).is_err());
+        assert!(range_values(&range(0.0, 1e300, 1.0)).unwrap_err().contains("exceeds the limit"));
+        assert!(range_values(&range(-1.7e308, 1.7e308, 1.0)).is_err());
+        assert!(range_values(&range(0.0, 1.0, 1e-300)).is_err());
+    }
+
+    #[test]
+    fn grids_vary_the_last_range_fastest() {
+        let mut request = request(vec![range(0.0, 1.0, 1.0), RangeSpec { variable: "y".to_string(), ..range(0.0, 2.0, 1.0) }]);
+        let rows = evaluate_table(&compiled("x * 10 + y"), &request).unwrap();
+        let results: Vec<f64> = rows.iter().map(|row| row.result.unwrap().as_f64()).collect();
+        assert_eq!(results, vec![0.0, 1.0, 2.0, 10.0, 11.0, 12.0]);
+
+        request.ranges.truncate(1);
+        let rows = evaluate_table(&compiled("x * 10 + y"), &request).unwrap();
+        assert_eq!(rows[0].error.as_deref(), Some("Unbound variable 'y'"));
+        request.ranges[0].variable = "z".to_string();
+        assert!(evaluate_table(&compiled("x"), &request).is_err());
+    }
+
+    #[test]
+    fn truth_tables_count_up_from_all_false() {
+        let table = evaluate_truth_table(&compiled("a and not b"), &HashMap::new()).unwrap();
+        assert_eq!(table.variables, vec!["a", "b"]);
+        let rows: Vec<(Vec<bool>, Value)> = table.rows.into_iter().map(|row| (row.inputs, row.result.unwrap())).collect();
+        assert_eq!(rows[2], (vec![true, false], Value::Boolean(true)));
+        assert_eq!(rows.iter().filter(|(_, result)| *result == Value::Boolean(true)).count(), 1);
+        assert!(evaluate_truth_table(&compiled("a and x > 1"), &HashMap::new()).is_err());
+    }
+}
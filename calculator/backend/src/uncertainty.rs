@@ -0,0 +1,697 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::f64::consts::{FRAC_PI_2, LN_10, LN_2, PI};
+use std::sync::Arc;
+
+use crate::ast::{BinaryOp, Expr, UnaryOp};
+use crate::compile::{apply_binary, apply_unary};
+use crate::format::{format_number, FormatOptions};
+use crate::functions;
+use crate::i18n::{BOOLEANS_UNSUPPORTED, DATES_UNSUPPORTED, TEXT_UNSUPPORTED};
+use crate::registry::{self, Function};
+use crate::value::ExtendedReal;
+use crate::parser;
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Mode {
+    /// First-order (linear) error propagation with Gaussian standard uncertainties.
+    #[default]
+    Linear,
+    /// Guaranteed enclosures, rounded outward after every operation.
+    Interval,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct UncertaintyRequest {
+    pub expression: String,
+    #[serde(default)]
+    pub mode: Mode,
+    #[serde(default)]
+    pub bindings: HashMap<String, String>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct UncertaintyResponse {
+    pub mode: Mode,
+    pub value: ExtendedReal,
+    pub uncertainty: ExtendedReal,
+    pub low: ExtendedReal,
+    pub high: ExtendedReal,
+    pub formatted: Option<String>,
+    pub error: Option<String>,
+}
+
+impl UncertaintyResponse {
+    pub fn error(mode: Mode, message: String) -> Self {
+        let zero = ExtendedReal(0.0);
+        Self { mode, value: zero, uncertainty: zero, low: zero, high: zero, formatted: None, error: Some(message) }
+    }
+}
+
+pub fn run(request: &UncertaintyRequest) -> Result<UncertaintyResponse, String> {
+    let expr = parser::parse(&request.expression)?;
+    let mut bindings = HashMap::new();
+    for (name, source) in &request.bindings {
+        bindings.insert(name.clone(), parser::parse(source).map_err(|e| format!("Binding '{}': {}", name, e))?);
+    }
+
+    match request.mode {
+        Mode::Linear => {
+            let mut evaluator = LinearEvaluator {
+                bindings: &bindings,
+                cache: HashMap::new(),
+                sigmas: Vec::new(),
+                resolving: Vec::new(),
+                depth: 0,
+            };
+            let result = evaluator.eval(&expr)?;
+            let sigma = evaluator.sigma(&result);
+            Ok(UncertaintyResponse {
+                mode: Mode::Linear,
+                value: ExtendedReal(result.value),
+                uncertainty: ExtendedReal(sigma),
+                low: ExtendedReal(result.value - sigma),
+                high: ExtendedReal(result.value + sigma),
+                formatted: Some(format_uncertain(result.value, sigma)),
+                error: None,
+            })
+        }
+        Mode::Interval => {
+            let mut evaluator = IntervalEvaluator { bindings: &bindings, cache: HashMap::new(), resolving: Vec::new(), depth: 0 };
+            let result = evaluator.eval(&expr)?;
+            Ok(UncertaintyResponse {
+                mode: Mode::Interval,
+                value: ExtendedReal(result.midpoint()),
+                uncertainty: ExtendedReal(result.radius()),
+                low: ExtendedReal(result.low),
+                high: ExtendedReal(result.high),
+                formatted: Some(format!(
+                    "[{}, {}]",
+                    format_number(result.low, &FormatOptions::default())?,
+                    format_number(result.high, &FormatOptions::default())?
+                )),
+                error: None,
+            })
+        }
+    }
+}
+
+/// Rounds the uncertainty to two significant digits and the value to the same decimal place.
+fn format_uncertain(value: f64, sigma: f64) -> String {
+    if sigma == 0.0 || !sigma.is_finite() {
+        return value.to_string();
+    }
+    let decimals = (1 - sigma.log10().floor() as i32).max(0) as usize;
+    format!("{:.*} ± {:.*}", decimals, value, decimals, sigma)
+}
+
+// Expressions are parsed no deeper than `parser::MAX_DEPTH`, but bindings can chain them
+// into a deeper tree, so evaluation counts its depth as well.
+fn enter(depth: &mut usize) -> Result<(), String> {
+    if *depth == parser::MAX_DEPTH {
+        return Err(format!("Bindings nest more than {} levels deep", parser::MAX_DEPTH));
+    }
+    *depth += 1;
+    Ok(())
+}
+
+/// Marks the binding `name` as being evaluated, unless that closes a cycle.
+fn resolve(resolving: &mut Vec<String>, name: &str) -> Result<(), String> {
+    if let Some(start) = resolving.iter().position(|other| other == name) {
+        let cycle: Vec<&str> = resolving[start..].iter().map(String::as_str).chain([name]).collect();
+        return Err(format!("Circular binding: {}", cycle.join(" → ")));
+    }
+    resolving.push(name.to_string());
+    Ok(())
+}
+
+fn constant(name: &str) -> Result<f64, String> {
+    functions::constant(name).ok_or_else(|| format!("Unknown variable '{}'", name))
+}
+
+fn lookup(name: &str) -> Result<Arc<dyn Function>, String> {
+    let function = registry::lookup(name).ok_or_else(|| format!("Unknown function '{}'", name))?;
+    if !function.pure() || !function.is_numeric() {
+        return Err(format!("Function '{}' cannot be used with uncertain values", name));
+    }
+    Ok(function)
+}
+
+// A value with its partial derivatives with respect to each independent uncertain input,
+// so that repeated uses of the same input stay correlated (`x - x` has no uncertainty).
+#[derive(Debug, Clone)]
+struct Linear {
+    value: f64,
+    partials: BTreeMap<usize, f64>,
+}
+
+impl Linear {
+    fn exact(value: f64) -> Self {
+        Self { value, partials: BTreeMap::new() }
+    }
+
+    fn combine(value: f64, terms: &[(&Linear, f64)]) -> Self {
+        let mut partials = BTreeMap::new();
+        for (operand, derivative) in terms {
+            if *derivative == 0.0 {
+                continue;
+            }
+            for (source, partial) in &operand.partials {
+                *partials.entry(*source).or_insert(0.0) += derivative * partial;
+            }
+        }
+        Self { value, partials }
+    }
+}
+
+struct LinearEvaluator<'a> {
+    bindings: &'a HashMap<String, Expr>,
+    cache: HashMap<String, Linear>,
+    sigmas: Vec<f64>,
+    resolving: Vec<String>,
+    depth: usize,
+}
+
+impl LinearEvaluator<'_> {
+    fn sigma(&self, result: &Linear) -> f64 {
+        result
+            .partials
+            .iter()
+            .map(|(source, partial)| (partial * self.sigmas[*source]).powi(2))
+            .sum::<f64>()
+            .sqrt()
+    }
+
+    fn source(&mut self, value: f64, sigma: f64) -> Result<Linear, String> {
+        if sigma < 0.0 || !sigma.is_finite() {
+            return Err("Uncertainty must be a finite, non-negative number".to_string());
+        }
+        self.sigmas.push(sigma);
+        let mut partials = BTreeMap::new();
+        partials.insert(self.sigmas.len() - 1, 1.0);
+        Ok(Linear { value, partials })
+    }
+
+    fn exact_value(&mut self, expr: &Expr) -> Result<f64, String> {
+        let value = self.eval(expr)?;
+        if !value.partials.is_empty() {
+            return Err("The parts of '±' and '[a, b]' must be exact numbers".to_string());
+        }
+        Ok(value.value)
+    }
+
+    fn eval(&mut self, expr: &Expr) -> Result<Linear, String> {
+        enter(&mut self.depth)?;
+        let value = self.evaluate(expr);
+        self.depth -= 1;
+        value
+    }
+
+    fn evaluate(&mut self, expr: &Expr) -> Result<Linear, String> {
+        match expr {
+            Expr::Number { value, .. } => Ok(Linear::exact(*value)),
+            Expr::Boolean { .. } | Expr::Unary { op: UnaryOp::Not, .. } => {
+                Err(BOOLEANS_UNSUPPORTED.error().to_string())
+            }
+            Expr::Duration { .. } | Expr::Workdays { .. } | Expr::Date { .. } => {
+                Err(DATES_UNSUPPORTED.error().to_string())
+            }
+            Expr::Text { .. } => Err(TEXT_UNSUPPORTED.error().to_string()),
+            Expr::Binary { op, .. } if op.is_comparison() || op.is_logical() => {
+                Err(BOOLEANS_UNSUPPORTED.error().to_string())
+            }
+            Expr::Variable { name, .. } => {
+                if let Some(value) = self.cache.get(name) {
+                    return Ok(value.clone());
+                }
+                let value = match self.bindings.get(name) {
+                    Some(bound) => {
+                        resolve(&mut self.resolving, name)?;
+                        let value = self.eval(bound);
+                        self.resolving.pop();
+                        value?
+                    }
+                    None => Linear::exact(constant(name)?),
+                };
+                self.cache.insert(name.clone(), value.clone());
+                Ok(value)
+            }
+            Expr::Interval { low, high, .. } => {
+                let (low, high) = (self.exact_value(low)?, self.exact_value(high)?);
+                if low > high {
+                    return Err("Interval lower bound exceeds its upper bound".to_string());
+                }
+                self.source((low + high) / 2.0, (high - low) / 2.0)
+            }
+            Expr::Binary { op: BinaryOp::PlusMinus, left, right, .. } => {
+                let (value, sigma) = (self.exact_value(left)?, self.exact_value(right)?);
+                self.source(value, sigma)
+            }
+            Expr::Unary { op, operand, .. } => {
+                let x = self.eval(operand)?;
+                let value = apply_unary(*op, x.value)?;
+                let derivative = match op {
+                    UnaryOp::Neg => -1.0,
+                    UnaryOp::Plus => 1.0,
+                    UnaryOp::Factorial if x.partials.is_empty() => 0.0,
+                    UnaryOp::Factorial => return Err("Factorial of an uncertain value is not defined".to_string()),
+                    UnaryOp::Not => unreachable!(),
+                };
+                Ok(Linear::combine(value, &[(&x, derivative)]))
+            }
+            Expr::Binary { op, left, right, .. } => {
+                let a = self.eval(left)?;
+                let b = self.eval(right)?;
+                let value = apply_binary(*op, a.value, b.value)?;
+                let (da, db) = match op {
+                    BinaryOp::Add => (1.0, 1.0),
+                    BinaryOp::Sub => (1.0, -1.0),
+                    BinaryOp::Mul => (b.value, a.value),
+                    BinaryOp::Div => (1.0 / b.value, -a.value / (b.value * b.value)),
+                    BinaryOp::Rem => (1.0, -(a.value / b.value).trunc()),
+                    BinaryOp::Pow => {
+                        let da = b.value * a.value.powf(b.value - 1.0);
+                        let db = if b.partials.is_empty() { 0.0 } else { value * a.value.ln() };
+                        (da, db)
+                    }
+                    _ => unreachable!(),
+                };
+                Ok(Linear::combine(value, &[(&a, da), (&b, db)]))
+            }
+            Expr::Call { name, args, .. } => {
+                let function = lookup(name)?;
+                function.check_arity(args.len())?;
+                let values = args.iter().map(|arg| self.eval(arg)).collect::<Result<Vec<_>, _>>()?;
+                let xs: Vec<f64> = values.iter().map(|v| v.value).collect();
+                let value = function.evaluate(&xs)?;
+
+                let derivatives = (0..xs.len())
+                    .map(|i| match unary_derivative(name, xs[i], value) {
+                        _ if values[i].partials.is_empty() => Ok(0.0),
+                        Some(derivative) if xs.len() == 1 => Ok(derivative),
+                        _ => numeric_partial(&*function, &xs, i),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                let terms: Vec<(&Linear, f64)> = values.iter().zip(derivatives).collect();
+                Ok(Linear::combine(value, &terms))
+            }
+        }
+    }
+}
+
+fn unary_derivative(name: &str, x: f64, fx: f64) -> Option<f64> {
+    Some(match name {
+        "abs" => x.signum(),
+        "sign" => 0.0,
+        "sqrt" => 0.5 / fx,
+        "cbrt" => 1.0 / (3.0 * fx * fx),
+        "exp" => fx,
+        "ln" => 1.0 / x,
+        "log10" | "log" => 1.0 / (x * LN_10),
+        "log2" => 1.0 / (x * LN_2),
+        "sin" => x.cos(),
+        "cos" => -x.sin(),
+        "tan" => 1.0 + fx * fx,
+        "asin" => 1.0 / (1.0 - x * x).sqrt(),
+        "acos" => -1.0 / (1.0 - x * x).sqrt(),
+        "atan" => 1.0 / (1.0 + x * x),
+        "sinh" => x.cosh(),
+        "cosh" => x.sinh(),
+        "tanh" => 1.0 - fx * fx,
+        "floor" | "ceil" | "round" | "trunc" => 0.0,
+        _ => return None,
+    })
+}
+
+// Central difference for functions without a closed-form derivative above.
+fn numeric_partial(function: &dyn Function, xs: &[f64], index: usize) -> Result<f64, String> {
+    let h = 1e-6 * xs[index].abs().max(1.0);
+    let mut forward = xs.to_vec();
+    let mut backward = xs.to_vec();
+    forward[index] += h;
+    backward[index] -= h;
+    Ok((function.evaluate(&forward)? - function.evaluate(&backward)?) / (2.0 * h))
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Interval {
+    low: f64,
+    high: f64,
+}
+
+impl Interval {
+    fn point(value: f64) -> Self {
+        Self { low: value, high: value }
+    }
+
+    // Widens by one ulp on each side so the true result stays enclosed despite rounding.
+    // Zero and infinite bounds are exact and stay as they are.
+    fn outward(low: f64, high: f64) -> Result<Self, String> {
+        if low.is_nan() || high.is_nan() {
+            return Err("Interval result is undefined".to_string());
+        }
+        let widen = |x: f64, f: fn(f64) -> f64| if x == 0.0 || x.is_infinite() { x } else { f(x) };
+        Ok(Self { low: widen(low, f64::next_down), high: widen(high, f64::next_up) })
+    }
+
+    fn midpoint(&self) -> f64 {
+        self.low + (self.high - self.low) / 2.0
+    }
+
+    fn radius(&self) -> f64 {
+        (self.high - self.low) / 2.0
+    }
+
+    fn is_point(&self) -> bool {
+        self.low == self.high
+    }
+
+    fn contains(&self, x: f64) -> bool {
+        self.low <= x && x <= self.high
+    }
+
+    fn from_corners(values: [f64; 4]) -> Result<Self, String> {
+        let low = values.iter().copied().fold(f64::INFINITY, f64::min);
+        let high = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        Self::outward(low, high)
+    }
+
+    fn monotone(&self, f: fn(f64) -> f64, increasing: bool) -> Result<Self, String> {
+        let (a, b) = (f(self.low), f(self.high));
+        if increasing {
+            Self::outward(a, b)
+        } else {
+            Self::outward(b, a)
+        }
+    }
+
+    // Extremes of a periodic function: endpoints plus any interior critical points.
+    fn periodic(&self, f: fn(f64) -> f64, first_peak: f64) -> Result<Self, String> {
+        if self.high - self.low >= 2.0 * PI {
+            return Ok(Interval { low: -1.0, high: 1.0 });
+        }
+        let (mut low, mut high) = (f(self.low).min(f(self.high)), f(self.low).max(f(self.high)));
+        let mut k = ((self.low - first_peak) / PI).ceil();
+        while first_peak + k * PI <= self.high {
+            let extreme = f(first_peak + k * PI).round();
+            low = low.min(extreme);
+            high = high.max(extreme);
+            k += 1.0;
+        }
+        let bounds = Self::outward(low, high)?;
+        Ok(Interval { low: bounds.low.max(-1.0), high: bounds.high.min(1.0) })
+    }
+
+    fn power(&self, exponent: Interval) -> Result<Self, String> {
+        if exponent.is_point() && exponent.low.fract() == 0.0 {
+            let n = exponent.low;
+            if n >= 0.0 && n % 2.0 == 0.0 && self.contains(0.0) {
+                let magnitude = self.low.abs().max(self.high.abs());
+                return Self::outward(0.0, magnitude.powf(n));
+            }
+            if n < 0.0 && self.contains(0.0) {
+                return Err("Negative power of an interval containing zero".to_string());
+            }
+            return Self::from_corners([self.low.powf(n), self.high.powf(n), self.low.powf(n), self.high.powf(n)]);
+        }
+        if self.low < 0.0 {
+            return Err("Fractional power of an interval with negative values".to_string());
+        }
+        if self.low == 0.0 && exponent.low <= 0.0 {
+            return Err("Non-positive power of an interval containing zero".to_string());
+        }
+        Self::from_corners([
+            self.low.powf(exponent.low),
+            self.low.powf(exponent.high),
+            self.high.powf(exponent.low),
+            self.high.powf(exponent.high),
+        ])
+    }
+}
+
+struct IntervalEvaluator<'a> {
+    bindings: &'a HashMap<String, Expr>,
+    cache: HashMap<String, Interval>,
+    resolving: Vec<String>,
+    depth: usize,
+}
+
+impl IntervalEvaluator<'_> {
+    fn exact_value(&mut self, expr: &Expr) -> Result<f64, String> {
+        let value = self.eval(expr)?;
+        if !value.is_point() {
+            return Err("The parts of '±' and '[a, b]' must be exact numbers".to_string());
+        }
+        Ok(value.low)
+    }
+
+    fn eval(&mut self, expr: &Expr) -> Result<Interval, String> {
+        enter(&mut self.depth)?;
+        let value = self.evaluate(expr);
+        self.depth -= 1;
+        value
+    }
+
+    fn evaluate(&mut self, expr: &Expr) -> Result<Interval, String> {
+        match expr {
+            Expr::Number { value, .. } => Ok(Interval::point(*value)),
+            Expr::Boolean { .. } | Expr::Unary { op: UnaryOp::Not, .. } => {
+                Err(BOOLEANS_UNSUPPORTED.error().to_string())
+            }
+            Expr::Duration { .. } | Expr::Workdays { .. } | Expr::Date { .. } => {
+                Err(DATES_UNSUPPORTED.error().to_string())
+            }
+            Expr::Text { .. } => Err(TEXT_UNSUPPORTED.error().to_string()),
+            Expr::Binary { op, .. } if op.is_comparison() || op.is_logical() => {
+                Err(BOOLEANS_UNSUPPORTED.error().to_string())
+            }
+            Expr::Variable { name, .. } => {
+                if let Some(value) = self.cache.get(name) {
+                    return Ok(*value);
+                }
+                let value = match self.bindings.get(name) {
+                    Some(bound) => {
+                        resolve(&mut self.resolving, name)?;
+                        let value = self.eval(bound);
+                        self.resolving.pop();
+                        value?
+                    }
+                    None => Interval::point(constant(name)?),
+                };
+                self.cache.insert(name.clone(), value);
+                Ok(value)
+            }
+            Expr::Interval { low, high, .. } => {
+                let (low, high) = (self.exact_value(low)?, self.exact_value(high)?);
+                if low > high {
+                    return Err("Interval lower bound exceeds its upper bound".to_string());
+                }
+                Ok(Interval { low, high })
+            }
+            Expr::Unary { op, operand, .. } => {
+                let x = self.eval(operand)?;
+                match op {
+                    UnaryOp::Neg => Ok(Interval { low: -x.high, high: -x.low }),
+                    UnaryOp::Plus => Ok(x),
+                    UnaryOp::Factorial if x.is_point() => Ok(Interval::point(apply_unary(*op, x.low)?)),
+                    UnaryOp::Factorial => Err("Factorial of an interval is not defined".to_string()),
+                    UnaryOp::Not => unreachable!(),
+                }
+            }
+            Expr::Binary { op: BinaryOp::PlusMinus, left, right, .. } => {
+                let (value, radius) = (self.exact_value(left)?, self.exact_value(right)?);
+                if radius < 0.0 || !radius.is_finite() {
+                    return Err("Uncertainty must be a finite, non-negative number".to_string());
+                }
+                Interval::outward(value - radius, value + radius)
+            }
+            Expr::Binary { op, left, right, .. } => {
+                let a = self.eval(left)?;
+                let b = self.eval(right)?;
+                match op {
+                    BinaryOp::Add => Interval::outward(a.low + b.low, a.high + b.high),
+                    BinaryOp::Sub => Interval::outward(a.low - b.high, a.high - b.low),
+                    BinaryOp::Mul => Interval::from_corners([a.low * b.low, a.low * b.high, a.high * b.low, a.high * b.high]),
+                    BinaryOp::Div => {
+                        if b.contains(0.0) {
+                            return Err("Division by an interval containing zero".to_string());
+                        }
+                        Interval::from_corners([a.low / b.low, a.low / b.high, a.high / b.low, a.high / b.high])
+                    }
+                    BinaryOp::Pow => a.power(b),
+                    BinaryOp::Rem if a.is_point() && b.is_point() => Ok(Interval::point(apply_binary(*op, a.low, b.low)?)),
+                    BinaryOp::Rem => Err("'%' is not supported for intervals".to_string()),
+                    _ => unreachable!(),
+                }
+            }
+            Expr::Call { name, args, .. } => {
+                let function = lookup(name)?;
+                function.check_arity(args.len())?;
+                let values = args.iter().map(|arg| self.eval(arg)).collect::<Result<Vec<_>, _>>()?;
+                if values.iter().all(Interval::is_point) {
+                    let xs: Vec<f64> = values.iter().map(|v| v.low).collect();
+                    let value = function.evaluate(&xs)?;
+                    return Interval::outward(value, value);
+                }
+                interval_function(name, &values)
+            }
+        }
+    }
+}
+
+fn interval_function(name: &str, args: &[Interval]) -> Result<Interval, String> {
+    let x = args[0];
+    let require = |ok: bool, message: &str| if ok { Ok(()) } else { Err(format!("{}: {}", name, message)) };
+
+    match name {
+        "sqrt" => {
+            require(x.low >= 0.0, "interval includes negative values")?;
+            x.monotone(f64::sqrt, true)
+        }
+        "ln" | "log10" | "log2" | "log" if args.len() == 1 => {
+            require(x.low > 0.0, "interval includes non-positive values")?;
+            let f = match name {
+                "ln" => f64::ln,
+                "log2" => f64::log2,
+                _ => f64::log10,
+            };
+            x.monotone(f, true)
+        }
+        "asin" | "acos" => {
+            require(x.low >= -1.0 && x.high <= 1.0, "interval exceeds [-1, 1]")?;
+            if name == "asin" {
+                x.monotone(f64::asin, true)
+            } else {
+                x.monotone(f64::acos, false)
+            }
+        }
+        "cbrt" => x.monotone(f64::cbrt, true),
+        "exp" => x.monotone(f64::exp, true),
+        "atan" => x.monotone(f64::atan, true),
+        "sinh" => x.monotone(f64::sinh, true),
+        "tanh" => x.monotone(f64::tanh, true),
+        "floor" => x.monotone(f64::floor, true),
+        "ceil" => x.monotone(f64::ceil, true),
+        "round" => x.monotone(f64::round, true),
+        "trunc" => x.monotone(f64::trunc, true),
+        "abs" | "cosh" => {
+            let f = if name == "abs" { f64::abs } else { f64::cosh };
+            let high = f(x.low).max(f(x.high));
+            let low = if x.contains(0.0) { f(0.0) } else { f(x.low).min(f(x.high)) };
+            Interval::outward(low, high)
+        }
+        "sin" => x.periodic(f64::sin, FRAC_PI_2),
+        "cos" => x.periodic(f64::cos, 0.0),
+        "tan" => {
+            let k = ((x.low - FRAC_PI_2) / PI).ceil();
+            require(FRAC_PI_2 + k * PI > x.high, "interval crosses an asymptote")?;
+            x.monotone(f64::tan, true)
+        }
+        "min" | "max" => {
+            let pick = if name == "min" { f64::min } else { f64::max };
+            let low = args.iter().map(|a| a.low).reduce(pick).unwrap();
+            let high = args.iter().map(|a| a.high).reduce(pick).unwrap();
+            Ok(Interval { low, high })
+        }
+        "pow" => args[0].power(args[1]),
+        _ => Err(format!("Function '{}' is not supported for intervals", name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(expression: &str, mode: Mode, bindings: &[(&str, &str)]) -> UncertaintyRequest {
+        let bindings = bindings.iter().map(|(name, source)| (name.to_string(), source.to_string())).collect();
+        UncertaintyRequest { expression: expression.to_string(), mode, bindings }
+    }
+
+    fn linear(expression: &str) -> (f64, f64) {
+        let response = run(&request(expression, Mode::Linear, &[])).unwrap();
+        (response.value.0, response.uncertainty.0)
+    }
+
+    fn interval(expression: &str) -> (f64, f64) {
+        let response = run(&request(expression, Mode::Interval, &[])).unwrap();
+        (response.low.0, response.high.0)
+    }
+
+    fn close(actual: f64, expected: f64) -> bool {
+        (actual - expected).abs() <= 1e-12 * expected.abs().max(1.0)
+    }
+
+    #[test]
+    fn linear_propagation() {
+        let (value, sigma) = linear("(10 ± 0.3) + (5 ± 0.4)");
+        assert!(close(value, 15.0) && close(sigma, 0.5), "{} ± {}", value, sigma);
+        let (value, sigma) = linear("(2 ± 0.1) * (3 ± 0.2)");
+        assert!(close(value, 6.0) && close(sigma, 0.5), "{} ± {}", value, sigma);
+        let (value, sigma) = linear("sqrt(4 ± 0.4)");
+        assert!(close(value, 2.0) && close(sigma, 0.1), "{} ± {}", value, sigma);
+
+        // A bound variable is one quantity, so it is correlated with itself.
+        let response = run(&request("x - x", Mode::Linear, &[("x", "5 ± 1")])).unwrap();
+        assert_eq!((response.value.0, response.uncertainty.0), (0.0, 0.0));
+        let response = run(&request("x + x", Mode::Linear, &[("x", "5 ± 1")])).unwrap();
+        assert!(close(response.uncertainty.0, 2.0));
+        assert_eq!(response.formatted.as_deref(), Some("10.0 ± 2.0"));
+    }
+
+    #[test]
+    fn intervals_enclose_the_result() {
+        let (low, high) = interval("[1, 2] * [3, 4]");
+        assert!(low <= 3.0 && close(low, 3.0) && high >= 8.0 && close(high, 8.0), "[{}, {}]", low, high);
+        let (low, high) = interval("[-1, 2]^2");
+        assert!(low <= 0.0 && close(low, 0.0) && high >= 4.0 && close(high, 4.0), "[{}, {}]", low, high);
+        let (low, high) = interval("sin([0, pi])");
+        assert!(low <= 0.0 && close(high, 1.0) && high >= 1.0, "[{}, {}]", low, high);
+        // Outward rounding widens an inexact sum to the floats on either side of it.
+        let (low, high) = interval("[0.1, 0.1] + [0.2, 0.2]");
+        assert!(low < high && low <= 0.1 + 0.2 && 0.1 + 0.2 <= high, "[{}, {}]", low, high);
+    }
+
+    #[test]
+    fn undefined_uses_are_errors() {
+        let error = |expression: &str, mode: Mode| run(&request(expression, mode, &[])).unwrap_err();
+        assert_eq!(error("1 / [-1, 1]", Mode::Interval), "Division by an interval containing zero");
+        assert_eq!(error("[2, 1]", Mode::Interval), "Interval lower bound exceeds its upper bound");
+        assert_eq!(error("sqrt([-1, 1])", Mode::Interval), "sqrt: interval includes negative values");
+        assert_eq!(error("tan([1, 2])", Mode::Interval), "tan: interval crosses an asymptote");
+        assert_eq!(error("5 ± -1", Mode::Linear), "Uncertainty must be a finite, non-negative number");
+        assert_eq!(error("(5 ± 1)!", Mode::Linear), "Factorial of an uncertain value is not defined");
+        assert_eq!(error("y + 1", Mode::Linear), "Unknown variable 'y'");
+    }
+
+    #[test]
+    fn circular_bindings_are_errors() {
+        for mode in [Mode::Linear, Mode::Interval] {
+            let error = |bindings: &[(&str, &str)]| run(&request("x", mode, bindings)).unwrap_err();
+            assert_eq!(error(&[("x", "x + 1")]), "Circular binding: x → x");
+            assert_eq!(error(&[("x", "y ± 1"), ("y", "2x")]), "Circular binding: x → y → x");
+            assert_eq!(error(&[("x", "y"), ("y", "z"), ("z", "y")]), "Circular binding: y → z → y");
+
+            // Long chains of bindings are limited like deep expressions.
+            let run_chain = |length: usize| {
+                let mut request = request("x0", mode, &[]);
+                for i in 0..length {
+                    request.bindings.insert(format!("x{}", i), format!("x{} + 1", i + 1));
+                }
+                request.bindings.insert(format!("x{}", length), "1".to_string());
+                run(&request)
+            };
+            assert_eq!(run_chain(100).unwrap().value.0, 101.0);
+            let message = format!("Bindings nest more than {} levels deep", parser::MAX_DEPTH);
+            assert_eq!(run_chain(200).unwrap_err(), message);
+        }
+    }
+
+    #[test]
+    fn non_finite_bounds_serialize_as_strings() {
+        let response = run(&request("x", Mode::Interval, &[("x", "[1, 1e308 * 10]")])).unwrap();
+        let response = serde_json::to_value(response).unwrap();
+        assert_eq!((&response["low"], &response["high"]), (&serde_json::json!(1.0), &serde_json::json!("inf")));
+    }
+}
@@ -0,0 +1,208 @@
+use schemars::gen::SchemaGenerator;
+use schemars::schema::Schema;
+use schemars::JsonSchema;
+use serde::ser::{Serialize, SerializeMap, Serializer};
+
+use crate::dates;
+use crate::format::{format_number, FormatOptions};
+use crate::i18n::{self, Error, Phrase};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Type {
+    Number,
+    Boolean,
+    Duration,
+    Date,
+    Workdays,
+}
+
+impl Type {
+    pub fn name(self) -> &'static str {
+        match self {
+            Type::Number => "number",
+            Type::Boolean => "boolean",
+            Type::Duration => "duration",
+            Type::Date => "date",
+            Type::Workdays => "workdays",
+        }
+    }
+
+    /// The name in every language, for error messages.
+    pub fn phrase(self) -> &'static Phrase {
+        match self {
+            Type::Number => &i18n::NUMBER,
+            Type::Boolean => &i18n::BOOLEAN,
+            Type::Duration => &i18n::DURATION,
+            Type::Date => &i18n::DATE,
+            Type::Workdays => &i18n::WORKDAYS,
+        }
+    }
+}
+
+/// A typed evaluation result. Compiled programs run on plain `f64`s (booleans as 1/0,
+/// durations in seconds, dates as days since 1970-01-01, workdays as a count); the
+/// static type recorded at compile time turns the final number back into a value.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    Number(f64),
+    Boolean(bool),
+    Duration(f64),
+    Date(f64),
+    Workdays(f64),
+}
+
+impl Value {
+    pub fn from_raw(raw: f64, value_type: Type) -> Self {
+        match value_type {
+            Type::Number => Value::Number(raw),
+            Type::Boolean => Value::Boolean(raw != 0.0),
+            Type::Duration => Value::Duration(raw),
+            Type::Date => Value::Date(raw),
+            Type::Workdays => Value::Workdays(raw),
+        }
+    }
+
+    pub fn value_type(self) -> Type {
+        match self {
+            Value::Number(_) => Type::Number,
+            Value::Boolean(_) => Type::Boolean,
+            Value::Duration(_) => Type::Duration,
+            Value::Date(_) => Type::Date,
+            Value::Workdays(_) => Type::Workdays,
+        }
+    }
+
+    pub fn as_f64(self) -> f64 {
+        match self {
+            Value::Boolean(value) => value as u8 as f64,
+            Value::Number(value) | Value::Duration(value) | Value::Date(value) | Value::Workdays(value) => value,
+        }
+    }
+
+    pub fn format(self, options: &FormatOptions) -> Result<String, Error> {
+        match self {
+            Value::Number(value) => format_number(value, options),
+            Value::Boolean(value) => Ok(value.to_string()),
+            Value::Duration(seconds) => Ok(dates::format_duration(seconds)),
+            Value::Date(days) => Ok(dates::format_date(days)),
+            Value::Workdays(count) => Ok(format!("{} workdays", format_number(count, options)?)),
+        }
+    }
+}
+
+/// A number as it goes out in JSON, which has no literal for NaN or infinity: those
+/// become the strings `"nan"`, `"inf"` and `"-inf"`, and -0 becomes 0.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExtendedReal(pub f64);
+
+impl Serialize for ExtendedReal {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.0 {
+            value if value.is_nan() => serializer.serialize_str("nan"),
+            f64::INFINITY => serializer.serialize_str("inf"),
+            f64::NEG_INFINITY => serializer.serialize_str("-inf"),
+            // Matches -0 too.
+            0.0 => serializer.serialize_f64(0.0),
+            value => serializer.serialize_f64(value),
+        }
+    }
+}
+
+impl JsonSchema for ExtendedReal {
+    fn schema_name() -> String {
+        "ExtendedReal".to_string()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        schema(serde_json::json!({
+            "description": "A number, or \"nan\", \"inf\" or \"-inf\".",
+            "oneOf": [{ "type": "number" }, { "type": "string", "enum": ["nan", "inf", "-inf"] }]
+        }))
+    }
+}
+
+/// Numbers and booleans serialize as plain JSON values; the other types as a one-field
+/// object naming their unit, e.g. `{"seconds": 5400}` or `{"date": "2026-05-30"}`.
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match *self {
+            Value::Number(value) => ExtendedReal(value).serialize(serializer),
+            Value::Boolean(value) => serializer.serialize_bool(value),
+            Value::Duration(seconds) => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry("seconds", &ExtendedReal(seconds))?;
+                map.end()
+            }
+            Value::Date(days) => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry("date", &dates::format_date(days))?;
+                map.end()
+            }
+            Value::Workdays(count) => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry("workdays", &ExtendedReal(count))?;
+                map.end()
+            }
+        }
+    }
+}
+
+impl JsonSchema for Value {
+    fn schema_name() -> String {
+        "Value".to_string()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        let number = serde_json::to_value(gen.subschema_for::<ExtendedReal>()).unwrap();
+        let unit = |name: &str, schema: &serde_json::Value| {
+            serde_json::json!({
+                "type": "object",
+                "required": [name],
+                "properties": { name: schema },
+                "additionalProperties": false
+            })
+        };
+        schema(serde_json::json!({
+            "description": "A number or boolean, or a duration, date or workday count named by its unit.",
+            "oneOf": [
+                number,
+                { "type": "boolean" },
+                unit("seconds", &number),
+                unit("date", &serde_json::json!({ "type": "string", "format": "date" })),
+                unit("workdays", &number)
+            ]
+        }))
+    }
+}
+
+fn schema(json: serde_json::Value) -> Schema {
+    serde_json::from_value(json).expect("a valid schema")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn json(value: impl Serialize) -> String {
+        serde_json::to_string(&value).unwrap()
+    }
+
+    #[test]
+    fn extended_reals() {
+        assert_eq!(json(ExtendedReal(1.5)), "1.5");
+        assert_eq!(json(ExtendedReal(f64::INFINITY)), r#""inf""#);
+        assert_eq!(json(ExtendedReal(f64::NEG_INFINITY)), r#""-inf""#);
+        assert_eq!(json(ExtendedReal(f64::NAN)), r#""nan""#);
+        assert_eq!(json(ExtendedReal(-0.0)), "0.0");
+        assert_eq!(json(ExtendedReal(f64::MAX)), "1.7976931348623157e+308");
+    }
+
+    #[test]
+    fn values_use_extended_reals() {
+        assert_eq!(json(Value::Number(f64::NAN)), r#""nan""#);
+        assert_eq!(json(Value::Number(-0.0)), "0.0");
+        assert_eq!(json(Value::Duration(f64::INFINITY)), r#"{"seconds":"inf"}"#);
+        assert_eq!(json(Value::Workdays(-0.0)), r#"{"workdays":0.0}"#);
+    }
+}
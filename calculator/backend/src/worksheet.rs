@@ -0,0 +1,444 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use crate::ast::Expr;
+use crate::compile::CompiledExpression;
+use crate::functions;
+use crate::parser;
+use crate::value::Value;
+
+pub const MAX_CELLS: usize = 10_000;
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct CellRequest {
+    pub expression: String,
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct CellView {
+    pub name: String,
+    pub expression: String,
+    pub dependencies: Vec<String>,
+    pub value: Option<Value>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct WorksheetResponse {
+    pub cells: Vec<CellView>,
+    pub error: Option<String>,
+}
+
+impl WorksheetResponse {
+    pub fn error(message: String) -> Self {
+        Self { cells: Vec::new(), error: Some(message) }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Cell {
+    expression: String,
+    ast: Expr,
+    dependencies: Vec<String>,
+    value: Result<Value, String>,
+}
+
+impl Cell {
+    fn dependencies(&self) -> &[String] {
+        &self.dependencies
+    }
+}
+
+/// Variable references (cells, session variables) in order of first appearance.
+pub fn references(expr: &Expr, names: &mut Vec<String>) {
+    match expr {
+        Expr::Variable { name, .. } => {
+            if functions::constant(name).is_none() && name != "today" && !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+        Expr::Unary { operand, .. } => references(operand, names),
+        Expr::Binary { left, right, .. } | Expr::Interval { low: left, high: right, .. } => {
+            references(left, names);
+            references(right, names);
+        }
+        Expr::Call { args, .. } => args.iter().for_each(|arg| references(arg, names)),
+        Expr::Number { .. }
+        | Expr::Boolean { .. }
+        | Expr::Duration { .. }
+        | Expr::Workdays { .. }
+        | Expr::Date { .. }
+        | Expr::Text { .. } => {}
+    }
+}
+
+/// Named cells holding expressions over other cells. Values are kept up to date: a change
+/// recomputes the cell and everything that depends on it, in topological order.
+#[derive(Debug, Default)]
+pub struct Worksheet {
+    cells: BTreeMap<String, Cell>,
+}
+
+fn check_cell_name(name: &str) -> Result<(), String> {
+    if !parser::is_identifier(name) || functions::constant(name).is_some() {
+        return Err(format!("'{}' is not a valid cell name", name));
+    }
+    Ok(())
+}
+
+impl Worksheet {
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    /// Sets a cell and returns it together with its recomputed dependents.
+    pub fn set(&mut self, name: &str, expression: &str) -> Result<Vec<CellView>, String> {
+        check_cell_name(name)?;
+        if !self.cells.contains_key(name) && self.cells.len() >= MAX_CELLS {
+            return Err(format!("A worksheet holds at most {} cells", MAX_CELLS));
+        }
+        let ast = parser::parse(expression)?;
+        let mut dependencies = Vec::new();
+        references(&ast, &mut dependencies);
+        let cell = Cell {
+            expression: expression.to_string(),
+            ast,
+            dependencies,
+            value: Err("Not evaluated".to_string()),
+        };
+        self.cells.insert(name.to_string(), cell);
+        Ok(self.recompute(&[name.to_string()]))
+    }
+
+    /// Removes a cell and returns its recomputed dependents, which now see it as unknown.
+    pub fn remove(&mut self, name: &str) -> Result<Vec<CellView>, String> {
+        self.cells
+            .remove(name)
+            .ok_or_else(|| format!("Cell '{}' not found", name))?;
+        let dependents: Vec<String> = self
+            .cells
+            .iter()
+            .filter(|(_, cell)| cell.dependencies().iter().any(|d| d == name))
+            .map(|(dependent, _)| dependent.clone())
+            .collect();
+        Ok(self.recompute(&dependents))
+    }
+
+    pub fn get(&self, name: &str) -> Result<CellView, String> {
+        self.cells
+            .get(name)
+            .map(|cell| self.view(name, cell))
+            .ok_or_else(|| format!("Cell '{}' not found", name))
+    }
+
+    /// All cells in name order, with their current values.
+    pub fn cells(&self) -> Vec<CellView> {
+        self.cells.iter().map(|(name, cell)| self.view(name, cell)).collect()
+    }
+
+    /// Recomputes every cell and returns them in evaluation order.
+    pub fn evaluate_all(&mut self) -> Vec<CellView> {
+        let names: Vec<String> = self.cells.keys().cloned().collect();
+        self.recompute(&names)
+    }
+
+    fn view(&self, name: &str, cell: &Cell) -> CellView {
+        CellView {
+            name: name.to_string(),
+            expression: cell.expression.clone(),
+            dependencies: cell.dependencies().to_vec(),
+            value: cell.value.as_ref().ok().copied(),
+            error: cell.value.as_ref().err().cloned(),
+        }
+    }
+
+    // Recomputes `roots` and all of their transitive dependents with Kahn's algorithm,
+    // breaking ties by name. Cells left over sit on, or downstream of, a cycle.
+    fn recompute(&mut self, roots: &[String]) -> Vec<CellView> {
+        let mut dependents: HashMap<&str, Vec<&str>> = HashMap::new();
+        for (name, cell) in &self.cells {
+            for dependency in cell.dependencies() {
+                dependents.entry(dependency.as_str()).or_default().push(name.as_str());
+            }
+        }
+
+        let mut affected: BTreeSet<String> = BTreeSet::new();
+        let mut pending: Vec<&str> = roots.iter().map(String::as_str).collect();
+        while let Some(name) = pending.pop() {
+            if self.cells.contains_key(name) && affected.insert(name.to_string()) {
+                pending.extend(dependents.get(name).into_iter().flatten().copied());
+            }
+        }
+
+        let mut in_degree: BTreeMap<&str, usize> = affected.iter().map(|name| (name.as_str(), 0)).collect();
+        for name in &affected {
+            for dependency in self.cells[name].dependencies() {
+                if affected.contains(dependency) {
+                    *in_degree.get_mut(name.as_str()).unwrap() += 1;
+                }
+            }
+        }
+
+        let mut ready: BTreeSet<&str> = in_degree.iter().filter(|(_, d)| **d == 0).map(|(n, _)| *n).collect();
+        let mut order: Vec<String> = Vec::with_capacity(affected.len());
+        while let Some(name) = ready.pop_first() {
+            order.push(name.to_string());
+            for dependent in dependents.get(name).into_iter().flatten() {
+                if let Some(degree) = in_degree.get_mut(dependent) {
+                    *degree -= 1;
+                    if *degree == 0 {
+                        ready.insert(dependent);
+                    }
+                }
+            }
+        }
+        drop(dependents);
+
+        let resolved: BTreeSet<&str> = order.iter().map(String::as_str).collect();
+        let unresolved: BTreeSet<&str> =
+            affected.iter().map(String::as_str).filter(|name| !resolved.contains(name)).collect();
+        let components = self.components(&unresolved);
+        // Cells on a cycle report it; the rest only depend on one and report that
+        // dependency's error, as any cell with a failing dependency does.
+        let (cyclic, downstream): (Vec<&str>, Vec<&str>) = unresolved.iter().copied().partition(|name| {
+            let component = components[*name];
+            self.cells[*name].dependencies().iter().any(|d| components.get(d.as_str()) == Some(&component))
+        });
+        let cyclic: Vec<(String, String)> = cyclic
+            .into_iter()
+            .map(|name| (name.to_string(), format!("Circular reference: {}", self.find_cycle(name, &components))))
+            .collect();
+        let downstream: Vec<String> = downstream.into_iter().map(String::from).collect();
+
+        let mut updated = Vec::with_capacity(affected.len());
+        for name in &order {
+            let value = self.evaluate_cell(name);
+            let cell = self.cells.get_mut(name).unwrap();
+            cell.value = value;
+            updated.push(self.view(name, &self.cells[name]));
+        }
+        for (name, error) in cyclic {
+            self.cells.get_mut(&name).unwrap().value = Err(error);
+            updated.push(self.view(&name, &self.cells[&name]));
+        }
+        // Each of these has a dependency that is on a cycle or downstream of one, so none
+        // evaluates; marking them all first gives each the error of its first such dependency.
+        for name in &downstream {
+            self.cells.get_mut(name).unwrap().value = Err("Not evaluated".to_string());
+        }
+        for name in &downstream {
+            let value = self.evaluate_cell(name);
+            self.cells.get_mut(name).unwrap().value = value;
+            updated.push(self.view(name, &self.cells[name]));
+        }
+        updated
+    }
+
+    // Numbers the strongly connected components among `unresolved` (Kosaraju's algorithm,
+    // with explicit stacks since a chain may be as long as the sheet). Cells in the same
+    // component as one of their dependencies sit on a cycle.
+    fn components<'a>(&'a self, unresolved: &BTreeSet<&'a str>) -> HashMap<&'a str, usize> {
+        let dependencies = |name: &str| -> Vec<&'a str> {
+            let cell = &self.cells[name];
+            cell.dependencies().iter().map(String::as_str).filter(|d| unresolved.contains(d)).collect()
+        };
+        let mut dependents: HashMap<&str, Vec<&str>> = HashMap::new();
+        for name in unresolved {
+            for dependency in dependencies(name) {
+                dependents.entry(dependency).or_default().push(name);
+            }
+        }
+
+        let mut visited: BTreeSet<&str> = BTreeSet::new();
+        let mut finished: Vec<&str> = Vec::with_capacity(unresolved.len());
+        for &start in unresolved {
+            if !visited.insert(start) {
+                continue;
+            }
+            let mut stack = vec![(start, dependencies(start), 0)];
+            while let Some((name, next, index)) = stack.last_mut() {
+                match next.get(*index) {
+                    Some(&dependency) => {
+                        *index += 1;
+                        if visited.insert(dependency) {
+                            stack.push((dependency, dependencies(dependency), 0));
+                        }
+                    }
+                    None => {
+                        finished.push(*name);
+                        stack.pop();
+                    }
+                }
+            }
+        }
+
+        let mut components: HashMap<&str, usize> = HashMap::new();
+        for (component, &start) in finished.iter().rev().enumerate() {
+            if components.contains_key(start) {
+                continue;
+            }
+            let mut stack = vec![start];
+            components.insert(start, component);
+            while let Some(name) = stack.pop() {
+                for &dependent in dependents.get(name).into_iter().flatten() {
+                    if !components.contains_key(dependent) {
+                        components.insert(dependent, component);
+                        stack.push(dependent);
+                    }
+                }
+            }
+        }
+        components
+    }
+
+    // The shortest loop from `start` back to itself within its component, rendered as
+    // `a → b → a`.
+    fn find_cycle(&self, start: &str, components: &HashMap<&str, usize>) -> String {
+        let component = components[start];
+        let mut previous: HashMap<&str, &str> = HashMap::new();
+        let mut queue = std::collections::VecDeque::from([start]);
+        while let Some(name) = queue.pop_front() {
+            for dependency in self.cells[name].dependencies() {
+                let dependency = dependency.as_str();
+                if components.get(dependency) != Some(&component) {
+                    continue;
+                }
+                if dependency == start {
+                    let mut cycle = vec![start, name];
+                    while let Some(&before) = previous.get(*cycle.last().unwrap()) {
+                        cycle.push(before);
+                    }
+                    cycle.reverse();
+                    return cycle.join(" → ");
+                }
+                if !previous.contains_key(dependency) {
+                    previous.insert(dependency, name);
+                    queue.push_back(dependency);
+                }
+            }
+        }
+        unreachable!("'{}' is on a cycle", start)
+    }
+
+    // Recompiles the cell with its dependencies' current types, so a cell can hold a date
+    // or duration and be used as one elsewhere.
+    fn evaluate_cell(&self, name: &str) -> Result<Value, String> {
+        let cell = &self.cells[name];
+        let mut typed = Vec::with_capacity(cell.dependencies().len());
+        let mut values = Vec::with_capacity(cell.dependencies().len());
+        for dependency in cell.dependencies() {
+            let value = self
+                .cells
+                .get(dependency)
+                .ok_or_else(|| format!("Unknown cell '{}'", dependency))?
+                .value
+                .as_ref()
+                .map_err(|_| format!("Cell '{}' has an error", dependency))?;
+            typed.push((dependency.clone(), Some(value.value_type())));
+            values.push(value.as_f64());
+        }
+        Ok(CompiledExpression::compile_typed(&cell.ast, &typed)?.evaluate(&values)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sheet(cells: &[(&str, &str)]) -> Worksheet {
+        let mut worksheet = Worksheet::default();
+        for (name, expression) in cells {
+            worksheet.set(name, expression).unwrap();
+        }
+        worksheet
+    }
+
+    fn outcome(view: &CellView) -> Result<f64, String> {
+        match (&view.value, &view.error) {
+            (Some(value), _) => Ok(value.as_f64()),
+            (None, error) => Err(error.clone().unwrap()),
+        }
+    }
+
+    fn value(worksheet: &Worksheet, name: &str) -> Result<f64, String> {
+        outcome(&worksheet.get(name).unwrap())
+    }
+
+    #[test]
+    fn changes_recompute_dependents_in_order() {
+        let mut worksheet = sheet(&[("total", "subtotal * (1 + tax)"), ("subtotal", "price * qty"), ("price", "10")]);
+        assert_eq!(value(&worksheet, "subtotal"), Err("Unknown cell 'qty'".to_string()));
+        worksheet.set("tax", "0.5").unwrap();
+        let updated = worksheet.set("qty", "3").unwrap();
+        let order: Vec<(&str, Result<f64, String>)> =
+            updated.iter().map(|view| (view.name.as_str(), outcome(view))).collect();
+        assert_eq!(order, [("qty", Ok(3.0)), ("subtotal", Ok(30.0)), ("total", Ok(45.0))]);
+        assert_eq!(worksheet.get("total").unwrap().dependencies, ["subtotal", "tax"]);
+
+        let updated = worksheet.set("price", "20").unwrap();
+        assert_eq!(updated.len(), 3);
+        assert_eq!(value(&worksheet, "total"), Ok(90.0));
+    }
+
+    #[test]
+    fn removing_a_cell_fails_its_dependents() {
+        let mut worksheet = sheet(&[("a", "1"), ("b", "a + 1"), ("c", "b * 2"), ("d", "7")]);
+        let updated = worksheet.remove("a").unwrap();
+        assert_eq!(updated.iter().map(|view| view.name.as_str()).collect::<Vec<_>>(), ["b", "c"]);
+        assert_eq!(value(&worksheet, "b"), Err("Unknown cell 'a'".to_string()));
+        assert_eq!(value(&worksheet, "c"), Err("Cell 'b' has an error".to_string()));
+        assert_eq!(value(&worksheet, "d"), Ok(7.0));
+        assert!(worksheet.remove("a").is_err());
+    }
+
+    #[test]
+    fn cycles_are_reported_on_the_cells_in_them() {
+        let mut worksheet = sheet(&[("x", "y + 1"), ("z", "x * 2"), ("w", "z + 1")]);
+        worksheet.set("y", "x + 1").unwrap();
+        assert_eq!(value(&worksheet, "x"), Err("Circular reference: x → y → x".to_string()));
+        assert_eq!(value(&worksheet, "y"), Err("Circular reference: y → x → y".to_string()));
+        assert_eq!(value(&worksheet, "z"), Err("Cell 'x' has an error".to_string()));
+        assert_eq!(value(&worksheet, "w"), Err("Cell 'z' has an error".to_string()));
+
+        worksheet.set("y", "1").unwrap();
+        assert_eq!((value(&worksheet, "x"), value(&worksheet, "w")), (Ok(2.0), Ok(5.0)));
+
+        worksheet.set("s", "s + 1").unwrap();
+        assert_eq!(value(&worksheet, "s"), Err("Circular reference: s → s".to_string()));
+    }
+
+    #[test]
+    fn cells_between_two_cycles_are_not_on_either() {
+        let worksheet = sheet(&[("p", "q"), ("q", "p"), ("m", "p + 1"), ("r", "m + s"), ("s", "r")]);
+        assert_eq!(value(&worksheet, "m"), Err("Cell 'p' has an error".to_string()));
+        assert_eq!(value(&worksheet, "r"), Err("Circular reference: r → s → r".to_string()));
+        assert_eq!(value(&worksheet, "s"), Err("Circular reference: s → r → s".to_string()));
+        let mut worksheet = worksheet;
+        let cells = worksheet.evaluate_all();
+        assert_eq!(cells.len(), 5);
+        assert!(cells.iter().all(|view| view.error.is_some()));
+    }
+
+    #[test]
+    fn long_cycles_are_found_without_recursion() {
+        let count = 500;
+        let mut worksheet = Worksheet::default();
+        for i in 1..count {
+            worksheet.set(&format!("c{}", i), &format!("c{} + 1", i - 1)).unwrap();
+        }
+        worksheet.set("c0", &format!("c{}", count - 1)).unwrap();
+        let error = value(&worksheet, "c0").unwrap_err();
+        assert!(error.starts_with("Circular reference: c0 → c499 → c498"), "{}", &error[..60]);
+        assert!(error.ends_with("c1 → c0"));
+    }
+
+    #[test]
+    fn names_and_size_are_checked() {
+        let mut worksheet = Worksheet::default();
+        assert!(worksheet.set("pi", "1").is_err());
+        assert!(worksheet.set("2a", "1").is_err());
+        assert!(worksheet.set("a", "1 +").is_err());
+        assert!(worksheet.is_empty());
+    }
+}
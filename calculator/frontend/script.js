@@ -2,6 +2,11 @@ let currentInput = '';
 let operatorAdded = false;
 let currentLanguage = 'zh';
 
+// Memory lives on the server under this id, so it survives reloads.
+const sessionId = localStorage.getItem('calculatorSession') ||
+    Date.now().toString(36) + Math.random().toString(36).slice(2, 10);
+localStorage.setItem('calculatorSession', sessionId);
+
 const translations = {
     zh: {
         title: 'Rust计算器',
@@ -12,7 +17,8 @@ const translations = {
         pleaseEnter: '请输入表达式',
         error: '错误: ',
         connectionError: '连接错误: 请确保后端服务器正在运行',
-        calculateSuccess: '计算成功'
+        calculateSuccess: '计算成功',
+        memory: '内存: '
     },
     en: {
         title: 'Rust Calculator',
@@ -23,7 +29,8 @@ const translations = {
         pleaseEnter: 'Please enter an expression',
         error: 'Error: ',
         connectionError: 'Connection error: Please ensure the backend server is running',
-        calculateSuccess: 'Calculation successful'
+        calculateSuccess: 'Calculation successful',
+        memory: 'Memory: '
     }
 };
 
@@ -69,12 +76,12 @@ async function calculate() {
     showStatus(translations[currentLanguage].calculating, 'info');
 
     try {
-        const response = await fetch('http://localhost:3030/calculate', {
+        const response = await fetch('http://localhost:3030/v1/calculate', {
             method: 'POST',
             headers: {
                 'Content-Type': 'application/json'
             },
-            body: JSON.stringify({ expression: expression })
+            body: JSON.stringify({ expression: expression, session: sessionId })
         });
 
         const data = await response.json();
@@ -83,7 +90,7 @@ async function calculate() {
             showStatus(translations[currentLanguage].error + data.error, 'error');
             document.getElementById('result').textContent = '';
         } else {
-            document.getElementById('result').textContent = translations[currentLanguage].result + data.result;
+            document.getElementById('result').textContent = translations[currentLanguage].result + (data.formatted ?? data.result);
             showStatus(translations[currentLanguage].calculateSuccess, 'success');
             currentInput = data.result.toString();
             document.getElementById('expression').value = currentInput;
@@ -95,6 +102,68 @@ async function calculate() {
     }
 }
 
+async function memoryRequest(method, path, expression) {
+    try {
+        const response = await fetch(`http://localhost:3030/v1/memory/${sessionId}/M${path}`, {
+            method: method,
+            headers: {
+                'Content-Type': 'application/json'
+            },
+            body: expression === undefined ? undefined : JSON.stringify({ expression: expression })
+        });
+
+        const data = await response.json();
+
+        if (data.error) {
+            showStatus(translations[currentLanguage].error + data.error, 'error');
+            return null;
+        }
+        return data.slots[0].value;
+    } catch (error) {
+        showStatus(translations[currentLanguage].connectionError, 'error');
+        console.error('Error:', error);
+        return null;
+    }
+}
+
+async function memoryUpdate(operation) {
+    if (!currentInput.trim()) {
+        showStatus(translations[currentLanguage].pleaseEnter, 'error');
+        return;
+    }
+
+    const value = await memoryRequest('POST', '/' + operation, currentInput.trim());
+    if (value !== null) {
+        showStatus(translations[currentLanguage].memory + value, 'success');
+    }
+}
+
+function memoryAdd() {
+    memoryUpdate('add');
+}
+
+function memorySubtract() {
+    memoryUpdate('subtract');
+}
+
+async function memoryRecall() {
+    const value = await memoryRequest('GET', '');
+    if (value !== null) {
+        // Like a desk calculator, MR replaces the number being typed.
+        const operands = currentInput.split(' ');
+        operands[operands.length - 1] = value.toString();
+        currentInput = operands.join(' ');
+        document.getElementById('expression').value = currentInput;
+        operatorAdded = false;
+    }
+}
+
+async function memoryClear() {
+    if (await memoryRequest('DELETE', '') !== null) {
+        showStatus(translations[currentLanguage].memory + 0, 'success');
+    }
+}
+
 function toggleLanguage() {
     currentLanguage = currentLanguage === 'zh' ? 'en' : 'zh';
     updateLanguage();